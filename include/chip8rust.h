@@ -0,0 +1,26 @@
+#ifndef CHIP8RUST_H
+#define CHIP8RUST_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+/* Build with `cargo rustc --lib --release --features ffi --crate-type cdylib` and link against the
+   chip8rust library. */
+
+typedef struct Chip8Emulator Chip8Emulator;
+
+/* Both return NULL if the emulator could not be created. */
+Chip8Emulator *chip8_create(void);
+Chip8Emulator *chip8_create_from_config(const char *config_path);
+void chip8_destroy(Chip8Emulator *emulator);
+
+bool chip8_load_rom(Chip8Emulator *emulator, const uint8_t *rom, size_t length);
+bool chip8_step(Chip8Emulator *emulator);
+bool chip8_run_for(Chip8Emulator *emulator, uint64_t microseconds);
+
+/* One byte per pixel (0 or 1), row by row, valid until the next call. */
+const uint8_t *chip8_get_framebuffer(Chip8Emulator *emulator, size_t *width, size_t *height);
+void chip8_set_key(Chip8Emulator *emulator, uint8_t key, bool pressed);
+
+#endif
@@ -0,0 +1,95 @@
+use crate::emulator::Emulator;
+use crate::overlay::{self, Canvas};
+use std::sync::Arc;
+use winit::keyboard::KeyCode;
+use winit_input_helper::WinitInputHelper;
+
+const CLOSE_DIALOG_KEYS: [KeyCode; 2] = [KeyCode::Escape, KeyCode::Enter];
+
+const TEXT_SCALE: usize = 2;
+const ROW_HEIGHT: usize = 7 * TEXT_SCALE;
+const MENU_MARGIN: usize = 8;
+const TEXT_COLUMN_CHARS: usize = 64;
+const BACKGROUND_COLOR: u32 = 0x101018;
+const TEXT_COLOR: u32 = 0xFFFFFF;
+const HINT_COLOR: u32 = 0x9999AA;
+
+pub struct AboutDialog {
+    emulator: Arc<Emulator>,
+    open: bool,
+    lines: Vec<String>,
+}
+
+impl AboutDialog {
+    pub fn new(emulator: Arc<Emulator>) -> Self {
+        Self {
+            emulator,
+            open: false,
+            lines: Vec::new(),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        return self.open;
+    }
+
+    // The report is also printed, as text can't be copied out of the window.
+    pub fn open(&mut self, program_path: Option<&str>) {
+        self.open = true;
+        self.lines = self.emulator.get_configuration_report();
+        self.lines
+            .insert(1, format!("rom = {}", program_path.unwrap_or("none")));
+
+        println!("{}", self.lines.join("\n"));
+    }
+
+    pub fn update(&mut self, input: &WinitInputHelper) {
+        if CLOSE_DIALOG_KEYS.iter().any(|key| input.key_pressed(*key)) {
+            self.open = false;
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<'_>) {
+        let (_, text_height) = overlay::get_text_size("", TEXT_SCALE);
+        let text_offset = (ROW_HEIGHT - text_height) / 2;
+        let (menu_width, _) = overlay::get_text_size(&" ".repeat(TEXT_COLUMN_CHARS), TEXT_SCALE);
+        let menu_width = menu_width + MENU_MARGIN * 2;
+        let row_count = self.lines.len() + 2;
+
+        canvas.fill_rect(
+            MENU_MARGIN,
+            MENU_MARGIN,
+            menu_width,
+            ROW_HEIGHT * row_count + MENU_MARGIN * 2,
+            BACKGROUND_COLOR,
+        );
+
+        let mut y = MENU_MARGIN * 2;
+
+        overlay::draw_text(
+            canvas,
+            MENU_MARGIN * 2,
+            y + text_offset,
+            "About - also printed to the console, escape to close",
+            TEXT_SCALE,
+            HINT_COLOR,
+        );
+
+        y += ROW_HEIGHT * 2;
+
+        for line in &self.lines {
+            let line: String = line.chars().take(TEXT_COLUMN_CHARS).collect();
+
+            overlay::draw_text(
+                canvas,
+                MENU_MARGIN * 2,
+                y + text_offset,
+                &line,
+                TEXT_SCALE,
+                TEXT_COLOR,
+            );
+
+            y += ROW_HEIGHT;
+        }
+    }
+}
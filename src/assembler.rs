@@ -0,0 +1,442 @@
+//! Turns instructions written the way the disassembler prints them back into a program, so a
+//! listing can be edited and reassembled.
+//!
+//! Each line holds at most one instruction, optionally after a label such as `loop:`, which can
+//! then stand in for an address. `DB` and `DW` give raw bytes and words, and anything after a `;`
+//! is a comment. Mnemonics, registers and labels are case-insensitive.
+
+use crate::ram::PROGRAM_START_ADDRESS;
+use crate::symbols::Symbols;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    V(u16),
+    I,
+    IndirectI,
+    DT,
+    ST,
+    K,
+    F,
+    HF,
+    B,
+    Number(u16),
+}
+
+struct Line<'a> {
+    number: usize,
+    text: &'a str,
+    label: Option<&'a str>,
+    mnemonic: String,
+    operands: Vec<&'a str>,
+}
+
+fn parse_lines(source: &str) -> Result<Vec<Line<'_>>, String> {
+    let mut lines = Vec::new();
+
+    for (i, line) in source.lines().enumerate() {
+        let number = i + 1;
+        let text = line.split(';').next().unwrap_or("").trim();
+
+        let (label, instruction) = match text.split_once(':') {
+            Some((label, instruction)) => (Some(label.trim()), instruction.trim()),
+            None => (None, text),
+        };
+
+        if let Some(label) = label
+            && !is_label(label)
+        {
+            return Err(format!("Line {number} has an invalid label ({label})."));
+        }
+
+        let (mnemonic, operands) = instruction
+            .split_once(char::is_whitespace)
+            .unwrap_or((instruction, ""));
+
+        lines.push(Line {
+            number,
+            text,
+            label,
+            mnemonic: mnemonic.to_uppercase(),
+            operands: match operands.trim() {
+                "" => Vec::new(),
+                operands => operands.split(',').map(str::trim).collect(),
+            },
+        });
+    }
+
+    return Ok(lines);
+}
+
+fn is_label(text: &str) -> bool {
+    let mut chars = text.chars();
+
+    return chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+}
+
+fn get_size(line: &Line) -> usize {
+    return match line.mnemonic.as_str() {
+        "" => 0,
+        "DB" => line.operands.len(),
+        "DW" => line.operands.len() * 2,
+        _ => 2,
+    };
+}
+
+fn parse_number(text: &str) -> Option<u16> {
+    return match text.strip_prefix("0X") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    };
+}
+
+fn parse_operand(text: &str, labels: &HashMap<String, u16>) -> Option<Operand> {
+    let text = text.to_uppercase();
+
+    let operand = match text.as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::DT,
+        "ST" => Operand::ST,
+        "K" => Operand::K,
+        "F" => Operand::F,
+        "HF" => Operand::HF,
+        "B" => Operand::B,
+        _ => match text
+            .strip_prefix('V')
+            .filter(|reg| reg.len() == 1)
+            .and_then(|reg| u16::from_str_radix(reg, 16).ok())
+        {
+            Some(reg) => Operand::V(reg),
+            None => Operand::Number(parse_number(&text).or_else(|| labels.get(&text).copied())?),
+        },
+    };
+
+    return Some(operand);
+}
+
+// The operands are already checked to be registers where one is expected, so only numbers need
+// checking to fit.
+fn encode(mnemonic: &str, operands: &[Operand]) -> Option<u16> {
+    use Operand::*;
+
+    let addr = |value: u16| (value <= 0xFFF).then_some(value);
+    let byte = |value: u16| (value <= 0xFF).then_some(value);
+
+    let opcode = match (mnemonic, operands) {
+        ("CLS", []) => 0x00E0,
+        ("RET", []) => 0x00EE,
+        ("SYS", [Number(a)]) => addr(*a)?,
+        ("JP", [Number(a)]) => 0x1000 | addr(*a)?,
+        ("JP", [V(0), Number(a)]) => 0xB000 | addr(*a)?,
+        ("CALL", [Number(a)]) => 0x2000 | addr(*a)?,
+        ("SE", [V(x), Number(kk)]) => 0x3000 | x << 8 | byte(*kk)?,
+        ("SNE", [V(x), Number(kk)]) => 0x4000 | x << 8 | byte(*kk)?,
+        ("SE", [V(x), V(y)]) => 0x5000 | x << 8 | y << 4,
+        ("LD", [V(x), Number(kk)]) => 0x6000 | x << 8 | byte(*kk)?,
+        ("ADD", [V(x), Number(kk)]) => 0x7000 | x << 8 | byte(*kk)?,
+        ("LD", [V(x), V(y)]) => 0x8000 | x << 8 | y << 4,
+        ("OR", [V(x), V(y)]) => 0x8001 | x << 8 | y << 4,
+        ("AND", [V(x), V(y)]) => 0x8002 | x << 8 | y << 4,
+        ("XOR", [V(x), V(y)]) => 0x8003 | x << 8 | y << 4,
+        ("ADD", [V(x), V(y)]) => 0x8004 | x << 8 | y << 4,
+        ("SUB", [V(x), V(y)]) => 0x8005 | x << 8 | y << 4,
+        ("SHR", [V(x), V(y)]) => 0x8006 | x << 8 | y << 4,
+        ("SUBN", [V(x), V(y)]) => 0x8007 | x << 8 | y << 4,
+        ("SHL", [V(x), V(y)]) => 0x800E | x << 8 | y << 4,
+        // Shifting a register into itself works the same with either shift quirk.
+        ("SHR", [V(x)]) => 0x8006 | x << 8 | x << 4,
+        ("SHL", [V(x)]) => 0x800E | x << 8 | x << 4,
+        ("SNE", [V(x), V(y)]) => 0x9000 | x << 8 | y << 4,
+        ("LD", [I, Number(a)]) => 0xA000 | addr(*a)?,
+        ("RND", [V(x), Number(kk)]) => 0xC000 | x << 8 | byte(*kk)?,
+        ("DRW", [V(x), V(y), Number(n)]) if *n <= 0xF => 0xD000 | x << 8 | y << 4 | n,
+        ("SKP", [V(x)]) => 0xE09E | x << 8,
+        ("SKNP", [V(x)]) => 0xE0A1 | x << 8,
+        ("LD", [V(x), DT]) => 0xF007 | x << 8,
+        ("LD", [V(x), K]) => 0xF00A | x << 8,
+        ("LD", [DT, V(x)]) => 0xF015 | x << 8,
+        ("LD", [ST, V(x)]) => 0xF018 | x << 8,
+        ("ADD", [I, V(x)]) => 0xF01E | x << 8,
+        ("LD", [F, V(x)]) => 0xF029 | x << 8,
+        ("LD", [HF, V(x)]) => 0xF030 | x << 8,
+        ("LD", [B, V(x)]) => 0xF033 | x << 8,
+        ("LD", [IndirectI, V(x)]) => 0xF055 | x << 8,
+        ("LD", [V(x), IndirectI]) => 0xF065 | x << 8,
+        _ => return None,
+    };
+
+    return Some(opcode);
+}
+
+/// A program along with what a debugger needs to show it in terms of its source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assembly {
+    pub program: Vec<u8>,
+    // The labels, spelled as they were defined.
+    pub symbols: Symbols,
+    // The number of the line each instruction or run of data came from, by its address.
+    pub source_lines: BTreeMap<u16, usize>,
+}
+
+/// Assembles the source into a program to be loaded at 0x200, or describes the first line that
+/// couldn't be assembled.
+pub fn assemble(source: &str) -> Result<Vec<u8>, String> {
+    return assemble_with_debug_info(source).map(|assembly| assembly.program);
+}
+
+pub fn assemble_with_debug_info(source: &str) -> Result<Assembly, String> {
+    let lines = parse_lines(source)?;
+
+    // Labels can be used before they are defined, so their addresses are all found first.
+    let mut labels = HashMap::new();
+    let mut symbols = Vec::new();
+    let mut address = PROGRAM_START_ADDRESS as usize;
+
+    for line in &lines {
+        if let Some(label) = line.label {
+            if labels
+                .insert(label.to_uppercase(), address as u16)
+                .is_some()
+            {
+                return Err(format!("Line {} redefines a label ({label}).", line.number));
+            }
+
+            symbols.push((String::from(label), address as u16));
+        }
+
+        address += get_size(line);
+    }
+
+    let mut program = Vec::new();
+    let mut source_lines = BTreeMap::new();
+
+    for line in &lines {
+        if get_size(line) > 0 {
+            let address = PROGRAM_START_ADDRESS + program.len() as u16;
+            source_lines.insert(address, line.number);
+        }
+
+        let invalid_line = || {
+            format!(
+                "Line {} is not a valid instruction ({}).",
+                line.number, line.text
+            )
+        };
+
+        let operands = line
+            .operands
+            .iter()
+            .map(|operand| parse_operand(operand, &labels))
+            .collect::<Option<Vec<Operand>>>()
+            .ok_or_else(invalid_line)?;
+
+        match (line.mnemonic.as_str(), operands.as_slice()) {
+            ("", []) => (),
+            ("DB", values) => {
+                for value in values {
+                    match value {
+                        Operand::Number(value) if *value <= 0xFF => program.push(*value as u8),
+                        _ => return Err(invalid_line()),
+                    }
+                }
+            }
+            ("DW", values) => {
+                for value in values {
+                    match value {
+                        Operand::Number(value) => program.extend(value.to_be_bytes()),
+                        _ => return Err(invalid_line()),
+                    }
+                }
+            }
+            (mnemonic, operands) => {
+                let opcode = encode(mnemonic, operands).ok_or_else(invalid_line)?;
+                program.extend(opcode.to_be_bytes());
+            }
+        }
+    }
+
+    return Ok(Assembly {
+        program,
+        symbols: Symbols::new(symbols),
+        source_lines,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassembler;
+
+    #[test]
+    fn test_assemble_disassembly() {
+        let opcodes: [u16; 37] = [
+            0x00E0, 0x00EE, 0x0123, 0x1204, 0x2206, 0x3A12, 0x4B34, 0x5AB0, 0x6C56, 0x7D78, 0x8AB0,
+            0x8AB1, 0x8AB2, 0x8AB3, 0x8AB4, 0x8AB5, 0x8AB6, 0x8AB7, 0x8ABE, 0x9AB0, 0xA9AB, 0xB345,
+            0xCE0F, 0xD12F, 0xE59E, 0xE6A1, 0xF007, 0xF10A, 0xF215, 0xF318, 0xF41E, 0xF529, 0xF633,
+            0xF755, 0xFF65, 0x5121, 0xFFFF,
+        ];
+
+        // Each opcode on its own, as a whole program would mostly be listed as unreachable data.
+        let listing: String = opcodes
+            .iter()
+            .map(|opcode| disassembler::disassemble(*opcode).mnemonic + "\n")
+            .collect();
+        let program: Vec<u8> = opcodes
+            .iter()
+            .flat_map(|opcode| opcode.to_be_bytes())
+            .collect();
+        assert_eq!(Ok(program), assemble(&listing));
+    }
+
+    #[test]
+    fn test_assemble_every_mnemonic() {
+        let source = "
+            CLS
+            RET
+            SYS 0x123
+            JP 0x204
+            JP V0, 0x345
+            CALL 0x206
+            SE VA, 0x12
+            SE VA, VB
+            SNE VB, 52
+            SNE VA, VB
+            LD VC, 0x56
+            LD VA, VB
+            LD I, 0x9AB
+            LD VF, DT
+            LD V1, K
+            LD DT, V2
+            LD ST, V3
+            LD F, V5
+            LD HF, V7
+            LD B, V6
+            LD [I], V7
+            LD VF, [I]
+            ADD VD, 0x78
+            ADD VA, VB
+            ADD I, V4
+            OR VA, VB
+            AND VA, VB
+            XOR VA, VB
+            SUB VA, VB
+            SUBN VA, VB
+            SHR VA, VB
+            SHR VA
+            SHL VA, VB
+            SHL VA
+            RND VE, 0x0F
+            DRW V1, V2, 15
+            SKP V5
+            SKNP V6
+        ";
+        let opcodes: [u16; 38] = [
+            0x00E0, 0x00EE, 0x0123, 0x1204, 0xB345, 0x2206, 0x3A12, 0x5AB0, 0x4B34, 0x9AB0, 0x6C56,
+            0x8AB0, 0xA9AB, 0xFF07, 0xF10A, 0xF215, 0xF318, 0xF529, 0xF730, 0xF633, 0xF755, 0xFF65,
+            0x7D78, 0x8AB4, 0xF41E, 0x8AB1, 0x8AB2, 0x8AB3, 0x8AB5, 0x8AB7, 0x8AB6, 0x8AA6, 0x8ABE,
+            0x8AAE, 0xCE0F, 0xD12F, 0xE59E, 0xE6A1,
+        ];
+
+        let program: Vec<u8> = opcodes
+            .iter()
+            .flat_map(|opcode| opcode.to_be_bytes())
+            .collect();
+        assert_eq!(Ok(program), assemble(source));
+    }
+
+    #[test]
+    fn test_assemble_forward_references() {
+        let source = "
+                    CALL draw
+                    JP V0, table
+            draw:   LD I, sprite
+                    RET
+            table:  DW draw
+            sprite: DB 0x80
+        ";
+
+        assert_eq!(
+            Ok(vec![
+                0x22, 0x04, 0xB2, 0x08, 0xA2, 0x0A, 0x00, 0xEE, 0x02, 0x04, 0x80
+            ]),
+            assemble(source)
+        );
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        assert_eq!(
+            Err(String::from(
+                "Line 2 is not a valid instruction (MOV V0, 1)."
+            )),
+            assemble("CLS\nMOV V0, 1")
+        );
+
+        // Operands of the wrong kind, or missing.
+        assert!(assemble("LD V0, Q").unwrap_err().starts_with("Line 1"));
+        assert!(assemble("ADD DT, V0").unwrap_err().starts_with("Line 1"));
+        assert!(assemble("SKP 0x10").unwrap_err().starts_with("Line 1"));
+        assert!(assemble("DRW V0, V1").unwrap_err().starts_with("Line 1"));
+        assert!(assemble("DB V0").unwrap_err().starts_with("Line 1"));
+
+        // Numbers too large for their place in the opcode.
+        assert!(assemble("JP 0x1000").unwrap_err().starts_with("Line 1"));
+        assert!(assemble("CALL 4096").unwrap_err().starts_with("Line 1"));
+        assert!(assemble("LD I, 0x1000").unwrap_err().starts_with("Line 1"));
+        assert!(
+            assemble("DRW V0, V1, 16")
+                .unwrap_err()
+                .starts_with("Line 1")
+        );
+        assert!(assemble("DB 0x100").unwrap_err().starts_with("Line 1"));
+
+        assert_eq!(
+            Err(String::from("Line 1 has an invalid label (1st).")),
+            assemble("1st: CLS")
+        );
+    }
+
+    #[test]
+    fn test_assemble_labels() {
+        let source = "
+            ; Counts V0 up forever.
+            start:  ld v0, 0
+            loop:   add V0, 1
+                    JP loop
+            sprite: DB 0xF0, 0x90
+                    DW start, 0x1234
+        ";
+
+        assert_eq!(
+            Ok(vec![
+                0x60, 0x00, 0x70, 0x01, 0x12, 0x02, 0xF0, 0x90, 0x02, 0x00, 0x12, 0x34
+            ]),
+            assemble(source)
+        );
+
+        let assembly = assemble_with_debug_info(source).unwrap();
+        assert_eq!(Some("loop"), assembly.symbols.get_name(0x202));
+        assert_eq!(Some("sprite"), assembly.symbols.get_name(0x206));
+        assert_eq!(
+            vec![(0x200, 3), (0x202, 4), (0x204, 5), (0x206, 6), (0x208, 7)],
+            assembly.source_lines.into_iter().collect::<Vec<_>>()
+        );
+
+        assert!(assemble("JP nowhere").unwrap_err().starts_with("Line 1"));
+        assert!(
+            assemble("CLS\nLD V0, 0x100")
+                .unwrap_err()
+                .starts_with("Line 2")
+        );
+        assert!(
+            assemble("a: CLS\na: CLS")
+                .unwrap_err()
+                .starts_with("Line 2")
+        );
+        assert!(assemble("SHR VG").is_err());
+    }
+}
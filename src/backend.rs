@@ -0,0 +1,447 @@
+#[cfg(feature = "audio")]
+use crate::config::{AudioBackend, SoundTimerConfig, ToneWaveform};
+
+#[cfg(feature = "audio")]
+use rodio::cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+#[cfg(feature = "audio")]
+use rodio::cpal::{FromSample, SampleFormat, SizedSample};
+#[cfg(feature = "audio")]
+use rodio::{OutputStream, OutputStreamBuilder, Sink, Source, cpal};
+#[cfg(feature = "audio")]
+use std::sync::Arc;
+#[cfg(feature = "audio")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "audio")]
+use std::time::Duration;
+
+pub trait Display {
+    fn present(&mut self, framebuffer: &[bool], resolution: (usize, usize)) -> bool;
+}
+
+pub trait AudioSink: Send + Sync {
+    fn play(&self);
+
+    fn pause(&self);
+}
+
+pub trait InputSource {
+    fn is_key_pressed(&self, key: &str) -> bool;
+
+    fn is_key_released(&self, key: &str) -> bool;
+}
+
+pub struct NullAudioSink;
+
+impl AudioSink for NullAudioSink {
+    fn play(&self) {}
+
+    fn pause(&self) {}
+}
+
+// Starting and stopping a sink takes long enough to swallow the shortest beeps, so the tone
+// runs the whole time and is silenced between beeps instead.
+#[cfg(feature = "audio")]
+struct GatedTone<S: Source> {
+    source: S,
+    playing: Arc<AtomicBool>,
+    minimum_samples: usize,
+    // None while silent.
+    samples_played: Option<usize>,
+}
+
+#[cfg(feature = "audio")]
+impl<S: Source> Iterator for GatedTone<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.source.next()?;
+
+        self.samples_played = match (self.playing.load(Ordering::Relaxed), self.samples_played) {
+            (true, samples_played) => Some(samples_played.unwrap_or(0) + 1),
+            (false, Some(samples_played)) if samples_played < self.minimum_samples => {
+                Some(samples_played + 1)
+            }
+            (false, _) => None,
+        };
+
+        return match self.samples_played {
+            Some(_) => Some(sample),
+            None => Some(0.0),
+        };
+    }
+}
+
+#[cfg(feature = "audio")]
+impl<S: Source> Source for GatedTone<S> {
+    fn current_span_len(&self) -> Option<usize> {
+        return self.source.current_span_len();
+    }
+
+    fn channels(&self) -> u16 {
+        return self.source.channels();
+    }
+
+    fn sample_rate(&self) -> u32 {
+        return self.source.sample_rate();
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        return None;
+    }
+}
+
+#[cfg(feature = "audio")]
+fn gate<S: Source>(
+    source: S,
+    playing: &Arc<AtomicBool>,
+    config: &SoundTimerConfig,
+) -> GatedTone<S> {
+    let samples_per_second = source.sample_rate() as f64 * source.channels() as f64;
+
+    return GatedTone {
+        source,
+        playing: playing.clone(),
+        minimum_samples: (config.minimum_tone_length * samples_per_second) as usize,
+        samples_played: None,
+    };
+}
+
+#[cfg(feature = "audio")]
+const OSCILLATOR_SAMPLE_RATE: u32 = 48000;
+
+// rodio's generators have a fixed sample rate and a square wave that is always high for half of
+// each cycle, so the tone is generated here instead, at whatever rate the device wants.
+#[cfg(feature = "audio")]
+struct Oscillator {
+    waveform: ToneWaveform,
+    frequency: f32,
+    duty_cycle: f32,
+    sample_rate: u32,
+    // How far through the current cycle the next sample is, from 0 to 1.
+    phase: f32,
+}
+
+#[cfg(feature = "audio")]
+impl Oscillator {
+    fn new(config: &SoundTimerConfig, sample_rate: u32) -> Self {
+        Self {
+            waveform: config.tone_waveform.clone(),
+            frequency: config.tone_frequency,
+            duty_cycle: config.square_duty_cycle,
+            sample_rate,
+            phase: 0.0,
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+impl Iterator for Oscillator {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = match self.waveform {
+            ToneWaveform::Sine => (self.phase * std::f32::consts::TAU).sin(),
+            ToneWaveform::Square if self.phase < self.duty_cycle => 1.0,
+            ToneWaveform::Square => -1.0,
+            ToneWaveform::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+            ToneWaveform::Sawtooth => 2.0 * self.phase - 1.0,
+        };
+
+        self.phase = (self.phase + self.frequency / self.sample_rate as f32).fract();
+        return Some(sample);
+    }
+}
+
+#[cfg(feature = "audio")]
+impl Source for Oscillator {
+    fn current_span_len(&self) -> Option<usize> {
+        return None;
+    }
+
+    fn channels(&self) -> u16 {
+        return 1;
+    }
+
+    fn sample_rate(&self) -> u32 {
+        return self.sample_rate;
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        return None;
+    }
+}
+
+#[cfg(feature = "audio")]
+pub struct RodioAudioSink {
+    _stream_handle: OutputStream,
+    _sink: Sink,
+    playing: Arc<AtomicBool>,
+}
+
+#[cfg(feature = "audio")]
+impl RodioAudioSink {
+    pub fn try_new(config: &SoundTimerConfig) -> Option<Self> {
+        let stream_handle = match Self::open_stream(config) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Error: Failed to open audio stream ({e}).");
+                return None;
+            }
+        };
+
+        let sink = rodio::Sink::connect_new(&stream_handle.mixer());
+        let playing = Arc::new(AtomicBool::new(false));
+
+        sink.append(gate(
+            Oscillator::new(config, OSCILLATOR_SAMPLE_RATE),
+            &playing,
+            config,
+        ));
+
+        return Some(Self {
+            _stream_handle: stream_handle,
+            _sink: sink,
+            playing,
+        });
+    }
+
+    // Zero leaves the device's default in place.
+    fn open_stream(config: &SoundTimerConfig) -> Result<OutputStream, rodio::StreamError> {
+        if config.sample_rate == 0 && config.buffer_size == 0 {
+            return OutputStreamBuilder::open_default_stream();
+        }
+
+        let mut builder = OutputStreamBuilder::from_default_device()?;
+
+        if config.sample_rate > 0 {
+            builder = builder.with_sample_rate(config.sample_rate);
+        }
+
+        if config.buffer_size > 0 {
+            builder = builder.with_buffer_size(cpal::BufferSize::Fixed(config.buffer_size));
+        }
+
+        return builder.open_stream().or_else(|e| {
+            eprintln!(
+                "Warning: Could not open audio with the configured sample rate and buffer size, so using the defaults ({e})."
+            );
+            return OutputStreamBuilder::open_default_stream();
+        });
+    }
+}
+
+#[cfg(feature = "audio")]
+impl AudioSink for RodioAudioSink {
+    fn play(&self) {
+        self.playing.store(true, Ordering::Relaxed);
+    }
+
+    fn pause(&self) {
+        self.playing.store(false, Ordering::Relaxed);
+    }
+}
+
+// Skips rodio's mixer and sink, filling the device's buffer straight from the oscillator, so the
+// buffer size is the only thing between the sound timer and the speaker.
+#[cfg(feature = "audio")]
+pub struct CpalAudioSink {
+    _stream: cpal::Stream,
+    playing: Arc<AtomicBool>,
+}
+
+#[cfg(feature = "audio")]
+impl CpalAudioSink {
+    pub fn try_new(config: &SoundTimerConfig) -> Option<Self> {
+        let playing = Arc::new(AtomicBool::new(false));
+
+        let stream = match Self::open_stream(config, &playing) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Error: Failed to open audio stream ({e}).");
+                return None;
+            }
+        };
+
+        if let Err(e) = stream.play() {
+            eprintln!("Error: Failed to start audio stream ({e}).");
+            return None;
+        }
+
+        return Some(Self {
+            _stream: stream,
+            playing,
+        });
+    }
+
+    // Zero leaves the device's default in place.
+    fn open_stream(
+        config: &SoundTimerConfig,
+        playing: &Arc<AtomicBool>,
+    ) -> Result<cpal::Stream, String> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or("no output device")?;
+        let supported_config = device.default_output_config().map_err(|e| e.to_string())?;
+        let sample_format = supported_config.sample_format();
+        let default_config = supported_config.config();
+        let mut stream_config = default_config.clone();
+
+        if config.sample_rate > 0 {
+            stream_config.sample_rate = cpal::SampleRate(config.sample_rate);
+        }
+
+        if config.buffer_size > 0 {
+            stream_config.buffer_size = cpal::BufferSize::Fixed(config.buffer_size);
+        }
+
+        let build = |stream_config| {
+            return match sample_format {
+                SampleFormat::F32 => {
+                    Self::build_stream::<f32>(&device, stream_config, config, playing)
+                }
+                SampleFormat::I16 => {
+                    Self::build_stream::<i16>(&device, stream_config, config, playing)
+                }
+                SampleFormat::U16 => {
+                    Self::build_stream::<u16>(&device, stream_config, config, playing)
+                }
+                format => Err(format!("unsupported sample format {format}")),
+            };
+        };
+
+        return build(&stream_config).or_else(|e| {
+            if stream_config == default_config {
+                return Err(e);
+            }
+
+            eprintln!(
+                "Warning: Could not open audio with the configured sample rate and buffer size, so using the defaults ({e})."
+            );
+            return build(&default_config);
+        });
+    }
+
+    // The same sample goes to every channel.
+    fn build_stream<T: SizedSample + FromSample<f32>>(
+        device: &cpal::Device,
+        stream_config: &cpal::StreamConfig,
+        config: &SoundTimerConfig,
+        playing: &Arc<AtomicBool>,
+    ) -> Result<cpal::Stream, String> {
+        let channels = stream_config.channels as usize;
+        let mut tone = gate(
+            Oscillator::new(config, stream_config.sample_rate.0),
+            playing,
+            config,
+        );
+
+        return device
+            .build_output_stream(
+                stream_config,
+                move |data: &mut [T], _| {
+                    for frame in data.chunks_mut(channels) {
+                        frame.fill(T::from_sample(tone.next().unwrap_or(0.0)));
+                    }
+                },
+                |e| eprintln!("Error: Audio stream failed ({e})."),
+                None,
+            )
+            .map_err(|e| e.to_string());
+    }
+}
+
+#[cfg(feature = "audio")]
+impl AudioSink for CpalAudioSink {
+    fn play(&self) {
+        self.playing.store(true, Ordering::Relaxed);
+    }
+
+    fn pause(&self) {
+        self.playing.store(false, Ordering::Relaxed);
+    }
+}
+
+#[cfg(feature = "audio")]
+pub fn create_default_audio_sink(config: &SoundTimerConfig) -> Option<Box<dyn AudioSink>> {
+    return match config.audio_backend {
+        AudioBackend::Rodio => Some(Box::new(RodioAudioSink::try_new(config)?)),
+        AudioBackend::Cpal => Some(Box::new(CpalAudioSink::try_new(config)?)),
+    };
+}
+
+#[cfg(not(feature = "audio"))]
+pub fn create_default_audio_sink(
+    _config: &crate::config::SoundTimerConfig,
+) -> Option<Box<dyn AudioSink>> {
+    eprintln!("Warning: Built without the audio feature, so running without sound.");
+    return Some(Box::new(NullAudioSink));
+}
+
+#[cfg(all(test, feature = "audio"))]
+mod tests {
+    use super::*;
+    use rodio::source;
+
+    #[test]
+    fn test_gated_tone_plays_for_minimum_length() {
+        let playing = Arc::new(AtomicBool::new(true));
+        let mut tone = GatedTone {
+            source: source::SquareWave::new(440.0),
+            playing: playing.clone(),
+            minimum_samples: 10,
+            samples_played: None,
+        };
+
+        assert_ne!(Some(0.0), tone.next());
+        playing.store(false, Ordering::Relaxed);
+
+        for _ in 1..10 {
+            assert_ne!(Some(0.0), tone.next());
+        }
+
+        assert_eq!(Some(0.0), tone.next());
+    }
+
+    #[test]
+    fn test_oscillator_waveforms() {
+        let config = |tone_waveform, square_duty_cycle| {
+            let mut config = crate::config::generate_default_configs().sound_timer;
+            config.tone_frequency = 480.0;
+            config.tone_waveform = tone_waveform;
+            config.square_duty_cycle = square_duty_cycle;
+            return config;
+        };
+
+        // 480 Hz gives exactly 100 samples per cycle.
+        let high_samples = Oscillator::new(&config(ToneWaveform::Square, 0.25), 48000)
+            .take(1000)
+            .filter(|sample| *sample > 0.0)
+            .count();
+        assert_eq!(250, high_samples);
+
+        // At 4800 Hz, each cycle is 10 samples.
+        let samples: Vec<f32> = Oscillator::new(&config(ToneWaveform::Sawtooth, 0.5), 4800)
+            .take(10)
+            .collect();
+        assert_eq!(-1.0, samples[0]);
+        assert!((samples[5] - 0.0).abs() < 1e-6);
+
+        let samples: Vec<f32> = Oscillator::new(&config(ToneWaveform::Triangle, 0.5), 4800)
+            .take(10)
+            .collect();
+        assert_eq!(-1.0, samples[0]);
+        assert!((samples[5] - 1.0).abs() < 1e-6);
+
+        for waveform in [
+            ToneWaveform::Sine,
+            ToneWaveform::Triangle,
+            ToneWaveform::Sawtooth,
+        ] {
+            assert!(
+                Oscillator::new(&config(waveform, 0.5), 44100)
+                    .take(44100)
+                    .all(|sample| (-1.0..=1.0).contains(&sample))
+            );
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Runs a set of ROMs without a window, each for a number of frames, and reports how each one
+//! ended, for checking a collection of ROMs or comparing builds of the emulator across one.
+//!
+//! Every ROM gets a fresh emulator with the same config and seeded randomness, so the same ROMs
+//! always give the same report. Pauses, such as from breaking on draws, are resumed straight away.
+
+use crate::backend::NullAudioSink;
+use crate::config::Config;
+use crate::emulator::EmulatorBuilder;
+use crate::lifecycle::ShutdownReason;
+use crate::observer::EmulatorObserver;
+use crate::tas;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BatchResult {
+    pub rom: String,
+    pub rom_sha1: Option<String>,
+    pub frames: usize,
+    pub framebuffer_sha1: String,
+    pub halt_reason: Option<String>,
+    // Each one only once, as "opcode at address".
+    pub unknown_opcodes: Vec<String>,
+}
+
+#[derive(Default)]
+struct UnknownOpcodeRecorder {
+    unknown_opcodes: Mutex<Vec<(u16, u16)>>,
+}
+
+impl EmulatorObserver for UnknownOpcodeRecorder {
+    fn on_unknown_opcode(&self, opcode: u16, address: u16) {
+        let mut unknown_opcodes = self.unknown_opcodes.lock().unwrap();
+
+        if !unknown_opcodes.contains(&(opcode, address)) {
+            unknown_opcodes.push((opcode, address));
+        }
+    }
+}
+
+/// Returns None if the emulator couldn't be built from the config.
+pub fn run_rom(config: &Config, path: &Path, frame_count: usize) -> Option<BatchResult> {
+    let emulator = EmulatorBuilder::new(config.clone())
+        .with_audio_sink(Box::new(NullAudioSink))
+        .build()?;
+
+    let recorder = Arc::new(UnknownOpcodeRecorder::default());
+    emulator.add_observer(recorder.clone());
+
+    let mut result = BatchResult {
+        rom: path.file_name().map_or_else(
+            || path.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        ),
+        rom_sha1: None,
+        frames: 0,
+        framebuffer_sha1: String::new(),
+        halt_reason: None,
+        unknown_opcodes: Vec::new(),
+    };
+
+    if let Err(e) = emulator.load_rom(&path.to_string_lossy()) {
+        result.halt_reason = Some(e.to_string());
+        return Some(result);
+    }
+
+    emulator.seed_randomness();
+
+    while result.frames < frame_count && emulator.is_running() {
+        if emulator.is_paused() {
+            emulator.resume();
+        }
+
+        emulator.execute_frame();
+        result.frames += 1;
+    }
+
+    result.rom_sha1 = emulator.get_rom_hash();
+    result.framebuffer_sha1 = tas::get_framebuffer_hash(&emulator.framebuffer());
+    result.halt_reason = match emulator.get_shutdown_reason() {
+        Some(ShutdownReason::Halted(error)) => Some(error.to_string()),
+        _ => None,
+    };
+    result.unknown_opcodes = recorder
+        .unknown_opcodes
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(opcode, address)| format!("{opcode:04X} at {address:03X}"))
+        .collect();
+
+    return Some(result);
+}
+
+fn format_csv_field(field: &str) -> String {
+    if !field.contains([',', '"', '\n']) {
+        return String::from(field);
+    }
+
+    return format!("\"{}\"", field.replace('"', "\"\""));
+}
+
+pub fn format_csv(results: &[BatchResult]) -> String {
+    let mut csv =
+        String::from("rom,rom_sha1,frames,framebuffer_sha1,halt_reason,unknown_opcodes\n");
+
+    for result in results {
+        let fields = [
+            result.rom.clone(),
+            result.rom_sha1.clone().unwrap_or_default(),
+            result.frames.to_string(),
+            result.framebuffer_sha1.clone(),
+            result.halt_reason.clone().unwrap_or_default(),
+            result.unknown_opcodes.join("; "),
+        ];
+
+        let fields: Vec<String> = fields.iter().map(|field| format_csv_field(field)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+
+    return csv;
+}
+
+pub fn format_json(results: &[BatchResult]) -> String {
+    return serde_json::to_string_pretty(results).unwrap_or_default() + "\n";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_run_rom_reports_unknown_opcodes_and_halts() {
+        let dir = env::temp_dir().join("chip8rust_batch_test");
+        fs::create_dir_all(&dir).unwrap();
+
+        // SYS 0x123 twice over; then RET with nothing to return to
+        let rom_path = dir.join("unknown, then halt.ch8");
+        fs::write(&rom_path, [0x01, 0x23, 0x01, 0x23, 0x00, 0xEE]).unwrap();
+
+        let mut config = config::generate_default_configs();
+        config.ram.allow_stack_overflow = false;
+        let result = run_rom(&config, &rom_path, 10).unwrap();
+
+        assert_eq!("unknown, then halt.ch8", result.rom);
+        assert_eq!(1, result.frames);
+        assert_eq!(
+            vec![String::from("0123 at 200"), String::from("0123 at 202")],
+            result.unknown_opcodes
+        );
+        assert_eq!(
+            Some(String::from("Stack overflowed while popping.")),
+            result.halt_reason
+        );
+
+        // The name has a comma in it, so it is quoted.
+        let csv = format_csv(&[result]);
+        let row = csv.lines().nth(1).unwrap();
+        assert!(row.starts_with("\"unknown, then halt.ch8\","));
+        assert!(row.ends_with(",Stack overflowed while popping.,0123 at 200; 0123 at 202"));
+
+        let missing = run_rom(&config, &dir.join("missing.ch8"), 10).unwrap();
+        assert_eq!(0, missing.frames);
+        assert!(missing.halt_reason.is_some());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
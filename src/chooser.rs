@@ -0,0 +1,380 @@
+use crate::overlay::{self, Canvas};
+use std::fs;
+use std::path::{Path, PathBuf};
+use winit::keyboard::KeyCode;
+use winit_input_helper::WinitInputHelper;
+
+const CLOSE_CHOOSER_KEY: KeyCode = KeyCode::Escape;
+const PREVIOUS_ENTRY_KEY: KeyCode = KeyCode::ArrowUp;
+const NEXT_ENTRY_KEY: KeyCode = KeyCode::ArrowDown;
+const SELECT_ENTRY_KEY: KeyCode = KeyCode::Enter;
+const PARENT_DIRECTORY_KEY: KeyCode = KeyCode::Backspace;
+
+// Octo cartridges (.gif) are built as they are loaded.
+pub const ROM_EXTENSIONS: [&str; 3] = ["ch8", "c8", "gif"];
+const VISIBLE_ENTRIES: usize = 20;
+const TEXT_SCALE: usize = 2;
+const ROW_HEIGHT: usize = 7 * TEXT_SCALE;
+const MENU_MARGIN: usize = 8;
+const NAME_COLUMN_CHARS: usize = 64;
+const BACKGROUND_COLOR: u32 = 0x101018;
+const SELECTED_ROW_COLOR: u32 = 0x303048;
+const TEXT_COLOR: u32 = 0xFFFFFF;
+const HINT_COLOR: u32 = 0x9999AA;
+const ERROR_COLOR: u32 = 0xFF6666;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum ChooserEntry {
+    Recent(PathBuf),
+    Parent(PathBuf),
+    Directory(PathBuf),
+    Rom(PathBuf),
+}
+
+pub struct RomChooser {
+    open: bool,
+    directory: PathBuf,
+    recent_programs: Vec<PathBuf>,
+    entries: Vec<ChooserEntry>,
+    selected: usize,
+    error: Option<String>,
+}
+
+impl Default for RomChooser {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+impl RomChooser {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            directory: PathBuf::from("."),
+            recent_programs: Vec::new(),
+            entries: Vec::new(),
+            selected: 0,
+            error: None,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        return self.open;
+    }
+
+    pub fn open(&mut self, directory: &Path, recent_programs: &[String], error: Option<String>) {
+        self.open = true;
+        self.error = error;
+
+        // ROMs that have since been moved or deleted are left out, rather than failing to load.
+        self.recent_programs = recent_programs
+            .iter()
+            .map(PathBuf::from)
+            .filter(|path| path.is_file())
+            .collect();
+
+        self.change_directory(directory);
+
+        if !self.recent_programs.is_empty() {
+            self.selected = 0;
+        }
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+        self.error = None;
+    }
+
+    pub fn show_error(&mut self, error: String) {
+        self.error = Some(error);
+    }
+
+    // Returns the ROM picked by the user, if any. It can only be closed without picking one if
+    // there is already a program to go back to.
+    pub fn update(&mut self, input: &WinitInputHelper, can_close: bool) -> Option<PathBuf> {
+        if input.key_pressed(CLOSE_CHOOSER_KEY) && can_close {
+            self.close();
+        } else if input.key_pressed(PREVIOUS_ENTRY_KEY) && !self.entries.is_empty() {
+            self.selected = (self.selected + self.entries.len() - 1) % self.entries.len();
+        } else if input.key_pressed(NEXT_ENTRY_KEY) && !self.entries.is_empty() {
+            self.selected = (self.selected + 1) % self.entries.len();
+        } else if input.key_pressed(PARENT_DIRECTORY_KEY) {
+            let parent = self.entries.iter().find_map(|entry| match entry {
+                ChooserEntry::Parent(parent) => Some(parent.clone()),
+                _ => None,
+            });
+
+            if let Some(parent) = parent {
+                self.change_directory(&parent);
+            }
+        } else if input.key_pressed(SELECT_ENTRY_KEY) {
+            match self.entries.get(self.selected).cloned() {
+                Some(ChooserEntry::Parent(directory) | ChooserEntry::Directory(directory)) => {
+                    self.change_directory(&directory);
+                }
+                Some(ChooserEntry::Recent(path) | ChooserEntry::Rom(path)) => return Some(path),
+                None => (),
+            }
+        }
+
+        return None;
+    }
+
+    fn change_directory(&mut self, directory: &Path) {
+        self.directory = directory
+            .canonicalize()
+            .unwrap_or_else(|_| directory.to_path_buf());
+        self.selected = 0;
+
+        self.entries = self
+            .recent_programs
+            .iter()
+            .cloned()
+            .map(ChooserEntry::Recent)
+            .collect();
+
+        match list_entries(&self.directory) {
+            Ok(entries) => self.entries.extend(entries),
+            Err(e) => {
+                eprintln!(
+                    "Error: Could not list ROMs in {} ({e}).",
+                    self.directory.display()
+                );
+                self.error = Some(format!("Could not open {}.", self.directory.display()));
+            }
+        }
+
+        // Start on the first ROM in the directory, rather than the directories above it.
+        if let Some(first_rom) = self
+            .entries
+            .iter()
+            .position(|e| matches!(e, ChooserEntry::Rom(_)))
+        {
+            self.selected = first_rom;
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<'_>) {
+        let (_, text_height) = overlay::get_text_size("", TEXT_SCALE);
+        let text_offset = (ROW_HEIGHT - text_height) / 2;
+        let (menu_width, _) = overlay::get_text_size(&" ".repeat(NAME_COLUMN_CHARS), TEXT_SCALE);
+        let menu_width = menu_width + MENU_MARGIN * 2;
+        let row_count = VISIBLE_ENTRIES + 5;
+
+        canvas.fill_rect(
+            MENU_MARGIN,
+            MENU_MARGIN,
+            menu_width,
+            ROW_HEIGHT * row_count + MENU_MARGIN * 2,
+            BACKGROUND_COLOR,
+        );
+
+        let mut y = MENU_MARGIN * 2;
+
+        overlay::draw_text(
+            canvas,
+            MENU_MARGIN * 2,
+            y + text_offset,
+            "Open ROM - enter to open, backspace to go up, escape to close",
+            TEXT_SCALE,
+            HINT_COLOR,
+        );
+
+        y += ROW_HEIGHT;
+
+        overlay::draw_text(
+            canvas,
+            MENU_MARGIN * 2,
+            y + text_offset,
+            &truncate_start(&self.directory.display().to_string(), NAME_COLUMN_CHARS),
+            TEXT_SCALE,
+            HINT_COLOR,
+        );
+
+        y += ROW_HEIGHT * 2;
+
+        let first_visible = self
+            .selected
+            .saturating_sub(VISIBLE_ENTRIES - 1)
+            .min(self.entries.len().saturating_sub(VISIBLE_ENTRIES));
+
+        for (i, entry) in self
+            .entries
+            .iter()
+            .enumerate()
+            .skip(first_visible)
+            .take(VISIBLE_ENTRIES)
+        {
+            if i == self.selected {
+                canvas.fill_rect(MENU_MARGIN, y, menu_width, ROW_HEIGHT, SELECTED_ROW_COLOR);
+            }
+
+            overlay::draw_text(
+                canvas,
+                MENU_MARGIN * 2,
+                y + text_offset,
+                &get_entry_name(entry),
+                TEXT_SCALE,
+                TEXT_COLOR,
+            );
+
+            y += ROW_HEIGHT;
+        }
+
+        if self.entries.len() == self.recent_programs.len() {
+            overlay::draw_text(
+                canvas,
+                MENU_MARGIN * 2,
+                y + text_offset,
+                "No ROMs found here.",
+                TEXT_SCALE,
+                HINT_COLOR,
+            );
+        }
+
+        if let Some(error) = &self.error {
+            overlay::draw_text(
+                canvas,
+                MENU_MARGIN * 2,
+                MENU_MARGIN * 2 + ROW_HEIGHT * (row_count - 1) + text_offset,
+                &truncate_start(error, NAME_COLUMN_CHARS),
+                TEXT_SCALE,
+                ERROR_COLOR,
+            );
+        }
+    }
+}
+
+fn list_entries(directory: &Path) -> std::io::Result<Vec<ChooserEntry>> {
+    let mut directories = Vec::new();
+    let mut roms = Vec::new();
+
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            directories.push(path);
+        } else if is_rom(&path) {
+            roms.push(path);
+        }
+    }
+
+    directories.sort();
+    roms.sort();
+
+    let mut entries = Vec::new();
+
+    if let Some(parent) = directory.parent() {
+        entries.push(ChooserEntry::Parent(parent.to_path_buf()));
+    }
+
+    entries.extend(directories.into_iter().map(ChooserEntry::Directory));
+    entries.extend(roms.into_iter().map(ChooserEntry::Rom));
+
+    return Ok(entries);
+}
+
+fn is_rom(path: &Path) -> bool {
+    return path.extension().is_some_and(|extension| {
+        ROM_EXTENSIONS
+            .iter()
+            .any(|rom_extension| extension.eq_ignore_ascii_case(rom_extension))
+    });
+}
+
+fn get_entry_name(entry: &ChooserEntry) -> String {
+    let file_name = |path: &Path| {
+        path.file_name()
+            .map_or_else(String::new, |n| n.to_string_lossy().into_owned())
+    };
+
+    let name = match entry {
+        ChooserEntry::Recent(path) => format!("recent: {}", file_name(path)),
+        ChooserEntry::Parent(_) => String::from(".."),
+        ChooserEntry::Directory(path) => format!("{}/", file_name(path)),
+        ChooserEntry::Rom(path) => file_name(path),
+    };
+
+    return truncate_start(&name, NAME_COLUMN_CHARS);
+}
+
+// Long paths keep their end, which is the part that tells them apart.
+fn truncate_start(text: &str, max_chars: usize) -> String {
+    let char_count = text.chars().count();
+
+    if char_count <= max_chars {
+        return String::from(text);
+    }
+
+    let kept: String = text.chars().skip(char_count - (max_chars - 3)).collect();
+    return format!("...{kept}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_entries() {
+        let directory = std::env::temp_dir().join("chip8rust_test_list_entries");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(directory.join("games")).unwrap();
+        fs::write(directory.join("b.ch8"), [0x00]).unwrap();
+        fs::write(directory.join("a.CH8"), [0x00]).unwrap();
+        fs::write(directory.join("notes.txt"), [0x00]).unwrap();
+
+        let entries = list_entries(&directory).unwrap();
+
+        assert_eq!(
+            vec![
+                ChooserEntry::Parent(std::env::temp_dir()),
+                ChooserEntry::Directory(directory.join("games")),
+                ChooserEntry::Rom(directory.join("a.CH8")),
+                ChooserEntry::Rom(directory.join("b.ch8")),
+            ],
+            entries
+        );
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_open_lists_recent_programs_first() {
+        let directory = std::env::temp_dir().join("chip8rust_test_recent_programs");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("recent.ch8"), [0x00]).unwrap();
+
+        let recent_program = directory.join("recent.ch8");
+        let missing_program = directory.join("missing.ch8");
+
+        let mut chooser = RomChooser::new();
+        chooser.open(
+            &directory,
+            &[
+                missing_program.to_string_lossy().into_owned(),
+                recent_program.to_string_lossy().into_owned(),
+            ],
+            None,
+        );
+
+        assert_eq!(
+            Some(&ChooserEntry::Recent(recent_program.clone())),
+            chooser.entries.get(chooser.selected)
+        );
+        assert_eq!(
+            Some(&ChooserEntry::Rom(
+                directory.canonicalize().unwrap().join("recent.ch8")
+            )),
+            chooser.entries.last()
+        );
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_truncate_start() {
+        assert_eq!("short", truncate_start("short", 8));
+        assert_eq!("...ng/path", truncate_start("a/long/path", 10));
+    }
+}
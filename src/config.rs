@@ -1,101 +1,243 @@
+use crate::macros;
+use crate::ram::PROGRAM_START_ADDRESS;
 use serde::Deserialize;
 use serde_with::serde_as;
+use std::collections::BTreeMap;
 use std::fs;
+use std::path::Path;
 use toml;
-use winit::keyboard::{Key, SmolStr};
 
-const CONFIG_FILE_PATH: &str = "config.toml";
+pub const CONFIG_FILE_PATH: &str = "config.toml";
+pub const DEFAULT_CONFIG: &str = include_str!("../config.toml");
+const ENV_VAR_PREFIX: &str = "CHIP8_";
+const MAX_STACK_SIZE: usize = 1024;
+const MAX_FONT_STARTING_ADDRESS: u16 = 0xFB0;
+const MAX_BIG_FONT_STARTING_ADDRESS: u16 = 0xF60;
+const CONFIG_SECTIONS: [&str; 8] = [
+    "cpu",
+    "gpu",
+    "input",
+    "ram",
+    "delay_timer",
+    "sound_timer",
+    "threads",
+    "rewind",
+];
+const ENV_VAR_ALIASES: [(&str, &str); 1] = [("CPU_IPS", "CPU_INSTRUCTIONS_PER_SECOND")];
+// The fields that can be left out of their section, so overrides can add them when they are.
+const OPTIONAL_FIELDS: [(&str, &str); 4] = [
+    ("gpu", "render_heartbeat_frequency"),
+    ("gpu", "collision_reporting"),
+    ("gpu", "monitor"),
+    ("ram", "stack_warning_depth"),
+];
 
-#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
 pub enum Preset {
     CHIP8,
+    SCHIP,
     Custom,
 }
 
 #[serde_as]
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Config {
     pub preset: Preset,
+    pub resume_last_program: bool,
+    pub auto_save_state: bool,
     pub cpu: CPUConfig,
     pub gpu: GPUConfig,
     pub input: InputConfig,
     pub ram: RAMConfig,
     pub delay_timer: DelayTimerConfig,
     pub sound_timer: SoundTimerConfig,
+    pub threads: ThreadsConfig,
+    pub rewind: RewindConfig,
+    pub roms: BTreeMap<String, RomConfig>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct CPUConfig {
     pub instructions_per_second: f64,
+    pub unlimited_speed: bool,
+    pub timing_jitter: f64,
+    pub timing_jitter_seed: u64,
     pub reset_flag_for_bitwise_operations: bool,
     pub use_new_shift_instruction: bool,
     pub use_new_jump_instruction: bool,
     pub set_flag_for_index_overflow: bool,
+    pub write_flag_before_result: bool,
     pub move_index_with_reads: bool,
     pub limit_to_one_draw_per_frame: bool,
-    pub allow_program_counter_overflow: bool,
+    pub break_on_draw: BreakOnDraw,
+    pub unknown_opcode_policy: UnknownOpcodePolicy,
+    pub program_counter_overflow_policy: ProgramCounterOverflowPolicy,
     pub use_true_randomness: bool,
     pub fake_randomness_seed: u64,
-    pub allow_index_register_overflow: bool,
+    pub index_register_overflow_policy: IndexRegisterOverflowPolicy,
 }
 
-#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakOnDraw {
+    Off,
+    Always,
+    Collision,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum UnknownOpcodePolicy {
+    Skip,
+    Halt,
+    Pause,
+    Plugin,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SpriteEdgePolicy {
+    Wrap,
+    Clip,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgramCounterOverflowPolicy {
+    Wrap,
+    Halt,
+    Restart,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexRegisterOverflowPolicy {
+    Wrap,
+    Halt,
+    Mask,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum RenderOccasion {
     Changes,
     Frequency,
+    Vsync,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionReporting {
+    #[default]
+    Flag,
+    RowCount,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Palette {
+    Custom,
+    HighContrast,
+    Deuteranopia,
+}
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct GPUConfig {
+    pub palette: Palette,
     pub pixel_color_when_active: u32,
     pub pixel_color_when_inactive: u32,
     pub screen_border_color: u32,
+    pub change_border_color_when_beeping: bool,
+    pub screen_border_color_when_beeping: u32,
+    pub invert_display_when_beeping: bool,
+    pub invert_border_with_colors: bool,
     pub horizontal_resolution: usize,
     pub vertical_resolution: usize,
     pub wrap_sprite_positions: bool,
-    pub wrap_sprite_pixels: bool,
+    pub horizontal_sprite_edge_policy: SpriteEdgePolicy,
+    pub vertical_sprite_edge_policy: SpriteEdgePolicy,
     pub render_occasion: RenderOccasion,
     pub render_frequency: f64,
+    pub render_heartbeat_frequency: Option<f64>,
+    pub tick_timers_on_refresh: bool,
+    // Filled in from the preset when left out.
+    pub collision_reporting: Option<CollisionReporting>,
+    pub display_rotation: u16,
+    pub pixel_aspect_ratio: [usize; 2],
+    pub show_frame_pacing_overlay: bool,
+    pub show_status_bar: bool,
+    pub monitor: Option<String>,
 }
 
-fn deserialize_keys<'de, D>(deserializer: D) -> Result<[Key<SmolStr>; 16], D::Error>
+fn deserialize_keys<'de, D>(deserializer: D) -> Result<[String; 16], D::Error>
 where
     D: serde::Deserializer<'de>,
 {
     let vec = Vec::<String>::deserialize(deserializer)?;
     return vec
-        .into_iter()
-        .map(|key| Key::Character(SmolStr::new(key)))
-        .collect::<Vec<_>>()
         .try_into()
         .map_err(|_| serde::de::Error::custom("expected exactly 16 keys"));
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyTriggerMode {
+    Held,
+    Edge,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct InputMacro {
+    pub hotkey: String,
+    pub inputs: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct InputConfig {
     #[serde(deserialize_with = "deserialize_keys")]
-    pub key_bindings: [Key<SmolStr>; 16],
+    pub key_bindings: [String; 16],
+    pub key_trigger_mode: KeyTriggerMode,
+    pub macros: Vec<InputMacro>,
 }
 
 #[serde_as]
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct RAMConfig {
     pub stack_size: usize,
+    pub stack_mode: StackMode,
+    pub stack_warning_depth: Option<usize>,
     pub allow_stack_overflow: bool,
-    pub allow_heap_overflow: bool,
+    pub heap_overflow_policy: HeapOverflowPolicy,
     pub font_starting_address: u16,
     #[serde_as(as = "[_; 80]")]
     pub font_data: [u8; 80],
+    pub big_font_starting_address: u16,
+    #[serde_as(as = "[_; 160]")]
+    pub big_font_data: [u8; 160],
+}
+
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum HeapOverflowPolicy {
+    Wrap,
+    Ignore,
+    Halt,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum StackMode {
+    Fixed,
+    Growing,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct DelayTimerConfig {
     pub delay_timer_decrement_rate: f64,
 }
 
-#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum ToneWaveform {
     Sine,
@@ -104,31 +246,452 @@ pub enum ToneWaveform {
     Sawtooth,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioBackend {
+    Rodio,
+    Cpal,
+}
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct SoundTimerConfig {
     pub sound_timer_decrement_rate: f64,
     pub tone_frequency: f32,
     pub tone_waveform: ToneWaveform,
+    pub square_duty_cycle: f32,
+    pub mute_during_turbo: bool,
+    pub minimum_tone_length: f64,
+    pub sample_rate: u32,
+    pub buffer_size: u32,
+    pub audio_backend: AudioBackend,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ThreadPriority {
+    Low,
+    Normal,
+    High,
 }
 
-pub fn generate_configs() -> Option<Config> {
-    let Ok(raw_config) = fs::read_to_string(CONFIG_FILE_PATH) else {
-        eprintln!("Error: Could not read config.toml at {}", CONFIG_FILE_PATH);
+#[derive(Deserialize, Debug, Clone)]
+pub struct ThreadsConfig {
+    pub cpu_thread_priority: ThreadPriority,
+    pub cpu_thread_cores: Vec<usize>,
+    pub watchdog_timeout: f64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RewindConfig {
+    pub enabled: bool,
+    pub history_length: f64,
+    pub snapshot_interval: f64,
+    pub keyframe_interval: usize,
+}
+
+// Applied over the rest of the config when the ROM with this SHA-1 is loaded.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct RomConfig {
+    pub instructions_per_second: Option<f64>,
+    pub limit_to_one_draw_per_frame: Option<bool>,
+}
+
+pub fn generate_default_configs() -> Config {
+    let mut config: Config =
+        toml::from_str(DEFAULT_CONFIG).expect("The default config should always be valid.");
+
+    apply_preset(&mut config);
+
+    return config;
+}
+
+pub fn generate_configs_from(path: &str, profile: Option<&str>) -> Option<Config> {
+    let Ok(raw_config) = fs::read_to_string(path) else {
+        eprintln!("Error: Could not read config at {}", path);
         return None;
     };
 
-    let mut config: Config = toml::from_str(&raw_config)
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    let parsed_config = match extension.as_str() {
+        "json" => serde_json::from_str(&raw_config).map_err(|e| e.to_string()),
+        "yaml" | "yml" => serde_yaml::from_str(&raw_config).map_err(|e| e.to_string()),
+        _ => toml::from_str(&raw_config).map_err(|e| e.to_string()),
+    };
+
+    let mut raw_table: toml::Table = parsed_config
         .map_err(|err| {
-            eprintln!("Error: Could not parse config.toml ({}).", err);
+            eprintln!("Error: Could not parse {} ({}).", path, err);
         })
         .ok()?;
 
+    let profiles = raw_table.remove("profile");
+
+    if let Some(profile) = profile {
+        let Some(toml::Value::Table(profile_table)) =
+            profiles.as_ref().and_then(|p| p.get(profile)).cloned()
+        else {
+            eprintln!("Error: Could not find profile {} in {}.", profile, path);
+            return None;
+        };
+
+        merge_tables(&mut raw_table, profile_table);
+    }
+
+    apply_env_overrides(&mut raw_table, std::env::vars());
+
+    let mut config: Config = toml::Value::Table(raw_table)
+        .try_into()
+        .map_err(|err| {
+            eprintln!("Error: Could not parse {} ({}).", path, err);
+        })
+        .ok()?;
+
+    apply_preset(&mut config);
+
+    let problems = validate_config(&config);
+
+    for problem in &problems {
+        eprintln!("Error: {problem}");
+    }
+
+    if !problems.is_empty() {
+        eprintln!("Error: Found {} problem(s) in {}.", problems.len(), path);
+        return None;
+    }
+
+    warn_about_config(&config);
+
+    return Some(config);
+}
+
+fn merge_tables(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_section)), toml::Value::Table(section)) => {
+                merge_tables(base_section, section)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn apply_env_overrides(raw_table: &mut toml::Table, vars: impl Iterator<Item = (String, String)>) {
+    for (name, raw_value) in vars {
+        let Some(key) = name.strip_prefix(ENV_VAR_PREFIX) else {
+            continue;
+        };
+
+        let key = ENV_VAR_ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == key)
+            .map_or(key, |(_, full)| full)
+            .to_ascii_lowercase();
+
+        let section_and_field = CONFIG_SECTIONS.iter().find_map(|section| {
+            let field = key.strip_prefix(section)?.strip_prefix('_')?;
+            return Some((*section, field));
+        });
+
+        let optional = section_and_field.is_some_and(|field| OPTIONAL_FIELDS.contains(&field));
+
+        let (table, field) = match section_and_field {
+            Some((section, field)) => (
+                raw_table.get_mut(section).and_then(|s| s.as_table_mut()),
+                field,
+            ),
+            None => (Some(&mut *raw_table), key.as_str()),
+        };
+
+        let Some(table) = table.filter(|t| optional || t.contains_key(field)) else {
+            eprintln!("Warning: Ignoring unknown config override {name}.");
+            continue;
+        };
+
+        let value = toml::from_str::<toml::Table>(&format!("value = {raw_value}"))
+            .ok()
+            .and_then(|mut t| t.remove("value"))
+            .unwrap_or(toml::Value::String(raw_value));
+
+        table.insert(field.to_string(), value);
+    }
+}
+
+pub fn check_config(path: &str, profile: Option<&str>) -> bool {
+    if generate_configs_from(path, profile).is_none() {
+        return false;
+    }
+
+    println!("No problems found in {path}.");
+    return true;
+}
+
+pub fn validate_config(config: &Config) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if config.cpu.instructions_per_second <= 0.0 {
+        problems.push(format!(
+            "cpu.instructions_per_second must be greater than 0 instructions per second, but is {}.",
+            config.cpu.instructions_per_second
+        ));
+    }
+
+    if !(0.0..1.0).contains(&config.cpu.timing_jitter) {
+        problems.push(format!(
+            "cpu.timing_jitter must be at least 0 and less than 1, but is {}.",
+            config.cpu.timing_jitter
+        ));
+    }
+
+    for (field, resolution) in [
+        ("horizontal_resolution", config.gpu.horizontal_resolution),
+        ("vertical_resolution", config.gpu.vertical_resolution),
+    ] {
+        if resolution == 0 {
+            problems.push(format!(
+                "gpu.{field} must be at least 1 pixel, but is 0 pixels."
+            ));
+        }
+    }
+
+    for (field, color) in [
+        (
+            "pixel_color_when_active",
+            config.gpu.pixel_color_when_active,
+        ),
+        (
+            "pixel_color_when_inactive",
+            config.gpu.pixel_color_when_inactive,
+        ),
+        ("screen_border_color", config.gpu.screen_border_color),
+        (
+            "screen_border_color_when_beeping",
+            config.gpu.screen_border_color_when_beeping,
+        ),
+    ] {
+        if color > 0xFFFFFF {
+            problems.push(format!(
+                "gpu.{field} must be a 24-bit RGB color from 0x000000 to 0xFFFFFF, but is {color:#08X}."
+            ));
+        }
+    }
+
+    if config.gpu.render_occasion == RenderOccasion::Frequency && config.gpu.render_frequency <= 0.0
+    {
+        problems.push(format!(
+            "gpu.render_frequency must be greater than 0 Hz when rendering at a frequency, but is {} Hz.",
+            config.gpu.render_frequency
+        ));
+    }
+
+    if let Some(frequency) = config.gpu.render_heartbeat_frequency
+        && frequency <= 0.0
+    {
+        problems.push(format!(
+            "gpu.render_heartbeat_frequency must be greater than 0 Hz, but is {frequency} Hz."
+        ));
+    }
+
+    if ![0, 90, 180, 270].contains(&config.gpu.display_rotation) {
+        problems.push(format!(
+            "gpu.display_rotation must be 0, 90, 180 or 270 degrees, but is {} degrees.",
+            config.gpu.display_rotation
+        ));
+    }
+
+    if config.gpu.pixel_aspect_ratio.contains(&0) {
+        problems.push(format!(
+            "gpu.pixel_aspect_ratio must have a width and height of at least 1, but is {:?}.",
+            config.gpu.pixel_aspect_ratio
+        ));
+    }
+
+    for (i, key) in config.input.key_bindings.iter().enumerate() {
+        if let Some(j) = config.input.key_bindings[..i].iter().position(|k| k == key) {
+            problems.push(format!(
+                "input.key_bindings[{i:#X}] must be unique, but {key:?} is already bound to input.key_bindings[{j:#X}]."
+            ));
+        }
+    }
+
+    for (i, input_macro) in config.input.macros.iter().enumerate() {
+        if config.input.key_bindings.contains(&input_macro.hotkey) {
+            problems.push(format!(
+                "input.macros[{i}].hotkey must not be a key binding, but {:?} is already bound to an input.",
+                input_macro.hotkey
+            ));
+        }
+
+        if macros::parse_frames(&input_macro.inputs).is_none() {
+            problems.push(format!(
+                "input.macros[{i}].inputs must be frames of hex keys or -, but is {:?}.",
+                input_macro.inputs
+            ));
+        }
+    }
+
+    if config.ram.stack_size == 0 || config.ram.stack_size > MAX_STACK_SIZE {
+        problems.push(format!(
+            "ram.stack_size must be from 1 to {MAX_STACK_SIZE} entries, but is {} entries.",
+            config.ram.stack_size
+        ));
+    }
+
+    if config.ram.stack_warning_depth == Some(0) {
+        problems.push(String::from(
+            "ram.stack_warning_depth must be at least 1 entry, but is 0 entries.",
+        ));
+    }
+
+    if config.ram.font_starting_address > MAX_FONT_STARTING_ADDRESS {
+        problems.push(format!(
+            "ram.font_starting_address must be from 0x000 to {MAX_FONT_STARTING_ADDRESS:#05X}, so that all 80 bytes fit in memory, but is {:#05X}.",
+            config.ram.font_starting_address
+        ));
+    }
+
+    if config.ram.big_font_starting_address > MAX_BIG_FONT_STARTING_ADDRESS {
+        problems.push(format!(
+            "ram.big_font_starting_address must be from 0x000 to {MAX_BIG_FONT_STARTING_ADDRESS:#05X}, so that all 160 bytes fit in memory, but is {:#05X}.",
+            config.ram.big_font_starting_address
+        ));
+    }
+
+    let font = config.ram.font_starting_address..config.ram.font_starting_address + 80;
+    let big_font = config.ram.big_font_starting_address..config.ram.big_font_starting_address + 160;
+
+    if font.start < big_font.end && big_font.start < font.end {
+        problems.push(format!(
+            "ram.big_font_starting_address must leave room for the 80 bytes of the font at {:#05X}, but is {:#05X}.",
+            config.ram.font_starting_address, config.ram.big_font_starting_address
+        ));
+    }
+
+    for (field, rate) in [
+        (
+            "delay_timer.delay_timer_decrement_rate",
+            config.delay_timer.delay_timer_decrement_rate,
+        ),
+        (
+            "sound_timer.sound_timer_decrement_rate",
+            config.sound_timer.sound_timer_decrement_rate,
+        ),
+        (
+            "sound_timer.tone_frequency",
+            config.sound_timer.tone_frequency as f64,
+        ),
+    ] {
+        if rate <= 0.0 {
+            problems.push(format!(
+                "{field} must be greater than 0 Hz, but is {rate} Hz."
+            ));
+        }
+    }
+
+    if !(config.sound_timer.square_duty_cycle > 0.0 && config.sound_timer.square_duty_cycle < 1.0) {
+        problems.push(format!(
+            "sound_timer.square_duty_cycle must be between 0 and 1, but is {}.",
+            config.sound_timer.square_duty_cycle
+        ));
+    }
+
+    if config.sound_timer.minimum_tone_length < 0.0 {
+        problems.push(format!(
+            "sound_timer.minimum_tone_length must be at least 0 seconds, but is {} seconds.",
+            config.sound_timer.minimum_tone_length
+        ));
+    }
+
+    if config.threads.watchdog_timeout < 0.0 {
+        problems.push(format!(
+            "threads.watchdog_timeout must be at least 0 seconds, but is {} seconds.",
+            config.threads.watchdog_timeout
+        ));
+    }
+
+    for (field, seconds) in [
+        ("history_length", config.rewind.history_length),
+        ("snapshot_interval", config.rewind.snapshot_interval),
+    ] {
+        if seconds <= 0.0 {
+            problems.push(format!(
+                "rewind.{field} must be greater than 0 seconds, but is {seconds} seconds."
+            ));
+        }
+    }
+
+    if config.rewind.keyframe_interval == 0 {
+        problems.push(String::from(
+            "rewind.keyframe_interval must be at least 1 snapshot, but is 0 snapshots.",
+        ));
+    }
+
+    for (rom_hash, rom_config) in &config.roms {
+        if rom_hash.len() != 40 || !rom_hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            problems.push(format!(
+                "roms.{rom_hash} must be named after the 40 hex digit SHA-1 of a ROM."
+            ));
+        }
+
+        if let Some(instructions_per_second) = rom_config.instructions_per_second
+            && instructions_per_second <= 0.0
+        {
+            problems.push(format!(
+                "roms.{rom_hash}.instructions_per_second must be greater than 0 instructions per second, but is {instructions_per_second}."
+            ));
+        }
+    }
+
+    return problems;
+}
+
+fn warn_about_config(config: &Config) {
+    let font_end_address = config.ram.font_starting_address as usize + 80;
+
+    if font_end_address > PROGRAM_START_ADDRESS as usize {
+        eprintln!(
+            "Warning: ram.font_starting_address is {:#05X}, so the font overlaps programs loaded at {PROGRAM_START_ADDRESS:#05X} (use 0x000 to 0x1B0 to avoid this).",
+            config.ram.font_starting_address
+        );
+    }
+
+    let big_font_end_address = config.ram.big_font_starting_address as usize + 160;
+
+    if big_font_end_address > PROGRAM_START_ADDRESS as usize {
+        eprintln!(
+            "Warning: ram.big_font_starting_address is {:#05X}, so the big font overlaps programs loaded at {PROGRAM_START_ADDRESS:#05X} (use 0x000 to 0x160 to avoid this).",
+            config.ram.big_font_starting_address
+        );
+    }
+
+    if config.gpu.tick_timers_on_refresh && config.gpu.render_occasion != RenderOccasion::Vsync {
+        eprintln!(
+            "Warning: gpu.tick_timers_on_refresh only applies when gpu.render_occasion is \"vsync\", so it is ignored."
+        );
+    }
+}
+
+fn apply_preset(config: &mut Config) {
     match config.preset {
-        Preset::CHIP8 => enable_chip8_preset(&mut config),
+        Preset::CHIP8 => enable_chip8_preset(config),
+        Preset::SCHIP => enable_schip_preset(config),
         Preset::Custom => (),
     }
 
-    return Some(config);
+    // If set in the file, this overrides the preset's collision reporting.
+    config
+        .gpu
+        .collision_reporting
+        .get_or_insert(match config.preset {
+            Preset::SCHIP => CollisionReporting::RowCount,
+            Preset::CHIP8 | Preset::Custom => CollisionReporting::Flag,
+        });
 }
 
 fn enable_chip8_preset(config: &mut Config) {
@@ -136,15 +699,39 @@ fn enable_chip8_preset(config: &mut Config) {
     config.cpu.use_new_shift_instruction = false;
     config.cpu.use_new_jump_instruction = false;
     config.cpu.set_flag_for_index_overflow = false;
+    config.cpu.write_flag_before_result = false;
     config.cpu.move_index_with_reads = true;
     config.cpu.limit_to_one_draw_per_frame = true;
     config.gpu.horizontal_resolution = 64;
     config.gpu.vertical_resolution = 32;
     config.gpu.wrap_sprite_positions = true;
-    config.gpu.wrap_sprite_pixels = false;
+    config.gpu.horizontal_sprite_edge_policy = SpriteEdgePolicy::Clip;
+    config.gpu.vertical_sprite_edge_policy = SpriteEdgePolicy::Clip;
     config.gpu.render_occasion = RenderOccasion::Frequency;
     config.gpu.render_frequency = 60.0;
     config.ram.stack_size = 16;
+    config.ram.stack_mode = StackMode::Fixed;
+    config.delay_timer.delay_timer_decrement_rate = 60.0;
+    config.sound_timer.sound_timer_decrement_rate = 60.0;
+}
+
+fn enable_schip_preset(config: &mut Config) {
+    config.cpu.reset_flag_for_bitwise_operations = false;
+    config.cpu.use_new_shift_instruction = true;
+    config.cpu.use_new_jump_instruction = true;
+    config.cpu.set_flag_for_index_overflow = false;
+    config.cpu.write_flag_before_result = false;
+    config.cpu.move_index_with_reads = false;
+    config.cpu.limit_to_one_draw_per_frame = false;
+    config.gpu.horizontal_resolution = 128;
+    config.gpu.vertical_resolution = 64;
+    config.gpu.wrap_sprite_positions = true;
+    config.gpu.horizontal_sprite_edge_policy = SpriteEdgePolicy::Clip;
+    config.gpu.vertical_sprite_edge_policy = SpriteEdgePolicy::Clip;
+    config.gpu.render_occasion = RenderOccasion::Frequency;
+    config.gpu.render_frequency = 60.0;
+    config.ram.stack_size = 16;
+    config.ram.stack_mode = StackMode::Fixed;
     config.delay_timer.delay_timer_decrement_rate = 60.0;
     config.sound_timer.sound_timer_decrement_rate = 60.0;
 }
@@ -155,6 +742,166 @@ mod tests {
 
     #[test]
     fn test_generate_configs() {
-        let _ = generate_configs().unwrap();
+        let _ = generate_configs_from(CONFIG_FILE_PATH, None).unwrap();
+    }
+
+    #[test]
+    fn test_generate_default_configs() {
+        let config = generate_default_configs();
+        assert!(validate_config(&config).is_empty());
+    }
+
+    #[test]
+    fn test_generate_configs_from_json() {
+        let toml_config: toml::Value =
+            toml::from_str(&fs::read_to_string(CONFIG_FILE_PATH).unwrap()).unwrap();
+        let config_path = std::env::temp_dir().join(format!(
+            "chip8rust_test_configs_from_json_{}.json",
+            std::process::id()
+        ));
+        fs::write(&config_path, serde_json::to_string(&toml_config).unwrap()).unwrap();
+
+        let config = generate_configs_from(config_path.to_str().unwrap(), None);
+
+        fs::remove_file(config_path).unwrap();
+
+        assert!(config.is_some());
+    }
+
+    #[test]
+    fn test_generate_configs_from_yaml() {
+        let toml_config: toml::Value =
+            toml::from_str(&fs::read_to_string(CONFIG_FILE_PATH).unwrap()).unwrap();
+        let config_path = std::env::temp_dir().join(format!(
+            "chip8rust_test_configs_from_yaml_{}.yaml",
+            std::process::id()
+        ));
+        fs::write(&config_path, serde_yaml::to_string(&toml_config).unwrap()).unwrap();
+
+        let config = generate_configs_from(config_path.to_str().unwrap(), None);
+
+        fs::remove_file(config_path).unwrap();
+
+        assert!(config.is_some());
+    }
+
+    #[test]
+    fn test_generate_configs_with_profile() {
+        let config = generate_configs_from(CONFIG_FILE_PATH, Some("fast")).unwrap();
+        assert_eq!(2000.0, config.cpu.instructions_per_second);
+        assert!(generate_configs_from(CONFIG_FILE_PATH, Some("missing")).is_none());
+    }
+
+    #[test]
+    fn test_merge_tables() {
+        let mut base: toml::Table = toml::from_str("a = 1\n[s]\nb = 2\nc = 3").unwrap();
+        let overrides: toml::Table = toml::from_str("a = 4\n[s]\nc = 5").unwrap();
+        let ideal: toml::Table = toml::from_str("a = 4\n[s]\nb = 2\nc = 5").unwrap();
+
+        merge_tables(&mut base, overrides);
+
+        assert_eq!(ideal, base);
+    }
+
+    #[test]
+    fn test_apply_env_overrides() {
+        let mut raw_table: toml::Table =
+            toml::from_str(&fs::read_to_string(CONFIG_FILE_PATH).unwrap()).unwrap();
+
+        let vars = [
+            ("CHIP8_PRESET", "custom"),
+            ("CHIP8_CPU_IPS", "1000"),
+            ("CHIP8_GPU_PIXEL_ASPECT_RATIO", "[2, 1]"),
+            ("CHIP8_SOUND_TIMER_TONE_WAVEFORM", "sine"),
+            ("CHIP8_GPU_COLLISION_REPORTING", "row_count"),
+            ("CHIP8_GPU_MONITOR", "DP-1"),
+            ("CHIP8_GPU_NOT_A_FIELD", "true"),
+            ("CHIP8_NOT_A_FIELD", "true"),
+            ("HOME", "/root"),
+        ];
+
+        apply_env_overrides(
+            &mut raw_table,
+            vars.iter().map(|(k, v)| (k.to_string(), v.to_string())),
+        );
+
+        let config: Config = toml::Value::Table(raw_table).try_into().unwrap();
+
+        assert_eq!(Preset::Custom, config.preset);
+        assert_eq!(1000.0, config.cpu.instructions_per_second);
+        assert_eq!([2, 1], config.gpu.pixel_aspect_ratio);
+        assert_eq!(ToneWaveform::Sine, config.sound_timer.tone_waveform);
+        assert_eq!(
+            Some(CollisionReporting::RowCount),
+            config.gpu.collision_reporting
+        );
+        assert_eq!(Some("DP-1".to_string()), config.gpu.monitor);
+    }
+
+    #[test]
+    fn test_collision_reporting_from_preset() {
+        let mut config = generate_configs_from(CONFIG_FILE_PATH, None).unwrap();
+        assert_eq!(
+            Some(CollisionReporting::Flag),
+            config.gpu.collision_reporting
+        );
+
+        config.preset = Preset::SCHIP;
+        config.gpu.collision_reporting = None;
+        apply_preset(&mut config);
+        assert_eq!(
+            Some(CollisionReporting::RowCount),
+            config.gpu.collision_reporting
+        );
+        assert_eq!(128, config.gpu.horizontal_resolution);
+
+        // If set in the file, it overrides the preset's.
+        config.gpu.collision_reporting = Some(CollisionReporting::Flag);
+        apply_preset(&mut config);
+        assert_eq!(
+            Some(CollisionReporting::Flag),
+            config.gpu.collision_reporting
+        );
+    }
+
+    #[test]
+    fn test_validate_default_config() {
+        let config = generate_configs_from(CONFIG_FILE_PATH, None).unwrap();
+        assert!(validate_config(&config).is_empty());
+    }
+
+    #[test]
+    fn test_validate_invalid_config() {
+        let mut config = generate_configs_from(CONFIG_FILE_PATH, None).unwrap();
+        config.cpu.instructions_per_second = 0.0;
+        config.gpu.screen_border_color = 0x1000000;
+        config.input.key_bindings[5] = config.input.key_bindings[2].clone();
+        config.input.macros = vec![InputMacro {
+            hotkey: config.input.key_bindings[0].clone(),
+            inputs: String::from("5 5 - G"),
+        }];
+        config.ram.font_starting_address = 0xFC0;
+        config.roms.insert(
+            String::from("not a hash"),
+            RomConfig {
+                instructions_per_second: Some(-1.0),
+                limit_to_one_draw_per_frame: None,
+            },
+        );
+
+        let problems = validate_config(&config);
+
+        assert_eq!(8, problems.len());
+        assert!(problems[0].starts_with("cpu.instructions_per_second"));
+        assert!(problems[1].starts_with("gpu.screen_border_color"));
+        assert!(problems[2].starts_with("input.key_bindings[0x5]"));
+        assert!(problems[3].starts_with("input.macros[0].hotkey"));
+        assert!(problems[4].starts_with("input.macros[0].inputs"));
+        assert_eq!(
+            "ram.font_starting_address must be from 0x000 to 0xFB0, so that all 80 bytes fit in memory, but is 0xFC0.",
+            problems[5]
+        );
+        assert!(problems[6].starts_with("roms.not a hash must"));
+        assert!(problems[7].starts_with("roms.not a hash.instructions_per_second"));
     }
 }
@@ -1,165 +1,349 @@
-use crate::config::CPUConfig;
-use crate::emulib::Limiter;
+use crate::config::{
+    CPUConfig, IndexRegisterOverflowPolicy, ProgramCounterOverflowPolicy, UnknownOpcodePolicy,
+};
+use crate::emulator::{Machine, OpcodeHandler};
+use crate::emulib::TimingHistory;
 use crate::gpu::GPU;
 use crate::input::InputManager;
 use crate::instructions::{self, InstructionFunction, Opcode};
+use crate::lifecycle::Lifecycle;
+use crate::observer::{EmulatorError, Observers};
+use crate::profiling;
 use crate::ram::{PROGRAM_START_ADDRESS, RAM};
 use crate::timer::{DelayTimer, SoundTimer};
 use fastrand;
+use std::collections::BTreeSet;
 use std::ops::{Bound, RangeBounds};
 use std::slice::SliceIndex;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Duration;
+
+pub const FRAMES_PER_SECOND: f64 = 60.0;
 
 pub struct CPU {
-    pub active: Arc<AtomicBool>,
-    pub config: CPUConfig,
+    pub lifecycle: Arc<Lifecycle>,
+    config: RwLock<CPUConfig>,
     pub gpu: Arc<GPU>,
     pub ram: Arc<RAM>,
     pub delay_timer: Arc<DelayTimer>,
     pub sound_timer: Arc<SoundTimer>,
     pub input_manager: Arc<InputManager>,
+    pub batch_times: Arc<TimingHistory>,
+    observers: Arc<Observers>,
+    opcode_handler: RwLock<Option<Arc<dyn OpcodeHandler>>>,
+    unknown_opcode_break: Mutex<Option<EmulatorError>>,
+    step_out_depth: Mutex<Option<usize>>,
+    breakpoints: RwLock<BTreeSet<u16>>,
+    executed_instructions: AtomicU64,
+    execution: Mutex<()>,
     pc: Mutex<u16>,
+    instruction_address: Mutex<u16>,
     index: Mutex<u16>,
     v: Mutex<[u8; 16]>,
 }
 
+// The components the CPU runs programs on, which the rest of the emulator shares.
+pub struct Peripherals {
+    pub gpu: Arc<GPU>,
+    pub ram: Arc<RAM>,
+    pub delay_timer: Arc<DelayTimer>,
+    pub sound_timer: Arc<SoundTimer>,
+    pub input_manager: Arc<InputManager>,
+}
+
 impl CPU {
-    pub fn try_new(
-        active: Arc<AtomicBool>,
+    pub fn new(
+        lifecycle: Arc<Lifecycle>,
         config: CPUConfig,
-        gpu: Arc<GPU>,
-        ram: Arc<RAM>,
-        delay_timer: Arc<DelayTimer>,
-        sound_timer: Arc<SoundTimer>,
-        input_manager: Arc<InputManager>,
-    ) -> Option<Arc<Self>> {
-        if config.instructions_per_second <= 0.0 {
-            eprintln!("Error: The CPU's instruction-per-second rate must be greater than 0.");
-            active.store(false, Ordering::Relaxed);
-            return None;
-        }
-
-        return Some(Arc::new(Self {
-            active,
-            config,
-            gpu,
-            ram,
-            delay_timer,
-            sound_timer,
-            input_manager,
+        peripherals: Peripherals,
+        batch_times: Arc<TimingHistory>,
+        observers: Arc<Observers>,
+    ) -> Arc<Self> {
+        return Arc::new(Self {
+            lifecycle,
+            config: RwLock::new(config),
+            gpu: peripherals.gpu,
+            ram: peripherals.ram,
+            delay_timer: peripherals.delay_timer,
+            sound_timer: peripherals.sound_timer,
+            input_manager: peripherals.input_manager,
+            batch_times,
+            observers,
+            opcode_handler: RwLock::new(None),
+            unknown_opcode_break: Mutex::new(None),
+            step_out_depth: Mutex::new(None),
+            breakpoints: RwLock::new(BTreeSet::new()),
+            executed_instructions: AtomicU64::new(0),
+            execution: Mutex::new(()),
             pc: Mutex::new(PROGRAM_START_ADDRESS),
+            instruction_address: Mutex::new(PROGRAM_START_ADDRESS),
             index: Mutex::new(0),
             v: Mutex::new([0; 16]),
-        }));
+        });
     }
 
     #[cfg(test)]
     pub fn new_default_all_false(
-        active: Arc<AtomicBool>,
+        lifecycle: Arc<Lifecycle>,
         gpu: Arc<GPU>,
         ram: Arc<RAM>,
         delay_timer: Arc<DelayTimer>,
         sound_timer: Arc<SoundTimer>,
         input_manager: Arc<InputManager>,
     ) -> Arc<Self> {
-        Self::try_new(
-            active,
+        Self::new(
+            lifecycle,
             CPUConfig {
                 instructions_per_second: 700.0,
+                unlimited_speed: false,
+                timing_jitter: 0.0,
+                timing_jitter_seed: 0,
                 reset_flag_for_bitwise_operations: false,
                 use_new_shift_instruction: false,
                 use_new_jump_instruction: false,
                 set_flag_for_index_overflow: false,
+                write_flag_before_result: false,
                 move_index_with_reads: false,
                 limit_to_one_draw_per_frame: false,
-                allow_program_counter_overflow: false,
+                break_on_draw: crate::config::BreakOnDraw::Off,
+                unknown_opcode_policy: crate::config::UnknownOpcodePolicy::Skip,
+                program_counter_overflow_policy: ProgramCounterOverflowPolicy::Halt,
                 use_true_randomness: false,
                 fake_randomness_seed: 0,
-                allow_index_register_overflow: false,
+                index_register_overflow_policy: IndexRegisterOverflowPolicy::Halt,
             },
-            gpu,
-            ram,
-            delay_timer,
-            sound_timer,
-            input_manager,
+            Peripherals {
+                gpu,
+                ram,
+                delay_timer,
+                sound_timer,
+                input_manager,
+            },
+            Arc::new(TimingHistory::new(1)),
+            Observers::new(),
         )
-        .unwrap()
     }
 
     #[cfg(test)]
     pub fn new_default_all_true(
-        active: Arc<AtomicBool>,
+        lifecycle: Arc<Lifecycle>,
         gpu: Arc<GPU>,
         ram: Arc<RAM>,
         delay_timer: Arc<DelayTimer>,
         sound_timer: Arc<SoundTimer>,
         input_manager: Arc<InputManager>,
     ) -> Arc<Self> {
-        Self::try_new(
-            active,
+        Self::new(
+            lifecycle,
             CPUConfig {
                 instructions_per_second: 700.0,
+                unlimited_speed: false,
+                timing_jitter: 0.0,
+                timing_jitter_seed: 0,
                 reset_flag_for_bitwise_operations: true,
                 use_new_shift_instruction: true,
                 use_new_jump_instruction: true,
                 set_flag_for_index_overflow: true,
+                write_flag_before_result: true,
                 move_index_with_reads: true,
                 limit_to_one_draw_per_frame: true,
-                allow_program_counter_overflow: true,
+                break_on_draw: crate::config::BreakOnDraw::Off,
+                unknown_opcode_policy: crate::config::UnknownOpcodePolicy::Skip,
+                program_counter_overflow_policy: ProgramCounterOverflowPolicy::Wrap,
                 use_true_randomness: true,
                 fake_randomness_seed: 0,
-                allow_index_register_overflow: true,
+                index_register_overflow_policy: IndexRegisterOverflowPolicy::Wrap,
+            },
+            Peripherals {
+                gpu,
+                ram,
+                delay_timer,
+                sound_timer,
+                input_manager,
             },
-            gpu,
-            ram,
-            delay_timer,
-            sound_timer,
-            input_manager,
+            Arc::new(TimingHistory::new(1)),
+            Observers::new(),
         )
-        .unwrap()
     }
 
-    pub fn run(&self) {
-        if !self.config.use_true_randomness {
-            fastrand::seed(self.config.fake_randomness_seed);
+    pub fn get_config(&self) -> RwLockReadGuard<'_, CPUConfig> {
+        return self.config.read().unwrap();
+    }
+
+    pub fn get_config_mut(&self) -> RwLockWriteGuard<'_, CPUConfig> {
+        return self.config.write().unwrap();
+    }
+
+    // The generator is per thread, so this has to be called on whichever thread runs the CPU.
+    pub fn seed_randomness(&self) {
+        let config = self.get_config();
+
+        if !config.use_true_randomness {
+            fastrand::seed(config.fake_randomness_seed);
         }
+    }
 
-        let mut limiter = Limiter::new(self.config.instructions_per_second, true);
+    pub fn record_batch(&self, duration: Duration) {
+        self.batch_times.record(duration);
+        self.observers.notify_cpu_batch(duration);
+    }
 
-        while self.active.load(Ordering::Relaxed) {
-            limiter.wait_if_early();
+    // Holding this stops the CPU between instructions, so the machine can be changed safely.
+    pub fn lock_execution(&self) -> MutexGuard<'_, ()> {
+        let _span = profiling::span!("cpu.lock_execution");
+        return self.execution.lock().unwrap();
+    }
 
-            let Some(instruction) = self.fetch_instruction() else {
-                return;
-            };
+    // The unknown opcode that the CPU paused on, with the "pause" unknown opcode policy.
+    pub fn get_unknown_opcode_break(&self) -> Option<EmulatorError> {
+        return self.unknown_opcode_break.lock().unwrap().clone();
+    }
 
-            // println!("{:#06x}", instruction.get_full());
+    // Pauses once the current subroutine returns, going by the depth of the call stack. Returns
+    // false outside of a subroutine, where there is nothing to step out of.
+    pub fn step_out(&self) -> bool {
+        let depth = self.ram.get_stack_depth();
 
-            let Some(function) = self.decode_instruction(&instruction) else {
-                continue;
-            };
+        if depth == 0 {
+            return false;
+        }
+
+        *self.step_out_depth.lock().unwrap() = Some(depth);
+        return true;
+    }
+
+    // Checked after each instruction, so resuming from a breakpoint runs on past it.
+    pub fn set_breakpoints(&self, addresses: &[u16]) {
+        *self.breakpoints.write().unwrap() = addresses.iter().copied().collect();
+    }
+
+    pub fn get_breakpoints(&self) -> Vec<u16> {
+        return self.breakpoints.read().unwrap().iter().copied().collect();
+    }
+
+    pub fn reset(&self) {
+        *self.unknown_opcode_break.lock().unwrap() = None;
+        *self.step_out_depth.lock().unwrap() = None;
+        *self.pc.lock().unwrap() = PROGRAM_START_ADDRESS;
+        *self.instruction_address.lock().unwrap() = PROGRAM_START_ADDRESS;
+        *self.index.lock().unwrap() = 0;
+        *self.v.lock().unwrap() = [0; 16];
+    }
+
+    pub fn get_executed_instructions(&self) -> u64 {
+        return self.executed_instructions.load(Ordering::Relaxed);
+    }
+
+    pub fn get_instructions_per_frame(&self) -> u64 {
+        return (self.get_config().instructions_per_second / FRAMES_PER_SECOND)
+            .round()
+            .max(1.0) as u64;
+    }
+
+    // Returns whether the instruction should end the current frame, either because it drew with
+    // limit_to_one_draw_per_frame set or because it is waiting for a key. Stepping never blocks,
+    // so waiting for the render is left to whoever drives the CPU.
+    pub fn step(&self) -> Option<bool> {
+        let execution = self.execution.lock().unwrap();
+        self.input_manager.start_frame(self.delay_timer.get_ticks());
+        let instruction = self.fetch_instruction()?;
+        self.executed_instructions.fetch_add(1, Ordering::Relaxed);
+
+        // println!("{:#06x}", instruction.get_full());
+
+        let Some(function) = self.decode_instruction(&instruction) else {
+            return self.handle_unknown_opcode(instruction.get_full(), execution);
+        };
+
+        let should_yield = self.execute_instruction(&instruction, &function);
+        return Some(self.check_step_out() || self.check_breakpoint() || should_yield);
+    }
+
+    fn check_breakpoint(&self) -> bool {
+        let pc = *self.get_pc_ref();
+
+        if !self.breakpoints.read().unwrap().contains(&pc) {
+            return false;
+        }
 
-            if self.execute_instruction(&instruction, &function) {
-                limiter.reset();
+        self.lifecycle.pause();
+        return true;
+    }
+
+    fn check_step_out(&self) -> bool {
+        let mut step_out_depth = self.step_out_depth.lock().unwrap();
+
+        match *step_out_depth {
+            Some(depth) if self.ram.get_stack_depth() < depth => {
+                *step_out_depth = None;
+                self.lifecycle.pause();
+                return true;
             }
+            _ => return false,
         }
     }
 
-    fn fetch_instruction(&self) -> Option<Opcode> {
-        let mut pc = self.pc.lock().unwrap();
+    pub fn set_opcode_handler(&self, opcode_handler: Arc<dyn OpcodeHandler>) {
+        *self.opcode_handler.write().unwrap() = Some(opcode_handler);
+    }
 
-        if *pc >= 0xFFE && !self.config.allow_program_counter_overflow {
-            eprintln!("Error: Program counter overflowed.");
-            self.active.store(false, Ordering::Relaxed);
-            return None;
+    fn handle_unknown_opcode(&self, opcode: u16, execution: MutexGuard<'_, ()>) -> Option<bool> {
+        let address = *self.instruction_address.lock().unwrap();
+        let error = EmulatorError::UnknownOpcode { opcode, address };
+        self.observers.notify_unknown_opcode(opcode, address);
+
+        // Copied out, so the handler is free to change the config.
+        let policy = self.get_config().unknown_opcode_policy;
+
+        match policy {
+            UnknownOpcodePolicy::Skip => {
+                eprintln!("Warning: {error} Skipping it.");
+                return Some(false);
+            }
+            UnknownOpcodePolicy::Halt => (),
+            // Only the first pauses, so resuming carries on past it rather than stopping again.
+            UnknownOpcodePolicy::Pause => {
+                let mut unknown_opcode_break = self.unknown_opcode_break.lock().unwrap();
+
+                if unknown_opcode_break.is_some() {
+                    eprintln!("Warning: {error} Skipping it.");
+                    return Some(false);
+                }
+
+                eprintln!("Warning: {error} Pausing on it.");
+                *unknown_opcode_break = Some(error);
+                self.decrement_pc();
+                self.lifecycle.pause();
+                return Some(true);
+            }
+            UnknownOpcodePolicy::Plugin => {
+                let opcode_handler = self.opcode_handler.read().unwrap().clone();
+                // Released first, so the handler can load a program or a save state, or reset.
+                drop(execution);
+
+                if let Some(should_yield) =
+                    opcode_handler.and_then(|h| h.execute(&Machine::new(self), opcode))
+                {
+                    return Some(should_yield);
+                }
+            }
         }
 
+        self.observers.halt(&self.lifecycle, error);
+        return None;
+    }
+
+    fn fetch_instruction(&self) -> Option<Opcode> {
+        let mut pc = self.pc.lock().unwrap();
+        let next_pc = self.get_next_pc(*pc)?;
+
         let Some(instruction_bytes) = self.ram.read_bytes(*pc, 2) else {
             return None;
         };
 
-        *pc = (*pc + 2) % 0x1000;
+        *self.instruction_address.lock().unwrap() = *pc;
+        *pc = next_pc;
 
         return Some(Opcode::from_u8s(instruction_bytes[0], instruction_bytes[1]));
     }
@@ -190,19 +374,39 @@ impl CPU {
         *self.pc.lock().unwrap() = value;
     }
 
+    // None when running off the end of memory halts the program.
+    fn get_next_pc(&self, pc: u16) -> Option<u16> {
+        if pc < 0xFFE {
+            return Some(pc + 2);
+        }
+
+        match self.get_config().program_counter_overflow_policy {
+            ProgramCounterOverflowPolicy::Wrap => return Some((pc + 2) % 0x1000),
+            ProgramCounterOverflowPolicy::Restart => return Some(PROGRAM_START_ADDRESS),
+            ProgramCounterOverflowPolicy::Halt => {
+                self.observers
+                    .halt(&self.lifecycle, EmulatorError::ProgramCounterOverflow);
+                return None;
+            }
+        }
+    }
+
     pub fn increment_pc(&self) -> bool {
         let mut pc = self.pc.lock().unwrap();
 
-        if *pc >= 0xFFE && !self.config.allow_program_counter_overflow {
-            eprintln!("Error: Program counter overflowed.");
-            self.active.store(false, Ordering::Relaxed);
+        let Some(next_pc) = self.get_next_pc(*pc) else {
             return false;
-        }
+        };
 
-        *pc = (*pc + 2) % 0x1000;
+        *pc = next_pc;
         return true;
     }
 
+    // Rewinds to the instruction just fetched, so it runs again on the next step.
+    pub fn decrement_pc(&self) {
+        *self.pc.lock().unwrap() = *self.instruction_address.lock().unwrap();
+    }
+
     pub fn get_index_reg_ref(&self) -> MutexGuard<'_, u16> {
         return self.index.lock().unwrap();
     }
@@ -232,16 +436,23 @@ impl CPU {
         value: u16,
     ) -> Option<bool> {
         let (val, wrapped) = index_ref.overflowing_add(value);
+        let policy = self.get_config().index_register_overflow_policy;
 
-        if wrapped && !self.config.allow_index_register_overflow {
-            eprintln!("Error: Index register overflowed.");
-            self.active.store(false, Ordering::Relaxed);
+        if wrapped && policy == IndexRegisterOverflowPolicy::Halt {
+            self.observers
+                .halt(&self.lifecycle, EmulatorError::IndexRegisterOverflow);
             return None;
         }
 
-        *index_ref = val;
+        // Whether it went out of range is judged before masking, so VF can still be set for it.
+        let out_of_range = val > 0xFFF;
+
+        *index_ref = match policy {
+            IndexRegisterOverflowPolicy::Mask => val & 0xFFF,
+            _ => val,
+        };
 
-        return Some(*index_ref > 0xFFF);
+        return Some(out_of_range);
     }
 
     pub fn get_v_regs_ref(&self) -> MutexGuard<'_, [u8; 16]> {
@@ -320,17 +531,17 @@ mod tests {
         Liberal,
     }
 
-    fn create_objects(cfg_type: ConfigType) -> (Arc<CPU>, Arc<AtomicBool>) {
-        let active = Arc::new(AtomicBool::new(true));
+    fn create_objects(cfg_type: ConfigType) -> (Arc<CPU>, Arc<Lifecycle>) {
+        let lifecycle = Lifecycle::new();
 
-        let delay_timer = DelayTimer::new_default(active.clone());
-        let sound_timer = SoundTimer::new_default(active.clone());
-        let ram = RAM::new_default_conservative(active.clone());
-        let gpu = GPU::new_default_wrapping(active.clone());
-        let input_manager = InputManager::new_default(active.clone());
+        let delay_timer = DelayTimer::new_default();
+        let sound_timer = SoundTimer::new_default(lifecycle.clone());
+        let ram = RAM::new_default_conservative(lifecycle.clone());
+        let gpu = GPU::new_default_wrapping();
+        let input_manager = InputManager::new_default(lifecycle.clone());
         let cpu = match cfg_type {
             ConfigType::Conservative => CPU::new_default_all_false(
-                active.clone(),
+                lifecycle.clone(),
                 gpu,
                 ram,
                 delay_timer,
@@ -338,7 +549,7 @@ mod tests {
                 input_manager,
             ),
             ConfigType::Liberal => CPU::new_default_all_true(
-                active.clone(),
+                lifecycle.clone(),
                 gpu,
                 ram,
                 delay_timer,
@@ -347,12 +558,12 @@ mod tests {
             ),
         };
 
-        return (cpu, active);
+        return (cpu, lifecycle);
     }
 
     #[test]
     fn test_increment_program_counter() {
-        let (cpu, active) = create_objects(ConfigType::Conservative);
+        let (cpu, lifecycle) = create_objects(ConfigType::Conservative);
 
         let old_val = *cpu.pc.lock().unwrap();
 
@@ -361,44 +572,63 @@ mod tests {
         }
 
         assert_eq!(old_val + 10, *cpu.pc.lock().unwrap());
-        assert!(active.load(Ordering::Relaxed));
+        assert!(lifecycle.is_running());
     }
 
     #[test]
     fn test_successful_program_counter_overflow() {
-        let (cpu, active) = create_objects(ConfigType::Liberal);
+        let (cpu, lifecycle) = create_objects(ConfigType::Liberal);
 
         for _ in 0..((0x1000 - PROGRAM_START_ADDRESS) / 2) {
             assert!(cpu.increment_pc());
         }
 
         assert_eq!(0x000, *cpu.pc.lock().unwrap());
-        assert!(active.load(Ordering::Relaxed));
+        assert!(lifecycle.is_running());
     }
 
     #[test]
     fn test_failed_program_counter_overflow() {
-        let (cpu, active) = create_objects(ConfigType::Conservative);
+        let (cpu, lifecycle) = create_objects(ConfigType::Conservative);
 
         for _ in 0..((0xFFF - PROGRAM_START_ADDRESS) / 2) {
             assert!(cpu.increment_pc());
         }
 
         assert!(!cpu.increment_pc());
-        assert!(!active.load(Ordering::Relaxed));
+        assert!(!lifecycle.is_running());
+    }
+
+    #[test]
+    fn test_program_counter_overflow_restarts() {
+        let (cpu, lifecycle) = create_objects(ConfigType::Conservative);
+        cpu.get_config_mut().program_counter_overflow_policy =
+            ProgramCounterOverflowPolicy::Restart;
+
+        cpu.set_pc(0xFFE);
+        assert!(cpu.increment_pc());
+        assert_eq!(PROGRAM_START_ADDRESS, *cpu.get_pc_ref());
+
+        // The instruction at the end of memory still runs, and can be run again.
+        cpu.set_pc(0xFFE);
+        assert!(cpu.fetch_instruction().is_some());
+        assert_eq!(PROGRAM_START_ADDRESS, *cpu.get_pc_ref());
+        cpu.decrement_pc();
+        assert_eq!(0xFFE, *cpu.get_pc_ref());
+        assert!(lifecycle.is_running());
     }
 
     #[test]
     fn test_set_program_counter_manually() {
-        let (cpu, active) = create_objects(ConfigType::Conservative);
+        let (cpu, lifecycle) = create_objects(ConfigType::Conservative);
         cpu.set_pc(0x567);
         assert_eq!(0x567, *cpu.get_pc_ref());
-        assert!(active.load(Ordering::Relaxed));
+        assert!(lifecycle.is_running());
     }
 
     #[test]
     fn test_get_program_counter_reference() {
-        let (cpu, active) = create_objects(ConfigType::Conservative);
+        let (cpu, lifecycle) = create_objects(ConfigType::Conservative);
 
         {
             let mut pc = cpu.get_pc_ref();
@@ -409,28 +639,41 @@ mod tests {
         *pc += 2;
 
         assert_eq!(0x569, *pc);
-        assert!(active.load(Ordering::Relaxed));
+        assert!(lifecycle.is_running());
     }
 
     #[test]
     fn test_set_index_register() {
-        let (cpu, active) = create_objects(ConfigType::Conservative);
+        let (cpu, lifecycle) = create_objects(ConfigType::Conservative);
         cpu.set_index_reg(0x567);
         assert_eq!(0x567, cpu.get_index_reg());
-        assert!(active.load(Ordering::Relaxed));
+        assert!(lifecycle.is_running());
+    }
+
+    #[test]
+    fn test_masked_index_register_overflow() {
+        let (cpu, lifecycle) = create_objects(ConfigType::Conservative);
+        cpu.get_config_mut().index_register_overflow_policy = IndexRegisterOverflowPolicy::Mask;
+
+        cpu.set_index_reg(0xFFE);
+        assert_eq!(Some(true), cpu.increment_index_reg_by(0x05));
+        assert_eq!(0x003, cpu.get_index_reg());
+        assert_eq!(Some(false), cpu.increment_index_reg_by(0x05));
+        assert_eq!(0x008, cpu.get_index_reg());
+        assert!(lifecycle.is_running());
     }
 
     #[test]
     fn test_set_v_register() {
-        let (cpu, active) = create_objects(ConfigType::Conservative);
+        let (cpu, lifecycle) = create_objects(ConfigType::Conservative);
         cpu.set_v_reg(0x5, 0x67);
         assert_eq!(0x67, cpu.get_v_reg(0x5));
-        assert!(active.load(Ordering::Relaxed));
+        assert!(lifecycle.is_running());
     }
 
     #[test]
     fn test_get_v_register_reference() {
-        let (cpu, active) = create_objects(ConfigType::Conservative);
+        let (cpu, lifecycle) = create_objects(ConfigType::Conservative);
 
         {
             let mut v = cpu.get_v_regs_ref();
@@ -439,21 +682,21 @@ mod tests {
 
         let v = cpu.get_v_regs_ref();
         assert_eq!(0x67, v[0x5]);
-        assert!(active.load(Ordering::Relaxed));
+        assert!(lifecycle.is_running());
     }
 
     #[test]
     fn test_get_v_register_for_x_and_y() {
-        let (cpu, active) = create_objects(ConfigType::Conservative);
+        let (cpu, lifecycle) = create_objects(ConfigType::Conservative);
         cpu.set_v_reg(2, 0x34);
         cpu.set_v_reg(5, 0x67);
         assert_eq!((0x34, 0x67), cpu.get_v_reg_xy(2, 5));
-        assert!(active.load(Ordering::Relaxed));
+        assert!(lifecycle.is_running());
     }
 
     #[test]
     fn test_set_v_register_range() {
-        let (cpu, active) = create_objects(ConfigType::Conservative);
+        let (cpu, lifecycle) = create_objects(ConfigType::Conservative);
 
         let ideal_bytes = vec![0x48, 0x65, 0x6c, 0x6c, 0x6f];
         cpu.set_v_reg_range(2, &ideal_bytes);
@@ -462,12 +705,12 @@ mod tests {
             assert_eq!(ideal_bytes[i as usize], cpu.get_v_reg(i + 2));
         }
 
-        assert!(active.load(Ordering::Relaxed));
+        assert!(lifecycle.is_running());
     }
 
     #[test]
     fn test_get_v_register_range() {
-        let (cpu, active) = create_objects(ConfigType::Conservative);
+        let (cpu, lifecycle) = create_objects(ConfigType::Conservative);
 
         let ideal_bytes = vec![0x48, 0x65, 0x6c, 0x6c, 0x6f];
 
@@ -476,6 +719,6 @@ mod tests {
         }
 
         assert_eq!(ideal_bytes, cpu.get_v_reg_range(2..7));
-        assert!(active.load(Ordering::Relaxed));
+        assert!(lifecycle.is_running());
     }
 }
@@ -0,0 +1,481 @@
+use crate::disassembler;
+use crate::emulator::Emulator;
+use crate::observer::EmulatorError;
+use crate::overlay::{self, Canvas};
+use crate::rewind::RewindBuffer;
+use crate::sourcemap::SourceMap;
+use crate::symbols::Symbols;
+use std::sync::Arc;
+
+const TEXT_SCALE: usize = 2;
+const ROW_HEIGHT: usize = 7 * TEXT_SCALE;
+const MENU_MARGIN: usize = 8;
+const BACKGROUND_COLOR: u32 = 0x101018;
+const TEXT_COLOR: u32 = 0xFFFFFF;
+const HINT_COLOR: u32 = 0x9999AA;
+const MAX_SPRITE_ROWS: u16 = 16;
+const SPRITE_PIXEL_SIZE: usize = 6;
+const SPRITE_BACKGROUND_COLOR: u32 = 0x000000;
+const SPRITE_COLOR: u32 = 0xFFFFFF;
+const STEP_HISTORY_LENGTH: usize = 10_000;
+const STEP_HISTORY_KEYFRAME_INTERVAL: usize = 100;
+
+/// A compact overlay showing the instruction at the program counter, the registers it touches
+/// and the sprite at the index register, for stepping through a program one instruction at a
+/// time, forwards or backwards. Programs assembled from source beside them are shown line by line.
+pub struct Debugger {
+    emulator: Arc<Emulator>,
+    open: bool,
+    shown_unknown_opcode_break: Option<EmulatorError>,
+    // The state before each step, to step back through. Only kept while paused.
+    step_history: RewindBuffer,
+    symbols: Symbols,
+    source_map: Option<SourceMap>,
+    // Kept as given, to be found again in each program loaded.
+    breakpoint_locations: Vec<String>,
+    was_paused: bool,
+}
+
+impl Debugger {
+    pub fn new(emulator: Arc<Emulator>) -> Self {
+        Self {
+            emulator,
+            open: false,
+            shown_unknown_opcode_break: None,
+            step_history: RewindBuffer::new(STEP_HISTORY_LENGTH, STEP_HISTORY_KEYFRAME_INTERVAL),
+            symbols: Symbols::default(),
+            source_map: None,
+            breakpoint_locations: Vec::new(),
+            was_paused: false,
+        }
+    }
+
+    // Addresses are shown by name wherever the program has symbols for them.
+    pub fn set_symbols(&mut self, symbols: Symbols) {
+        self.symbols = symbols;
+    }
+
+    /// Reads the symbols and source of the program just loaded, if it has them, and sets the
+    /// breakpoints in it. Without a symbol file, the labels in the source are used instead.
+    pub fn load_debug_info(&mut self, program_path: &str) {
+        self.source_map = self
+            .emulator
+            .get_rom_hash()
+            .and_then(|rom_hash| SourceMap::load(program_path, &rom_hash));
+
+        self.symbols = Symbols::load(program_path)
+            .or_else(|| Some(self.source_map.as_ref()?.get_symbols().clone()))
+            .unwrap_or_default();
+
+        self.set_breakpoints();
+    }
+
+    pub fn set_breakpoint_locations(&mut self, locations: Vec<String>) {
+        self.breakpoint_locations = locations;
+        self.set_breakpoints();
+    }
+
+    // A location is an address in hex such as 0x206, a source line number or a symbol.
+    fn resolve_location(&self, location: &str) -> Option<u16> {
+        if let Some(hex) = location.strip_prefix("0x") {
+            return u16::from_str_radix(hex, 16).ok();
+        }
+
+        if let Ok(line_number) = location.parse() {
+            return self.source_map.as_ref()?.get_address(line_number);
+        }
+
+        return self.symbols.get_address(location);
+    }
+
+    fn set_breakpoints(&self) {
+        let addresses: Vec<u16> = self
+            .breakpoint_locations
+            .iter()
+            .filter_map(|location| {
+                let address = self.resolve_location(location);
+
+                if address.is_none() {
+                    eprintln!("Warning: Could not find breakpoint {location} in the program.");
+                }
+
+                return address;
+            })
+            .collect();
+
+        self.emulator.set_breakpoints(&addresses);
+    }
+
+    pub fn is_open(&self) -> bool {
+        return self.open;
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    // Opens by itself when the CPU pauses on an unknown opcode or a breakpoint, to show where.
+    pub fn update(&mut self) {
+        let unknown_opcode_break = self.emulator.get_unknown_opcode_break();
+
+        if unknown_opcode_break.is_some() && unknown_opcode_break != self.shown_unknown_opcode_break
+        {
+            self.open = true;
+        }
+
+        self.shown_unknown_opcode_break = unknown_opcode_break;
+
+        let paused = self.emulator.is_paused();
+
+        if paused
+            && !self.was_paused
+            && self
+                .emulator
+                .get_breakpoints()
+                .contains(&self.emulator.get_pc())
+        {
+            self.open = true;
+        }
+
+        self.was_paused = paused;
+
+        // Once the program runs on, there is no telling how it got to where it pauses next.
+        if !self.emulator.is_paused() {
+            self.step_history.clear();
+        }
+    }
+
+    // The first step only pauses, so the instruction shown is the next one to run.
+    pub fn step(&mut self) {
+        self.open = true;
+
+        if !self.emulator.is_paused() {
+            self.emulator.pause();
+            return;
+        }
+
+        let save_state = self.emulator.save_state();
+
+        if self.emulator.debug_step() {
+            self.step_history.push(&save_state);
+        }
+    }
+
+    // Goes back to before the last step, as far back as the steps taken since pausing.
+    pub fn step_back(&mut self) {
+        self.open = true;
+
+        if !self.emulator.is_paused() {
+            return;
+        }
+
+        if let Some(save_state) = self.step_history.pop()
+            && let Err(e) = self.emulator.load_state(&save_state)
+        {
+            eprintln!("Error: {e}");
+        }
+    }
+
+    // Only while paused, as it resumes until the subroutine returns.
+    pub fn step_out(&mut self) {
+        self.open = true;
+        self.emulator.debug_step_out();
+    }
+
+    fn get_opcode(&self) -> u16 {
+        let bytes = self.emulator.peek_memory(self.emulator.get_pc(), 2);
+        return u16::from_be_bytes([bytes[0], bytes[1]]);
+    }
+
+    // As many rows as the next instruction draws, if it draws, or as many as a sprite can have.
+    fn get_sprite(&self) -> Vec<u8> {
+        let opcode = self.get_opcode();
+
+        let rows = match opcode >> 12 == 0xD && opcode & 0xF != 0 {
+            true => opcode & 0xF,
+            false => MAX_SPRITE_ROWS,
+        };
+
+        return self
+            .emulator
+            .peek_memory(self.emulator.get_index_reg(), rows);
+    }
+
+    fn get_lines(&self) -> Vec<String> {
+        let pc = self.emulator.get_pc();
+        let opcode = self.get_opcode();
+        let disassembly = disassembler::disassemble_with_symbols(opcode, &self.symbols);
+        let v = self.emulator.get_v_regs();
+
+        let mut registers: Vec<String> = (0..16)
+            .filter(|reg| disassembly.registers & 1 << reg != 0)
+            .map(|reg| format!("V{reg:X} {:02X}", v[reg]))
+            .collect();
+
+        if disassembly.uses_index {
+            registers.push(format!("I {:03X}", self.emulator.get_index_reg()));
+        }
+
+        // The timers change between instructions, so they are always shown.
+        registers.push(format!("DT {:02X}", self.emulator.get_delay_timer()));
+        registers.push(format!("ST {:02X}", self.emulator.get_sound_timer()));
+
+        let mut lines = vec![
+            format!(
+                "{}  {opcode:04X}  {}",
+                self.symbols.format_address(pc),
+                disassembly.mnemonic
+            ),
+            registers.join("  "),
+        ];
+
+        if let Some((line_number, line)) = self
+            .source_map
+            .as_ref()
+            .and_then(|source_map| source_map.get_line(pc))
+        {
+            lines.insert(1, format!("Line {line_number}  {line}"));
+        }
+
+        let call_stack = self.emulator.get_call_stack();
+
+        if !call_stack.is_empty() {
+            let return_addresses: Vec<String> = call_stack
+                .iter()
+                .rev()
+                .map(|address| self.symbols.format_address(*address))
+                .collect();
+            lines.push(format!("Returns to {}", return_addresses.join(", ")));
+        }
+
+        let breakpoints = self.emulator.get_breakpoints();
+
+        if !breakpoints.is_empty() {
+            let breakpoints: Vec<String> = breakpoints
+                .iter()
+                .map(|address| self.symbols.format_address(*address))
+                .collect();
+            lines.push(format!("Breaks at {}", breakpoints.join(", ")));
+        }
+
+        if let Some(error) = self.emulator.get_unknown_opcode_break() {
+            lines.push(format!("Paused on {error}"));
+        }
+
+        return lines;
+    }
+
+    // Sits in the bottom right corner of the display, out of the way of the other overlays.
+    pub fn draw(&self, canvas: &mut Canvas<'_>, display_width: usize, display_height: usize) {
+        let mut lines = self.get_lines();
+        lines.push(String::from(match self.emulator.is_paused() {
+            true => "paused - Ctrl+N step, Ctrl+B back, Ctrl+U out",
+            false => "running - Ctrl+N to pause",
+        }));
+
+        let (_, text_height) = overlay::get_text_size("", TEXT_SCALE);
+        let text_offset = (ROW_HEIGHT - text_height) / 2;
+
+        let width = lines
+            .iter()
+            .map(|line| overlay::get_text_size(line, TEXT_SCALE).0)
+            .max()
+            .unwrap_or(0)
+            + MENU_MARGIN * 2;
+        let height = ROW_HEIGHT * lines.len() + MENU_MARGIN * 2;
+
+        let x = display_width.saturating_sub(width + MENU_MARGIN);
+        let y = display_height.saturating_sub(height + MENU_MARGIN);
+
+        canvas.fill_rect(x, y, width, height, BACKGROUND_COLOR);
+        self.draw_sprite(canvas, x + width, y);
+
+        for (i, line) in lines.iter().enumerate() {
+            let color = match i == lines.len() - 1 {
+                true => HINT_COLOR,
+                false => TEXT_COLOR,
+            };
+
+            overlay::draw_text(
+                canvas,
+                x + MENU_MARGIN,
+                y + MENU_MARGIN + ROW_HEIGHT * i + text_offset,
+                line,
+                TEXT_SCALE,
+                color,
+            );
+        }
+    }
+
+    // Sits just above the rest of the overlay, right-aligned with it.
+    fn draw_sprite(&self, canvas: &mut Canvas<'_>, right: usize, bottom: usize) {
+        let sprite = self.get_sprite();
+        let (sprite_width, sprite_height) =
+            overlay::get_sprite_size(sprite.len(), SPRITE_PIXEL_SIZE);
+
+        let width = sprite_width + MENU_MARGIN * 2;
+        let height = sprite_height + MENU_MARGIN * 2;
+        let x = right.saturating_sub(width);
+        let y = bottom.saturating_sub(height + MENU_MARGIN);
+
+        canvas.fill_rect(x, y, width, height, BACKGROUND_COLOR);
+        canvas.fill_rect(
+            x + MENU_MARGIN,
+            y + MENU_MARGIN,
+            sprite_width,
+            sprite_height,
+            SPRITE_BACKGROUND_COLOR,
+        );
+
+        overlay::draw_sprite(
+            canvas,
+            x + MENU_MARGIN,
+            y + MENU_MARGIN,
+            &sprite,
+            SPRITE_PIXEL_SIZE,
+            SPRITE_COLOR,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::EmulatorBuilder;
+
+    #[test]
+    fn test_step_pauses_then_steps() {
+        let emulator = Arc::new(EmulatorBuilder::default().build().unwrap());
+        let rom = [0x6A, 0x02, 0xA2, 0x00, 0xD1, 0x23];
+        assert!(emulator.load_rom_bytes(&rom).is_ok());
+
+        let mut debugger = Debugger::new(emulator.clone());
+        assert_eq!("200  6A02  LD VA, 0x02", debugger.get_lines()[0]);
+        assert_eq!(MAX_SPRITE_ROWS as usize, debugger.get_sprite().len());
+
+        debugger.step();
+        assert!(debugger.is_open());
+        assert!(emulator.is_paused());
+        assert_eq!(0x200, emulator.get_pc());
+
+        debugger.step();
+        assert_eq!(
+            vec![
+                String::from("202  A200  LD I, 0x200"),
+                String::from("I 000  DT 00  ST 00")
+            ],
+            debugger.get_lines()
+        );
+
+        emulator.set_delay_timer(0x2A);
+        assert_eq!("I 000  DT 2A  ST 00", debugger.get_lines()[1]);
+
+        // The sprite follows the draw about to run, which here is the program itself.
+        debugger.step();
+        assert_eq!(rom[..3].to_vec(), debugger.get_sprite());
+    }
+
+    #[test]
+    fn test_step_back() {
+        let emulator = Arc::new(EmulatorBuilder::default().build().unwrap());
+        // LD VA, 2; ADD VA, 3; CALL 0x200
+        let rom = [0x6A, 0x02, 0x7A, 0x03, 0x22, 0x00];
+        assert!(emulator.load_rom_bytes(&rom).is_ok());
+
+        let mut debugger = Debugger::new(emulator.clone());
+        debugger.step();
+
+        for _ in 0..3 {
+            debugger.step();
+        }
+
+        assert_eq!(0x200, emulator.get_pc());
+        assert_eq!(5, emulator.get_v_regs()[0xA]);
+        assert_eq!(1, emulator.get_call_stack().len());
+
+        debugger.step_back();
+        assert_eq!(0x204, emulator.get_pc());
+        assert_eq!(0, emulator.get_call_stack().len());
+
+        debugger.step_back();
+        debugger.step_back();
+        assert_eq!(0x200, emulator.get_pc());
+        assert_eq!(0, emulator.get_v_regs()[0xA]);
+
+        // There is nothing before the first step to go back to.
+        debugger.step_back();
+        assert_eq!(0x200, emulator.get_pc());
+        assert!(emulator.is_paused());
+
+        // Running on forgets the steps taken.
+        debugger.step();
+        emulator.resume();
+        debugger.update();
+        emulator.pause();
+        debugger.step_back();
+        assert_eq!(0x202, emulator.get_pc());
+    }
+
+    #[test]
+    fn test_symbols() {
+        let emulator = Arc::new(EmulatorBuilder::default().build().unwrap());
+        // CALL 0x204; (halt); ADD V1, 1; CALL 0x204
+        let rom = [0x22, 0x04, 0x00, 0x00, 0x71, 0x01, 0x22, 0x04];
+        assert!(emulator.load_rom_bytes(&rom).is_ok());
+
+        let mut debugger = Debugger::new(emulator.clone());
+        debugger.set_symbols(Symbols::parse("main = 0x200\ncount = 0x204").unwrap());
+        assert_eq!("main  2204  CALL count", debugger.get_lines()[0]);
+
+        // The first step only pauses, then the program calls into count twice.
+        for _ in 0..4 {
+            debugger.step();
+        }
+
+        assert_eq!(
+            vec![
+                String::from("count  7101  ADD V1, 0x01"),
+                String::from("V1 01  DT 00  ST 00"),
+                String::from("Returns to count+4, main+2"),
+            ],
+            debugger.get_lines()
+        );
+    }
+    #[test]
+    fn test_source_lines_and_breakpoints() {
+        let directory = std::env::temp_dir().join("chip8rust_test_debugger_source");
+        std::fs::create_dir_all(&directory).unwrap();
+        let program_path = directory.join("count.ch8");
+        let program_path = program_path.to_str().unwrap();
+
+        let source = "ld v0, 0\nloop: add v0, 1\n\n  jp loop\n";
+        std::fs::write(directory.join("count.8o"), source).unwrap();
+
+        let emulator = Arc::new(EmulatorBuilder::default().build().unwrap());
+        let rom = crate::assembler::assemble(source).unwrap();
+        assert!(emulator.load_rom_bytes(&rom).is_ok());
+
+        let mut debugger = Debugger::new(emulator.clone());
+        debugger.set_breakpoint_locations(vec![String::from("3"), String::from("nowhere")]);
+        debugger.load_debug_info(program_path);
+        assert_eq!(vec![0x204], emulator.get_breakpoints());
+
+        while !emulator.is_paused() {
+            assert!(emulator.step());
+        }
+
+        debugger.update();
+        assert!(debugger.is_open());
+        assert_eq!(
+            vec![
+                String::from("loop+2  1202  JP loop"),
+                String::from("Line 4  jp loop"),
+                String::from("DT 00  ST 00"),
+                String::from("Breaks at loop+2"),
+            ],
+            debugger.get_lines()
+        );
+
+        let _ = std::fs::remove_dir_all(directory);
+    }
+}
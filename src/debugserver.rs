@@ -0,0 +1,784 @@
+//! A debugging interface for editors and other tools, served over TCP. Two protocols are spoken,
+//! told apart by the first message a tool sends.
+//!
+//! Tools that speak the Debug Adapter Protocol frame each message with a `Content-Length` header.
+//! The requests needed to attach, pause, step, set instruction breakpoints, show the call stack and
+//! registers and read memory are answered, and a `stopped` event is sent whenever the program
+//! pauses, for whatever reason, and a `terminated` event when it ends. Source breakpoints aren't supported, as the program is machine code.
+//!
+//! Anything else is taken as the emulator's own simpler protocol, JSON with one message per line.
+//! Each request is an object naming a command, and gets one response back, such as
+//! `{"command": "set_breakpoints", "addresses": [518]}` and `{"ok": true}`. The commands are
+//! `pause`, `resume`, `step`, `step_out`, `set_breakpoints`, `get_state`, `read_memory` (with
+//! an `address` and a `length`) and `set_timers` (with a `delay_timer`, a `sound_timer` or both).
+//! A failed request gets `{"ok": false, "error": "..."}`. There are no events, so tools poll
+//! `get_state` to find out when a breakpoint is hit.
+
+use crate::emulator::Emulator;
+use crate::emulib;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const CONTENT_LENGTH_HEADER: &str = "Content-Length:";
+// Anything longer is refused rather than read into memory, so a tool can't exhaust it.
+const MAX_MESSAGE_LENGTH: usize = 1024 * 1024;
+const MAX_HEADER_LENGTH: usize = 1024;
+// There is only the one thread of execution, and one set of variables, the registers.
+const THREAD_ID: u64 = 1;
+const REGISTERS_REFERENCE: u64 = 1;
+// How often a Debug Adapter Protocol session checks whether the program has stopped or ended.
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+enum Request {
+    Pause,
+    Resume,
+    Step,
+    StepOut,
+    SetBreakpoints {
+        addresses: Vec<u16>,
+    },
+    GetState,
+    ReadMemory {
+        address: u16,
+        length: u16,
+    },
+    SetTimers {
+        delay_timer: Option<u8>,
+        sound_timer: Option<u8>,
+    },
+}
+
+#[derive(Serialize)]
+struct State {
+    running: bool,
+    paused: bool,
+    pc: u16,
+    index: u16,
+    v: [u8; 16],
+    call_stack: Vec<u16>,
+    delay_timer: u8,
+    sound_timer: u8,
+    breakpoints: Vec<u16>,
+}
+
+fn get_state(emulator: &Emulator) -> State {
+    return State {
+        running: emulator.is_running(),
+        paused: emulator.is_paused(),
+        pc: emulator.get_pc(),
+        index: emulator.get_index_reg(),
+        v: emulator.get_v_regs(),
+        call_stack: emulator.get_call_stack(),
+        delay_timer: emulator.get_delay_timer(),
+        sound_timer: emulator.get_sound_timer(),
+        breakpoints: emulator.get_breakpoints(),
+    };
+}
+
+fn handle_request(emulator: &Emulator, line: &str) -> Value {
+    let request = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => return json!({ "ok": false, "error": e.to_string() }),
+    };
+
+    let not_paused = || json!({ "ok": false, "error": "The program isn't paused." });
+
+    return match request {
+        Request::Pause => {
+            emulator.pause();
+            json!({ "ok": true })
+        }
+        Request::Resume => {
+            emulator.resume();
+            json!({ "ok": true })
+        }
+        Request::Step => match emulator.debug_step() {
+            true => json!({ "ok": true }),
+            false => not_paused(),
+        },
+        Request::StepOut => match emulator.debug_step_out() {
+            true => json!({ "ok": true }),
+            false => json!({ "ok": false, "error": "The program isn't paused in a subroutine." }),
+        },
+        Request::SetBreakpoints { addresses } => {
+            emulator.set_breakpoints(&addresses);
+            json!({ "ok": true })
+        }
+        Request::GetState => json!({ "ok": true, "state": get_state(emulator) }),
+        Request::ReadMemory { address, length } => {
+            json!({ "ok": true, "memory": emulator.peek_memory(address, length) })
+        }
+        Request::SetTimers {
+            delay_timer,
+            sound_timer,
+        } => {
+            if let Some(delay_timer) = delay_timer {
+                emulator.set_delay_timer(delay_timer);
+            }
+
+            if let Some(sound_timer) = sound_timer {
+                emulator.set_sound_timer(sound_timer);
+            }
+
+            json!({ "ok": true })
+        }
+    };
+}
+
+// Ends when the tool disconnects or sends something other than text.
+fn serve(emulator: &Emulator, stream: TcpStream) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+
+    while read_line(&mut reader, &mut line, MAX_MESSAGE_LENGTH)? > 0 {
+        if line.starts_with(CONTENT_LENGTH_HEADER) {
+            let session = DapSession::new(emulator, writer);
+            return session.serve(reader, line);
+        }
+
+        if !line.trim().is_empty() {
+            writeln!(writer, "{}", handle_request(emulator, line.trim()))?;
+        }
+
+        line.clear();
+    }
+
+    return Ok(());
+}
+
+struct DapSession<'a> {
+    emulator: &'a Emulator,
+    writer: Mutex<TcpStream>,
+    seq: AtomicU64,
+    // Set while the program runs, so the next pause is reported however briefly it ran.
+    awaiting_stop: AtomicBool,
+    // Why the program was asked to stop, for the stopped event, when it was asked.
+    requested_stop: Mutex<Option<&'static str>>,
+}
+
+impl<'a> DapSession<'a> {
+    fn new(emulator: &'a Emulator, writer: TcpStream) -> Self {
+        Self {
+            emulator,
+            writer: Mutex::new(writer),
+            seq: AtomicU64::new(0),
+            awaiting_stop: AtomicBool::new(!emulator.is_paused()),
+            requested_stop: Mutex::new(None),
+        }
+    }
+
+    // The header already read is the first of the first message.
+    fn serve(&self, mut reader: BufReader<TcpStream>, header: String) -> io::Result<()> {
+        let connected = AtomicBool::new(true);
+
+        return thread::scope(|scope| {
+            scope.spawn(|| self.watch(&connected));
+
+            let mut header = Some(header);
+            let result = loop {
+                match read_message(&mut reader, header.take()) {
+                    Ok(Some(request)) => match self.handle(&request) {
+                        Ok(true) => (),
+                        Ok(false) => break Ok(()),
+                        Err(e) => break Err(e),
+                    },
+                    Ok(None) => break Ok(()),
+                    Err(e) => break Err(e),
+                }
+            };
+
+            connected.store(false, Ordering::Relaxed);
+            return result;
+        });
+    }
+
+    fn send(&self, mut message: Value) -> io::Result<()> {
+        message["seq"] = json!(self.seq.fetch_add(1, Ordering::Relaxed) + 1);
+        let body = message.to_string();
+
+        let mut writer = self.writer.lock().unwrap();
+        write!(
+            writer,
+            "{CONTENT_LENGTH_HEADER} {}\r\n\r\n{body}",
+            body.len()
+        )?;
+        return writer.flush();
+    }
+
+    fn send_event(&self, event: &str, body: Value) -> io::Result<()> {
+        return self.send(json!({ "type": "event", "event": event, "body": body }));
+    }
+
+    fn send_stopped(&self, reason: &str) -> io::Result<()> {
+        return self.send_event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        );
+    }
+
+    fn get_stop_reason(&self) -> &'static str {
+        if let Some(reason) = self.requested_stop.lock().unwrap().take() {
+            return reason;
+        }
+
+        if self
+            .emulator
+            .get_breakpoints()
+            .contains(&self.emulator.get_pc())
+        {
+            return "breakpoint";
+        }
+
+        if self.emulator.get_unknown_opcode_break().is_some() {
+            return "exception";
+        }
+
+        return "pause";
+    }
+
+    // Polls rather than waits, as the program can be paused from the window and other tools too.
+    fn watch(&self, connected: &AtomicBool) {
+        while connected.load(Ordering::Relaxed) {
+            if !self.emulator.is_running() {
+                let _ = self.send_event("terminated", json!({}));
+                return;
+            }
+
+            if !self.emulator.is_paused() {
+                self.awaiting_stop.store(true, Ordering::Relaxed);
+            } else if self.awaiting_stop.swap(false, Ordering::Relaxed) {
+                let _ = self.send_stopped(self.get_stop_reason());
+            }
+
+            thread::sleep(EVENT_POLL_INTERVAL);
+        }
+    }
+
+    // Returns whether the session goes on.
+    fn handle(&self, request: &Value) -> io::Result<bool> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let arguments = &request["arguments"];
+        let not_paused = || String::from("The program isn't paused.");
+
+        let result = match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsInstructionBreakpoints": true,
+                "supportsReadMemoryRequest": true,
+            })),
+            "launch" | "attach" | "configurationDone" | "disconnect" => Ok(json!({})),
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "CHIP-8" }] })),
+            "setInstructionBreakpoints" => self.set_instruction_breakpoints(arguments),
+            "setBreakpoints" => {
+                let count = arguments["breakpoints"].as_array().map_or(0, Vec::len);
+                let breakpoint = json!({
+                    "verified": false,
+                    "message": "Only instruction breakpoints are supported.",
+                });
+                Ok(json!({ "breakpoints": vec![breakpoint; count] }))
+            }
+            "continue" => {
+                self.awaiting_stop.store(true, Ordering::Relaxed);
+                self.emulator.resume();
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "pause" => {
+                *self.requested_stop.lock().unwrap() = Some("pause");
+                self.emulator.pause();
+                Ok(json!({}))
+            }
+            "next" | "stepIn" => match self.emulator.debug_step() {
+                true => Ok(json!({})),
+                false => Err(not_paused()),
+            },
+            "stepOut" => {
+                *self.requested_stop.lock().unwrap() = Some("step");
+                self.awaiting_stop.store(true, Ordering::Relaxed);
+
+                match self.emulator.debug_step_out() {
+                    true => Ok(json!({})),
+                    false => {
+                        self.requested_stop.lock().unwrap().take();
+                        self.awaiting_stop.store(false, Ordering::Relaxed);
+                        Err(String::from("The program isn't paused in a subroutine."))
+                    }
+                }
+            }
+            "stackTrace" => Ok(self.get_stack_trace()),
+            "scopes" => Ok(json!({ "scopes": [{
+                "name": "Registers",
+                "variablesReference": REGISTERS_REFERENCE,
+                "expensive": false,
+            }] })),
+            "variables" => Ok(self.get_variables(arguments)),
+            "readMemory" => self.read_memory(arguments),
+            _ => Err(format!("{command} isn't supported.")),
+        };
+
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": command,
+            "success": result.is_ok(),
+        });
+
+        let succeeded = result.is_ok();
+
+        match result {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+
+        self.send(response)?;
+
+        match command {
+            "initialize" => self.send_event("initialized", json!({}))?,
+            "next" | "stepIn" if succeeded => self.send_stopped("step")?,
+            "disconnect" => return Ok(false),
+            _ => (),
+        }
+
+        return Ok(true);
+    }
+
+    fn set_instruction_breakpoints(&self, arguments: &Value) -> Result<Value, String> {
+        let mut addresses = Vec::new();
+
+        for breakpoint in arguments["breakpoints"].as_array().into_iter().flatten() {
+            let reference = breakpoint["instructionReference"]
+                .as_str()
+                .unwrap_or_default();
+            let offset = breakpoint["offset"].as_i64().unwrap_or(0);
+
+            let Some(address) = parse_address(reference, offset) else {
+                return Err(format!("{reference} isn't an address."));
+            };
+
+            addresses.push(address);
+        }
+
+        self.emulator.set_breakpoints(&addresses);
+
+        let breakpoints: Vec<Value> = addresses
+            .iter()
+            .map(|address| {
+                return json!({
+                    "verified": true,
+                    "instructionReference": format_address(*address),
+                });
+            })
+            .collect();
+        return Ok(json!({ "breakpoints": breakpoints }));
+    }
+
+    // The innermost frame is where the program is, and each one out is where a call returns to.
+    fn get_stack_trace(&self) -> Value {
+        let addresses = std::iter::once(self.emulator.get_pc())
+            .chain(self.emulator.get_call_stack().into_iter().rev());
+
+        let frames: Vec<Value> = addresses
+            .enumerate()
+            .map(|(id, address)| {
+                return json!({
+                    "id": id,
+                    "name": format_address(address),
+                    "line": 0,
+                    "column": 0,
+                    "instructionPointerReference": format_address(address),
+                });
+            })
+            .collect();
+
+        return json!({ "totalFrames": frames.len(), "stackFrames": frames });
+    }
+
+    fn get_variables(&self, arguments: &Value) -> Value {
+        if arguments["variablesReference"] != json!(REGISTERS_REFERENCE) {
+            return json!({ "variables": [] });
+        }
+
+        let mut registers: Vec<(String, String)> = self
+            .emulator
+            .get_v_regs()
+            .iter()
+            .enumerate()
+            .map(|(i, value)| (format!("V{i:X}"), format!("0x{value:02X}")))
+            .collect();
+
+        registers.extend([
+            (
+                String::from("I"),
+                format_address(self.emulator.get_index_reg()),
+            ),
+            (String::from("PC"), format_address(self.emulator.get_pc())),
+            (
+                String::from("DT"),
+                format!("0x{:02X}", self.emulator.get_delay_timer()),
+            ),
+            (
+                String::from("ST"),
+                format!("0x{:02X}", self.emulator.get_sound_timer()),
+            ),
+        ]);
+
+        let variables: Vec<Value> = registers
+            .into_iter()
+            .map(|(name, value)| json!({ "name": name, "value": value, "variablesReference": 0 }))
+            .collect();
+        return json!({ "variables": variables });
+    }
+
+    fn read_memory(&self, arguments: &Value) -> Result<Value, String> {
+        let reference = arguments["memoryReference"].as_str().unwrap_or_default();
+        let offset = arguments["offset"].as_i64().unwrap_or(0);
+        let count = arguments["count"]
+            .as_u64()
+            .unwrap_or(0)
+            .min(u16::MAX as u64) as u16;
+
+        let Some(address) = parse_address(reference, offset) else {
+            return Err(format!("{reference} isn't an address."));
+        };
+
+        let memory = self.emulator.peek_memory(address, count);
+        return Ok(json!({ "address": format_address(address), "data": encode_base64(&memory) }));
+    }
+}
+
+// Returns None once the tool disconnects. Headers other than the length are skipped.
+fn read_message(
+    reader: &mut BufReader<TcpStream>,
+    first_header: Option<String>,
+) -> io::Result<Option<Value>> {
+    let mut length = None;
+    let mut header = first_header.unwrap_or_default();
+
+    loop {
+        if header.is_empty() && read_line(reader, &mut header, MAX_HEADER_LENGTH)? == 0 {
+            return Ok(None);
+        }
+
+        if header.trim().is_empty() {
+            break;
+        }
+
+        if let Some(value) = header.strip_prefix(CONTENT_LENGTH_HEADER) {
+            length = value.trim().parse::<usize>().ok();
+        }
+
+        header.clear();
+    }
+
+    let Some(length) = length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message without a Content-Length",
+        ));
+    };
+
+    if length > MAX_MESSAGE_LENGTH {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("message of {length} bytes, over the limit of {MAX_MESSAGE_LENGTH}"),
+        ));
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    return serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+}
+
+// Reads a line as BufRead::read_line does, failing if it goes on past the limit.
+fn read_line(
+    reader: &mut BufReader<TcpStream>,
+    line: &mut String,
+    limit: usize,
+) -> io::Result<usize> {
+    let read = reader.by_ref().take(limit as u64).read_line(line)?;
+
+    if read == limit && !line.ends_with('\n') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("line over the limit of {limit} bytes"),
+        ));
+    }
+
+    return Ok(read);
+}
+
+// Addresses are given as text, in hex with a 0x prefix or in decimal, with an offset on top.
+fn parse_address(reference: &str, offset: i64) -> Option<u16> {
+    let address = match reference
+        .strip_prefix("0x")
+        .or_else(|| reference.strip_prefix("0X"))
+    {
+        Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+        None => reference.parse().ok()?,
+    };
+
+    return u16::try_from(address + offset).ok();
+}
+
+fn format_address(address: u16) -> String {
+    return format!("0x{address:03X}");
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, byte)| bits | (*byte as u32) << (16 - i * 8));
+
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => text.push(BASE64_ALPHABET[(bits >> (18 - i * 6) & 0x3F) as usize] as char),
+                false => text.push('='),
+            }
+        }
+    }
+
+    return text;
+}
+
+/// Listens for tools on the address, such as `127.0.0.1:6502`, on a thread of its own, each
+/// connection getting a thread too. Returns the address listened on, for when the port was 0.
+pub fn start(emulator: Arc<Emulator>, address: &str) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(address)?;
+    let local_address = listener.local_addr()?;
+
+    emulib::spawn_named_thread("debug server", move || {
+        for stream in listener.incoming().flatten() {
+            let emulator = emulator.clone();
+
+            emulib::spawn_named_thread("debug connection", move || {
+                let _ = serve(&emulator, stream);
+            });
+        }
+    });
+
+    return Ok(local_address);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::EmulatorBuilder;
+
+    #[test]
+    fn test_debug_session() {
+        let emulator = Arc::new(EmulatorBuilder::default().build().unwrap());
+        // LD V0, 0; ADD V0, 1; JP 0x202
+        assert!(
+            emulator
+                .load_rom_bytes(&[0x60, 0x00, 0x70, 0x01, 0x12, 0x02])
+                .is_ok()
+        );
+
+        let address = start(emulator.clone(), "127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(address).unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let mut reader = BufReader::new(stream);
+
+        let mut send = |request: &str| {
+            writeln!(writer, "{request}").unwrap();
+            let mut response = String::new();
+            reader.read_line(&mut response).unwrap();
+            return serde_json::from_str::<Value>(&response).unwrap();
+        };
+
+        assert_eq!(json!({ "ok": true }), send(r#"{"command": "pause"}"#));
+        assert_eq!(
+            json!({ "ok": true }),
+            send(r#"{"command": "set_breakpoints", "addresses": [516]}"#)
+        );
+        assert_eq!(json!({ "ok": true }), send(r#"{"command": "step"}"#));
+        assert_eq!(json!({ "ok": true }), send(r#"{"command": "step"}"#));
+
+        let state = send(r#"{"command": "get_state"}"#)["state"].clone();
+        assert_eq!(json!(0x204), state["pc"]);
+        assert_eq!(json!(1), state["v"][0]);
+        assert_eq!(json!([0x204]), state["breakpoints"]);
+
+        assert_eq!(
+            json!({ "ok": true, "memory": [0x70, 0x01] }),
+            send(r#"{"command": "read_memory", "address": 514, "length": 2}"#)
+        );
+
+        assert_eq!(
+            json!({ "ok": true }),
+            send(r#"{"command": "set_timers", "delay_timer": 30}"#)
+        );
+        let state = send(r#"{"command": "get_state"}"#)["state"].clone();
+        assert_eq!(json!(30), state["delay_timer"]);
+        assert_eq!(json!(0), state["sound_timer"]);
+
+        assert_eq!(json!(false), send(r#"{"command": "step_out"}"#)["ok"]);
+        assert_eq!(json!(false), send(r#"{"command": "jump"}"#)["ok"]);
+        assert_eq!(json!(false), send("not json")["ok"]);
+    }
+
+    #[test]
+    fn test_dap_session() {
+        let emulator = Arc::new(EmulatorBuilder::default().build().unwrap());
+        // LD V0, 0; CALL 0x206; JP 0x202; ADD V0, 1; RET
+        assert!(
+            emulator
+                .load_rom_bytes(&[0x60, 0x00, 0x22, 0x06, 0x12, 0x02, 0x70, 0x01, 0x00, 0xEE])
+                .is_ok()
+        );
+        emulator.pause();
+        let handles = emulator.spawn_threads();
+
+        let address = start(emulator.clone(), "127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(address).unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let mut reader = BufReader::new(stream);
+        // Events can come before the response they follow from, so they are kept until asked for.
+        let mut received: Vec<Value> = Vec::new();
+
+        let mut next = |matches: &dyn Fn(&Value) -> bool| {
+            loop {
+                if let Some(i) = received.iter().position(matches) {
+                    return received.remove(i);
+                }
+
+                let message = read_message(&mut reader, None).unwrap().unwrap();
+                received.push(message);
+            }
+        };
+        let mut seq = 0;
+        let mut send = |command: &str, arguments: Value| {
+            seq += 1;
+            let body = json!({
+                "seq": seq,
+                "type": "request",
+                "command": command,
+                "arguments": arguments,
+            })
+            .to_string();
+            write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+            return seq;
+        };
+        let response = |seq: u64| {
+            return move |message: &Value| message["request_seq"] == json!(seq);
+        };
+        let event = |name: &'static str| {
+            return move |message: &Value| message["event"] == json!(name);
+        };
+
+        let seq = send("initialize", json!({ "adapterID": "chip8rust" }));
+        let initialized = next(&response(seq));
+        assert_eq!(json!(true), initialized["success"]);
+        assert_eq!(
+            json!(true),
+            initialized["body"]["supportsInstructionBreakpoints"]
+        );
+        next(&event("initialized"));
+
+        let seq = send(
+            "setInstructionBreakpoints",
+            json!({ "breakpoints": [{ "instructionReference": "0x206" }] }),
+        );
+        assert_eq!(
+            json!(true),
+            next(&response(seq))["body"]["breakpoints"][0]["verified"]
+        );
+        assert_eq!(vec![0x206], emulator.get_breakpoints());
+
+        let seq = send("configurationDone", json!({}));
+        assert_eq!(json!(true), next(&response(seq))["success"]);
+
+        let seq = send("continue", json!({ "threadId": 1 }));
+        assert_eq!(json!(true), next(&response(seq))["success"]);
+        let stopped = next(&event("stopped"));
+        assert_eq!(json!("breakpoint"), stopped["body"]["reason"]);
+        assert_eq!(0x206, emulator.get_pc());
+
+        let seq = send("stackTrace", json!({ "threadId": 1 }));
+        let frames = next(&response(seq))["body"]["stackFrames"].clone();
+        assert_eq!(json!("0x206"), frames[0]["instructionPointerReference"]);
+        assert_eq!(json!("0x204"), frames[1]["instructionPointerReference"]);
+
+        let seq = send("next", json!({ "threadId": 1 }));
+        assert_eq!(json!(true), next(&response(seq))["success"]);
+        assert_eq!(json!("step"), next(&event("stopped"))["body"]["reason"]);
+
+        let seq = send("variables", json!({ "variablesReference": 1 }));
+        let variables = next(&response(seq))["body"]["variables"].clone();
+        assert_eq!(
+            json!({ "name": "V0", "value": "0x01", "variablesReference": 0 }),
+            variables[0]
+        );
+        assert_eq!(json!("0x208"), variables[17]["value"]);
+
+        let seq = send(
+            "readMemory",
+            json!({ "memoryReference": "0x200", "offset": 2, "count": 4 }),
+        );
+        // 22 06 12 02
+        assert_eq!(json!("IgYSAg=="), next(&response(seq))["body"]["data"]);
+
+        let seq = send("setBreakpoints", json!({ "breakpoints": [{ "line": 3 }] }));
+        assert_eq!(
+            json!(false),
+            next(&response(seq))["body"]["breakpoints"][0]["verified"]
+        );
+
+        let seq = send("evaluate", json!({ "expression": "V0" }));
+        assert_eq!(json!(false), next(&response(seq))["success"]);
+
+        emulator.stop();
+        next(&event("terminated"));
+        handles
+            .into_iter()
+            .for_each(|handle| handle.join().unwrap());
+
+        let seq = send("disconnect", json!({}));
+        assert_eq!(json!(true), next(&response(seq))["success"]);
+    }
+
+    #[test]
+    fn test_dap_refuses_oversized_messages() {
+        let emulator = Arc::new(EmulatorBuilder::default().build().unwrap());
+        emulator.pause();
+        let address = start(emulator.clone(), "127.0.0.1:0").unwrap();
+
+        let long_header = format!("Content-Length: 2\r\nX-{}\r\n\r\n{{}}", "a".repeat(2000));
+        for message in ["Content-Length: 99999999999\r\n\r\n{}", &long_header] {
+            let mut stream = TcpStream::connect(address).unwrap();
+            write!(stream, "{message}").unwrap();
+
+            // The connection is closed without an answer, and the emulator goes on. Closing it
+            // with the rest of the message unread can reset it, rather than end it cleanly.
+            let mut received = Vec::new();
+            let _ = stream.read_to_end(&mut received);
+            assert!(received.is_empty());
+            assert!(emulator.is_running());
+        }
+    }
+
+    #[test]
+    fn test_encode_base64() {
+        assert_eq!("", encode_base64(&[]));
+        assert_eq!("AA==", encode_base64(&[0]));
+        assert_eq!("AP8=", encode_base64(&[0, 0xFF]));
+        assert_eq!("TWFu", encode_base64(b"Man"));
+    }
+}
@@ -0,0 +1,316 @@
+//! Turns opcodes into mnemonics, in the style of Cowgod's Chip-8 technical reference.
+
+use crate::ram::PROGRAM_START_ADDRESS;
+use crate::symbols::Symbols;
+use std::collections::BTreeMap;
+
+const DATA_BYTES_PER_LINE: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disassembly {
+    pub mnemonic: String,
+    // One bit per V register the instruction may read or write, with bit n for Vn.
+    pub registers: u16,
+    pub uses_index: bool,
+}
+
+impl Disassembly {
+    fn new(mnemonic: String, registers: &[usize], uses_index: bool) -> Self {
+        Self {
+            mnemonic,
+            registers: registers.iter().fold(0, |bits, reg| bits | 1 << reg),
+            uses_index,
+        }
+    }
+}
+
+// VF is counted for the instructions where a quirk might set it, whatever the config.
+pub fn disassemble(opcode: u16) -> Disassembly {
+    let addr = opcode & 0x0FFF;
+    let kk = (opcode & 0x00FF) as u8;
+    let n = (opcode & 0x000F) as u8;
+    let x = ((opcode & 0x0F00) >> 8) as usize;
+    let y = ((opcode & 0x00F0) >> 4) as usize;
+
+    let (mnemonic, registers, uses_index): (String, &[usize], bool) = match opcode >> 12 {
+        0x0 => match addr {
+            0x0E0 => (String::from("CLS"), &[], false),
+            0x0EE => (String::from("RET"), &[], false),
+            _ => (format!("SYS {addr:#05X}"), &[], false),
+        },
+        0x1 => (format!("JP {addr:#05X}"), &[], false),
+        0x2 => (format!("CALL {addr:#05X}"), &[], false),
+        0x3 => (format!("SE V{x:X}, {kk:#04X}"), &[x], false),
+        0x4 => (format!("SNE V{x:X}, {kk:#04X}"), &[x], false),
+        0x5 if n == 0x0 => (format!("SE V{x:X}, V{y:X}"), &[x, y], false),
+        0x6 => (format!("LD V{x:X}, {kk:#04X}"), &[x], false),
+        0x7 => (format!("ADD V{x:X}, {kk:#04X}"), &[x], false),
+        0x8 => match n {
+            0x0 => (format!("LD V{x:X}, V{y:X}"), &[x, y], false),
+            0x1 => (format!("OR V{x:X}, V{y:X}"), &[x, y, 0xF], false),
+            0x2 => (format!("AND V{x:X}, V{y:X}"), &[x, y, 0xF], false),
+            0x3 => (format!("XOR V{x:X}, V{y:X}"), &[x, y, 0xF], false),
+            0x4 => (format!("ADD V{x:X}, V{y:X}"), &[x, y, 0xF], false),
+            0x5 => (format!("SUB V{x:X}, V{y:X}"), &[x, y, 0xF], false),
+            0x6 => (format!("SHR V{x:X}, V{y:X}"), &[x, y, 0xF], false),
+            0x7 => (format!("SUBN V{x:X}, V{y:X}"), &[x, y, 0xF], false),
+            0xE => (format!("SHL V{x:X}, V{y:X}"), &[x, y, 0xF], false),
+            _ => return invalid(opcode),
+        },
+        0x9 if n == 0x0 => (format!("SNE V{x:X}, V{y:X}"), &[x, y], false),
+        0xA => (format!("LD I, {addr:#05X}"), &[], true),
+        0xB => (format!("JP V0, {addr:#05X}"), &[0], false),
+        0xC => (format!("RND V{x:X}, {kk:#04X}"), &[x], false),
+        0xD => (format!("DRW V{x:X}, V{y:X}, {n}"), &[x, y, 0xF], true),
+        0xE => match kk {
+            0x9E => (format!("SKP V{x:X}"), &[x], false),
+            0xA1 => (format!("SKNP V{x:X}"), &[x], false),
+            _ => return invalid(opcode),
+        },
+        0xF => match kk {
+            0x07 => (format!("LD V{x:X}, DT"), &[x], false),
+            0x0A => (format!("LD V{x:X}, K"), &[x], false),
+            0x15 => (format!("LD DT, V{x:X}"), &[x], false),
+            0x18 => (format!("LD ST, V{x:X}"), &[x], false),
+            0x1E => (format!("ADD I, V{x:X}"), &[x, 0xF], true),
+            0x29 => (format!("LD F, V{x:X}"), &[x], true),
+            0x30 => (format!("LD HF, V{x:X}"), &[x], true),
+            0x33 => (format!("LD B, V{x:X}"), &[x], true),
+            0x55 => (format!("LD [I], V{x:X}"), &[], true),
+            0x65 => (format!("LD V{x:X}, [I]"), &[], true),
+            _ => return invalid(opcode),
+        },
+        _ => return invalid(opcode),
+    };
+
+    let mut disassembly = Disassembly::new(mnemonic, registers, uses_index);
+
+    // The register loads and stores touch every register up to Vx.
+    if opcode >> 12 == 0xF && matches!(kk, 0x55 | 0x65) {
+        disassembly.registers = (1u32 << (x + 1)).wrapping_sub(1) as u16;
+    }
+
+    return disassembly;
+}
+
+fn invalid(opcode: u16) -> Disassembly {
+    return Disassembly::new(format!("DW {opcode:#06X}"), &[], false);
+}
+
+// Puts the name in place of the address the instruction refers to, if there is one.
+fn name_address(mut disassembly: Disassembly, opcode: u16, name: Option<&str>) -> Disassembly {
+    if matches!(opcode >> 12, 0x1 | 0x2 | 0xA | 0xB)
+        && let Some(name) = name
+    {
+        let address = format!("{:#05X}", opcode & 0x0FFF);
+        disassembly.mnemonic = disassembly.mnemonic.replace(&address, name);
+    }
+
+    return disassembly;
+}
+
+pub fn disassemble_with_symbols(opcode: u16, symbols: &Symbols) -> Disassembly {
+    return name_address(
+        disassemble(opcode),
+        opcode,
+        symbols.get_name(opcode & 0x0FFF),
+    );
+}
+
+fn is_valid(opcode: u16) -> bool {
+    return disassemble(opcode) != invalid(opcode);
+}
+
+// Where the CPU can go after the instruction, as far as can be told without running it. Returns
+// are followed from the calls instead, and a jump offset by V0 could land anywhere.
+fn get_next_addresses(opcode: u16, address: usize) -> Vec<usize> {
+    let next = address + 2;
+    let target = (opcode & 0x0FFF) as usize;
+
+    return match opcode >> 12 {
+        0x0 if opcode == 0x00EE => Vec::new(),
+        0x1 => vec![target],
+        0x2 => vec![target, next],
+        0xB => Vec::new(),
+        0x3 | 0x4 | 0x5 | 0x9 | 0xE => vec![next, next + 2],
+        _ => vec![next],
+    };
+}
+
+/// Lists the program as instructions, following every jump, call and skip from the start to find
+/// which bytes are code. Bytes that are never reached are listed as data, and the targets of jumps
+/// and calls get labels, so the listing can be edited and assembled back into the same program.
+/// Each line has its address and raw bytes in a comment.
+///
+/// Addresses with a symbol are labelled with its name instead, and data is split up so that each
+/// of them starts a line.
+pub fn disassemble_program(program: &[u8], symbols: &Symbols) -> String {
+    let start = PROGRAM_START_ADDRESS as usize;
+    let end = start + program.len();
+    let get_opcode = |address: usize| {
+        let bytes = program.get(address - start..address - start + 2)?;
+        return Some(u16::from_be_bytes([bytes[0], bytes[1]]));
+    };
+
+    let mut is_code = vec![false; program.len()];
+    let mut targets = BTreeMap::new();
+    let mut pending = vec![start];
+
+    while let Some(address) = pending.pop() {
+        if !(start..end).contains(&address) || is_code[address - start] {
+            continue;
+        }
+
+        let Some(opcode) = get_opcode(address).filter(|opcode| is_valid(*opcode)) else {
+            continue;
+        };
+
+        is_code[address - start] = true;
+
+        let target = (opcode & 0x0FFF) as usize;
+
+        match opcode >> 12 {
+            0x1 => {
+                targets.entry(target).or_insert("label");
+            }
+            0x2 => {
+                targets.insert(target, "sub");
+            }
+            _ => (),
+        }
+
+        pending.extend(get_next_addresses(opcode, address));
+    }
+
+    // Each line is an instruction or a run of data, which stops short of the next instruction.
+    let mut lines = Vec::new();
+    let mut address = start;
+
+    while address < end {
+        let length = match is_code[address - start] {
+            true => 2,
+            false => {
+                1 + (address + 1..end)
+                    .take(DATA_BYTES_PER_LINE - 1)
+                    .take_while(|address| {
+                        return !is_code[address - start]
+                            && symbols.get_name(*address as u16).is_none();
+                    })
+                    .count()
+            }
+        };
+
+        lines.push((address, length));
+        address += length;
+    }
+
+    // Instructions can overlap, and only those that start a line can be labelled.
+    let labels: BTreeMap<usize, String> = lines
+        .iter()
+        .filter_map(|(address, _)| {
+            if let Some(name) = symbols.get_name(*address as u16) {
+                return Some((*address, String::from(name)));
+            }
+
+            let kind = targets.get(address).filter(|_| is_code[address - start])?;
+            return Some((*address, format!("{kind}_{address:03X}")));
+        })
+        .collect();
+
+    let mut listing = String::new();
+
+    for (address, length) in lines {
+        let bytes = &program[address - start..address - start + length];
+        let raw: String = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+
+        let mnemonic = match is_code[address - start] {
+            true => {
+                let opcode = u16::from_be_bytes([bytes[0], bytes[1]]);
+                let label = labels.get(&((opcode & 0x0FFF) as usize));
+                name_address(disassemble(opcode), opcode, label.map(String::as_str)).mnemonic
+            }
+            false => {
+                let values: Vec<String> = bytes.iter().map(|byte| format!("{byte:#04X}")).collect();
+                format!("DB {}", values.join(", "))
+            }
+        };
+
+        if let Some(label) = labels.get(&address) {
+            listing.push_str(&format!("{label}:\n"));
+        }
+
+        listing.push_str(&format!("{mnemonic:<20}; {address:03X}  {raw}\n"));
+    }
+
+    return listing;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler;
+
+    #[test]
+    fn test_disassemble() {
+        let load = disassemble(0x6A02);
+        assert_eq!("LD VA, 0x02", load.mnemonic);
+        assert_eq!(1 << 0xA, load.registers);
+        assert!(!load.uses_index);
+
+        let draw = disassemble(0xD125);
+        assert_eq!("DRW V1, V2, 5", draw.mnemonic);
+        assert_eq!(1 << 0x1 | 1 << 0x2 | 1 << 0xF, draw.registers);
+        assert!(draw.uses_index);
+
+        assert_eq!(0b1111, disassemble(0xF355).registers);
+        assert_eq!(0xFFFF, disassemble(0xFF65).registers);
+        assert_eq!("JP 0x200", disassemble(0x1200).mnemonic);
+        assert_eq!("DW 0x5121", disassemble(0x5121).mnemonic);
+    }
+
+    #[test]
+    fn test_disassemble_program() {
+        // JP 0x206; (sprite); CALL 0x20C; SE V0, 1; JP 0x206; RET; (unreached)
+        let program = [
+            0x12, 0x06, 0xF0, 0x90, 0x90, 0xF0, 0x22, 0x0C, 0x30, 0x01, 0x12, 0x06, 0x00, 0xEE,
+            0x60, 0x01, 0xAB,
+        ];
+
+        let listing = disassemble_program(&program, &Symbols::default());
+        assert_eq!(
+            vec![
+                "JP label_206        ; 200  1206",
+                "DB 0xF0, 0x90, 0x90, 0xF0; 202  F09090F0",
+                "label_206:",
+                "CALL sub_20C        ; 206  220C",
+                "SE V0, 0x01         ; 208  3001",
+                "JP label_206        ; 20A  1206",
+                "sub_20C:",
+                "RET                 ; 20C  00EE",
+                "DB 0x60, 0x01, 0xAB ; 20E  6001AB",
+            ],
+            listing.lines().collect::<Vec<_>>()
+        );
+
+        assert_eq!(Ok(program.to_vec()), assembler::assemble(&listing));
+
+        // Symbols name their addresses instead, even in data.
+        let symbols = Symbols::parse("main = 0x200\ndigit = 0x204\nforever = 0x206").unwrap();
+        let listing = disassemble_program(&program, &symbols);
+        let lines: Vec<&str> = listing.lines().collect();
+        assert_eq!("main:", lines[0]);
+        assert_eq!("JP forever          ; 200  1206", lines[1]);
+        assert_eq!("digit:", lines[3]);
+        assert_eq!("DB 0x90, 0xF0       ; 204  90F0", lines[4]);
+        assert_eq!(Ok(program.to_vec()), assembler::assemble(&listing));
+
+        assert_eq!(
+            "CALL forever",
+            disassemble_with_symbols(0x2206, &symbols).mnemonic
+        );
+        assert_eq!(
+            "LD I, 0x208",
+            disassemble_with_symbols(0xA208, &symbols).mnemonic
+        );
+    }
+}
@@ -0,0 +1,338 @@
+//! The loops that run the emulator in real time on its own threads, one per component. The
+//! components never sleep or read the clock themselves, so all of the pacing is done here, by
+//! stepping the CPU, ticking the timers and queueing renders as run_for does, only timed against
+//! the clock rather than by how long the caller says has passed.
+
+use crate::cpu::{self, CPU};
+use crate::emulib::{Limiter, TickClock, TimingJitter};
+use crate::gpu::GPU;
+use crate::lifecycle::Lifecycle;
+use crate::observer::{EmulatorError, Observers};
+use crate::profiling;
+use crate::timer::{DelayTimer, SoundTimer};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How often the CPU checks whether the window has presented the frame it is waiting on.
+const RENDER_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+pub fn run_cpu(cpu: &CPU) {
+    cpu.seed_randomness();
+
+    // Each loop returns early when the display wait is switched, such as by a per-ROM config.
+    loop {
+        let limit_to_one_draw_per_frame = cpu.get_config().limit_to_one_draw_per_frame;
+
+        if limit_to_one_draw_per_frame {
+            run_frame_paced(cpu);
+        } else {
+            run_free(cpu);
+        }
+
+        if !cpu.lifecycle.is_running()
+            || cpu.get_config().limit_to_one_draw_per_frame == limit_to_one_draw_per_frame
+        {
+            return;
+        }
+    }
+}
+
+fn run_free(cpu: &CPU) {
+    let mut jitter = create_timing_jitter(cpu);
+    let mut instructions_per_second = cpu.get_config().instructions_per_second;
+    let mut limiter = Limiter::new(instructions_per_second, true);
+
+    let mut batch_size = (instructions_per_second / cpu::FRAMES_PER_SECOND).ceil() as u64;
+    // Only the time spent executing counts, not the limiter's sleeps between instructions.
+    let mut batch_time = Duration::ZERO;
+    let mut batch_span = Some(profiling::span!("cpu.batch"));
+    let mut batch_count = 0;
+
+    while cpu.lifecycle.is_running() {
+        if cpu.lifecycle.wait_while_paused() {
+            limiter.reset();
+        }
+
+        let turbo = cpu.lifecycle.is_turbo();
+
+        match turbo {
+            true => limiter.reset(),
+            false => limiter.wait_if_early_scaled(jitter.next_scale()),
+        }
+
+        batch_count += 1;
+
+        if batch_count >= batch_size {
+            cpu.record_batch(batch_time);
+            batch_time = Duration::ZERO;
+            batch_count = 0;
+
+            // Ended before the next one starts, so the batches aren't nested in each other.
+            batch_span.take();
+            batch_span = Some(profiling::span!("cpu.batch"));
+
+            // Each batch is a frame's worth of instructions, so the timers keep up in turbo.
+            // Yielding lets the other threads in, as nothing else slows this loop down.
+            if turbo {
+                tick_timers(cpu);
+                thread::yield_now();
+            }
+
+            if cpu.get_config().limit_to_one_draw_per_frame {
+                return;
+            }
+
+            // The speed can be changed from the settings menu while running.
+            let new_instructions_per_second = cpu.get_config().instructions_per_second;
+
+            if new_instructions_per_second != instructions_per_second {
+                instructions_per_second = new_instructions_per_second;
+                limiter = Limiter::new(instructions_per_second, true);
+                batch_size = (instructions_per_second / cpu::FRAMES_PER_SECOND).ceil() as u64;
+            }
+        }
+
+        let step_start = Instant::now();
+
+        let Some(should_yield) = cpu.step() else {
+            return;
+        };
+
+        let step_end = Instant::now();
+        batch_time += step_end - step_start;
+        cpu.gpu.note_draw_time(step_end);
+
+        if should_yield && !turbo {
+            let _span = profiling::span!("cpu.wait_for_render");
+            wait_for_render(&cpu.gpu, &cpu.lifecycle);
+            limiter.reset();
+        }
+    }
+}
+
+fn run_frame_paced(cpu: &CPU) {
+    let mut jitter = create_timing_jitter(cpu);
+    let mut limiter = Limiter::new(cpu::FRAMES_PER_SECOND, true);
+
+    while cpu.lifecycle.is_running() {
+        if cpu.lifecycle.wait_while_paused() {
+            limiter.reset();
+        }
+
+        let turbo = cpu.lifecycle.is_turbo();
+
+        match turbo {
+            true => limiter.reset(),
+            false => limiter.wait_if_early(),
+        }
+
+        if !cpu.get_config().limit_to_one_draw_per_frame {
+            return;
+        }
+
+        let instructions_per_frame =
+            (cpu.get_instructions_per_frame() as f64 * jitter.next_scale()).round() as u64;
+        let batch_start = Instant::now();
+        let mut batch_time = None;
+        let _batch_span = profiling::span!("cpu.batch");
+
+        for _ in 0..instructions_per_frame {
+            let Some(should_yield) = cpu.step() else {
+                return;
+            };
+
+            if should_yield {
+                // Taken before waiting, so the batch time is only the time spent executing.
+                batch_time = Some(batch_start.elapsed());
+                cpu.gpu.note_draw_time(Instant::now());
+
+                if !turbo {
+                    let _span = profiling::span!("cpu.wait_for_render");
+                    wait_for_render(&cpu.gpu, &cpu.lifecycle);
+                    limiter.reset();
+                }
+
+                break;
+            }
+        }
+
+        // A frame's instructions run in one burst, so its end is close enough to when it drew.
+        cpu.gpu.note_draw_time(Instant::now());
+        cpu.record_batch(batch_time.unwrap_or_else(|| batch_start.elapsed()));
+
+        if turbo {
+            tick_timers(cpu);
+            thread::yield_now();
+        }
+    }
+}
+
+fn create_timing_jitter(cpu: &CPU) -> TimingJitter {
+    let config = cpu.get_config();
+    return TimingJitter::new(config.timing_jitter, config.timing_jitter_seed);
+}
+
+fn tick_timers(cpu: &CPU) {
+    cpu.delay_timer.tick();
+    cpu.sound_timer.tick();
+}
+
+// The window presents frames on a thread of its own, so this checks back until it has.
+fn wait_for_render(gpu: &GPU, lifecycle: &Lifecycle) {
+    while gpu.is_render_queued() && lifecycle.is_running() {
+        thread::sleep(RENDER_POLL_INTERVAL);
+    }
+}
+
+/// Stops the emulator if no instructions are executed within the timeout while running, which
+/// usually means the CPU thread has deadlocked.
+pub fn run_watchdog(cpu: &CPU, observers: &Observers, timeout: Duration) {
+    let mut last_executed_instructions = cpu.get_executed_instructions();
+    let mut last_progress = Instant::now();
+
+    while !cpu.lifecycle.wait_for_shutdown(timeout / 4) {
+        let executed_instructions = cpu.get_executed_instructions();
+
+        if executed_instructions != last_executed_instructions || cpu.lifecycle.is_paused() {
+            last_executed_instructions = executed_instructions;
+            last_progress = Instant::now();
+        } else if last_progress.elapsed() >= timeout {
+            observers.halt(&cpu.lifecycle, EmulatorError::CPUNotResponding);
+            return;
+        }
+    }
+}
+
+/// Queues a render at the configured render frequency, whenever the framebuffer has changed.
+pub fn run_separate_render(gpu: &GPU, lifecycle: &Lifecycle) {
+    let mut limiter = Limiter::new(gpu.get_config().render_frequency, true);
+
+    while lifecycle.is_running() {
+        if lifecycle.wait_while_paused() {
+            limiter.reset();
+        }
+
+        limiter.wait_if_early();
+
+        gpu.queue_render_if_changed();
+    }
+}
+
+pub fn run_delay_timer(delay_timer: &DelayTimer, lifecycle: &Lifecycle) {
+    let mut clock = TickClock::new(delay_timer.get_decrement_rate());
+
+    while lifecycle.is_running() {
+        if lifecycle.wait_while_paused() {
+            clock.reset();
+        }
+
+        let ticks = clock.wait_for_ticks();
+
+        // The CPU ticks the timers itself in turbo, keeping them in step with it.
+        if !lifecycle.is_turbo() {
+            for _ in 0..ticks {
+                delay_timer.tick();
+            }
+        }
+    }
+}
+
+pub fn run_sound_timer(sound_timer: &SoundTimer, lifecycle: &Lifecycle) {
+    let mut clock = TickClock::new(sound_timer.get_decrement_rate());
+
+    while lifecycle.is_running() {
+        // Stay quiet while paused, rather than holding whatever tone was playing.
+        if lifecycle.is_paused() {
+            sound_timer.silence();
+            lifecycle.wait_while_paused();
+            clock.reset();
+        }
+
+        let ticks = clock.wait_for_ticks();
+
+        if !lifecycle.is_turbo() {
+            for _ in 0..ticks {
+                sound_timer.tick();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::InputManager;
+    use crate::lifecycle::ShutdownReason;
+    use crate::ram::RAM;
+    use std::sync::Arc;
+
+    fn create_objects() -> (Arc<CPU>, Arc<Lifecycle>) {
+        let lifecycle = Lifecycle::new();
+
+        let cpu = CPU::new_default_all_false(
+            lifecycle.clone(),
+            GPU::new_default_wrapping(),
+            RAM::new_default_conservative(lifecycle.clone()),
+            DelayTimer::new_default(),
+            SoundTimer::new_default(lifecycle.clone()),
+            InputManager::new_default(lifecycle.clone()),
+        );
+
+        // JP 0x200
+        assert!(
+            cpu.ram
+                .load_program_bytes(&[0x12, 0x00], "the test")
+                .is_ok()
+        );
+
+        return (cpu, lifecycle);
+    }
+
+    #[test]
+    fn test_watchdog_halts_stalled_cpu() {
+        let (cpu, lifecycle) = create_objects();
+
+        run_watchdog(&cpu, &Observers::new(), Duration::from_millis(20));
+
+        assert_eq!(
+            Some(ShutdownReason::Halted(EmulatorError::CPUNotResponding)),
+            lifecycle.get_shutdown_reason()
+        );
+    }
+
+    #[test]
+    fn test_turbo_runs_uncapped() {
+        let (cpu, lifecycle) = create_objects();
+        cpu.delay_timer.set_value(255);
+        lifecycle.set_turbo(true);
+
+        let cpu_clone = cpu.clone();
+        let handle = thread::spawn(move || run_cpu(&cpu_clone));
+        thread::sleep(Duration::from_millis(100));
+        lifecycle.shutdown();
+        handle.join().unwrap();
+
+        // At 700 instructions per second, that would only be 70 instructions and 6 timer ticks.
+        assert!(cpu.get_executed_instructions() > 700);
+        assert!(cpu.delay_timer.get_value() < 255 - 6);
+    }
+
+    #[test]
+    fn test_uncapped_cpu_pauses_promptly() {
+        let (cpu, lifecycle) = create_objects();
+        lifecycle.set_turbo(true);
+
+        let cpu_clone = cpu.clone();
+        let handle = thread::spawn(move || run_cpu(&cpu_clone));
+        thread::sleep(Duration::from_millis(20));
+        lifecycle.pause();
+        thread::sleep(Duration::from_millis(20));
+
+        let executed_instructions = cpu.get_executed_instructions();
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(executed_instructions, cpu.get_executed_instructions());
+
+        lifecycle.shutdown();
+        handle.join().unwrap();
+    }
+}
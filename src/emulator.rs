@@ -0,0 +1,1625 @@
+use crate::backend::{self, AudioSink, Display, InputSource};
+use crate::config::{
+    self, CPUConfig, CollisionReporting, Config, DelayTimerConfig, GPUConfig, HeapOverflowPolicy,
+    IndexRegisterOverflowPolicy, InputConfig, KeyTriggerMode, Preset, ProgramCounterOverflowPolicy,
+    RAMConfig, RomConfig, SoundTimerConfig, SpriteEdgePolicy, StackMode, ThreadsConfig,
+    UnknownOpcodePolicy,
+};
+use crate::cpu::{self, CPU, Peripherals};
+use crate::driver;
+use crate::emulib::{self, TimingHistory};
+use crate::lifecycle::{Lifecycle, ShutdownReason};
+use crate::loader::{self, OctoOptions};
+use crate::observer::{EmulatorError, EmulatorObserver, Observers};
+use crate::overlay;
+use crate::profiling;
+use crate::ram::RAM;
+use crate::savestate::{SaveState, SaveStateError, Thumbnail};
+use std::collections::BTreeMap;
+use std::fs;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+// The components EmulatorBuilder can be given in place of the ones it builds from the config.
+pub use crate::gpu::GPU;
+pub use crate::input::InputManager;
+pub use crate::timer::{DelayTimer, SoundTimer};
+
+// Fractions of instructions and timer ticks carried over between calls to run_for.
+#[derive(Default)]
+struct RunBudget {
+    instructions: f64,
+    delay_timer_ticks: f64,
+    sound_timer_ticks: f64,
+}
+
+/// How many times a second execute_frame should be called to run at full speed.
+pub const FRAMES_PER_SECOND: f64 = cpu::FRAMES_PER_SECOND;
+
+/// Runs opcodes that aren't CHIP-8 instructions, when the unknown opcode policy is "plugin".
+pub trait OpcodeHandler: Send + Sync {
+    /// Returns whether the opcode should end the current frame, as with a draw, or None if the
+    /// handler doesn't recognise it either, which halts the program. The program counter has
+    /// already moved past the opcode.
+    fn execute(&self, machine: &Machine<'_>, opcode: u16) -> Option<bool>;
+}
+
+/// The registers and memory of the machine an OpcodeHandler runs on.
+pub struct Machine<'a> {
+    cpu: &'a CPU,
+}
+
+impl<'a> Machine<'a> {
+    pub(crate) fn new(cpu: &'a CPU) -> Self {
+        Self { cpu }
+    }
+
+    pub fn get_v_reg(&self, reg: u8) -> u8 {
+        return self.cpu.get_v_reg(reg);
+    }
+
+    pub fn set_v_reg(&self, reg: u8, val: u8) {
+        self.cpu.set_v_reg(reg, val);
+    }
+
+    pub fn get_index_reg(&self) -> u16 {
+        return self.cpu.get_index_reg();
+    }
+
+    pub fn set_index_reg(&self, value: u16) {
+        self.cpu.set_index_reg(value);
+    }
+
+    pub fn get_pc(&self) -> u16 {
+        return *self.cpu.get_pc_ref();
+    }
+
+    pub fn set_pc(&self, value: u16) {
+        self.cpu.set_pc(value);
+    }
+
+    /// Reads and writes go through the heap overflow policy, as the program's own do.
+    pub fn read_memory(&self, addr: u16, count: u16) -> Option<Vec<u8>> {
+        return self.cpu.ram.read_bytes(addr, count);
+    }
+
+    pub fn write_memory(&self, addr: u16, vals: &[u8]) -> bool {
+        return self.cpu.ram.write_bytes(vals, addr);
+    }
+}
+
+/// A complete CHIP-8 machine, built from a config.
+///
+/// It can either be run in real time on background threads with spawn_threads, or driven
+/// manually with step, execute_frame, run_for and run_async. None of these block: programs
+/// waiting for a key press (Fx0A) repeat the instruction until a key is pressed and released.
+pub struct Emulator {
+    lifecycle: Arc<Lifecycle>,
+    cpu: Arc<CPU>,
+    gpu: Arc<GPU>,
+    ram: Arc<RAM>,
+    delay_timer: Arc<DelayTimer>,
+    sound_timer: Arc<SoundTimer>,
+    input_manager: Arc<InputManager>,
+    observers: Arc<Observers>,
+    threads_config: ThreadsConfig,
+    preset: Preset,
+    run_budget: Mutex<RunBudget>,
+    // Only the timer ticks are used, carried over between refreshes of the monitor.
+    refresh_budget: Mutex<RunBudget>,
+    rom_configs: BTreeMap<String, RomConfig>,
+    // The configs from before a per-ROM config or Octo options changed them, put back when a
+    // different ROM is loaded.
+    replaced_configs: Mutex<Option<(CPUConfig, GPUConfig)>>,
+}
+
+impl Emulator {
+    pub fn try_new(config: Config) -> Option<Self> {
+        return EmulatorBuilder::new(config).build();
+    }
+
+    pub fn add_observer(&self, observer: Arc<dyn EmulatorObserver>) {
+        self.observers.add(observer);
+    }
+
+    /// For frontends to call each time they present a frame, so observers can time it.
+    pub fn notify_present(&self) {
+        self.observers.notify_present();
+    }
+
+    /// Replaces the running program, resetting the rest of the machine.
+    ///
+    /// If the ROM can't be loaded, the current program is left running.
+    /// Octo cartridges are built from their source as they are loaded.
+    pub fn load_rom(&self, path: &str) -> Result<(), EmulatorError> {
+        if loader::is_cartridge(path) {
+            return self.load_cartridge(path);
+        }
+
+        let octo_options = loader::load_options(path);
+        let _execution = self.cpu.lock_execution();
+        self.ram.load_program(path)?;
+        self.reset_components();
+        self.apply_rom_config(octo_options.as_ref());
+        return Ok(());
+    }
+
+    fn load_cartridge(&self, path: &str) -> Result<(), EmulatorError> {
+        let Ok(gif_bytes) = fs::read(path) else {
+            return Err(EmulatorError::ProgramNotFound(path.to_string()));
+        };
+
+        let (program, octo_options) = loader::build_cartridge(&gif_bytes).map_err(|reason| {
+            return EmulatorError::InvalidCartridge {
+                path: path.to_string(),
+                reason,
+            };
+        })?;
+
+        let _execution = self.cpu.lock_execution();
+        self.ram.load_program_bytes(&program, path)?;
+        self.reset_components();
+        self.apply_rom_config(Some(&octo_options));
+        return Ok(());
+    }
+
+    pub fn load_rom_bytes(&self, rom: &[u8]) -> Result<(), EmulatorError> {
+        let _execution = self.cpu.lock_execution();
+        self.ram.load_program_bytes(rom, "the given ROM")?;
+        self.reset_components();
+        self.apply_rom_config(None);
+        return Ok(());
+    }
+
+    // The CPU and GPU pick up config changes between frames, so this works while running. The
+    // config written for this ROM in particular wins over the options that came with it.
+    fn apply_rom_config(&self, octo_options: Option<&OctoOptions>) {
+        let mut cpu_config = self.cpu.get_config_mut();
+        let mut gpu_config = self.gpu.get_config_mut();
+        let mut replaced_configs = self.replaced_configs.lock().unwrap();
+
+        if let Some((replaced_cpu_config, replaced_gpu_config)) = replaced_configs.take() {
+            *cpu_config = replaced_cpu_config;
+            *gpu_config = replaced_gpu_config;
+        }
+
+        let rom_config = self
+            .get_rom_hash()
+            .and_then(|rom_hash| self.rom_configs.get(&rom_hash));
+
+        if rom_config.is_none() && octo_options.is_none() {
+            return;
+        }
+
+        *replaced_configs = Some((cpu_config.clone(), gpu_config.clone()));
+
+        if let Some(octo_options) = octo_options {
+            octo_options.apply(&mut cpu_config, &mut gpu_config);
+        }
+
+        let Some(rom_config) = rom_config else {
+            return;
+        };
+
+        if let Some(instructions_per_second) = rom_config.instructions_per_second {
+            cpu_config.instructions_per_second = instructions_per_second;
+        }
+
+        if let Some(limit_to_one_draw_per_frame) = rom_config.limit_to_one_draw_per_frame {
+            cpu_config.limit_to_one_draw_per_frame = limit_to_one_draw_per_frame;
+        }
+    }
+
+    // Everything except memory, which is reset as the program is loaded.
+    fn reset_components(&self) {
+        self.cpu.reset();
+        self.gpu.clear_framebuffer();
+        self.delay_timer.set_value(0);
+        self.sound_timer.set_value(0);
+        self.input_manager.reset();
+        *self.run_budget.lock().unwrap() = RunBudget::default();
+        *self.refresh_budget.lock().unwrap() = RunBudget::default();
+    }
+
+    pub fn spawn_threads(&self) -> Vec<JoinHandle<()>> {
+        let mut handles = Vec::new();
+        let timers_have_threads = !self.gpu.should_tick_timers_on_refresh();
+
+        // Otherwise the window ticks them through on_refresh.
+        if timers_have_threads {
+            let delay_timer = self.delay_timer.clone();
+            let lifecycle = self.lifecycle.clone();
+            handles.extend(emulib::spawn_named_thread("delay timer", move || {
+                driver::run_delay_timer(&delay_timer, &lifecycle)
+            }));
+
+            let sound_timer = self.sound_timer.clone();
+            let lifecycle = self.lifecycle.clone();
+            handles.extend(emulib::spawn_named_thread("sound timer", move || {
+                driver::run_sound_timer(&sound_timer, &lifecycle)
+            }));
+        }
+
+        if self.gpu.should_render_separately() {
+            let gpu = self.gpu.clone();
+            let lifecycle = self.lifecycle.clone();
+            handles.extend(emulib::spawn_named_thread("render", move || {
+                driver::run_separate_render(&gpu, &lifecycle)
+            }));
+        }
+
+        let cpu = self.cpu.clone();
+        let threads_config = self.threads_config.clone();
+        handles.extend(emulib::spawn_named_thread("cpu", move || {
+            emulib::apply_cpu_thread_hints(&threads_config);
+            driver::run_cpu(&cpu);
+        }));
+
+        // The emulator can't run without all of its threads.
+        if handles.len()
+            < 1 + 2 * timers_have_threads as usize + self.gpu.should_render_separately() as usize
+        {
+            self.stop();
+        }
+
+        if self.threads_config.watchdog_timeout > 0.0 {
+            let cpu = self.cpu.clone();
+            let observers = self.observers.clone();
+            let timeout = Duration::from_secs_f64(self.threads_config.watchdog_timeout);
+            handles.extend(emulib::spawn_named_thread("watchdog", move || {
+                driver::run_watchdog(&cpu, &observers, timeout)
+            }));
+        }
+
+        return handles;
+    }
+
+    pub fn step(&self) -> bool {
+        if !self.is_running() {
+            return false;
+        }
+
+        if self.is_paused() {
+            return true;
+        }
+
+        return self.cpu.step().is_some();
+    }
+
+    /// Runs a single instruction while paused, for stepping through a program in a debugger.
+    /// Does nothing unless paused, as the CPU thread would be running otherwise.
+    pub fn debug_step(&self) -> bool {
+        if !self.is_running() || !self.is_paused() {
+            return false;
+        }
+
+        return self.cpu.step().is_some();
+    }
+
+    /// Resumes a paused program until the subroutine it is in returns, pausing again on the
+    /// instruction after the call. Does nothing outside of a subroutine.
+    pub fn debug_step_out(&self) -> bool {
+        if !self.is_running() || !self.is_paused() || !self.cpu.step_out() {
+            return false;
+        }
+
+        self.resume();
+        return true;
+    }
+
+    /// Pauses the program whenever it is about to run the instruction at one of the addresses,
+    /// replacing any breakpoints set before. They are kept when a program is loaded.
+    pub fn set_breakpoints(&self, addresses: &[u16]) {
+        self.cpu.set_breakpoints(addresses);
+    }
+
+    pub fn get_breakpoints(&self) -> Vec<u16> {
+        return self.cpu.get_breakpoints();
+    }
+
+    pub fn run_for(&self, duration: Duration) -> bool {
+        let _span = profiling::span!("emulator.run_for");
+
+        if self.is_paused() {
+            return self.is_running();
+        }
+
+        let seconds = duration.as_secs_f64();
+        let mut run_budget = self.run_budget.lock().unwrap();
+
+        run_budget.instructions += seconds * self.cpu.get_config().instructions_per_second;
+        run_budget.delay_timer_ticks += seconds * self.delay_timer.get_decrement_rate();
+        run_budget.sound_timer_ticks += seconds * self.sound_timer.get_decrement_rate();
+
+        let instructions = run_budget.instructions.floor();
+        let delay_timer_ticks = run_budget.delay_timer_ticks.floor();
+        let sound_timer_ticks = run_budget.sound_timer_ticks.floor();
+
+        run_budget.instructions -= instructions;
+        run_budget.delay_timer_ticks -= delay_timer_ticks;
+        run_budget.sound_timer_ticks -= sound_timer_ticks;
+        drop(run_budget);
+
+        let instructions = instructions as u64;
+        let mut delay_timer_ticks_done = 0;
+        let mut sound_timer_ticks_done = 0;
+
+        for i in 0..=instructions {
+            // Spread the timer ticks evenly between the instructions.
+            let progress = match instructions {
+                0 => 1.0,
+                _ => i as f64 / instructions as f64,
+            };
+
+            while (delay_timer_ticks_done as f64) < (delay_timer_ticks * progress).floor() {
+                self.delay_timer.tick();
+                delay_timer_ticks_done += 1;
+            }
+
+            while (sound_timer_ticks_done as f64) < (sound_timer_ticks * progress).floor() {
+                self.sound_timer.tick();
+                sound_timer_ticks_done += 1;
+            }
+
+            if i < instructions && !self.step() {
+                return false;
+            }
+        }
+
+        return self.is_running();
+    }
+
+    /// Called by the window each time the monitor refreshes, with how long a refresh lasts. With
+    /// render_occasion set to "vsync" this queues a render, and with tick_timers_on_refresh set
+    /// too it ticks the timers, carrying fractions of a tick over to the next refresh.
+    pub fn on_refresh(&self, period: Duration) {
+        if self.gpu.is_synced_to_refresh() {
+            self.gpu.queue_render_if_changed();
+        }
+
+        // The CPU ticks the timers itself in turbo, keeping them in step with it.
+        if !self.gpu.should_tick_timers_on_refresh() || self.is_turbo() {
+            return;
+        }
+
+        if self.is_paused() {
+            self.sound_timer.silence();
+            return;
+        }
+
+        let seconds = period.as_secs_f64();
+        let mut refresh_budget = self.refresh_budget.lock().unwrap();
+
+        refresh_budget.delay_timer_ticks += seconds * self.delay_timer.get_decrement_rate();
+        refresh_budget.sound_timer_ticks += seconds * self.sound_timer.get_decrement_rate();
+
+        while refresh_budget.delay_timer_ticks >= 1.0 {
+            self.delay_timer.tick();
+            refresh_budget.delay_timer_ticks -= 1.0;
+        }
+
+        while refresh_budget.sound_timer_ticks >= 1.0 {
+            self.sound_timer.tick();
+            refresh_budget.sound_timer_ticks -= 1.0;
+        }
+    }
+
+    /// Runs a single 60 Hz frame: the configured number of instructions per frame, ending early on
+    /// a frame-limited draw or a key wait, followed by one tick of each timer.
+    ///
+    /// Returns whether the framebuffer changed.
+    pub fn execute_frame(&self) -> bool {
+        let _span = profiling::span!("emulator.execute_frame");
+
+        if self.is_paused() {
+            return false;
+        }
+
+        let framebuffer = self.gpu.get_framebuffer().clone();
+
+        for _ in 0..self.cpu.get_instructions_per_frame() {
+            if !self.is_running() {
+                break;
+            }
+
+            match self.cpu.step() {
+                Some(false) => continue,
+                _ => break,
+            }
+        }
+
+        self.delay_timer.tick();
+        self.sound_timer.tick();
+
+        return *self.gpu.get_framebuffer() != framebuffer;
+    }
+
+    /// Runs in real time without any threads, for use from async applications.
+    ///
+    /// `sleep` should return a future that completes after the given duration, such as
+    /// tokio::time::sleep, so the emulator never touches the clock itself. Keys can be pressed
+    /// from other tasks while this runs, and it finishes once the emulator stops.
+    pub async fn run_async<F, Fut>(&self, mut sleep: F)
+    where
+        F: FnMut(Duration) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        let frame_duration = Duration::from_secs_f64(1.0 / FRAMES_PER_SECOND);
+
+        while self.run_for(frame_duration) {
+            sleep(frame_duration).await;
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        return self.lifecycle.is_running();
+    }
+
+    pub fn stop(&self) {
+        self.lifecycle.shutdown();
+    }
+
+    pub fn pause(&self) {
+        self.lifecycle.pause();
+    }
+
+    pub fn resume(&self) {
+        self.lifecycle.resume();
+    }
+
+    /// While on, the CPU runs as fast as it can, with the timers ticked every frame's worth of
+    /// instructions so that waits in the program pass just as quickly.
+    ///
+    /// With unlimited_speed set, turbo is always on.
+    pub fn set_turbo(&self, turbo: bool) {
+        self.lifecycle
+            .set_turbo(turbo || self.cpu.get_config().unlimited_speed);
+    }
+
+    pub fn is_turbo(&self) -> bool {
+        return self.lifecycle.is_turbo();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        return self.lifecycle.is_paused();
+    }
+
+    pub fn get_shutdown_reason(&self) -> Option<ShutdownReason> {
+        return self.lifecycle.get_shutdown_reason();
+    }
+
+    pub fn get_lifecycle(&self) -> Arc<Lifecycle> {
+        return self.lifecycle.clone();
+    }
+
+    pub fn framebuffer(&self) -> Vec<bool> {
+        self.gpu.dequeue_render();
+        return self.gpu.get_framebuffer().to_pixels();
+    }
+
+    /// Hands the framebuffer to a display, returning whether it was shown.
+    pub fn present_to(&self, display: &mut dyn Display) -> bool {
+        return self.gpu.present_to(display);
+    }
+
+    pub fn screen_resolution(&self) -> (usize, usize) {
+        return self.gpu.get_screen_resolution();
+    }
+
+    pub fn press_key(&self, key_index: u8) {
+        self.input_manager.press_key(key_index);
+    }
+
+    pub fn release_key(&self, key_index: u8) {
+        self.input_manager.release_key(key_index);
+    }
+
+    /// Presses and releases the keypad's keys as a frontend's input reports their bindings.
+    pub fn update_input(&self, input: &dyn InputSource) {
+        self.input_manager.update_input(input);
+    }
+
+    pub fn get_pc(&self) -> u16 {
+        return *self.cpu.get_pc_ref();
+    }
+
+    pub fn get_index_reg(&self) -> u16 {
+        return self.cpu.get_index_reg();
+    }
+
+    /// The return addresses of the subroutines the program is in, innermost last.
+    pub fn get_call_stack(&self) -> Vec<u16> {
+        let (mut stack, stack_ptr) = self.ram.get_stack();
+        stack.truncate(stack_ptr);
+        return stack;
+    }
+
+    pub fn get_v_regs(&self) -> [u8; 16] {
+        return *self.cpu.get_v_regs_ref();
+    }
+
+    pub fn get_executed_instructions(&self) -> u64 {
+        return self.cpu.get_executed_instructions();
+    }
+
+    pub fn get_instructions_per_second(&self) -> f64 {
+        return self.cpu.get_config().instructions_per_second;
+    }
+
+    /// Seeds the random number generator of the calling thread from the config, unless it uses
+    /// true randomness, for runs driven from this thread to be reproducible.
+    pub fn seed_randomness(&self) {
+        self.cpu.seed_randomness();
+    }
+
+    pub fn get_delay_timer(&self) -> u8 {
+        return self.delay_timer.get_value();
+    }
+
+    pub fn get_sound_timer(&self) -> u8 {
+        return self.sound_timer.get_value();
+    }
+
+    pub fn set_delay_timer(&self, value: u8) {
+        self.delay_timer.set_value(value);
+    }
+
+    /// Starts the tone as the program would, so it plays once running (it stays quiet while
+    /// paused).
+    pub fn set_sound_timer(&self, value: u8) {
+        self.sound_timer.start(value);
+    }
+
+    pub fn is_beeping(&self) -> bool {
+        return self.sound_timer.is_beeping();
+    }
+
+    pub fn read_memory(&self, addr: u16, count: u16) -> Option<Vec<u8>> {
+        return self.ram.read_bytes(addr, count);
+    }
+
+    /// The unknown opcode the program is paused on, with the "pause" unknown opcode policy, or None
+    /// if it isn't paused on one.
+    pub fn get_unknown_opcode_break(&self) -> Option<EmulatorError> {
+        return self.cpu.get_unknown_opcode_break();
+    }
+
+    /// Unlike read_memory, wraps around the address space rather than halting the program.
+    pub fn peek_memory(&self, addr: u16, count: u16) -> Vec<u8> {
+        return self.ram.peek_bytes(addr, count);
+    }
+
+    /// The SHA-1 of the loaded ROM, as used by CHIP-8 ROM databases.
+    pub fn get_rom_hash(&self) -> Option<String> {
+        return self.ram.get_program_hash();
+    }
+
+    pub fn get_preset(&self) -> Preset {
+        return self.preset;
+    }
+
+    /// Describes the version, ROM and effective quirks, using the names from the config file,
+    /// so that bug reports can include exactly how the emulator was set up.
+    pub fn get_configuration_report(&self) -> Vec<String> {
+        let instructions_per_second = self.cpu.get_config().instructions_per_second;
+        let input_config = self.input_manager.get_config();
+        let (width, height) = self.gpu.get_screen_resolution();
+
+        let preset = match self.preset {
+            Preset::CHIP8 => "chip8",
+            Preset::SCHIP => "schip",
+            Preset::Custom => "custom",
+        };
+
+        let key_trigger_mode = match input_config.key_trigger_mode {
+            KeyTriggerMode::Held => "held",
+            KeyTriggerMode::Edge => "edge",
+        };
+
+        let mut report = vec![
+            format!("chip8rust {}", env!("CARGO_PKG_VERSION")),
+            format!(
+                "rom_sha1 = {}",
+                self.get_rom_hash().unwrap_or_else(|| String::from("none"))
+            ),
+            format!("preset = {preset}"),
+            format!("instructions_per_second = {instructions_per_second}"),
+        ];
+
+        report.extend(self.get_quirks_report());
+        report.push(format!("resolution = {width}x{height}"));
+        report.push(format!("key_trigger_mode = {key_trigger_mode}"));
+
+        return report;
+    }
+
+    /// The quirks that change how programs behave, in the same form as the configuration report.
+    pub fn get_quirks_report(&self) -> Vec<String> {
+        let cpu_config = self.cpu.get_config();
+        let gpu_config = self.gpu.get_config();
+        let ram_config = self.ram.get_config();
+
+        let collision_reporting = match gpu_config.collision_reporting.unwrap_or_default() {
+            CollisionReporting::Flag => "flag",
+            CollisionReporting::RowCount => "row_count",
+        };
+
+        let edge_policy = |policy| match policy {
+            SpriteEdgePolicy::Wrap => "wrap",
+            SpriteEdgePolicy::Clip => "clip",
+        };
+
+        let unknown_opcode_policy = match cpu_config.unknown_opcode_policy {
+            UnknownOpcodePolicy::Skip => "skip",
+            UnknownOpcodePolicy::Halt => "halt",
+            UnknownOpcodePolicy::Pause => "pause",
+            UnknownOpcodePolicy::Plugin => "plugin",
+        };
+
+        let stack_mode = match ram_config.stack_mode {
+            StackMode::Fixed => "fixed",
+            StackMode::Growing => "growing",
+        };
+
+        let stack_warning_depth = match ram_config.stack_warning_depth {
+            Some(depth) => depth.to_string(),
+            None => String::from("none"),
+        };
+
+        let program_counter_overflow_policy = match cpu_config.program_counter_overflow_policy {
+            ProgramCounterOverflowPolicy::Wrap => "wrap",
+            ProgramCounterOverflowPolicy::Halt => "halt",
+            ProgramCounterOverflowPolicy::Restart => "restart",
+        };
+
+        let index_register_overflow_policy = match cpu_config.index_register_overflow_policy {
+            IndexRegisterOverflowPolicy::Wrap => "wrap",
+            IndexRegisterOverflowPolicy::Halt => "halt",
+            IndexRegisterOverflowPolicy::Mask => "mask",
+        };
+
+        let heap_overflow_policy = match ram_config.heap_overflow_policy {
+            HeapOverflowPolicy::Wrap => "wrap",
+            HeapOverflowPolicy::Ignore => "ignore",
+            HeapOverflowPolicy::Halt => "halt",
+        };
+
+        return vec![
+            format!(
+                "reset_flag_for_bitwise_operations = {}",
+                cpu_config.reset_flag_for_bitwise_operations
+            ),
+            format!(
+                "use_new_shift_instruction = {}",
+                cpu_config.use_new_shift_instruction
+            ),
+            format!(
+                "use_new_jump_instruction = {}",
+                cpu_config.use_new_jump_instruction
+            ),
+            format!(
+                "set_flag_for_index_overflow = {}",
+                cpu_config.set_flag_for_index_overflow
+            ),
+            format!(
+                "write_flag_before_result = {}",
+                cpu_config.write_flag_before_result
+            ),
+            format!(
+                "move_index_with_reads = {}",
+                cpu_config.move_index_with_reads
+            ),
+            format!(
+                "limit_to_one_draw_per_frame = {}",
+                cpu_config.limit_to_one_draw_per_frame
+            ),
+            format!(
+                "wrap_sprite_positions = {}",
+                gpu_config.wrap_sprite_positions
+            ),
+            format!(
+                "horizontal_sprite_edge_policy = {}",
+                edge_policy(gpu_config.horizontal_sprite_edge_policy)
+            ),
+            format!(
+                "vertical_sprite_edge_policy = {}",
+                edge_policy(gpu_config.vertical_sprite_edge_policy)
+            ),
+            format!("collision_reporting = {collision_reporting}"),
+            format!("unknown_opcode_policy = {unknown_opcode_policy}"),
+            format!("stack_mode = {stack_mode}"),
+            format!("stack_warning_depth = {stack_warning_depth}"),
+            format!("heap_overflow_policy = {heap_overflow_policy}"),
+            format!("program_counter_overflow_policy = {program_counter_overflow_policy}"),
+            format!("index_register_overflow_policy = {index_register_overflow_policy}"),
+        ];
+    }
+
+    pub fn save_state(&self) -> SaveState {
+        let _execution = self.cpu.lock_execution();
+        let (stack, stack_ptr) = self.ram.get_stack();
+        let resolution = self.screen_resolution();
+        let framebuffer = self.gpu.get_framebuffer().to_pixels();
+
+        return SaveState {
+            quirks: self.get_quirks_report(),
+            rom_hash: self.get_rom_hash(),
+            pc: self.get_pc(),
+            index: self.get_index_reg(),
+            v: self.get_v_regs(),
+            heap: self.ram.get_heap(),
+            stack,
+            stack_ptr,
+            delay_timer: self.get_delay_timer(),
+            sound_timer: self.get_sound_timer(),
+            resolution,
+            thumbnail: Some(Thumbnail::from_framebuffer(&framebuffer, resolution)),
+            framebuffer,
+        };
+    }
+
+    // The quirks the state was saved with that are no longer in effect.
+    fn get_changed_quirks(&self, state: &SaveState) -> Vec<String> {
+        let quirks = self.get_quirks_report();
+
+        return state
+            .quirks
+            .iter()
+            .filter(|quirk| !quirks.contains(quirk))
+            .cloned()
+            .collect();
+    }
+
+    /// Puts the machine back as it was when the state was saved.
+    ///
+    /// States saved with different quirks are still loaded, with a warning, as the program may
+    /// behave differently from then on.
+    pub fn load_state(&self, state: &SaveState) -> Result<(), SaveStateError> {
+        let resolution = self.screen_resolution();
+
+        if state.resolution != resolution {
+            return Err(SaveStateError::ResolutionMismatch(
+                state.resolution,
+                resolution,
+            ));
+        }
+
+        if let Some(stack_size) = self.ram.get_stack_limit()
+            && state.stack_ptr > stack_size
+        {
+            return Err(SaveStateError::StackTooLarge(state.stack_ptr, stack_size));
+        }
+
+        for quirk in self.get_changed_quirks(state) {
+            eprintln!("Warning: The save state was made with {quirk}.");
+        }
+
+        let _execution = self.cpu.lock_execution();
+
+        self.ram.restore(
+            &state.heap,
+            &state.stack,
+            state.stack_ptr,
+            state.rom_hash.clone(),
+        );
+
+        self.cpu.set_pc(state.pc);
+        *self.cpu.get_index_reg_ref() = state.index;
+        *self.cpu.get_v_regs_ref() = state.v;
+        self.delay_timer.set_value(state.delay_timer);
+        self.sound_timer.set_value(state.sound_timer);
+        self.input_manager.reset();
+        *self.run_budget.lock().unwrap() = RunBudget::default();
+
+        self.gpu.get_framebuffer().load_pixels(&state.framebuffer);
+        self.gpu.queue_render();
+
+        return Ok(());
+    }
+}
+
+// For the frontends inside the crate, which work with the components directly.
+#[cfg_attr(not(feature = "window"), allow(dead_code))]
+impl Emulator {
+    pub(crate) fn get_cpu(&self) -> Arc<CPU> {
+        return self.cpu.clone();
+    }
+
+    pub(crate) fn get_gpu(&self) -> Arc<GPU> {
+        return self.gpu.clone();
+    }
+
+    pub(crate) fn get_input_manager(&self) -> Arc<InputManager> {
+        return self.input_manager.clone();
+    }
+}
+
+pub struct EmulatorBuilder {
+    config: Config,
+    lifecycle: Arc<Lifecycle>,
+    beeping: Arc<AtomicBool>,
+    observers: Arc<Observers>,
+    gpu: Option<Arc<GPU>>,
+    input_manager: Option<Arc<InputManager>>,
+    delay_timer: Option<Arc<DelayTimer>>,
+    sound_timer: Option<Arc<SoundTimer>>,
+    audio_sink: Option<Box<dyn AudioSink>>,
+    opcode_handler: Option<Arc<dyn OpcodeHandler>>,
+}
+
+impl Default for EmulatorBuilder {
+    fn default() -> Self {
+        return Self::new(config::generate_default_configs());
+    }
+}
+
+impl EmulatorBuilder {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            lifecycle: Lifecycle::new(),
+            beeping: Arc::new(AtomicBool::new(false)),
+            observers: Observers::new(),
+            gpu: None,
+            input_manager: None,
+            delay_timer: None,
+            sound_timer: None,
+            audio_sink: None,
+            opcode_handler: None,
+        }
+    }
+
+    // Components built outside the builder should share these, so they stop and beep together.
+    pub fn get_lifecycle(&self) -> Arc<Lifecycle> {
+        return self.lifecycle.clone();
+    }
+
+    pub fn get_beeping(&self) -> Arc<AtomicBool> {
+        return self.beeping.clone();
+    }
+
+    pub fn get_observers(&self) -> Arc<Observers> {
+        return self.observers.clone();
+    }
+
+    pub fn with_cpu_config(mut self, config: CPUConfig) -> Self {
+        self.config.cpu = config;
+        return self;
+    }
+
+    pub fn with_gpu_config(mut self, config: GPUConfig) -> Self {
+        self.config.gpu = config;
+        return self;
+    }
+
+    pub fn with_input_config(mut self, config: InputConfig) -> Self {
+        self.config.input = config;
+        return self;
+    }
+
+    pub fn with_ram_config(mut self, config: RAMConfig) -> Self {
+        self.config.ram = config;
+        return self;
+    }
+
+    pub fn with_delay_timer_config(mut self, config: DelayTimerConfig) -> Self {
+        self.config.delay_timer = config;
+        return self;
+    }
+
+    pub fn with_sound_timer_config(mut self, config: SoundTimerConfig) -> Self {
+        self.config.sound_timer = config;
+        return self;
+    }
+
+    pub fn with_threads_config(mut self, config: ThreadsConfig) -> Self {
+        self.config.threads = config;
+        return self;
+    }
+
+    pub fn with_gpu(mut self, gpu: Arc<GPU>) -> Self {
+        self.gpu = Some(gpu);
+        return self;
+    }
+
+    pub fn with_input_manager(mut self, input_manager: Arc<InputManager>) -> Self {
+        self.input_manager = Some(input_manager);
+        return self;
+    }
+
+    pub fn with_delay_timer(mut self, delay_timer: Arc<DelayTimer>) -> Self {
+        self.delay_timer = Some(delay_timer);
+        return self;
+    }
+
+    pub fn with_sound_timer(mut self, sound_timer: Arc<SoundTimer>) -> Self {
+        self.sound_timer = Some(sound_timer);
+        return self;
+    }
+
+    pub fn with_audio_sink(mut self, audio_sink: Box<dyn AudioSink>) -> Self {
+        self.audio_sink = Some(audio_sink);
+        return self;
+    }
+
+    /// Only used when the unknown opcode policy is "plugin".
+    pub fn with_opcode_handler(mut self, opcode_handler: Arc<dyn OpcodeHandler>) -> Self {
+        self.opcode_handler = Some(opcode_handler);
+        return self;
+    }
+
+    pub fn build(self) -> Option<Emulator> {
+        let lifecycle = self.lifecycle;
+        let observers = self.observers;
+
+        // Configs read from a file are already checked, but ones built in code aren't.
+        let problems = config::validate_config(&self.config);
+
+        for problem in &problems {
+            eprintln!("Error: {problem}");
+        }
+
+        if !problems.is_empty() {
+            lifecycle.shutdown();
+            return None;
+        }
+
+        let delay_timer = match self.delay_timer {
+            Some(d) => d,
+            None => DelayTimer::new(observers.clone(), self.config.delay_timer),
+        };
+
+        let sound_timer = match self.sound_timer {
+            Some(s) => s,
+            None => {
+                let audio_sink = self.audio_sink.unwrap_or_else(|| {
+                    backend::create_default_audio_sink(&self.config.sound_timer).unwrap_or_else(
+                        || {
+                            eprintln!("Warning: Could not open audio, so running without sound.");
+                            Box::new(backend::NullAudioSink)
+                        },
+                    )
+                });
+
+                SoundTimer::new(
+                    lifecycle.clone(),
+                    self.beeping.clone(),
+                    observers.clone(),
+                    audio_sink,
+                    self.config.sound_timer,
+                )
+            }
+        };
+
+        let input_manager = match self.input_manager {
+            Some(i) => i,
+            None => InputManager::new(lifecycle.clone(), self.config.input),
+        };
+
+        let gpu = match self.gpu {
+            Some(g) => g,
+            None => GPU::new(self.beeping, observers.clone(), self.config.gpu),
+        };
+
+        let ram = RAM::new(lifecycle.clone(), observers.clone(), self.config.ram);
+
+        lifecycle.set_turbo(self.config.cpu.unlimited_speed);
+
+        let cpu = CPU::new(
+            lifecycle.clone(),
+            self.config.cpu,
+            Peripherals {
+                gpu: gpu.clone(),
+                ram: ram.clone(),
+                delay_timer: delay_timer.clone(),
+                sound_timer: sound_timer.clone(),
+                input_manager: input_manager.clone(),
+            },
+            Arc::new(TimingHistory::new(overlay::TIMING_HISTORY_LENGTH)),
+            observers.clone(),
+        );
+
+        if let Some(opcode_handler) = self.opcode_handler {
+            cpu.set_opcode_handler(opcode_handler);
+        }
+
+        return Some(Emulator {
+            lifecycle,
+            cpu,
+            gpu,
+            ram,
+            delay_timer,
+            sound_timer,
+            input_manager,
+            observers,
+            threads_config: self.config.threads,
+            preset: self.config.preset,
+            run_budget: Mutex::new(RunBudget::default()),
+            refresh_budget: Mutex::new(RunBudget::default()),
+            // Hashes are looked up in lowercase, as they are reported.
+            rom_configs: self
+                .config
+                .roms
+                .into_iter()
+                .map(|(rom_hash, rom_config)| (rom_hash.to_ascii_lowercase(), rom_config))
+                .collect(),
+            replaced_configs: Mutex::new(None),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{BreakOnDraw, RenderOccasion};
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    #[test]
+    fn test_run_for() {
+        let path = std::env::temp_dir().join("chip8rust_test_run_for.ch8");
+        let path = path.to_str().unwrap();
+
+        // LD V0, 5; ADD V0, 3; LD DT, V0; JP 0x206
+        fs::write(path, [0x60, 0x05, 0x70, 0x03, 0xF0, 0x15, 0x12, 0x06]).unwrap();
+
+        let config = config::generate_configs_from(config::CONFIG_FILE_PATH, None).unwrap();
+        let emulator = Emulator::try_new(config).unwrap();
+
+        assert!(emulator.load_rom(path).is_ok());
+        assert!(emulator.run_for(Duration::from_millis(100)));
+
+        assert_eq!(8, emulator.get_v_regs()[0x0]);
+        assert_eq!(0x206, emulator.get_pc());
+        assert!(emulator.get_delay_timer() < 8);
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_configuration_report() {
+        let emulator = EmulatorBuilder::default().build().unwrap();
+        let report = emulator.get_configuration_report();
+
+        assert!(report.contains(&String::from("rom_sha1 = none")));
+
+        assert!(emulator.load_rom_bytes(b"abc").is_ok());
+        let report = emulator.get_configuration_report();
+
+        assert_eq!(
+            format!("chip8rust {}", env!("CARGO_PKG_VERSION")),
+            report[0]
+        );
+        assert!(report.contains(&String::from(
+            "rom_sha1 = a9993e364706816aba3e25717850c26c9cd0d89d"
+        )));
+        assert!(report.contains(&String::from("resolution = 64x32")));
+        assert!(report.contains(&String::from("unknown_opcode_policy = skip")));
+        assert!(report.contains(&String::from("stack_mode = fixed")));
+        assert!(report.contains(&String::from("stack_warning_depth = none")));
+        assert!(report.contains(&String::from("heap_overflow_policy = wrap")));
+        assert!(report.contains(&String::from("program_counter_overflow_policy = wrap")));
+        assert!(report.contains(&String::from("index_register_overflow_policy = wrap")));
+    }
+
+    #[test]
+    fn test_rom_config_applied_on_load() {
+        let fast_rom = [0x12, 0x00];
+        let other_rom = [0x12, 0x02, 0x12, 0x02];
+
+        let mut config = config::generate_default_configs();
+        config.roms.insert(
+            emulib::get_sha1(&fast_rom).to_ascii_uppercase(),
+            RomConfig {
+                instructions_per_second: Some(5000.0),
+                limit_to_one_draw_per_frame: Some(false),
+            },
+        );
+
+        let emulator = Emulator::try_new(config).unwrap();
+        let cpu = emulator.get_cpu();
+
+        assert!(emulator.load_rom_bytes(&fast_rom).is_ok());
+        assert_eq!(5000.0, emulator.get_instructions_per_second());
+        assert!(!cpu.get_config().limit_to_one_draw_per_frame);
+
+        // Reloading the same ROM shouldn't mistake the override for the usual settings.
+        assert!(emulator.load_rom_bytes(&fast_rom).is_ok());
+        assert!(emulator.load_rom_bytes(&other_rom).is_ok());
+        assert_eq!(700.0, emulator.get_instructions_per_second());
+        assert!(cpu.get_config().limit_to_one_draw_per_frame);
+    }
+
+    #[test]
+    fn test_octo_options_applied_on_load() {
+        let directory = std::env::temp_dir().join("chip8rust_test_octo_options");
+        fs::create_dir_all(&directory).unwrap();
+        let rom_path = directory.join("game.ch8");
+        let other_rom_path = directory.join("other.ch8");
+
+        fs::write(&rom_path, [0x12, 0x00]).unwrap();
+        fs::write(&other_rom_path, [0x12, 0x00]).unwrap();
+        fs::write(
+            directory.join("game.json"),
+            r##"{ "tickrate": 30, "fillColor": "#FFCC00" }"##,
+        )
+        .unwrap();
+
+        let emulator = EmulatorBuilder::default().build().unwrap();
+        let default_color = emulator.get_gpu().get_config().pixel_color_when_active;
+
+        assert!(emulator.load_rom(rom_path.to_str().unwrap()).is_ok());
+        assert_eq!(1800.0, emulator.get_instructions_per_second());
+        assert_eq!(
+            0xFFCC00,
+            emulator.get_gpu().get_config().pixel_color_when_active
+        );
+
+        // A program without options goes back to the usual settings.
+        assert!(emulator.load_rom(other_rom_path.to_str().unwrap()).is_ok());
+        assert_eq!(700.0, emulator.get_instructions_per_second());
+        assert_eq!(
+            default_color,
+            emulator.get_gpu().get_config().pixel_color_when_active
+        );
+
+        let _ = fs::remove_dir_all(directory);
+    }
+
+    #[test]
+    fn test_save_and_load_state() {
+        let emulator = EmulatorBuilder::default().build().unwrap();
+
+        // LD V0, 5; LD DT, V0; CALL 0x208; JP 0x206; ADD V0, 1; DRW V0, V0, 5; RET
+        let rom = [
+            0x60, 0x05, 0xF0, 0x15, 0x22, 0x08, 0x12, 0x06, 0x70, 0x01, 0xD0, 0x05, 0x00, 0xEE,
+        ];
+        assert!(emulator.load_rom_bytes(&rom).is_ok());
+
+        for _ in 0..5 {
+            assert!(emulator.step());
+        }
+
+        let bytes = emulator.save_state().to_bytes();
+        let framebuffer = emulator.framebuffer();
+
+        assert!(emulator.load_rom_bytes(&[0x00, 0xE0]).is_ok());
+        assert!(emulator.step());
+
+        let save_state = SaveState::from_bytes(&bytes).unwrap();
+        assert_eq!(Ok(()), emulator.load_state(&save_state));
+
+        assert_eq!(0x20C, emulator.get_pc());
+        assert_eq!(6, emulator.get_v_regs()[0x0]);
+        assert_eq!(5, emulator.get_delay_timer());
+        assert_eq!(framebuffer, emulator.framebuffer());
+        assert_eq!(Some(emulib::get_sha1(&rom)), emulator.get_rom_hash());
+
+        // The stack is restored too, so returning goes back to the jump after the call.
+        assert!(emulator.step());
+        assert_eq!(0x206, emulator.get_pc());
+    }
+
+    #[test]
+    fn test_load_state_with_changed_quirks() {
+        let mut cpu_config = config::generate_default_configs().cpu;
+        cpu_config.program_counter_overflow_policy = ProgramCounterOverflowPolicy::Halt;
+
+        let emulator = EmulatorBuilder::default()
+            .with_cpu_config(cpu_config)
+            .build()
+            .unwrap();
+        let save_state = emulator.save_state();
+
+        // Loaded all the same, but the policy it was saved with is warned about.
+        let emulator = EmulatorBuilder::default().build().unwrap();
+        assert_eq!(
+            vec![String::from("program_counter_overflow_policy = halt")],
+            emulator.get_changed_quirks(&save_state)
+        );
+        assert_eq!(Ok(()), emulator.load_state(&save_state));
+    }
+
+    #[test]
+    fn test_step_never_blocks() {
+        let mut cpu_config = config::generate_default_configs().cpu;
+        cpu_config.limit_to_one_draw_per_frame = true;
+
+        let mut gpu_config = config::generate_default_configs().gpu;
+        gpu_config.render_occasion = RenderOccasion::Changes;
+
+        let emulator = EmulatorBuilder::default()
+            .with_cpu_config(cpu_config)
+            .with_gpu_config(gpu_config)
+            .build()
+            .unwrap();
+
+        // DRW V0, V0, 1; DRW V0, V0, 1; LD V1, K; LD ST, V1
+        assert!(
+            emulator
+                .load_rom_bytes(&[0xD0, 0x01, 0xD0, 0x01, 0xF1, 0x0A, 0xF1, 0x18])
+                .is_ok()
+        );
+
+        assert!(emulator.step());
+        assert!(emulator.step());
+        assert!(emulator.step());
+        assert_eq!(0x204, emulator.get_pc());
+
+        emulator.press_key(0x9);
+        assert!(emulator.step());
+        emulator.release_key(0x9);
+        assert!(emulator.step());
+        assert_eq!(0x9, emulator.get_v_regs()[0x1]);
+
+        assert!(emulator.step());
+        assert!(emulator.run_for(Duration::from_millis(20)));
+        assert!(emulator.is_beeping());
+    }
+
+    #[test]
+    fn test_on_refresh_ticks_timers() {
+        let mut gpu_config = config::generate_default_configs().gpu;
+        gpu_config.render_occasion = RenderOccasion::Vsync;
+        gpu_config.tick_timers_on_refresh = true;
+
+        let emulator = EmulatorBuilder::default()
+            .with_gpu_config(gpu_config)
+            .build()
+            .unwrap();
+
+        // LD V0, 10; LD DT, V0
+        assert!(emulator.load_rom_bytes(&[0x60, 0x0A, 0xF0, 0x15]).is_ok());
+        assert!(emulator.step());
+        assert!(emulator.step());
+        emulator.get_gpu().dequeue_render();
+
+        // Each refresh of a 100 Hz monitor is 0.6 ticks of a 60 Hz timer.
+        let period = Duration::from_millis(10);
+        emulator.on_refresh(period);
+        assert_eq!(10, emulator.get_delay_timer());
+        // Nothing was drawn, so there is nothing to render.
+        assert!(!emulator.get_gpu().is_render_queued());
+
+        emulator.on_refresh(period);
+        assert_eq!(9, emulator.get_delay_timer());
+
+        emulator.on_refresh(period);
+        emulator.on_refresh(period);
+        assert_eq!(8, emulator.get_delay_timer());
+
+        emulator.pause();
+        emulator.on_refresh(period);
+        emulator.on_refresh(period);
+        assert_eq!(8, emulator.get_delay_timer());
+    }
+
+    #[test]
+    fn test_frames_observed_when_presented() {
+        #[derive(Default)]
+        struct FrameCounter {
+            frames: Mutex<Vec<usize>>,
+        }
+
+        impl EmulatorObserver for FrameCounter {
+            fn on_frame(&self, framebuffer: &[bool]) {
+                let lit_pixels = framebuffer.iter().filter(|p| **p).count();
+                self.frames.lock().unwrap().push(lit_pixels);
+            }
+        }
+
+        let emulator = EmulatorBuilder::default().build().unwrap();
+        let counter = Arc::new(FrameCounter::default());
+        emulator.add_observer(counter.clone());
+
+        // LD I, 0x050 (the font's 0); DRW V0, V0, 5
+        assert!(emulator.load_rom_bytes(&[0xA0, 0x50, 0xD0, 0x05]).is_ok());
+        assert!(emulator.step());
+        assert!(emulator.step());
+
+        // Queuing the frame again, as the render frequency does, isn't presenting it.
+        emulator.get_gpu().queue_render();
+        assert!(counter.frames.lock().unwrap().is_empty());
+
+        emulator.framebuffer();
+        emulator.framebuffer();
+        assert_eq!(vec![14], *counter.frames.lock().unwrap());
+    }
+
+    #[test]
+    fn test_break_on_draw_collision() {
+        let mut cpu_config = config::generate_default_configs().cpu;
+        cpu_config.break_on_draw = BreakOnDraw::Collision;
+
+        let emulator = EmulatorBuilder::default()
+            .with_cpu_config(cpu_config)
+            .build()
+            .unwrap();
+
+        // LD I, 0x50; DRW V0, V0, 5; DRW V0, V0, 5; DRW V0, V0, 5
+        let rom = [0xA0, 0x50, 0xD0, 0x05, 0xD0, 0x05, 0xD0, 0x05];
+        assert!(emulator.load_rom_bytes(&rom).is_ok());
+
+        assert!(emulator.step());
+        assert!(emulator.step());
+        assert!(!emulator.is_paused());
+
+        // Drawing over the same sprite erases it, which counts as a collision.
+        assert!(emulator.step());
+        assert!(emulator.is_paused());
+        assert_eq!(0x206, emulator.get_pc());
+        assert_eq!(1, emulator.get_v_regs()[0xF]);
+    }
+
+    #[test]
+    fn test_debug_step_out() {
+        // CALL 0x206; LD V1, 1; JP 0x204; CALL 0x20C; LD V0, 5; RET; RET
+        let rom = [
+            0x22, 0x06, 0x61, 0x01, 0x12, 0x04, 0x22, 0x0C, 0x60, 0x05, 0x00, 0xEE, 0x00, 0xEE,
+        ];
+        let emulator = EmulatorBuilder::default().build().unwrap();
+        assert!(emulator.load_rom_bytes(&rom).is_ok());
+
+        let run_until_paused = || {
+            while !emulator.is_paused() {
+                assert!(emulator.step());
+            }
+        };
+
+        assert!(emulator.step());
+        assert!(emulator.step());
+        emulator.pause();
+        assert_eq!(0x20C, emulator.get_pc());
+
+        // Only the innermost subroutine is left at a time.
+        assert!(emulator.debug_step_out());
+        assert!(!emulator.is_paused());
+        run_until_paused();
+        assert_eq!(0x208, emulator.get_pc());
+
+        assert!(emulator.debug_step_out());
+        run_until_paused();
+        assert_eq!(0x202, emulator.get_pc());
+        assert_eq!(5, emulator.get_v_regs()[0]);
+        assert_eq!(0, emulator.get_v_regs()[1]);
+
+        assert!(!emulator.debug_step_out());
+        assert!(emulator.is_paused());
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        // LD V0, 0; ADD V0, 1; JP 0x202
+        let rom = [0x60, 0x00, 0x70, 0x01, 0x12, 0x02];
+        let emulator = EmulatorBuilder::default().build().unwrap();
+        emulator.set_breakpoints(&[0x204]);
+        assert!(emulator.load_rom_bytes(&rom).is_ok());
+        assert_eq!(vec![0x204], emulator.get_breakpoints());
+
+        let run_until_paused = || {
+            while !emulator.is_paused() {
+                assert!(emulator.step());
+            }
+        };
+
+        run_until_paused();
+        assert_eq!(0x204, emulator.get_pc());
+        assert_eq!(1, emulator.get_v_regs()[0]);
+
+        // Resuming runs the instruction at the breakpoint, stopping there again next time round.
+        emulator.resume();
+        run_until_paused();
+        assert_eq!(0x204, emulator.get_pc());
+        assert_eq!(2, emulator.get_v_regs()[0]);
+    }
+
+    struct DoubleV0Handler;
+
+    impl OpcodeHandler for DoubleV0Handler {
+        fn execute(&self, machine: &Machine<'_>, opcode: u16) -> Option<bool> {
+            if opcode != 0x0123 {
+                return None;
+            }
+
+            machine.set_v_reg(0, machine.get_v_reg(0) * 2);
+            return Some(false);
+        }
+    }
+
+    #[test]
+    fn test_unknown_opcode_policy() {
+        // LD V0, 3; SYS 0x123; SYS 0x456
+        let rom = [0x60, 0x03, 0x01, 0x23, 0x04, 0x56];
+
+        let create_emulator = |policy| {
+            let mut cpu_config = config::generate_default_configs().cpu;
+            cpu_config.unknown_opcode_policy = policy;
+
+            let emulator = EmulatorBuilder::default()
+                .with_cpu_config(cpu_config)
+                .with_opcode_handler(Arc::new(DoubleV0Handler))
+                .build()
+                .unwrap();
+
+            assert!(emulator.load_rom_bytes(&rom).is_ok());
+            assert!(emulator.step());
+            return emulator;
+        };
+
+        let emulator = create_emulator(UnknownOpcodePolicy::Skip);
+        assert!(emulator.step());
+        assert!(emulator.step());
+        assert_eq!(3, emulator.get_v_regs()[0]);
+
+        let emulator = create_emulator(UnknownOpcodePolicy::Halt);
+        assert!(!emulator.step());
+        assert_eq!(
+            Some(ShutdownReason::Halted(EmulatorError::UnknownOpcode {
+                opcode: 0x0123,
+                address: 0x202
+            })),
+            emulator.get_shutdown_reason()
+        );
+
+        // Only the first unknown opcode pauses, before it runs.
+        let emulator = create_emulator(UnknownOpcodePolicy::Pause);
+        assert!(emulator.step());
+        assert!(emulator.is_paused());
+        assert_eq!(0x202, emulator.get_pc());
+        assert_eq!(
+            Some(EmulatorError::UnknownOpcode {
+                opcode: 0x0123,
+                address: 0x202
+            }),
+            emulator.get_unknown_opcode_break()
+        );
+
+        assert!(emulator.debug_step());
+        assert!(emulator.debug_step());
+        assert!(emulator.is_paused());
+        assert_eq!(0x206, emulator.get_pc());
+
+        // The handler only knows the first, so the second still halts.
+        let emulator = create_emulator(UnknownOpcodePolicy::Plugin);
+        assert!(emulator.step());
+        assert_eq!(6, emulator.get_v_regs()[0]);
+        assert!(!emulator.step());
+        assert_eq!(0x206, emulator.get_pc());
+    }
+
+    // Loads another program from inside the handler, which takes the same lock as running it.
+    #[derive(Default)]
+    struct ReloadingHandler {
+        emulator: Mutex<Option<Arc<Emulator>>>,
+    }
+
+    impl OpcodeHandler for ReloadingHandler {
+        fn execute(&self, _machine: &Machine<'_>, _opcode: u16) -> Option<bool> {
+            let emulator = self.emulator.lock().unwrap().take()?;
+            // LD V1, 7
+            assert!(emulator.load_rom_bytes(&[0x61, 0x07]).is_ok());
+            return Some(true);
+        }
+    }
+
+    #[test]
+    fn test_opcode_handler_can_load_programs() {
+        let mut cpu_config = config::generate_default_configs().cpu;
+        cpu_config.unknown_opcode_policy = UnknownOpcodePolicy::Plugin;
+        let handler = Arc::new(ReloadingHandler::default());
+
+        let emulator = Arc::new(
+            EmulatorBuilder::default()
+                .with_cpu_config(cpu_config)
+                .with_opcode_handler(handler.clone())
+                .build()
+                .unwrap(),
+        );
+        *handler.emulator.lock().unwrap() = Some(emulator.clone());
+
+        // SYS 0x123
+        assert!(emulator.load_rom_bytes(&[0x01, 0x23]).is_ok());
+        assert!(emulator.step());
+        assert_eq!(0x200, emulator.get_pc());
+        assert!(emulator.step());
+        assert_eq!(7, emulator.get_v_regs()[1]);
+    }
+
+    #[test]
+    fn test_execute_frame() {
+        let emulator = EmulatorBuilder::default().build().unwrap();
+
+        // LD V0, 2; LD DT, V0; LD I, 0x50; DRW V0, V0, 1; JP 0x208
+        let rom = [0x60, 0x02, 0xF0, 0x15, 0xA0, 0x50, 0xD0, 0x01, 0x12, 0x08];
+        assert!(emulator.load_rom_bytes(&rom).is_ok());
+
+        assert!(emulator.execute_frame());
+        assert_eq!(1, emulator.get_delay_timer());
+
+        assert!(!emulator.execute_frame());
+        assert_eq!(0, emulator.get_delay_timer());
+        assert_eq!(0x208, emulator.get_pc());
+    }
+
+    #[test]
+    fn test_run_async() {
+        // LD V0, 1; ADD V0, 1; JP 0x202
+        let emulator = EmulatorBuilder::default().build().unwrap();
+        assert!(
+            emulator
+                .load_rom_bytes(&[0x60, 0x01, 0x70, 0x01, 0x12, 0x02])
+                .is_ok()
+        );
+
+        let mut frames = 0;
+        let sleep = |_| {
+            frames += 1;
+
+            if frames == 3 {
+                emulator.stop();
+            }
+
+            return std::future::ready(());
+        };
+
+        let mut future = pin!(emulator.run_async(sleep));
+        let mut context = Context::from_waker(Waker::noop());
+        assert_eq!(Poll::Ready(()), future.as_mut().poll(&mut context));
+
+        assert!(!emulator.is_running());
+        assert!(emulator.get_v_regs()[0x0] > 1);
+    }
+
+    #[test]
+    fn test_builder_substitutes_components() {
+        let builder = EmulatorBuilder::default();
+        let mut input_config = config::generate_default_configs().input;
+        input_config.key_trigger_mode = KeyTriggerMode::Edge;
+
+        let input_manager = InputManager::new(builder.get_lifecycle(), input_config);
+        let emulator = builder
+            .with_input_manager(input_manager.clone())
+            .build()
+            .unwrap();
+
+        assert!(Arc::ptr_eq(&input_manager, &emulator.get_input_manager()));
+        assert!(emulator.is_running());
+
+        // The program runs on the substituted timers.
+        let builder = EmulatorBuilder::default();
+        let delay_timer = DelayTimer::new(
+            builder.get_observers(),
+            config::generate_default_configs().delay_timer,
+        );
+        delay_timer.set_value(30);
+
+        let emulator = builder.with_delay_timer(delay_timer).build().unwrap();
+        assert_eq!(30, emulator.get_delay_timer());
+    }
+
+    struct HeldKeySource(String);
+
+    impl InputSource for HeldKeySource {
+        fn is_key_pressed(&self, key: &str) -> bool {
+            return key == self.0;
+        }
+
+        fn is_key_released(&self, key: &str) -> bool {
+            return key != self.0;
+        }
+    }
+
+    #[test]
+    fn test_update_input() {
+        let emulator = EmulatorBuilder::default().build().unwrap();
+        let key_bindings = config::generate_default_configs().input.key_bindings;
+
+        emulator.update_input(&HeldKeySource(key_bindings[0x5].clone()));
+        assert_eq!(1 << 0x5, emulator.get_input_manager().get_held_keys());
+
+        emulator.update_input(&HeldKeySource(key_bindings[0xA].clone()));
+        assert_eq!(1 << 0xA, emulator.get_input_manager().get_held_keys());
+    }
+}
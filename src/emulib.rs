@@ -1,3 +1,6 @@
+use crate::config::{ThreadPriority, ThreadsConfig};
+use std::collections::VecDeque;
+use std::sync::Mutex;
 use std::thread;
 use std::time;
 
@@ -21,6 +24,11 @@ impl Limiter {
     }
 
     pub fn wait_if_early(&mut self) {
+        self.wait_if_early_scaled(1.0);
+    }
+
+    // Scales the time until the next wait ends, such as to add jitter.
+    pub fn wait_if_early_scaled(&mut self, scale: f64) {
         let current = time::Instant::now();
 
         if current < self.target {
@@ -29,7 +37,7 @@ impl Limiter {
 
         self.target = match self.catch_up {
             false => time::Instant::now(),
-            true => match self.target.checked_add(self.delay) {
+            true => match self.target.checked_add(self.delay.mul_f64(scale)) {
                 Some(t) => t,
                 None => {
                     eprintln!("Error: Failed to catch-up limiter.");
@@ -43,3 +51,308 @@ impl Limiter {
         self.target = time::Instant::now();
     }
 }
+
+/// Counts ticks at a rate from when it started, rather than waiting a fixed interval between them,
+/// so that rates with no exact interval in nanoseconds, such as 59.94 Hz, don't drift over long
+/// sessions. Any fraction of a tick is carried over to the next wait.
+pub struct TickClock {
+    rate: f64,
+    start: time::Instant,
+    ticks: u64,
+}
+
+impl TickClock {
+    pub fn new(rate: f64) -> Self {
+        if rate <= 0.0 {
+            panic!("Rate of tick clocks must be greater than 0.");
+        }
+
+        Self {
+            rate,
+            start: time::Instant::now(),
+            ticks: 0,
+        }
+    }
+
+    fn get_ticks_due(&self, elapsed: time::Duration) -> u64 {
+        return (elapsed.as_secs_f64() * self.rate).floor() as u64;
+    }
+
+    /// Waits until the next tick is due, then returns how many ticks are due, which is more than
+    /// one when catching up after falling behind.
+    pub fn wait_for_ticks(&mut self) -> u64 {
+        let next_tick = time::Duration::from_secs_f64((self.ticks + 1) as f64 / self.rate);
+        let elapsed = self.start.elapsed();
+
+        if elapsed < next_tick {
+            thread::sleep(next_tick - elapsed);
+        }
+
+        let ticks = self
+            .get_ticks_due(self.start.elapsed())
+            .saturating_sub(self.ticks)
+            .max(1);
+
+        self.ticks += ticks;
+        return ticks;
+    }
+
+    pub fn reset(&mut self) {
+        self.start = time::Instant::now();
+        self.ticks = 0;
+    }
+}
+
+/// Seeded random variation in timing, as a scale around 1.0 of at most amount either way.
+pub struct TimingJitter {
+    amount: f64,
+    rng: fastrand::Rng,
+}
+
+impl TimingJitter {
+    pub fn new(amount: f64, seed: u64) -> Self {
+        Self {
+            amount,
+            rng: fastrand::Rng::with_seed(seed),
+        }
+    }
+
+    pub fn next_scale(&mut self) -> f64 {
+        if self.amount == 0.0 {
+            return 1.0;
+        }
+
+        return 1.0 + self.amount * (self.rng.f64() * 2.0 - 1.0);
+    }
+}
+
+pub struct TimingHistory {
+    capacity: usize,
+    samples: Mutex<VecDeque<time::Duration>>,
+}
+
+impl TimingHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            samples: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub fn record(&self, sample: time::Duration) {
+        let mut samples = self.samples.lock().unwrap();
+
+        if samples.len() == self.capacity {
+            samples.pop_front();
+        }
+
+        samples.push_back(sample);
+    }
+
+    pub fn get_samples(&self) -> Vec<time::Duration> {
+        return self.samples.lock().unwrap().iter().copied().collect();
+    }
+
+    // The sample that the fraction of samples are at or below, such as 0.5 for the median.
+    pub fn get_percentile(&self, fraction: f64) -> Option<time::Duration> {
+        let mut samples = self.get_samples();
+
+        if samples.is_empty() {
+            return None;
+        }
+
+        samples.sort();
+        let index = ((samples.len() - 1) as f64 * fraction.clamp(0.0, 1.0)).round() as usize;
+        return Some(samples[index]);
+    }
+
+    // The average number of samples per second, treating each sample as the time since the last.
+    pub fn get_rate(&self) -> f64 {
+        let samples = self.samples.lock().unwrap();
+        let total: time::Duration = samples.iter().sum();
+
+        if total.is_zero() {
+            return 0.0;
+        }
+
+        return samples.len() as f64 / total.as_secs_f64();
+    }
+}
+
+pub fn spawn_named_thread<F>(name: &str, function: F) -> Option<thread::JoinHandle<()>>
+where
+    F: FnOnce() + Send + 'static,
+{
+    return match thread::Builder::new()
+        .name(name.to_string())
+        .spawn(function)
+    {
+        Ok(handle) => Some(handle),
+        Err(e) => {
+            eprintln!("Error: Failed to spawn the {name} thread ({e}).");
+            None
+        }
+    };
+}
+
+// Priority and affinity are only hints, so failing to apply them is not an error.
+pub fn apply_cpu_thread_hints(config: &ThreadsConfig) {
+    if config.cpu_thread_priority != ThreadPriority::Normal {
+        set_current_thread_priority(config.cpu_thread_priority);
+    }
+
+    if !config.cpu_thread_cores.is_empty() {
+        set_current_thread_cores(&config.cpu_thread_cores);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_current_thread_priority(priority: ThreadPriority) {
+    let niceness = match priority {
+        ThreadPriority::Low => 10,
+        ThreadPriority::Normal => 0,
+        ThreadPriority::High => -10,
+    };
+
+    // On Linux, niceness is per-thread when given a thread ID.
+    let result =
+        unsafe { libc::setpriority(libc::PRIO_PROCESS, libc::gettid() as libc::id_t, niceness) };
+
+    if result != 0 {
+        eprintln!(
+            "Warning: Could not set the CPU thread's priority ({}).",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_current_thread_cores(cores: &[usize]) {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+
+    for &core in cores {
+        if core >= libc::CPU_SETSIZE as usize {
+            eprintln!(
+                "Warning: Could not pin the CPU thread to core {core}, as it does not exist."
+            );
+            return;
+        }
+
+        unsafe { libc::CPU_SET(core, &mut set) };
+    }
+
+    let result =
+        unsafe { libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) };
+
+    if result != 0 {
+        eprintln!(
+            "Warning: Could not pin the CPU thread to cores {cores:?} ({}).",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_current_thread_priority(_priority: ThreadPriority) {
+    eprintln!("Warning: Thread priorities are only supported on Linux.");
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_current_thread_cores(_cores: &[usize]) {
+    eprintln!("Warning: Thread core affinity is only supported on Linux.");
+}
+
+// SHA-1 is what CHIP-8 ROM databases use to identify programs.
+pub fn get_sha1(data: &[u8]) -> String {
+    return sha1_smol::Sha1::from(data).digest().to_string();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timing_history_drops_oldest_samples() {
+        let history = TimingHistory::new(3);
+
+        for i in 1..=5 {
+            history.record(time::Duration::from_millis(i));
+        }
+
+        let ideal_samples: Vec<time::Duration> = (3..=5).map(time::Duration::from_millis).collect();
+
+        assert_eq!(ideal_samples, history.get_samples());
+    }
+
+    #[test]
+    fn test_timing_history_rate() {
+        let history = TimingHistory::new(4);
+        assert_eq!(0.0, history.get_rate());
+
+        for _ in 0..4 {
+            history.record(time::Duration::from_millis(25));
+        }
+
+        assert_eq!(40.0, history.get_rate());
+    }
+
+    #[test]
+    fn test_tick_clock_has_no_drift() {
+        let clock = TickClock::new(59.94);
+
+        // An interval rounded to the nanosecond would be a few microseconds out after an hour.
+        assert_eq!(215784, clock.get_ticks_due(time::Duration::from_secs(3600)));
+        assert_eq!(
+            215783,
+            clock.get_ticks_due(time::Duration::from_secs(3600) - time::Duration::from_nanos(1))
+        );
+
+        let mut clock = TickClock::new(1000.0);
+        assert_eq!(1, clock.wait_for_ticks());
+        thread::sleep(time::Duration::from_millis(5));
+        assert!(clock.wait_for_ticks() >= 4);
+    }
+
+    #[test]
+    fn test_timing_history_percentile() {
+        let history = TimingHistory::new(5);
+        assert_eq!(None, history.get_percentile(0.5));
+
+        for i in [4, 1, 5, 3, 2] {
+            history.record(time::Duration::from_millis(i));
+        }
+
+        assert_eq!(
+            Some(time::Duration::from_millis(3)),
+            history.get_percentile(0.5)
+        );
+        assert_eq!(
+            Some(time::Duration::from_millis(5)),
+            history.get_percentile(0.95)
+        );
+        assert_eq!(
+            Some(time::Duration::from_millis(1)),
+            history.get_percentile(0.0)
+        );
+    }
+
+    #[test]
+    fn test_timing_jitter_is_seeded_and_bounded() {
+        let scales: Vec<f64> = {
+            let mut jitter = TimingJitter::new(0.25, 7);
+            (0..100).map(|_| jitter.next_scale()).collect()
+        };
+
+        let mut jitter = TimingJitter::new(0.25, 7);
+        assert!(scales.iter().all(|scale| *scale == jitter.next_scale()));
+        assert!(scales.iter().all(|scale| (0.75..=1.25).contains(scale)));
+        assert!(scales.iter().any(|scale| *scale != 1.0));
+
+        assert_eq!(1.0, TimingJitter::new(0.0, 7).next_scale());
+    }
+
+    #[test]
+    fn test_get_sha1() {
+        assert_eq!("a9993e364706816aba3e25717850c26c9cd0d89d", get_sha1(b"abc"));
+    }
+}
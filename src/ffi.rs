@@ -0,0 +1,188 @@
+use crate::config;
+use crate::emulator::{Emulator, EmulatorBuilder};
+use std::ffi::{CStr, c_char};
+use std::ptr;
+use std::slice;
+use std::time::Duration;
+
+pub struct Chip8Emulator {
+    emulator: Emulator,
+    framebuffer: Vec<u8>,
+}
+
+fn into_handle(emulator: Option<Emulator>) -> *mut Chip8Emulator {
+    let Some(emulator) = emulator else {
+        return ptr::null_mut();
+    };
+
+    return Box::into_raw(Box::new(Chip8Emulator {
+        emulator,
+        framebuffer: Vec::new(),
+    }));
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn chip8_create() -> *mut Chip8Emulator {
+    return into_handle(EmulatorBuilder::default().build());
+}
+
+/// # Safety
+/// `config_path` must be a valid, nul-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_create_from_config(
+    config_path: *const c_char,
+) -> *mut Chip8Emulator {
+    if config_path.is_null() {
+        return ptr::null_mut();
+    }
+
+    let Ok(config_path) = unsafe { CStr::from_ptr(config_path) }.to_str() else {
+        eprintln!("Error: The config path is not valid UTF-8.");
+        return ptr::null_mut();
+    };
+
+    let Some(config) = config::generate_configs_from(config_path, None) else {
+        return ptr::null_mut();
+    };
+
+    return into_handle(Emulator::try_new(config));
+}
+
+/// # Safety
+/// `emulator` must be null or a pointer returned by a chip8_create function, and must not be used
+/// afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_destroy(emulator: *mut Chip8Emulator) {
+    if emulator.is_null() {
+        return;
+    }
+
+    let handle = unsafe { Box::from_raw(emulator) };
+    handle.emulator.stop();
+}
+
+/// # Safety
+/// `emulator` must be a live handle, and `rom` must point to `length` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_load_rom(
+    emulator: *mut Chip8Emulator,
+    rom: *const u8,
+    length: usize,
+) -> bool {
+    let Some(handle) = (unsafe { emulator.as_ref() }) else {
+        return false;
+    };
+
+    if rom.is_null() {
+        return false;
+    }
+
+    let rom = unsafe { slice::from_raw_parts(rom, length) };
+    return handle.emulator.load_rom_bytes(rom).is_ok();
+}
+
+/// # Safety
+/// `emulator` must be a live handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_step(emulator: *mut Chip8Emulator) -> bool {
+    let Some(handle) = (unsafe { emulator.as_ref() }) else {
+        return false;
+    };
+
+    return handle.emulator.step();
+}
+
+/// # Safety
+/// `emulator` must be a live handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_run_for(emulator: *mut Chip8Emulator, microseconds: u64) -> bool {
+    let Some(handle) = (unsafe { emulator.as_ref() }) else {
+        return false;
+    };
+
+    return handle.emulator.run_for(Duration::from_micros(microseconds));
+}
+
+/// Returns one byte per pixel (0 or 1), row by row, which stays valid until the next call.
+///
+/// # Safety
+/// `emulator` must be a live handle, and `width` and `height` must be null or writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_get_framebuffer(
+    emulator: *mut Chip8Emulator,
+    width: *mut usize,
+    height: *mut usize,
+) -> *const u8 {
+    let Some(handle) = (unsafe { emulator.as_mut() }) else {
+        return ptr::null();
+    };
+
+    let (screen_width, screen_height) = handle.emulator.screen_resolution();
+
+    handle.framebuffer.clear();
+    handle.framebuffer.extend(
+        handle
+            .emulator
+            .framebuffer()
+            .iter()
+            .map(|pixel| *pixel as u8),
+    );
+
+    if !width.is_null() {
+        unsafe { *width = screen_width };
+    }
+
+    if !height.is_null() {
+        unsafe { *height = screen_height };
+    }
+
+    return handle.framebuffer.as_ptr();
+}
+
+/// # Safety
+/// `emulator` must be a live handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_set_key(emulator: *mut Chip8Emulator, key: u8, pressed: bool) {
+    let Some(handle) = (unsafe { emulator.as_ref() }) else {
+        return;
+    };
+
+    if key > 0xF {
+        return;
+    }
+
+    match pressed {
+        true => handle.emulator.press_key(key),
+        false => handle.emulator.release_key(key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffi_round_trip() {
+        // LD I, 0x50 (the font's 0 glyph, using the default config); DRW V0, V0, 5; JP 0x204
+        let rom = [0xA0, 0x50, 0xD0, 0x05, 0x12, 0x04];
+        let emulator = chip8_create();
+        assert!(!emulator.is_null());
+
+        unsafe {
+            assert!(chip8_load_rom(emulator, rom.as_ptr(), rom.len()));
+            assert!(chip8_step(emulator));
+            assert!(chip8_step(emulator));
+
+            let mut width = 0;
+            let mut height = 0;
+            let framebuffer = chip8_get_framebuffer(emulator, &mut width, &mut height);
+            let framebuffer = slice::from_raw_parts(framebuffer, width * height);
+
+            assert_eq!((64, 32), (width, height));
+            assert_eq!(&[1, 1, 1, 1, 0], &framebuffer[..5]);
+
+            chip8_set_key(emulator, 0x3, true);
+            chip8_destroy(emulator);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! The screen's pixels, packed a bit each into rows of u64 words, so a sprite row is drawn and
+//! checked for collisions with a couple of bitwise operations rather than a pixel at a time.
+//!
+//! Each row starts on a word of its own, with its leftmost pixel in the word's highest bit. Rows
+//! wider than 64 pixels take more than one word, and the bits past the end of a row are always 0,
+//! so whole rows can be compared and copied as words.
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    words_per_row: usize,
+    words: Vec<u64>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        let words_per_row = width.div_ceil(WORD_BITS);
+
+        Self {
+            width,
+            height,
+            words_per_row,
+            words: vec![0; words_per_row * height],
+        }
+    }
+
+    pub fn get_size(&self) -> (usize, usize) {
+        return (self.width, self.height);
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        let word = self.words[y * self.words_per_row + x / WORD_BITS];
+        return word >> (WORD_BITS - 1 - x % WORD_BITS) & 1 == 1;
+    }
+
+    /// The words making up a row, leftmost pixel first.
+    pub fn get_row(&self, y: usize) -> &[u64] {
+        return &self.words[y * self.words_per_row..(y + 1) * self.words_per_row];
+    }
+
+    pub fn is_clear(&self) -> bool {
+        return self.words.iter().all(|word| *word == 0);
+    }
+
+    pub fn clear(&mut self) {
+        self.words.fill(0);
+    }
+
+    /// Xors a sprite row into the row, with the sprite's highest bit at column x, which must be
+    /// inside the row. Returns whether any pixel was turned off.
+    ///
+    /// Pixels past the right edge wrap around to the left if `wrap` is set, and are dropped if not.
+    pub fn draw_row(&mut self, x: usize, y: usize, sprite_row: u8, wrap: bool) -> bool {
+        let bits = (sprite_row as u64) << (WORD_BITS - 8);
+        let mut collided = false;
+        let mut drawn = 0;
+        let mut x = x;
+
+        // Narrow screens can take more than one wrap to fit a whole sprite row.
+        while drawn < 8 {
+            let length = (8 - drawn).min(self.width - x);
+            collided |= self.xor_span(x, y, bits << drawn, length);
+            drawn += length;
+
+            if !wrap {
+                break;
+            }
+
+            x = 0;
+        }
+
+        return collided;
+    }
+
+    // Xors the highest `length` bits of `bits` into the row from column x, which has to have room
+    // for them. They can straddle two words.
+    fn xor_span(&mut self, x: usize, y: usize, bits: u64, length: usize) -> bool {
+        let bits = bits & !(u64::MAX >> length);
+        let index = y * self.words_per_row + x / WORD_BITS;
+        let offset = x % WORD_BITS;
+
+        let first = bits >> offset;
+        let mut collided = self.words[index] & first != 0;
+        self.words[index] ^= first;
+
+        if offset + length > WORD_BITS {
+            let second = bits << (WORD_BITS - offset);
+            collided |= self.words[index + 1] & second != 0;
+            self.words[index + 1] ^= second;
+        }
+
+        return collided;
+    }
+
+    /// One bool per pixel, row by row, as frontends and save states take them.
+    pub fn to_pixels(&self) -> Vec<bool> {
+        let mut pixels = Vec::with_capacity(self.width * self.height);
+
+        for y in 0..self.height {
+            pixels.extend((0..self.width).map(|x| self.get(x, y)));
+        }
+
+        return pixels;
+    }
+
+    /// Replaces every pixel, going row by row. The pixels must fill the framebuffer exactly.
+    pub fn load_pixels(&mut self, pixels: &[bool]) {
+        assert_eq!(self.width * self.height, pixels.len());
+        self.clear();
+
+        for (i, _) in pixels.iter().enumerate().filter(|(_, pixel)| **pixel) {
+            self.set(i % self.width, i / self.width);
+        }
+    }
+
+    fn set(&mut self, x: usize, y: usize) {
+        self.words[y * self.words_per_row + x / WORD_BITS] |= 1 << (WORD_BITS - 1 - x % WORD_BITS);
+    }
+}
+
+// Copies are taken every frame, so clone_from reuses the copy's memory.
+impl Clone for Framebuffer {
+    fn clone(&self) -> Self {
+        Self {
+            width: self.width,
+            height: self.height,
+            words_per_row: self.words_per_row,
+            words: self.words.clone(),
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.width = source.width;
+        self.height = source.height;
+        self.words_per_row = source.words_per_row;
+        self.words.clone_from(&source.words);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_row() {
+        let mut framebuffer = Framebuffer::new(100, 2);
+
+        // Straddles the first two words.
+        assert!(!framebuffer.draw_row(60, 0, 0b1000_0001, false));
+        assert!(framebuffer.get(60, 0));
+        assert!(!framebuffer.get(61, 0));
+        assert!(framebuffer.get(67, 0));
+        assert_eq!(&[1 << 3, 1 << 60], framebuffer.get_row(0));
+
+        assert!(framebuffer.draw_row(64, 0, 0b1111_0000, false));
+        assert!(!framebuffer.get(67, 0));
+        assert!(framebuffer.get(64, 0));
+
+        // Past the right edge, pixels wrap or are dropped.
+        assert!(!framebuffer.draw_row(96, 1, 0xFF, true));
+        assert!(framebuffer.get(99, 1) && framebuffer.get(0, 1) && framebuffer.get(3, 1));
+        assert!(!framebuffer.get(4, 1));
+        framebuffer.clear();
+        assert!(framebuffer.is_clear());
+        assert!(!framebuffer.draw_row(96, 1, 0xFF, false));
+        assert!(!framebuffer.get(0, 1));
+        assert_eq!(&[0, 0b1111 << 28], framebuffer.get_row(1));
+
+        // A screen narrower than a sprite row wraps it more than once.
+        let mut narrow = Framebuffer::new(3, 1);
+        assert!(!narrow.draw_row(2, 0, 0b1100_0000, true));
+        assert_eq!(vec![true, false, true], narrow.to_pixels());
+    }
+
+    #[test]
+    fn test_pixels_round_trip() {
+        let mut pixels = vec![false; 70 * 3];
+        pixels[0] = true;
+        pixels[69] = true;
+        pixels[70 + 64] = true;
+        pixels[70 * 3 - 1] = true;
+
+        let mut framebuffer = Framebuffer::new(70, 3);
+        framebuffer.load_pixels(&pixels);
+        assert_eq!(pixels, framebuffer.to_pixels());
+        assert!(framebuffer.get(64, 1));
+    }
+}
@@ -1,114 +1,293 @@
-use crate::config::{GPUConfig, RenderOccasion};
-use crate::emulib::Limiter;
+use crate::backend::Display;
+use crate::config::{CollisionReporting, GPUConfig, Palette, RenderOccasion, SpriteEdgePolicy};
+use crate::framebuffer::Framebuffer;
+use crate::observer::Observers;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Condvar, Mutex, MutexGuard};
-use std::time::Duration;
-
-const CONDVAR_WAIT_TIMEOUT: Duration = Duration::from_millis(100);
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Instant;
+
+// Colors for each palette preset, as (active, inactive, border).
+const HIGH_CONTRAST_COLORS: (u32, u32, u32) = (0xFFFFFF, 0x000000, 0x808080);
+const DEUTERANOPIA_COLORS: (u32, u32, u32) = (0xF0E442, 0x002B5C, 0x0072B2);
+
+// The parts of the palette that can be changed while running, applied over the config.
+#[derive(Clone, Copy)]
+struct PaletteState {
+    palette: Palette,
+    inverted: bool,
+}
 
 pub struct GPU {
-    active: Arc<AtomicBool>,
-    config: GPUConfig,
-    framebuffer: Mutex<Vec<bool>>,
+    beeping: Arc<AtomicBool>,
+    observers: Arc<Observers>,
+    config: RwLock<GPUConfig>,
+    palette_state: Mutex<PaletteState>,
+    framebuffer: Mutex<Framebuffer>,
     render_queued: Mutex<bool>,
-    render_queue_cvar: Condvar,
+    // Whether the framebuffer changed since a render was last dequeued, so renders at a frequency
+    // or on refresh can be skipped when there is nothing new to show.
+    changed_since_render: AtomicBool,
+    // Whether a sprite was drawn since the driver last noted the time, which it does after
+    // running instructions, as the GPU doesn't read the clock itself.
+    drawn_since_noted: AtomicBool,
+    // When the first draw since the last frame was presented happened, for measuring latency.
+    first_unpresented_draw: Mutex<Option<Instant>>,
 }
 
 impl GPU {
-    pub fn try_new(active: Arc<AtomicBool>, config: GPUConfig) -> Option<Arc<Self>> {
-        if config.render_occasion == RenderOccasion::Frequency && config.render_frequency <= 0.0 {
-            eprintln!("Error: The graphic render frequency must be greater than 0.");
-            active.store(false, Ordering::Relaxed);
-            return None;
-        }
-
-        let framebuffer_size =
-            config.horizontal_resolution as usize * config.vertical_resolution as usize;
-
-        return Some(Arc::new(Self {
-            active,
-            config,
-            framebuffer: Mutex::new(vec![false; framebuffer_size]),
+    pub fn new(
+        beeping: Arc<AtomicBool>,
+        observers: Arc<Observers>,
+        config: GPUConfig,
+    ) -> Arc<Self> {
+        let framebuffer =
+            Framebuffer::new(config.horizontal_resolution, config.vertical_resolution);
+
+        return Arc::new(Self {
+            beeping,
+            observers,
+            palette_state: Mutex::new(PaletteState {
+                palette: config.palette,
+                inverted: false,
+            }),
+            config: RwLock::new(config),
+            framebuffer: Mutex::new(framebuffer),
             render_queued: Mutex::new(false),
-            render_queue_cvar: Condvar::new(),
-        }));
+            changed_since_render: AtomicBool::new(false),
+            drawn_since_noted: AtomicBool::new(false),
+            first_unpresented_draw: Mutex::new(None),
+        });
     }
 
     #[cfg(test)]
-    pub fn new_default_wrapping(active: Arc<AtomicBool>) -> Arc<Self> {
-        Self::try_new(
-            active,
+    pub fn new_default_wrapping() -> Arc<Self> {
+        Self::new(
+            Arc::new(AtomicBool::new(false)),
+            Observers::new(),
             GPUConfig {
+                palette: Palette::Custom,
                 pixel_color_when_active: 0xFFFFFF,
                 pixel_color_when_inactive: 0x000000,
                 screen_border_color: 0x777777,
+                change_border_color_when_beeping: false,
+                screen_border_color_when_beeping: 0xAA3333,
+                invert_display_when_beeping: false,
+                invert_border_with_colors: false,
                 horizontal_resolution: 64,
                 vertical_resolution: 32,
                 wrap_sprite_positions: true,
-                wrap_sprite_pixels: true,
+                horizontal_sprite_edge_policy: SpriteEdgePolicy::Wrap,
+                vertical_sprite_edge_policy: SpriteEdgePolicy::Wrap,
                 render_occasion: RenderOccasion::Changes,
                 render_frequency: 0.0,
+                render_heartbeat_frequency: None,
+                tick_timers_on_refresh: false,
+                collision_reporting: Some(CollisionReporting::Flag),
+                display_rotation: 0,
+                pixel_aspect_ratio: [1, 1],
+                show_frame_pacing_overlay: false,
+                show_status_bar: false,
+                monitor: None,
             },
         )
-        .unwrap()
     }
 
     // #[cfg(test)]
-    // pub fn new_default_no_wrapping(active: Arc<AtomicBool>) -> Arc<Self> {
-    //     Self::try_new(
-    //         active,
+    // pub fn new_default_no_wrapping() -> Arc<Self> {
+    //     Self::new(
+    //         Arc::new(AtomicBool::new(false)),
+    //         Observers::new(),
     //         GPUConfig {
+    //             palette: Palette::Custom,
     //             pixel_color_when_active: 0xFFFFFF,
     //             pixel_color_when_inactive: 0x000000,
     //             screen_border_color: 0x777777,
+    //             change_border_color_when_beeping: false,
+    //             screen_border_color_when_beeping: 0xAA3333,
+    //             invert_display_when_beeping: false,
+    //             invert_border_with_colors: false,
     //             horizontal_resolution: 64,
     //             vertical_resolution: 32,
     //             wrap_sprite_positions: false,
-    //             wrap_sprite_pixels: false,
+    //             horizontal_sprite_edge_policy: SpriteEdgePolicy::Clip,
+    //             vertical_sprite_edge_policy: SpriteEdgePolicy::Clip,
     //             render_occasion: RenderOccasion::Changes,
     //             render_frequency: 0.0,
+    //             render_heartbeat_frequency: None,
+    //             tick_timers_on_refresh: false,
+    //             collision_reporting: Some(CollisionReporting::Flag),
+    //             display_rotation: 0,
+    //             pixel_aspect_ratio: [1, 1],
+    //             show_frame_pacing_overlay: false,
+    //             show_status_bar: false,
+    //             monitor: None,
     //         },
     //     )
     //     .unwrap()
     // }
 
     pub fn should_render_separately(&self) -> bool {
-        return self.config.render_occasion == RenderOccasion::Frequency;
+        return self.get_config().render_occasion == RenderOccasion::Frequency;
     }
 
-    pub fn run_separate_render(&self) {
-        let mut limiter = Limiter::new(self.config.render_frequency, true);
-
-        while self.active.load(Ordering::Relaxed) {
-            limiter.wait_if_early();
+    pub fn is_synced_to_refresh(&self) -> bool {
+        return self.get_config().render_occasion == RenderOccasion::Vsync;
+    }
 
-            self.queue_render();
-        }
+    pub fn should_tick_timers_on_refresh(&self) -> bool {
+        let config = self.get_config();
+        return config.render_occasion == RenderOccasion::Vsync && config.tick_timers_on_refresh;
     }
 
     pub fn get_screen_resolution(&self) -> (usize, usize) {
-        return (
-            self.config.horizontal_resolution,
-            self.config.vertical_resolution,
-        );
+        let config = self.get_config();
+        return (config.horizontal_resolution, config.vertical_resolution);
+    }
+
+    pub fn get_display_rotation(&self) -> u16 {
+        return self.get_config().display_rotation;
+    }
+
+    pub fn get_pixel_aspect_ratio(&self) -> (usize, usize) {
+        let [aspect_width, aspect_height] = self.get_config().pixel_aspect_ratio;
+        return (aspect_width, aspect_height);
+    }
+
+    pub fn should_show_frame_pacing_overlay(&self) -> bool {
+        return self.get_config().show_frame_pacing_overlay;
+    }
+
+    pub fn should_show_status_bar(&self) -> bool {
+        return self.get_config().show_status_bar;
+    }
+
+    pub fn get_render_heartbeat_frequency(&self) -> Option<f64> {
+        return self.get_config().render_heartbeat_frequency;
+    }
+
+    pub fn get_monitor(&self) -> Option<String> {
+        return self.get_config().monitor.clone();
+    }
+
+    pub fn get_config(&self) -> RwLockReadGuard<'_, GPUConfig> {
+        return self.config.read().unwrap();
+    }
+
+    pub fn get_config_mut(&self) -> RwLockWriteGuard<'_, GPUConfig> {
+        return self.config.write().unwrap();
+    }
+
+    pub fn get_palette(&self) -> Palette {
+        return self.palette_state.lock().unwrap().palette;
+    }
+
+    pub fn cycle_palette(&self) {
+        let mut palette_state = self.palette_state.lock().unwrap();
+
+        palette_state.palette = match palette_state.palette {
+            Palette::Custom => Palette::HighContrast,
+            Palette::HighContrast => Palette::Deuteranopia,
+            Palette::Deuteranopia => Palette::Custom,
+        };
+    }
+
+    /// Swaps the active and inactive colors of whichever palette is in use, and inverts the border
+    /// too with invert_border_with_colors set. This isn't saved to the config.
+    pub fn toggle_inverted_colors(&self) {
+        let mut palette_state = self.palette_state.lock().unwrap();
+        palette_state.inverted = !palette_state.inverted;
+    }
+
+    fn get_palette_colors(&self) -> (u32, u32, u32) {
+        let config = self.get_config();
+        let palette_state = *self.palette_state.lock().unwrap();
+
+        let (active, inactive, border) = match palette_state.palette {
+            Palette::Custom => (
+                config.pixel_color_when_active,
+                config.pixel_color_when_inactive,
+                config.screen_border_color,
+            ),
+            Palette::HighContrast => HIGH_CONTRAST_COLORS,
+            Palette::Deuteranopia => DEUTERANOPIA_COLORS,
+        };
+
+        if !palette_state.inverted {
+            return (active, inactive, border);
+        }
+
+        return match config.invert_border_with_colors {
+            true => (inactive, active, !border & 0xFFFFFF),
+            false => (inactive, active, border),
+        };
     }
 
     pub fn get_active_color(&self) -> u32 {
-        return self.config.pixel_color_when_active;
+        let (active, inactive, _) = self.get_palette_colors();
+
+        if self.is_display_inverted() {
+            return inactive;
+        }
+
+        return active;
     }
 
     pub fn get_inactive_color(&self) -> u32 {
-        return self.config.pixel_color_when_inactive;
+        let (active, inactive, _) = self.get_palette_colors();
+
+        if self.is_display_inverted() {
+            return active;
+        }
+
+        return inactive;
+    }
+
+    fn is_display_inverted(&self) -> bool {
+        return self.get_config().invert_display_when_beeping
+            && self.beeping.load(Ordering::Relaxed);
     }
 
     pub fn get_border_color(&self) -> u32 {
-        return self.config.screen_border_color;
+        let config = self.get_config();
+
+        if config.change_border_color_when_beeping && self.beeping.load(Ordering::Relaxed) {
+            return config.screen_border_color_when_beeping;
+        }
+
+        drop(config);
+
+        let (_, _, border) = self.get_palette_colors();
+        return border;
     }
 
-    pub fn get_framebuffer(&self) -> MutexGuard<'_, Vec<bool>> {
+    pub fn get_framebuffer(&self) -> MutexGuard<'_, Framebuffer> {
         return self.framebuffer.lock().unwrap();
     }
 
+    pub fn present_to(&self, display: &mut dyn Display) -> bool {
+        let resolution = self.get_screen_resolution();
+        let framebuffer = self.get_framebuffer().to_pixels();
+        self.dequeue_render();
+        return display.present(&framebuffer, resolution);
+    }
+
+    /// Notes the time as that of the first sprite drawn since the last frame was presented, if one
+    /// was drawn since this was last called. Called by whatever runs the CPU, after instructions.
+    pub fn note_draw_time(&self, now: Instant) {
+        if self.drawn_since_noted.swap(false, Ordering::Relaxed) {
+            self.first_unpresented_draw
+                .lock()
+                .unwrap()
+                .get_or_insert(now);
+        }
+    }
+
+    /// Takes the time of the first sprite drawn since this was last called, for a frontend to call
+    /// as it presents a frame.
+    pub fn take_first_unpresented_draw(&self) -> Option<Instant> {
+        return self.first_unpresented_draw.lock().unwrap().take();
+    }
+
     pub fn is_render_queued(&self) -> bool {
         return *self.render_queued.lock().unwrap();
     }
@@ -117,112 +296,323 @@ impl GPU {
         *self.render_queued.lock().unwrap() = true;
     }
 
-    pub fn dequeue_render(&self) {
-        *self.render_queued.lock().unwrap() = false;
-        self.render_queue_cvar.notify_all();
+    pub fn queue_render_if_changed(&self) {
+        if self.changed_since_render.load(Ordering::Relaxed) {
+            self.queue_render();
+        }
     }
 
-    pub fn wait_for_render(&self) {
-        let mut render_queued = self.render_queued.lock().unwrap();
+    /// Marks the queued frame as presented, telling observers about it if one was queued.
+    pub fn dequeue_render(&self) {
+        self.changed_since_render.store(false, Ordering::Relaxed);
+        let was_queued = std::mem::replace(&mut *self.render_queued.lock().unwrap(), false);
 
-        while *render_queued && self.active.load(Ordering::Relaxed) {
-            (render_queued, _) = self
-                .render_queue_cvar
-                .wait_timeout(render_queued, CONDVAR_WAIT_TIMEOUT)
-                .unwrap();
+        if was_queued && !self.observers.is_empty() {
+            self.observers
+                .notify_frame(&self.get_framebuffer().to_pixels());
         }
     }
 
     pub fn clear_framebuffer(&self) {
-        self.framebuffer.lock().unwrap().fill(false);
+        let mut framebuffer = self.framebuffer.lock().unwrap();
+
+        if framebuffer.is_clear() {
+            return;
+        }
+
+        framebuffer.clear();
+        drop(framebuffer);
+
+        self.mark_changed();
+    }
 
-        if self.config.render_occasion == RenderOccasion::Changes {
+    fn mark_changed(&self) {
+        self.changed_since_render.store(true, Ordering::Relaxed);
+
+        if self.get_config().render_occasion == RenderOccasion::Changes {
             self.queue_render();
         }
     }
 
-    pub fn draw_sprite(&self, sprite: Vec<u8>, x_pos: u8, y_pos: u8) -> bool {
+    pub fn draw_sprite(&self, sprite: Vec<u8>, x_pos: u8, y_pos: u8) -> u8 {
         if cfg!(debug_assertions) && sprite.len() > 15 {
             panic!("Error: Should not be draw a sprite larger than 16 bytes.");
         }
 
+        let config = self.get_config();
         let mut x_pos = x_pos as usize;
         let mut y_pos = y_pos as usize;
 
-        if self.config.wrap_sprite_positions {
-            x_pos %= self.config.horizontal_resolution;
-            y_pos %= self.config.vertical_resolution;
+        if config.wrap_sprite_positions {
+            x_pos %= config.horizontal_resolution;
+            y_pos %= config.vertical_resolution;
         } else {
-            if x_pos >= self.config.horizontal_resolution
-                || y_pos >= self.config.vertical_resolution
-            {
-                return false;
+            if x_pos >= config.horizontal_resolution || y_pos >= config.vertical_resolution {
+                return 0;
             }
         }
 
-        let mut collided = false;
+        let wrap = config.horizontal_sprite_edge_policy == SpriteEdgePolicy::Wrap;
+        let mut collided_rows = 0;
+        let mut clipped_rows = 0;
+        let mut changed = false;
         let mut framebuffer = self.framebuffer.lock().unwrap();
 
         for i in 0..sprite.len() {
-            if self.draw_byte(&mut framebuffer, sprite[i], x_pos, y_pos + i) {
-                collided = true;
+            if config.vertical_sprite_edge_policy == SpriteEdgePolicy::Clip
+                && y_pos + i >= config.vertical_resolution
+            {
+                clipped_rows += 1;
+                continue;
             }
+
+            let y = (y_pos + i) % config.vertical_resolution;
+
+            if framebuffer.draw_row(x_pos, y, sprite[i], wrap) {
+                collided_rows += 1;
+            }
+
+            changed |= sprite[i] != 0;
         }
 
-        if self.config.render_occasion == RenderOccasion::Changes {
-            self.queue_render();
+        drop(framebuffer);
+
+        self.drawn_since_noted.store(true, Ordering::Relaxed);
+
+        let collision = match config.collision_reporting.unwrap_or_default() {
+            CollisionReporting::Flag => (collided_rows > 0) as u8,
+            CollisionReporting::RowCount => collided_rows + clipped_rows,
+        };
+
+        drop(config);
+
+        // Blank sprites leave the framebuffer as it was, so there is nothing to render.
+        if changed {
+            self.mark_changed();
         }
 
-        return collided;
+        return collision;
     }
+}
 
-    fn draw_byte(
-        &self,
-        framebuffer: &mut MutexGuard<'_, Vec<bool>>,
-        mut byte: u8,
-        x_pos: usize,
-        y_pos: usize,
-    ) -> bool {
-        let mut collided = false;
-
-        for i in (0..8).rev() {
-            let should_draw_bit = byte & 0x01 == 1;
-            byte >>= 1;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn get_contrast_ratio(first: u32, second: u32) -> f64 {
+        let get_luminance = |color: u32| {
+            let linearise = |channel: u32| {
+                let c = (channel & 0xFF) as f64 / 255.0;
+
+                match c <= 0.03928 {
+                    true => c / 12.92,
+                    false => ((c + 0.055) / 1.055).powf(2.4),
+                }
+            };
+
+            0.2126 * linearise(color >> 16)
+                + 0.7152 * linearise(color >> 8)
+                + 0.0722 * linearise(color)
+        };
+
+        let (first, second) = (get_luminance(first), get_luminance(second));
+        return (first.max(second) + 0.05) / (first.min(second) + 0.05);
+    }
 
-            if !should_draw_bit {
-                continue;
-            }
+    fn create_row_count_gpu() -> Arc<GPU> {
+        GPU::new(
+            Arc::new(AtomicBool::new(false)),
+            Observers::new(),
+            GPUConfig {
+                palette: Palette::Custom,
+                pixel_color_when_active: 0xFFFFFF,
+                pixel_color_when_inactive: 0x000000,
+                screen_border_color: 0x777777,
+                change_border_color_when_beeping: false,
+                screen_border_color_when_beeping: 0xAA3333,
+                invert_display_when_beeping: false,
+                invert_border_with_colors: false,
+                horizontal_resolution: 64,
+                vertical_resolution: 32,
+                wrap_sprite_positions: true,
+                horizontal_sprite_edge_policy: SpriteEdgePolicy::Clip,
+                vertical_sprite_edge_policy: SpriteEdgePolicy::Clip,
+                render_occasion: RenderOccasion::Changes,
+                render_frequency: 0.0,
+                render_heartbeat_frequency: None,
+                tick_timers_on_refresh: false,
+                collision_reporting: Some(CollisionReporting::RowCount),
+                display_rotation: 0,
+                pixel_aspect_ratio: [1, 1],
+                show_frame_pacing_overlay: false,
+                show_status_bar: false,
+                monitor: None,
+            },
+        )
+    }
 
-            if let Some(true) = self.draw_pixel(framebuffer, x_pos + i, y_pos) {
-                collided = true;
-            }
+    #[test]
+    fn test_palette_presets_meet_contrast_ratio() {
+        for (active, inactive, _) in [HIGH_CONTRAST_COLORS, DEUTERANOPIA_COLORS] {
+            assert!(get_contrast_ratio(active, inactive) >= 7.0);
         }
+    }
+
+    #[test]
+    fn test_cycle_palette() {
+        let gpu = GPU::new_default_wrapping();
 
-        return collided;
+        gpu.cycle_palette();
+        assert_eq!(HIGH_CONTRAST_COLORS.0, gpu.get_active_color());
+
+        gpu.cycle_palette();
+        assert_eq!(DEUTERANOPIA_COLORS.1, gpu.get_inactive_color());
+
+        gpu.cycle_palette();
+        assert_eq!(0x777777, gpu.get_border_color());
     }
 
-    fn draw_pixel(
-        &self,
-        framebuffer: &mut MutexGuard<'_, Vec<bool>>,
-        mut x_pos: usize,
-        mut y_pos: usize,
-    ) -> Option<bool> {
-        let width = self.config.horizontal_resolution as usize;
-        let height = self.config.vertical_resolution as usize;
+    #[test]
+    fn test_toggle_inverted_colors() {
+        let gpu = GPU::new_default_wrapping();
+        let (active, inactive) = (gpu.get_active_color(), gpu.get_inactive_color());
 
-        if self.config.wrap_sprite_pixels {
-            x_pos %= width;
-            y_pos %= height;
-        } else {
-            if x_pos >= width || y_pos >= height {
-                return None;
+        gpu.toggle_inverted_colors();
+        assert_eq!(inactive, gpu.get_active_color());
+        assert_eq!(active, gpu.get_inactive_color());
+        assert_eq!(0x777777, gpu.get_border_color());
+
+        // The inversion carries over to the other palettes.
+        gpu.cycle_palette();
+        assert_eq!(HIGH_CONTRAST_COLORS.1, gpu.get_active_color());
+
+        gpu.get_config_mut().invert_border_with_colors = true;
+        assert_eq!(!HIGH_CONTRAST_COLORS.2 & 0xFFFFFF, gpu.get_border_color());
+
+        gpu.toggle_inverted_colors();
+        assert_eq!(HIGH_CONTRAST_COLORS.0, gpu.get_active_color());
+    }
+
+    #[test]
+    fn test_take_first_unpresented_draw() {
+        let gpu = GPU::new_default_wrapping();
+        let start = Instant::now();
+        let (first, second) = (
+            start + Duration::from_millis(1),
+            start + Duration::from_millis(2),
+        );
+
+        // Only a time noted after a draw counts.
+        gpu.note_draw_time(start);
+        assert_eq!(None, gpu.take_first_unpresented_draw());
+
+        // Later draws before the next present don't move the start of the wait.
+        gpu.draw_sprite(vec![0xFF], 0, 0);
+        gpu.note_draw_time(first);
+        gpu.draw_sprite(vec![0xFF], 8, 0);
+        gpu.note_draw_time(second);
+        assert_eq!(Some(first), gpu.take_first_unpresented_draw());
+        assert_eq!(None, gpu.take_first_unpresented_draw());
+
+        gpu.draw_sprite(vec![0xFF], 0, 0);
+        gpu.note_draw_time(second);
+        assert_eq!(Some(second), gpu.take_first_unpresented_draw());
+    }
+
+    #[test]
+    fn test_queue_render_only_on_changes() {
+        let gpu = GPU::new_default_wrapping();
+
+        gpu.draw_sprite(vec![0x00], 0, 0);
+        gpu.clear_framebuffer();
+        assert!(!gpu.is_render_queued());
+
+        gpu.draw_sprite(vec![0x80], 0, 0);
+        assert!(gpu.is_render_queued());
+        gpu.dequeue_render();
+        gpu.queue_render_if_changed();
+        assert!(!gpu.is_render_queued());
+
+        gpu.get_config_mut().render_occasion = RenderOccasion::Frequency;
+        gpu.clear_framebuffer();
+        assert!(!gpu.is_render_queued());
+        gpu.queue_render_if_changed();
+        assert!(gpu.is_render_queued());
+    }
+
+    #[test]
+    fn test_draw_sprite_collision_flag() {
+        let gpu = GPU::new_default_wrapping();
+
+        assert_eq!(0, gpu.draw_sprite(vec![0xFF, 0xFF, 0xFF], 0, 0));
+        assert_eq!(1, gpu.draw_sprite(vec![0xFF, 0xFF, 0xFF], 0, 0));
+    }
+
+    #[test]
+    fn test_draw_sprite_collision_row_count() {
+        let gpu = create_row_count_gpu();
+
+        assert_eq!(0, gpu.draw_sprite(vec![0x80, 0x00, 0x80], 0, 0));
+        assert_eq!(2, gpu.draw_sprite(vec![0x80, 0x80, 0x80], 0, 0));
+    }
+
+    #[test]
+    fn test_draw_sprite_collision_row_count_with_clipping() {
+        let gpu = create_row_count_gpu();
+
+        assert_eq!(
+            2,
+            gpu.draw_sprite(vec![0x80, 0x80, 0x80, 0x80, 0x80], 0, 29)
+        );
+        assert_eq!(
+            5,
+            gpu.draw_sprite(vec![0x80, 0x80, 0x80, 0x80, 0x80], 0, 29)
+        );
+    }
+
+    #[test]
+    fn test_draw_sprite_with_separate_edge_policies() {
+        let gpu = GPU::new_default_wrapping();
+        gpu.get_config_mut().horizontal_sprite_edge_policy = SpriteEdgePolicy::Clip;
+
+        gpu.draw_sprite(vec![0xFF, 0xFF], 60, 31);
+
+        let framebuffer = gpu.get_framebuffer();
+        assert!(framebuffer.get(63, 31));
+        assert!(framebuffer.get(63, 0));
+        assert!(!framebuffer.get(0, 31));
+        assert!(!framebuffer.get(0, 0));
+        drop(framebuffer);
+    }
+
+    #[test]
+    fn test_present_to_display() {
+        struct TestDisplay {
+            lit_pixels: usize,
+            resolution: (usize, usize),
+        }
+
+        impl Display for TestDisplay {
+            fn present(&mut self, framebuffer: &[bool], resolution: (usize, usize)) -> bool {
+                self.lit_pixels = framebuffer.iter().filter(|p| **p).count();
+                self.resolution = resolution;
+                return true;
             }
         }
 
-        let index = (y_pos * width + x_pos) as usize;
+        let gpu = GPU::new_default_wrapping();
+        let mut display = TestDisplay {
+            lit_pixels: 0,
+            resolution: (0, 0),
+        };
+
+        gpu.draw_sprite(vec![0b10100000], 0, 0);
+        gpu.queue_render();
 
-        let collision = framebuffer[index];
-        framebuffer[index] ^= true;
-        return Some(collision);
+        assert!(gpu.present_to(&mut display));
+        assert_eq!(2, display.lit_pixels);
+        assert_eq!(gpu.get_screen_resolution(), display.resolution);
+        assert!(!gpu.is_render_queued());
     }
 }
@@ -1,17 +1,10 @@
-use crate::config::InputConfig;
-use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
-use std::sync::{Arc, Condvar, Mutex};
-use std::time::Duration;
-use winit_input_helper::WinitInputHelper;
-
-#[cfg(test)]
-use winit::keyboard::Key;
-
-#[cfg(test)]
-use winit::keyboard::SmolStr;
+use crate::backend::InputSource;
+use crate::config::{InputConfig, KeyTriggerMode};
+use crate::lifecycle::Lifecycle;
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 const NUMBER_OF_INPUTS: usize = 16;
-const CONDVAR_WAIT_TIMEOUT: Duration = Duration::from_millis(100);
 
 #[derive(PartialEq, Eq)]
 enum NewestKeyState {
@@ -22,77 +15,169 @@ enum NewestKeyState {
 }
 
 pub struct InputManager {
-    active: Arc<AtomicBool>,
-    config: InputConfig,
+    lifecycle: Arc<Lifecycle>,
+    config: RwLock<InputConfig>,
     key_states: Mutex<[bool; 16]>,
+    // Presses since the frame started, seen by edge-triggered reads from the next frame on.
+    key_presses: Mutex<[bool; 16]>,
+    frame_key_presses: Mutex<[bool; 16]>,
+    frame: AtomicU64,
     newest_key_state: Mutex<NewestKeyState>,
     newest_key: AtomicU8,
-    newest_key_cvar: Condvar,
 }
 
 impl InputManager {
-    pub fn try_new(active: Arc<AtomicBool>, config: InputConfig) -> Option<Arc<Self>> {
-        return Some(Arc::new(Self {
-            active,
-            config,
+    pub fn new(lifecycle: Arc<Lifecycle>, config: InputConfig) -> Arc<Self> {
+        return Arc::new(Self {
+            lifecycle,
+            config: RwLock::new(config),
             key_states: Mutex::new([false; 16]),
+            key_presses: Mutex::new([false; 16]),
+            frame_key_presses: Mutex::new([false; 16]),
+            frame: AtomicU64::new(0),
             newest_key_state: Mutex::new(NewestKeyState::Finished),
             newest_key: AtomicU8::new(0),
-            newest_key_cvar: Condvar::new(),
-        }));
+        });
     }
 
     #[cfg(test)]
-    pub fn new_default(active: Arc<AtomicBool>) -> Arc<Self> {
-        Self::try_new(
-            active,
+    pub fn new_default(lifecycle: Arc<Lifecycle>) -> Arc<Self> {
+        Self::new(
+            lifecycle,
             InputConfig {
                 key_bindings: [
-                    Key::Character(SmolStr::new("1")),
-                    Key::Character(SmolStr::new("2")),
-                    Key::Character(SmolStr::new("3")),
-                    Key::Character(SmolStr::new("q")),
-                    Key::Character(SmolStr::new("w")),
-                    Key::Character(SmolStr::new("e")),
-                    Key::Character(SmolStr::new("a")),
-                    Key::Character(SmolStr::new("s")),
-                    Key::Character(SmolStr::new("d")),
-                    Key::Character(SmolStr::new("x")),
-                    Key::Character(SmolStr::new("z")),
-                    Key::Character(SmolStr::new("c")),
-                    Key::Character(SmolStr::new("4")),
-                    Key::Character(SmolStr::new("r")),
-                    Key::Character(SmolStr::new("f")),
-                    Key::Character(SmolStr::new("v")),
+                    String::from("1"),
+                    String::from("2"),
+                    String::from("3"),
+                    String::from("q"),
+                    String::from("w"),
+                    String::from("e"),
+                    String::from("a"),
+                    String::from("s"),
+                    String::from("d"),
+                    String::from("x"),
+                    String::from("z"),
+                    String::from("c"),
+                    String::from("4"),
+                    String::from("r"),
+                    String::from("f"),
+                    String::from("v"),
                 ],
+                key_trigger_mode: KeyTriggerMode::Held,
+                macros: Vec::new(),
             },
         )
-        .unwrap()
     }
 
-    pub fn update_input(&self, input: &WinitInputHelper) {
+    pub fn get_config(&self) -> RwLockReadGuard<'_, InputConfig> {
+        return self.config.read().unwrap();
+    }
+
+    pub fn get_config_mut(&self) -> RwLockWriteGuard<'_, InputConfig> {
+        return self.config.write().unwrap();
+    }
+
+    pub fn update_input(&self, input: &dyn InputSource) {
         let mut key_states = self.key_states.lock().unwrap();
+        let mut key_presses = self.key_presses.lock().unwrap();
         let mut newest_key_state = self.newest_key_state.lock().unwrap();
 
+        let config = self.get_config();
+
         for i in 0..NUMBER_OF_INPUTS {
-            if input.key_pressed_logical(self.config.key_bindings[i].as_ref()) {
-                key_states[i] = true;
-
-                if *newest_key_state == NewestKeyState::Requested {
-                    self.newest_key.store(i as u8, Ordering::Release);
-                    *newest_key_state = NewestKeyState::Held;
-                }
-            } else if input.key_released_logical(self.config.key_bindings[i].as_ref()) {
-                key_states[i] = false;
-
-                if *newest_key_state == NewestKeyState::Held {
-                    *newest_key_state = NewestKeyState::Sent;
-                    self.newest_key_cvar.notify_all();
-                }
+            if input.is_key_pressed(&config.key_bindings[i]) {
+                self.set_key_state(
+                    &mut key_states,
+                    &mut key_presses,
+                    &mut newest_key_state,
+                    i,
+                    true,
+                );
+            } else if input.is_key_released(&config.key_bindings[i]) {
+                self.set_key_state(
+                    &mut key_states,
+                    &mut key_presses,
+                    &mut newest_key_state,
+                    i,
+                    false,
+                );
             }
         }
+    }
+
+    pub fn press_key(&self, key_index: u8) {
+        self.update_key(key_index, true);
+    }
+
+    pub fn release_key(&self, key_index: u8) {
+        self.update_key(key_index, false);
+    }
+
+    fn update_key(&self, key_index: u8, pressed: bool) {
+        if cfg!(debug_assertions) && key_index > 0xF {
+            panic!("Error: Should not be possible to update non-existent key_states.");
+        }
+
+        let mut key_states = self.key_states.lock().unwrap();
+        let mut key_presses = self.key_presses.lock().unwrap();
+        let mut newest_key_state = self.newest_key_state.lock().unwrap();
+
+        self.set_key_state(
+            &mut key_states,
+            &mut key_presses,
+            &mut newest_key_state,
+            key_index as usize,
+            pressed,
+        );
+    }
+
+    fn set_key_state(
+        &self,
+        key_states: &mut [bool; 16],
+        key_presses: &mut [bool; 16],
+        newest_key_state: &mut NewestKeyState,
+        key_index: usize,
+        pressed: bool,
+    ) {
+        if pressed {
+            key_states[key_index] = true;
+            key_presses[key_index] = true;
+
+            if *newest_key_state == NewestKeyState::Requested {
+                self.newest_key.store(key_index as u8, Ordering::Release);
+                *newest_key_state = NewestKeyState::Held;
+            }
+        } else {
+            key_states[key_index] = false;
 
-        self.newest_key_cvar.notify_all();
+            if *newest_key_state == NewestKeyState::Held {
+                *newest_key_state = NewestKeyState::Sent;
+            }
+        }
+    }
+
+    /// Latches the presses of the frame before for edge-triggered reads, which see the same presses
+    /// all frame, and drops those of the frame before that. Does nothing if the frame hasn't moved on.
+    pub fn start_frame(&self, frame: u64) {
+        if self.frame.swap(frame, Ordering::Relaxed) == frame {
+            return;
+        }
+
+        let mut key_presses = self.key_presses.lock().unwrap();
+        *self.frame_key_presses.lock().unwrap() = *key_presses;
+        *key_presses = [false; 16];
+    }
+
+    // One bit per key, regardless of the trigger mode.
+    pub fn get_held_keys(&self) -> u16 {
+        return self
+            .key_states
+            .lock()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .filter(|(_, held)| **held)
+            .fold(0, |keys, (key, _)| keys | 1 << key);
     }
 
     pub fn get_key_state(&self, key_index: u8) -> bool {
@@ -100,32 +185,110 @@ impl InputManager {
             panic!("Error: Should not be possible to read non-existent key_states.");
         }
 
-        return self.key_states.lock().unwrap()[key_index as usize];
+        return match self.get_config().key_trigger_mode {
+            KeyTriggerMode::Held => self.key_states.lock().unwrap()[key_index as usize],
+            KeyTriggerMode::Edge => self.frame_key_presses.lock().unwrap()[key_index as usize],
+        };
+    }
+
+    pub fn reset(&self) {
+        *self.key_states.lock().unwrap() = [false; 16];
+        *self.key_presses.lock().unwrap() = [false; 16];
+        *self.frame_key_presses.lock().unwrap() = [false; 16];
+        *self.newest_key_state.lock().unwrap() = NewestKeyState::Finished;
     }
 
-    pub fn get_next_key_press(&self) -> u8 {
+    // Fx0A waits for a key to be pressed and then released. This never blocks, so the CPU repeats
+    // the instruction until a key is returned.
+    pub fn poll_next_key_press(&self) -> Option<u8> {
         let mut newest_key_state = self.newest_key_state.lock().unwrap();
 
-        while *newest_key_state != NewestKeyState::Finished && self.active.load(Ordering::Relaxed) {
-            (newest_key_state, _) = self
-                .newest_key_cvar
-                .wait_timeout(newest_key_state, CONDVAR_WAIT_TIMEOUT)
-                .unwrap();
+        match *newest_key_state {
+            NewestKeyState::Finished if self.lifecycle.is_running() => {
+                *newest_key_state = NewestKeyState::Requested;
+                return None;
+            }
+            NewestKeyState::Sent => {
+                *newest_key_state = NewestKeyState::Finished;
+                return Some(self.newest_key.load(Ordering::Acquire));
+            }
+            _ => return None,
         }
+    }
+}
 
-        *newest_key_state = NewestKeyState::Requested;
-        self.newest_key_cvar.notify_all();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        while *newest_key_state != NewestKeyState::Sent && self.active.load(Ordering::Relaxed) {
-            (newest_key_state, _) = self
-                .newest_key_cvar
-                .wait_timeout(newest_key_state, CONDVAR_WAIT_TIMEOUT)
-                .unwrap();
-        }
+    #[test]
+    fn test_held_key_state() {
+        let lifecycle = Lifecycle::new();
+        let input_manager = InputManager::new_default(lifecycle.clone());
+
+        input_manager.key_states.lock().unwrap()[0x5] = true;
+        input_manager.key_presses.lock().unwrap()[0x5] = true;
+
+        assert!(input_manager.get_key_state(0x5));
+        assert!(input_manager.get_key_state(0x5));
+        assert!(lifecycle.is_running());
+    }
+
+    #[test]
+    fn test_edge_key_state() {
+        let lifecycle = Lifecycle::new();
+        let input_manager = InputManager::new_default(lifecycle.clone());
+        input_manager.get_config_mut().key_trigger_mode = KeyTriggerMode::Edge;
+
+        input_manager.press_key(0x5);
+        input_manager.release_key(0x5);
+        assert!(!input_manager.get_key_state(0x5));
+
+        // Both Ex9E and ExA1 see the press for the whole of the next frame.
+        input_manager.start_frame(1);
+        assert!(input_manager.get_key_state(0x5));
+        assert!(input_manager.get_key_state(0x5));
+        input_manager.start_frame(1);
+        assert!(input_manager.get_key_state(0x5));
+
+        // It isn't seen again, even if no key was read in between.
+        input_manager.start_frame(2);
+        assert!(!input_manager.get_key_state(0x5));
+        input_manager.press_key(0x5);
+        input_manager.start_frame(3);
+        input_manager.start_frame(4);
+        assert!(!input_manager.get_key_state(0x5));
+        assert!(lifecycle.is_running());
+    }
+
+    #[test]
+    fn test_press_and_release_key() {
+        let lifecycle = Lifecycle::new();
+        let input_manager = InputManager::new_default(lifecycle.clone());
+
+        input_manager.press_key(0xA);
+        assert!(input_manager.get_key_state(0xA));
+
+        input_manager.release_key(0xA);
+        assert!(!input_manager.get_key_state(0xA));
+        assert!(lifecycle.is_running());
+    }
+
+    #[test]
+    fn test_poll_next_key_press() {
+        let lifecycle = Lifecycle::new();
+        let input_manager = InputManager::new_default(lifecycle.clone());
+
+        input_manager.press_key(0x3);
+        input_manager.release_key(0x3);
+        assert_eq!(None, input_manager.poll_next_key_press());
 
-        *newest_key_state = NewestKeyState::Finished;
-        self.newest_key_cvar.notify_all();
+        input_manager.press_key(0x7);
+        assert_eq!(None, input_manager.poll_next_key_press());
 
-        return self.newest_key.load(Ordering::Acquire);
+        input_manager.release_key(0x7);
+        assert_eq!(Some(0x7), input_manager.poll_next_key_press());
+        assert_eq!(None, input_manager.poll_next_key_press());
+        assert!(lifecycle.is_running());
     }
 }
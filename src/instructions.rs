@@ -1,3 +1,4 @@
+use crate::config::BreakOnDraw;
 use crate::cpu::CPU;
 
 pub struct Opcode {
@@ -15,9 +16,9 @@ impl Opcode {
         }
     }
 
-    // pub fn get_full(&self) -> u16 {
-    //     self.full
-    // }
+    pub fn get_full(&self) -> u16 {
+        self.full
+    }
 
     pub fn get_addr(&self) -> u16 {
         self.full & 0x0FFF
@@ -67,10 +68,8 @@ pub fn get_instruction_function(op: &Opcode) -> Option<InstructionFunction> {
         0x0 => match op.get_addr() {
             0x0E0 => Some(i_00E0_CLS),
             0x0EE => Some(i_00EE_RET),
-            _ => {
-                eprintln!("Error: Machine code routines are not supported.");
-                None
-            }
+            // Machine code routines can't be run, so are left to the unknown opcode policy.
+            _ => None,
         },
 
         0x1 => Some(i_1nnn_JP_addr),
@@ -80,10 +79,7 @@ pub fn get_instruction_function(op: &Opcode) -> Option<InstructionFunction> {
 
         0x5 => match op.get_n() {
             0x0 => Some(i_5xy0_SE_Vx_Vy),
-            _ => {
-                invalid_instruction_called();
-                None
-            }
+            _ => None,
         },
 
         0x6 => Some(i_6xkk_LD_Vx_byte),
@@ -99,18 +95,12 @@ pub fn get_instruction_function(op: &Opcode) -> Option<InstructionFunction> {
             0x6 => Some(i_8xy6_SHR_Vx),
             0x7 => Some(i_8xy7_SUBN_Vx_Vy),
             0xE => Some(i_8xyE_SHL_Vx),
-            _ => {
-                invalid_instruction_called();
-                None
-            }
+            _ => None,
         },
 
         0x9 => match op.get_n() {
             0x0 => Some(i_9xy0_SNE_Vx_Vy),
-            _ => {
-                invalid_instruction_called();
-                None
-            }
+            _ => None,
         },
 
         0xA => Some(i_Annn_LD_I_addr),
@@ -121,10 +111,7 @@ pub fn get_instruction_function(op: &Opcode) -> Option<InstructionFunction> {
         0xE => match op.get_kk() {
             0x9E => Some(i_Ex9E_SKP_Vx),
             0xA1 => Some(i_ExA1_SKNP_Vx),
-            _ => {
-                invalid_instruction_called();
-                None
-            }
+            _ => None,
         },
 
         0xF => match op.get_kk() {
@@ -134,23 +121,17 @@ pub fn get_instruction_function(op: &Opcode) -> Option<InstructionFunction> {
             0x18 => Some(i_Fx18_LD_ST_Vx),
             0x1E => Some(i_Fx1E_ADD_I_Vx),
             0x29 => Some(i_Fx29_LD_F_Vx),
+            0x30 => Some(i_Fx30_LD_HF_Vx),
             0x33 => Some(i_Fx33_LD_B_Vx),
             0x55 => Some(i_Fx55_LD_I_Vx),
             0x65 => Some(i_Fx65_LD_Vx_I),
-            _ => {
-                invalid_instruction_called();
-                None
-            }
+            _ => None,
         },
 
         _ => panic!("op.get_s() should not be returning a byte > 0x0F"),
     }
 }
 
-fn invalid_instruction_called() {
-    eprintln!("Error: Invalid instruction called.");
-}
-
 #[allow(non_snake_case)]
 fn i_00E0_CLS(this: &CPU, _: &Opcode) -> bool {
     this.gpu.clear_framebuffer();
@@ -236,7 +217,7 @@ fn i_8xy1_OR_Vx_Vy(this: &CPU, op: &Opcode) -> bool {
     let mut v = this.get_v_regs_ref();
     v[op.get_x_usize()] |= v[op.get_y_usize()];
 
-    if this.config.reset_flag_for_bitwise_operations {
+    if this.get_config().reset_flag_for_bitwise_operations {
         v[0xF] = 0;
     }
 
@@ -248,7 +229,7 @@ fn i_8xy2_AND_Vx_Vy(this: &CPU, op: &Opcode) -> bool {
     let mut v = this.get_v_regs_ref();
     v[op.get_x_usize()] &= v[op.get_y_usize()];
 
-    if this.config.reset_flag_for_bitwise_operations {
+    if this.get_config().reset_flag_for_bitwise_operations {
         v[0xF] = 0;
     }
 
@@ -260,20 +241,30 @@ fn i_8xy3_XOR_Vx_Vy(this: &CPU, op: &Opcode) -> bool {
     let mut v = this.get_v_regs_ref();
     v[op.get_x_usize()] ^= v[op.get_y_usize()];
 
-    if this.config.reset_flag_for_bitwise_operations {
+    if this.get_config().reset_flag_for_bitwise_operations {
         v[0xF] = 0;
     }
 
     return false;
 }
 
+// When Vx is VF, whichever of the result and the flag is written last is kept.
+fn write_result_and_flag(this: &CPU, v: &mut [u8; 16], x: usize, result: u8, flag: u8) {
+    if this.get_config().write_flag_before_result {
+        v[0xF] = flag;
+        v[x] = result;
+    } else {
+        v[x] = result;
+        v[0xF] = flag;
+    }
+}
+
 #[allow(non_snake_case)]
 fn i_8xy4_ADD_Vx_Vy(this: &CPU, op: &Opcode) -> bool {
     let (x, y) = op.get_x_and_y_usize();
     let mut v = this.get_v_regs_ref();
     let (val, wrapped) = v[x].overflowing_add(v[y]);
-    v[x] = val;
-    v[0xF] = wrapped as u8;
+    write_result_and_flag(this, &mut v, x, val, wrapped as u8);
     return false;
 }
 
@@ -282,8 +273,7 @@ fn i_8xy5_SUB_Vx_Vy(this: &CPU, op: &Opcode) -> bool {
     let (x, y) = (op.get_x_usize(), op.get_y_usize());
     let mut v = this.get_v_regs_ref();
     let (val, wrapped) = v[x].overflowing_sub(v[y]);
-    v[x] = val;
-    v[0xF] = (!wrapped) as u8;
+    write_result_and_flag(this, &mut v, x, val, (!wrapped) as u8);
     return false;
 }
 
@@ -292,13 +282,12 @@ fn i_8xy6_SHR_Vx(this: &CPU, op: &Opcode) -> bool {
     let (x, y) = (op.get_x_usize(), op.get_y_usize());
     let mut v = this.get_v_regs_ref();
 
-    let v_used = match this.config.use_new_shift_instruction {
+    let v_used = match this.get_config().use_new_shift_instruction {
         true => v[x],
         false => v[y],
     };
 
-    v[x] = v_used >> 1;
-    v[0xF] = v_used & 1;
+    write_result_and_flag(this, &mut v, x, v_used >> 1, v_used & 1);
     return false;
 }
 
@@ -307,8 +296,7 @@ fn i_8xy7_SUBN_Vx_Vy(this: &CPU, op: &Opcode) -> bool {
     let (x, y) = (op.get_x_usize(), op.get_y_usize());
     let mut v = this.get_v_regs_ref();
     let (val, wrapped) = v[y].overflowing_sub(v[x]);
-    v[x] = val;
-    v[0xF] = (!wrapped) as u8;
+    write_result_and_flag(this, &mut v, x, val, (!wrapped) as u8);
     return false;
 }
 
@@ -317,13 +305,12 @@ fn i_8xyE_SHL_Vx(this: &CPU, op: &Opcode) -> bool {
     let (x, y) = (op.get_x_usize(), op.get_y_usize());
     let mut v = this.get_v_regs_ref();
 
-    let v_used = match this.config.use_new_shift_instruction {
+    let v_used = match this.get_config().use_new_shift_instruction {
         true => v[x],
         false => v[y],
     };
 
-    v[x] = v_used << 1;
-    v[0xF] = (v_used & 0x80) >> 7;
+    write_result_and_flag(this, &mut v, x, v_used << 1, (v_used & 0x80) >> 7);
     return false;
 }
 
@@ -347,7 +334,7 @@ fn i_Annn_LD_I_addr(this: &CPU, op: &Opcode) -> bool {
 
 #[allow(non_snake_case)]
 fn i_Bnnn_JP_V0_addr(this: &CPU, op: &Opcode) -> bool {
-    this.set_pc(match this.config.use_new_jump_instruction {
+    this.set_pc(match this.get_config().use_new_jump_instruction {
         true => this.get_v_reg(op.get_x()) as u16 + op.get_addr(),
         false => this.get_v_reg(0) as u16 + op.get_addr(),
     });
@@ -368,19 +355,31 @@ fn i_Dxyn_DRW_Vx_Vy_nibble(this: &CPU, op: &Opcode) -> bool {
 
     let (x, y) = op.get_x_and_y_usize();
     let mut v = this.get_v_regs_ref();
-    v[0xF] = this.gpu.draw_sprite(sprite, v[x], v[y]) as u8;
+    v[0xF] = this.gpu.draw_sprite(sprite, v[x], v[y]);
 
-    if this.config.limit_to_one_draw_per_frame {
-        this.gpu.wait_for_render();
-        return true;
+    let config = this.get_config();
+
+    // Ending the frame as well stops the CPU before it runs anything else.
+    let should_break = match config.break_on_draw {
+        BreakOnDraw::Off => false,
+        BreakOnDraw::Always => true,
+        BreakOnDraw::Collision => v[0xF] != 0,
+    };
+
+    if should_break {
+        this.lifecycle.pause();
     }
 
-    return false;
+    return config.limit_to_one_draw_per_frame || should_break;
 }
 
 #[allow(non_snake_case)]
 fn i_Ex9E_SKP_Vx(this: &CPU, op: &Opcode) -> bool {
-    if this.input_manager.get_key_state(this.get_v_reg(op.get_x())) {
+    // Only the low nibble picks the key, as on the COSMAC VIP.
+    if this
+        .input_manager
+        .get_key_state(this.get_v_reg(op.get_x()) & 0xF)
+    {
         this.increment_pc();
     }
 
@@ -389,7 +388,10 @@ fn i_Ex9E_SKP_Vx(this: &CPU, op: &Opcode) -> bool {
 
 #[allow(non_snake_case)]
 fn i_ExA1_SKNP_Vx(this: &CPU, op: &Opcode) -> bool {
-    if !this.input_manager.get_key_state(this.get_v_reg(op.get_x())) {
+    if !this
+        .input_manager
+        .get_key_state(this.get_v_reg(op.get_x()) & 0xF)
+    {
         this.increment_pc();
     }
 
@@ -404,7 +406,12 @@ fn i_Fx07_LD_Vx_DT(this: &CPU, op: &Opcode) -> bool {
 
 #[allow(non_snake_case)]
 fn i_Fx0A_LD_Vx_K(this: &CPU, op: &Opcode) -> bool {
-    this.set_v_reg(op.get_x(), this.input_manager.get_next_key_press());
+    let Some(key) = this.input_manager.poll_next_key_press() else {
+        this.decrement_pc();
+        return true;
+    };
+
+    this.set_v_reg(op.get_x(), key);
     return true;
 }
 
@@ -416,7 +423,7 @@ fn i_Fx15_LD_DT_Vx(this: &CPU, op: &Opcode) -> bool {
 
 #[allow(non_snake_case)]
 fn i_Fx18_LD_ST_Vx(this: &CPU, op: &Opcode) -> bool {
-    this.sound_timer.set_value(this.get_v_reg(op.get_x()));
+    this.sound_timer.start(this.get_v_reg(op.get_x()));
     return false;
 }
 
@@ -428,7 +435,7 @@ fn i_Fx1E_ADD_I_Vx(this: &CPU, op: &Opcode) -> bool {
         return false;
     };
 
-    if index_out_of_range && this.config.set_flag_for_index_overflow {
+    if index_out_of_range && this.get_config().set_flag_for_index_overflow {
         v[0xF] = 1;
     }
 
@@ -441,14 +448,28 @@ fn i_Fx29_LD_F_Vx(this: &CPU, op: &Opcode) -> bool {
         panic!("Error: Should not be possible to query for two-character hex digits");
     }
 
-    this.set_index_reg(this.ram.get_hex_digit_address(this.get_v_reg(op.get_x())));
+    // Only the low nibble picks the digit, as on the COSMAC VIP.
+    this.set_index_reg(
+        this.ram
+            .get_hex_digit_address(this.get_v_reg(op.get_x()) & 0xF),
+    );
+    return false;
+}
+
+// From SUPER-CHIP, for drawing digits as 8 by 10 sprites.
+#[allow(non_snake_case)]
+fn i_Fx30_LD_HF_Vx(this: &CPU, op: &Opcode) -> bool {
+    this.set_index_reg(
+        this.ram
+            .get_big_hex_digit_address(this.get_v_reg(op.get_x()) & 0xF),
+    );
     return false;
 }
 
 #[allow(non_snake_case)]
 fn i_Fx33_LD_B_Vx(this: &CPU, op: &Opcode) -> bool {
     let vx = this.get_v_reg(op.get_x());
-    let bcd = vec![vx / 100, (vx / 10) % 10, vx % 10];
+    let bcd = [vx / 100, (vx / 10) % 10, vx % 10];
     this.ram.write_bytes(&bcd, this.get_index_reg());
     return false;
 }
@@ -461,7 +482,7 @@ fn i_Fx55_LD_I_Vx(this: &CPU, op: &Opcode) -> bool {
     this.ram
         .write_bytes(&this.get_v_reg_range(0..=x as usize), *index);
 
-    if this.config.move_index_with_reads {
+    if this.get_config().move_index_with_reads {
         this.increment_index_reg_ref_by(index, x as u16 + 1);
     }
 
@@ -479,9 +500,190 @@ fn i_Fx65_LD_Vx_I(this: &CPU, op: &Opcode) -> bool {
 
     this.set_v_reg_range(0, &bytes);
 
-    if this.config.move_index_with_reads {
+    if this.get_config().move_index_with_reads {
         this.increment_index_reg_ref_by(index, x as u16 + 1);
     }
 
     return false;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::GPU;
+    use crate::input::InputManager;
+    use crate::lifecycle::Lifecycle;
+    use crate::ram::RAM;
+    use crate::timer::{DelayTimer, SoundTimer};
+    use proptest::prelude::*;
+    use std::sync::Arc;
+
+    fn create_cpu(use_new_shift_instruction: bool) -> Arc<CPU> {
+        let lifecycle = Lifecycle::new();
+
+        let cpu = CPU::new_default_all_false(
+            lifecycle.clone(),
+            GPU::new_default_wrapping(),
+            RAM::new_default_conservative(lifecycle.clone()),
+            DelayTimer::new_default(),
+            SoundTimer::new_default(lifecycle.clone()),
+            InputManager::new_default(lifecycle),
+        );
+
+        cpu.get_config_mut().use_new_shift_instruction = use_new_shift_instruction;
+        return cpu;
+    }
+
+    // Runs a single 8xyn instruction on the given registers, returning them afterwards.
+    fn run_8xyn(cpu: &CPU, v: [u8; 16], x: u8, y: u8, n: u8) -> [u8; 16] {
+        *cpu.get_v_regs_ref() = v;
+
+        let op = Opcode::from_u8s(0x80 | x, (y << 4) | n);
+        let function = get_instruction_function(&op).unwrap();
+        function(cpu, &op);
+
+        return *cpu.get_v_regs_ref();
+    }
+
+    // Checks that only Vx and VF were written to.
+    fn assert_others_unchanged(before: &[u8; 16], after: &[u8; 16], x: u8) {
+        for reg in 0..0xF {
+            if reg != x as usize {
+                assert_eq!(before[reg], after[reg], "V{reg:X} changed");
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_add_sets_carry(v in any::<[u8; 16]>(), x in 0u8..0xF, y in 0u8..0xF) {
+            let cpu = create_cpu(false);
+            let after = run_8xyn(&cpu, v, x, y, 0x4);
+
+            let sum = v[x as usize] as u16 + v[y as usize] as u16;
+            prop_assert_eq!(sum as u8, after[x as usize]);
+            prop_assert_eq!((sum > 0xFF) as u8, after[0xF]);
+            assert_others_unchanged(&v, &after, x);
+        }
+
+        #[test]
+        fn test_add_is_commutative(v in any::<[u8; 16]>(), x in 0u8..0xF, y in 0u8..0xF) {
+            let cpu = create_cpu(false);
+            let xy = run_8xyn(&cpu, v, x, y, 0x4);
+            let yx = run_8xyn(&cpu, v, y, x, 0x4);
+
+            prop_assert_eq!(xy[x as usize], yx[y as usize]);
+            prop_assert_eq!(xy[0xF], yx[0xF]);
+        }
+
+        #[test]
+        fn test_sub_sets_no_borrow(v in any::<[u8; 16]>(), x in 0u8..0xF, y in 0u8..0xF) {
+            let cpu = create_cpu(false);
+            let (vx, vy) = (v[x as usize], v[y as usize]);
+
+            let sub = run_8xyn(&cpu, v, x, y, 0x5);
+            prop_assert_eq!(vx.wrapping_sub(vy), sub[x as usize]);
+            prop_assert_eq!((vx >= vy) as u8, sub[0xF]);
+            assert_others_unchanged(&v, &sub, x);
+
+            let subn = run_8xyn(&cpu, v, x, y, 0x7);
+            prop_assert_eq!(vy.wrapping_sub(vx), subn[x as usize]);
+            prop_assert_eq!((vy >= vx) as u8, subn[0xF]);
+            assert_others_unchanged(&v, &subn, x);
+        }
+
+        #[test]
+        fn test_sub_undoes_add(v in any::<[u8; 16]>(), x in 0u8..0xF, y in 0u8..0xF) {
+            prop_assume!(x != y);
+
+            let cpu = create_cpu(false);
+            let added = run_8xyn(&cpu, v, x, y, 0x4);
+            let subtracted = run_8xyn(&cpu, added, x, y, 0x5);
+
+            prop_assert_eq!(v[x as usize], subtracted[x as usize]);
+            // Undoing a carry borrows, and otherwise there is nothing to borrow.
+            prop_assert_eq!(added[0xF] == 0, subtracted[0xF] == 1);
+        }
+
+        #[test]
+        fn test_shifts_set_shifted_out_bit(
+            v in any::<[u8; 16]>(),
+            x in 0u8..0xF,
+            y in 0u8..0xF,
+            use_new_shift_instruction in any::<bool>(),
+        ) {
+            let cpu = create_cpu(use_new_shift_instruction);
+
+            let shifted = match use_new_shift_instruction {
+                true => v[x as usize],
+                false => v[y as usize],
+            };
+
+            let right = run_8xyn(&cpu, v, x, y, 0x6);
+            prop_assert_eq!(shifted >> 1, right[x as usize]);
+            prop_assert_eq!(shifted & 1, right[0xF]);
+            assert_others_unchanged(&v, &right, x);
+
+            let left = run_8xyn(&cpu, v, x, y, 0xE);
+            prop_assert_eq!(shifted << 1, left[x as usize]);
+            prop_assert_eq!(shifted >> 7, left[0xF]);
+            assert_others_unchanged(&v, &left, x);
+        }
+
+        // When VF is the destination, the flag is written last and replaces the result.
+        #[test]
+        fn test_flag_wins_when_vx_is_vf(
+            v in any::<[u8; 16]>(),
+            y in 0u8..0xF,
+            use_new_shift_instruction in any::<bool>(),
+        ) {
+            let cpu = create_cpu(use_new_shift_instruction);
+            let (vf, vy) = (v[0xF], v[y as usize]);
+
+            prop_assert_eq!((vf.checked_add(vy).is_none()) as u8, run_8xyn(&cpu, v, 0xF, y, 0x4)[0xF]);
+            prop_assert_eq!((vf >= vy) as u8, run_8xyn(&cpu, v, 0xF, y, 0x5)[0xF]);
+            prop_assert_eq!((vy >= vf) as u8, run_8xyn(&cpu, v, 0xF, y, 0x7)[0xF]);
+
+            let shifted = match use_new_shift_instruction {
+                true => vf,
+                false => vy,
+            };
+
+            prop_assert_eq!(shifted & 1, run_8xyn(&cpu, v, 0xF, y, 0x6)[0xF]);
+            prop_assert_eq!(shifted >> 7, run_8xyn(&cpu, v, 0xF, y, 0xE)[0xF]);
+        }
+
+        // Writing the flag first lets the result replace it instead.
+        #[test]
+        fn test_result_wins_when_flag_is_written_first(v in any::<[u8; 16]>(), y in 0u8..0xF) {
+            let cpu = create_cpu(false);
+            cpu.get_config_mut().write_flag_before_result = true;
+            let (vf, vy) = (v[0xF], v[y as usize]);
+
+            prop_assert_eq!(vf.wrapping_add(vy), run_8xyn(&cpu, v, 0xF, y, 0x4)[0xF]);
+            prop_assert_eq!(vf.wrapping_sub(vy), run_8xyn(&cpu, v, 0xF, y, 0x5)[0xF]);
+            prop_assert_eq!(vy.wrapping_sub(vf), run_8xyn(&cpu, v, 0xF, y, 0x7)[0xF]);
+            prop_assert_eq!(vy >> 1, run_8xyn(&cpu, v, 0xF, y, 0x6)[0xF]);
+            prop_assert_eq!(vy << 1, run_8xyn(&cpu, v, 0xF, y, 0xE)[0xF]);
+
+            // Other registers still get both.
+            let x = (y + 1) % 0xF;
+            let after = run_8xyn(&cpu, v, x, y, 0x4);
+            prop_assert_eq!(v[x as usize].wrapping_add(vy), after[x as usize]);
+            prop_assert_eq!(v[x as usize].checked_add(vy).is_none() as u8, after[0xF]);
+        }
+
+        // When VF is the source, its value from before the instruction is used.
+        #[test]
+        fn test_vf_is_read_before_being_set(v in any::<[u8; 16]>(), x in 0u8..0xF) {
+            let cpu = create_cpu(false);
+            let (vx, vf) = (v[x as usize], v[0xF]);
+
+            prop_assert_eq!(vx.wrapping_add(vf), run_8xyn(&cpu, v, x, 0xF, 0x4)[x as usize]);
+            prop_assert_eq!(vx.wrapping_sub(vf), run_8xyn(&cpu, v, x, 0xF, 0x5)[x as usize]);
+            prop_assert_eq!(vf.wrapping_sub(vx), run_8xyn(&cpu, v, x, 0xF, 0x7)[x as usize]);
+            prop_assert_eq!(vf >> 1, run_8xyn(&cpu, v, x, 0xF, 0x6)[x as usize]);
+            prop_assert_eq!(vf << 1, run_8xyn(&cpu, v, x, 0xF, 0xE)[x as usize]);
+        }
+    }
+}
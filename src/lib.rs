@@ -0,0 +1,55 @@
+#[cfg(feature = "window")]
+pub mod about;
+pub mod assembler;
+pub mod backend;
+pub mod batch;
+#[cfg(feature = "window")]
+pub mod chooser;
+pub mod config;
+mod cpu;
+#[cfg(feature = "window")]
+pub mod debugger;
+pub mod debugserver;
+pub mod disassembler;
+mod driver;
+pub mod emulator;
+pub mod emulib;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod framebuffer;
+// The window is what uses most of the GPU's display settings.
+#[cfg_attr(not(feature = "window"), allow(dead_code))]
+mod gpu;
+mod input;
+mod instructions;
+pub mod lifecycle;
+pub mod loader;
+pub mod macros;
+#[cfg(feature = "window")]
+pub mod menu;
+pub mod observer;
+pub mod octo;
+pub mod overlay;
+pub mod profiling;
+mod ram;
+pub mod rewind;
+pub mod rominfo;
+pub mod savestate;
+#[cfg(feature = "window")]
+mod scanlines;
+pub mod selftest;
+#[cfg(feature = "window")]
+pub mod settings;
+pub mod setup;
+#[cfg(feature = "window")]
+pub mod slots;
+pub mod sourcemap;
+pub mod state;
+pub mod symbols;
+pub mod tas;
+pub mod ticklog;
+#[cfg(feature = "window")]
+pub mod tiles;
+mod timer;
+#[cfg(feature = "window")]
+pub mod window;
@@ -0,0 +1,166 @@
+use crate::observer::EmulatorError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShutdownReason {
+    Requested,
+    Halted(EmulatorError),
+}
+
+struct LifecycleState {
+    paused: bool,
+    shutdown_reason: Option<ShutdownReason>,
+}
+
+/// Shared by every component of an emulator, to communicate shutdown, pausing, and why it
+/// stopped.
+pub struct Lifecycle {
+    // Mirrors whether shutdown_reason is set, so the hot loops can check it without locking.
+    running: AtomicBool,
+    // Mirrors paused, so uncapped loops can check it without fighting other threads for the lock.
+    paused: AtomicBool,
+    // While set, the CPU runs as fast as it can and drives the timers itself.
+    turbo: AtomicBool,
+    state: Mutex<LifecycleState>,
+    state_cvar: Condvar,
+}
+
+impl Lifecycle {
+    pub fn new() -> Arc<Self> {
+        return Arc::new(Self {
+            running: AtomicBool::new(true),
+            paused: AtomicBool::new(false),
+            turbo: AtomicBool::new(false),
+            state: Mutex::new(LifecycleState {
+                paused: false,
+                shutdown_reason: None,
+            }),
+            state_cvar: Condvar::new(),
+        });
+    }
+
+    pub fn is_running(&self) -> bool {
+        return self.running.load(Ordering::Acquire);
+    }
+
+    pub fn shutdown(&self) {
+        self.stop_with(ShutdownReason::Requested);
+    }
+
+    pub fn halt(&self, error: EmulatorError) {
+        self.stop_with(ShutdownReason::Halted(error));
+    }
+
+    // Only the first reason is kept, as later ones are usually a consequence of it.
+    fn stop_with(&self, reason: ShutdownReason) {
+        let mut state = self.state.lock().unwrap();
+
+        if state.shutdown_reason.is_none() {
+            state.shutdown_reason = Some(reason);
+        }
+
+        self.running.store(false, Ordering::Release);
+        self.state_cvar.notify_all();
+    }
+
+    pub fn get_shutdown_reason(&self) -> Option<ShutdownReason> {
+        return self.state.lock().unwrap().shutdown_reason.clone();
+    }
+
+    pub fn pause(&self) {
+        self.state.lock().unwrap().paused = true;
+        self.paused.store(true, Ordering::Release);
+    }
+
+    pub fn resume(&self) {
+        self.state.lock().unwrap().paused = false;
+        self.paused.store(false, Ordering::Release);
+        self.state_cvar.notify_all();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        return self.state.lock().unwrap().paused;
+    }
+
+    pub fn set_turbo(&self, turbo: bool) {
+        self.turbo.store(turbo, Ordering::Release);
+    }
+
+    pub fn is_turbo(&self) -> bool {
+        return self.turbo.load(Ordering::Acquire);
+    }
+
+    // Returns whether it had to wait, so callers can reset their limiters instead of catching up.
+    pub fn wait_while_paused(&self) -> bool {
+        if !self.paused.load(Ordering::Acquire) {
+            return false;
+        }
+
+        let state = self.state.lock().unwrap();
+
+        if !state.paused {
+            return false;
+        }
+
+        let _state = self
+            .state_cvar
+            .wait_while(state, |s| s.paused && s.shutdown_reason.is_none())
+            .unwrap();
+
+        return true;
+    }
+
+    // Returns whether the emulator shut down within the timeout.
+    pub fn wait_for_shutdown(&self, timeout: Duration) -> bool {
+        let state = self.state.lock().unwrap();
+
+        let (state, _) = self
+            .state_cvar
+            .wait_timeout_while(state, timeout, |s| s.shutdown_reason.is_none())
+            .unwrap();
+
+        return state.shutdown_reason.is_some();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_first_shutdown_reason_is_kept() {
+        let lifecycle = Lifecycle::new();
+        assert!(lifecycle.is_running());
+
+        lifecycle.halt(EmulatorError::ProgramCounterOverflow);
+        lifecycle.shutdown();
+
+        assert!(!lifecycle.is_running());
+        assert_eq!(
+            Some(ShutdownReason::Halted(
+                EmulatorError::ProgramCounterOverflow
+            )),
+            lifecycle.get_shutdown_reason()
+        );
+    }
+
+    #[test]
+    fn test_pause_and_resume() {
+        let lifecycle = Lifecycle::new();
+        assert!(!lifecycle.wait_while_paused());
+
+        lifecycle.pause();
+
+        let lifecycle_clone = lifecycle.clone();
+        let handle = thread::spawn(move || lifecycle_clone.wait_while_paused());
+
+        assert!(!lifecycle.wait_for_shutdown(Duration::from_millis(20)));
+        lifecycle.resume();
+
+        assert!(handle.join().unwrap());
+        assert!(lifecycle.is_running());
+    }
+}
@@ -0,0 +1,336 @@
+//! The options Octo keeps alongside a program, so it runs with the settings its author chose.
+//!
+//! They are read from a JSON file next to the program with the same name, such as `game.json`
+//! for `game.ch8`. The file can hold the options themselves, as Octo exports them, or a saved
+//! Octo project with the options under an `options` key.
+//!
+//! Octo also shares programs as cartridges: GIF images with a saved project hidden in the low
+//! two bits of each pixel's palette index, four pixels to a byte, starting with the project's
+//! length as a big-endian u32. The project holds Octo source rather than a ROM, which is built
+//! with the Octo compiler in octo.rs as the cartridge is loaded.
+
+use crate::config::{CPUConfig, GPUConfig, SpriteEdgePolicy};
+use crate::octo;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+const OPTIONS_EXTENSION: &str = "json";
+const CARTRIDGE_EXTENSION: &str = "gif";
+// Octo counts speed in instructions per 60 Hz frame.
+const FRAMES_PER_SECOND: f64 = 60.0;
+
+// Anything the emulator has no equivalent for, such as the XO-CHIP plane colors, is skipped.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OctoOptions {
+    pub tickrate: Option<u32>,
+    pub fill_color: Option<String>,
+    pub background_color: Option<String>,
+    pub buzz_color: Option<String>,
+    pub quiet_color: Option<String>,
+    pub shift_quirks: Option<bool>,
+    pub load_store_quirks: Option<bool>,
+    pub jump_quirks: Option<bool>,
+    pub logic_quirks: Option<bool>,
+    pub clip_quirks: Option<bool>,
+    pub v_blank_quirks: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cartridge {
+    pub program: String,
+    pub options: OctoOptions,
+}
+
+#[derive(Deserialize)]
+struct CartridgeProgram {
+    program: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OptionsFile {
+    Project { options: OctoOptions },
+    Options(OctoOptions),
+}
+
+pub fn parse_color(text: &str) -> Option<u32> {
+    let hex = text.strip_prefix('#')?;
+
+    if hex.len() != 6 {
+        return None;
+    }
+
+    return u32::from_str_radix(hex, 16).ok();
+}
+
+pub fn parse_options(text: &str) -> Result<OctoOptions, String> {
+    let options = match serde_json::from_str(text).map_err(|e| e.to_string())? {
+        OptionsFile::Project { options } => options,
+        OptionsFile::Options(options) => options,
+    };
+
+    for (name, color) in [
+        ("fillColor", &options.fill_color),
+        ("backgroundColor", &options.background_color),
+        ("buzzColor", &options.buzz_color),
+        ("quietColor", &options.quiet_color),
+    ] {
+        if let Some(color) = color
+            && parse_color(color).is_none()
+        {
+            return Err(format!("{name} is not a valid color ({color})."));
+        }
+    }
+
+    return Ok(options);
+}
+
+// Options left out are left out of the file too, so they don't override anything.
+pub fn format_options(options: &OctoOptions) -> String {
+    let mut value = serde_json::to_value(options).unwrap_or_default();
+
+    if let Some(fields) = value.as_object_mut() {
+        fields.retain(|_, field| !field.is_null());
+    }
+
+    return serde_json::to_string_pretty(&value).unwrap_or_default() + "\n";
+}
+
+/// Reads the options next to the program, if it has any. A file that can't be parsed is
+/// reported and skipped, so the program still runs with the usual config.
+pub fn load_options(program_path: &str) -> Option<OctoOptions> {
+    let options_path = Path::new(program_path).with_extension(OPTIONS_EXTENSION);
+
+    if options_path == Path::new(program_path) {
+        return None;
+    }
+
+    let text = fs::read_to_string(&options_path).ok()?;
+
+    return match parse_options(&text) {
+        Ok(options) => Some(options),
+        Err(e) => {
+            eprintln!(
+                "Warning: Ignoring Octo options in {} ({e})",
+                options_path.display()
+            );
+            None
+        }
+    };
+}
+
+pub fn decode_cartridge(gif_bytes: &[u8]) -> Result<Cartridge, String> {
+    let mut decode_options = gif::DecodeOptions::new();
+    decode_options.set_color_output(gif::ColorOutput::Indexed);
+    let mut decoder = decode_options
+        .read_info(gif_bytes)
+        .map_err(|e| e.to_string())?;
+
+    let mut bytes = Vec::new();
+
+    while let Some(frame) = decoder.read_next_frame().map_err(|e| e.to_string())? {
+        bytes.extend(frame.buffer.chunks_exact(4).map(|pixels| {
+            return pixels
+                .iter()
+                .fold(0, |byte, index| byte << 2 | index & 0b11);
+        }));
+    }
+
+    let Some((length, payload)) = bytes.split_first_chunk::<4>() else {
+        return Err(String::from("The image is too small to be a cartridge."));
+    };
+
+    let length = u32::from_be_bytes(*length) as usize;
+
+    if length > payload.len() {
+        return Err(String::from("The image doesn't hold a cartridge."));
+    }
+
+    // Octo writes one byte per character.
+    let project: String = payload[..length].iter().map(|byte| *byte as char).collect();
+    let program: CartridgeProgram = serde_json::from_str(&project).map_err(|e| e.to_string())?;
+
+    return Ok(Cartridge {
+        program: program.program,
+        options: parse_options(&project)?,
+    });
+}
+
+pub fn is_cartridge(path: &str) -> bool {
+    return Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(CARTRIDGE_EXTENSION));
+}
+
+/// Decodes a cartridge and builds its source, giving the program along with its options.
+pub fn build_cartridge(gif_bytes: &[u8]) -> Result<(Vec<u8>, OctoOptions), String> {
+    let cartridge = decode_cartridge(gif_bytes)?;
+    let program = octo::compile(&cartridge.program)?;
+    return Ok((program, cartridge.options));
+}
+
+impl OctoOptions {
+    // Octo's quirks are named for what they change from the original interpreter.
+    pub fn apply(&self, cpu_config: &mut CPUConfig, gpu_config: &mut GPUConfig) {
+        if let Some(tickrate) = self.tickrate {
+            cpu_config.instructions_per_second = tickrate as f64 * FRAMES_PER_SECOND;
+        }
+
+        if let Some(shift_quirks) = self.shift_quirks {
+            cpu_config.use_new_shift_instruction = shift_quirks;
+        }
+
+        if let Some(load_store_quirks) = self.load_store_quirks {
+            cpu_config.move_index_with_reads = !load_store_quirks;
+        }
+
+        if let Some(jump_quirks) = self.jump_quirks {
+            cpu_config.use_new_jump_instruction = jump_quirks;
+        }
+
+        if let Some(logic_quirks) = self.logic_quirks {
+            cpu_config.reset_flag_for_bitwise_operations = logic_quirks;
+        }
+
+        if let Some(v_blank_quirks) = self.v_blank_quirks {
+            cpu_config.limit_to_one_draw_per_frame = v_blank_quirks;
+        }
+
+        if let Some(clip_quirks) = self.clip_quirks {
+            let policy = match clip_quirks {
+                true => SpriteEdgePolicy::Clip,
+                false => SpriteEdgePolicy::Wrap,
+            };
+            gpu_config.horizontal_sprite_edge_policy = policy;
+            gpu_config.vertical_sprite_edge_policy = policy;
+        }
+
+        let color = |color: &Option<String>| color.as_deref().and_then(parse_color);
+
+        if let Some(fill_color) = color(&self.fill_color) {
+            gpu_config.pixel_color_when_active = fill_color;
+        }
+
+        if let Some(background_color) = color(&self.background_color) {
+            gpu_config.pixel_color_when_inactive = background_color;
+        }
+
+        if let Some(quiet_color) = color(&self.quiet_color) {
+            gpu_config.screen_border_color = quiet_color;
+        }
+
+        if let Some(buzz_color) = color(&self.buzz_color) {
+            gpu_config.screen_border_color_when_beeping = buzz_color;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+
+    #[test]
+    fn test_apply_options() {
+        let options = parse_options(
+            r##"{ "tickrate": 20, "fillColor": "#FFCC00", "backgroundColor": "#996600",
+                  "shiftQuirks": true, "loadStoreQuirks": true, "clipQuirks": false,
+                  "fillColor2": "#FF6600", "screenRotation": 0 }"##,
+        )
+        .unwrap();
+
+        let mut config = config::generate_default_configs();
+        options.apply(&mut config.cpu, &mut config.gpu);
+
+        assert_eq!(1200.0, config.cpu.instructions_per_second);
+        assert!(config.cpu.use_new_shift_instruction);
+        assert!(!config.cpu.move_index_with_reads);
+        assert_eq!(
+            SpriteEdgePolicy::Wrap,
+            config.gpu.vertical_sprite_edge_policy
+        );
+        assert_eq!(0xFFCC00, config.gpu.pixel_color_when_active);
+        assert_eq!(0x996600, config.gpu.pixel_color_when_inactive);
+
+        // Options left out keep the config's settings.
+        let defaults = config::generate_default_configs();
+        assert_eq!(
+            defaults.cpu.use_new_jump_instruction,
+            config.cpu.use_new_jump_instruction
+        );
+
+        let project = parse_options(r#"{ "program": ": main", "options": { "tickrate": 7 } }"#);
+        assert_eq!(Some(7), project.unwrap().tickrate);
+
+        assert!(
+            parse_options(r#"{ "fillColor": "yellow" }"#)
+                .unwrap_err()
+                .starts_with("fillColor")
+        );
+    }
+
+    fn encode_cartridge(project: &str) -> Vec<u8> {
+        let mut bytes = (project.len() as u32).to_be_bytes().to_vec();
+        bytes.extend(project.bytes());
+
+        // Each pixel's palette index keeps the cartridge artwork in its upper bits.
+        let mut pixels: Vec<u8> = bytes
+            .iter()
+            .flat_map(|byte| [6, 4, 2, 0].map(|shift| 0b1000 | byte >> shift & 0b11))
+            .collect();
+
+        let (width, height) = (16, pixels.len().div_ceil(16) as u16);
+        pixels.resize(width as usize * height as usize, 0);
+
+        let mut gif_bytes = Vec::new();
+        let palette: Vec<u8> = (0..16u8).flat_map(|i| [i * 16, i * 16, i * 16]).collect();
+        let mut encoder = gif::Encoder::new(&mut gif_bytes, width, height, &palette).unwrap();
+        encoder
+            .write_frame(&gif::Frame::from_indexed_pixels(
+                width, height, pixels, None,
+            ))
+            .unwrap();
+        drop(encoder);
+
+        return gif_bytes;
+    }
+
+    #[test]
+    fn test_decode_cartridge() {
+        let gif_bytes = encode_cartridge(
+            r#"{ "key": "", "program": ": main\n  loop again", "options": { "tickrate": 500 } }"#,
+        );
+
+        let cartridge = decode_cartridge(&gif_bytes).unwrap();
+        assert_eq!(": main\n  loop again", cartridge.program);
+        assert_eq!(Some(500), cartridge.options.tickrate);
+        assert_eq!(
+            "{\n  \"tickrate\": 500\n}\n",
+            format_options(&cartridge.options)
+        );
+
+        assert!(decode_cartridge(b"not a gif").is_err());
+    }
+
+    #[test]
+    fn test_build_cartridge() {
+        let gif_bytes = encode_cartridge(
+            r#"{ "program": ": main\n  v0 += 1\n  jump main", "options": { "tickrate": 20 } }"#,
+        );
+
+        let (program, options) = build_cartridge(&gif_bytes).unwrap();
+        assert_eq!(vec![0x12, 0x02, 0x70, 0x01, 0x12, 0x02], program);
+        assert_eq!(Some(20), options.tickrate);
+
+        let gif_bytes = encode_cartridge(r#"{ "program": "loop again" }"#);
+        assert_eq!(
+            Err(String::from("The program has no main label.")),
+            build_cartridge(&gif_bytes)
+        );
+
+        assert!(is_cartridge("games/Cart.GIF"));
+        assert!(!is_cartridge("games/cart.ch8"));
+    }
+}
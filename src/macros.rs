@@ -0,0 +1,220 @@
+//! Input macros, each a short sequence of keypad states played back when its hotkey is pressed.
+//!
+//! A sequence has one entry per 60 Hz frame, written like the frames of a TAS input file but
+//! separated by spaces, such as `5 5 - - 6 6`.
+
+use crate::backend::InputSource;
+use crate::config::InputMacro;
+use crate::input::InputManager;
+use crate::tas;
+use std::time::{Duration, Instant};
+
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+pub fn parse_frames(text: &str) -> Option<Vec<u16>> {
+    return text.split_whitespace().map(tas::parse_keys).collect();
+}
+
+pub fn format_frames(frames: &[u16]) -> String {
+    return frames
+        .iter()
+        .map(|keys| tas::format_keys(*keys))
+        .collect::<Vec<_>>()
+        .join(" ");
+}
+
+fn get_frame(started: Instant, now: Instant) -> usize {
+    return (now.saturating_duration_since(started).as_nanos() / FRAME_DURATION.as_nanos())
+        as usize;
+}
+
+struct Playback {
+    frames: Vec<u16>,
+    started: Instant,
+    next_frame: usize,
+    held_keys: u16,
+}
+
+struct Recording {
+    index: usize,
+    frames: Vec<u16>,
+    started: Instant,
+}
+
+pub struct MacroPlayer {
+    hotkeys: Vec<String>,
+    macros: Vec<Vec<u16>>,
+    playback: Option<Playback>,
+    recording: Option<Recording>,
+}
+
+impl MacroPlayer {
+    // The macros should already have been validated, so any that don't parse are left empty.
+    pub fn new(macros: &[InputMacro]) -> Self {
+        Self {
+            hotkeys: macros.iter().map(|m| m.hotkey.clone()).collect(),
+            macros: macros
+                .iter()
+                .map(|m| parse_frames(&m.inputs).unwrap_or_default())
+                .collect(),
+            playback: None,
+            recording: None,
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        return self.playback.is_some();
+    }
+
+    pub fn is_recording(&self) -> bool {
+        return self.recording.is_some();
+    }
+
+    pub fn get_pressed_macro(&self, input: &dyn InputSource) -> Option<usize> {
+        return self
+            .hotkeys
+            .iter()
+            .position(|hotkey| input.is_key_pressed(hotkey));
+    }
+
+    // Playing a macro again restarts it, releasing anything it was holding first.
+    pub fn play(&mut self, index: usize, input_manager: &InputManager) {
+        if self.is_recording() || index >= self.macros.len() {
+            return;
+        }
+
+        self.stop(input_manager);
+        self.playback = Some(Playback {
+            frames: self.macros[index].clone(),
+            started: Instant::now(),
+            next_frame: 0,
+            held_keys: 0,
+        });
+    }
+
+    pub fn stop(&mut self, input_manager: &InputManager) {
+        if let Some(playback) = self.playback.take() {
+            set_held_keys(input_manager, playback.held_keys, 0);
+        }
+    }
+
+    // Idle frames before the first input and after the last are left out of the recording.
+    pub fn toggle_recording(&mut self, index: usize) {
+        if self.is_playing() || index >= self.macros.len() {
+            return;
+        }
+
+        let Some(recording) = self.recording.take() else {
+            self.recording = Some(Recording {
+                index,
+                frames: Vec::new(),
+                started: Instant::now(),
+            });
+            println!("Recording macro on {}...", self.hotkeys[index]);
+            return;
+        };
+
+        let start = recording.frames.iter().position(|keys| *keys != 0);
+        let end = recording.frames.iter().rposition(|keys| *keys != 0);
+
+        let frames = match (start, end) {
+            (Some(start), Some(end)) => recording.frames[start..=end].to_vec(),
+            _ => Vec::new(),
+        };
+
+        println!(
+            "Recorded macro: {{ hotkey = {:?}, inputs = {:?} }}",
+            self.hotkeys[recording.index],
+            format_frames(&frames)
+        );
+        self.macros[recording.index] = frames;
+    }
+
+    pub fn update(&mut self, input_manager: &InputManager) {
+        self.advance(input_manager, Instant::now());
+    }
+
+    // Frames missed between updates are still played in order, so short taps aren't dropped.
+    fn advance(&mut self, input_manager: &InputManager, now: Instant) {
+        if let Some(recording) = self.recording.as_mut() {
+            let frame = get_frame(recording.started, now);
+            let keys = input_manager.get_held_keys();
+            recording.frames.resize(frame + 1, keys);
+        }
+
+        let Some(playback) = self.playback.as_mut() else {
+            return;
+        };
+
+        let frame = get_frame(playback.started, now);
+
+        while playback.next_frame <= frame.min(playback.frames.len()) {
+            let keys = playback
+                .frames
+                .get(playback.next_frame)
+                .copied()
+                .unwrap_or(0);
+            set_held_keys(input_manager, playback.held_keys, keys);
+            playback.held_keys = keys;
+            playback.next_frame += 1;
+        }
+
+        if playback.next_frame > playback.frames.len() {
+            self.playback = None;
+        }
+    }
+}
+
+fn set_held_keys(input_manager: &InputManager, held_keys: u16, keys: u16) {
+    for key in 0..16 {
+        match (keys & 1 << key != 0, held_keys & 1 << key != 0) {
+            (true, false) => input_manager.press_key(key),
+            (false, true) => input_manager.release_key(key),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lifecycle::Lifecycle;
+
+    #[test]
+    fn test_parse_and_format_frames() {
+        assert_eq!(
+            Some(vec![1 << 5, 1 << 5, 0, 0b11]),
+            parse_frames("5 5 - 01")
+        );
+        assert_eq!(Some(Vec::new()), parse_frames(""));
+        assert_eq!(None, parse_frames("5 x"));
+        assert_eq!("5 - 01", format_frames(&[1 << 5, 0, 0b11]));
+    }
+
+    #[test]
+    fn test_play_macro() {
+        let input_manager = InputManager::new_default(Lifecycle::new());
+        let mut macro_player = MacroPlayer::new(&[InputMacro {
+            hotkey: String::from("m"),
+            inputs: String::from("5 5 - 6"),
+        }]);
+
+        macro_player.play(0, &input_manager);
+        let started = macro_player.playback.as_ref().unwrap().started;
+
+        macro_player.advance(&input_manager, started);
+        assert_eq!(1 << 5, input_manager.get_held_keys());
+
+        macro_player.advance(&input_manager, started + FRAME_DURATION * 2);
+        assert_eq!(0, input_manager.get_held_keys());
+
+        macro_player.advance(&input_manager, started + FRAME_DURATION * 3);
+        assert_eq!(1 << 6, input_manager.get_held_keys());
+        assert!(macro_player.is_playing());
+
+        // Everything is released once the sequence runs out.
+        macro_player.advance(&input_manager, started + FRAME_DURATION * 10);
+        assert_eq!(0, input_manager.get_held_keys());
+        assert!(!macro_player.is_playing());
+    }
+}
@@ -1,121 +1,744 @@
-mod config;
-mod cpu;
-mod emulib;
-mod gpu;
-mod input;
-mod instructions;
-mod ram;
-mod timer;
-mod window;
-
-use crate::cpu::CPU;
-use crate::gpu::GPU;
-use crate::input::InputManager;
-use crate::ram::RAM;
-use crate::timer::{DelayTimer, SoundTimer};
-use crate::window::WindowManager;
-use clap::Parser;
+use chip8rust::backend::NullAudioSink;
+use chip8rust::emulator::{Emulator, EmulatorBuilder, FRAMES_PER_SECOND};
+use chip8rust::lifecycle::ShutdownReason;
+use chip8rust::observer::EmulatorError;
+use chip8rust::settings::SettingsMenu;
+use chip8rust::symbols::Symbols;
+use chip8rust::ticklog::TickLog;
+use chip8rust::window::WindowManager;
+use chip8rust::{
+    assembler, batch, chooser, config, debugserver, disassembler, loader, rominfo, selftest, setup,
+    state, tas,
+};
+use clap::{Parser, Subcommand};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::thread;
+use std::time::Instant;
 use winit::event_loop::{ControlFlow, EventLoop};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
+    /// Path to the program to run, as with the run command
+    program_path: Option<String>,
+
+    /// Path to the config file (TOML, JSON or YAML, chosen by extension)
+    #[arg(short, long, global = true, default_value = config::CONFIG_FILE_PATH)]
+    config: String,
+
+    /// Name of a profile in the config file to apply over the base settings
+    #[arg(short, long, global = true)]
+    profile: Option<String>,
+
+    /// Run the self-test first, as with the run command
+    #[arg(long)]
+    selftest: bool,
+
+    /// Reload the program whenever its file changes, as with the run command
+    #[arg(long)]
+    watch: bool,
+
+    /// Pause when the program reaches a location, as with the run command
+    #[arg(long = "break", value_name = "LOCATION")]
+    breakpoints: Vec<String>,
+
+    /// Serve the debugging protocol on an address, as with the run command
+    #[arg(long, value_name = "ADDRESS")]
+    debug_server: Option<String>,
+
+    /// Log when each part of the emulator does its work to a file, as with the run command
+    #[arg(long, value_name = "PATH")]
+    tick_log: Option<String>,
+
+    /// File to write a flame graph of where the emulator itself spends its time to, in the folded stack format
+    #[cfg(feature = "profiling")]
+    #[arg(long, global = true)]
+    flame_graph: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run a program in a window (the default without a command)
+    Run {
+        /// Path to the program to run (the last program when resume_last_program is enabled, or chosen in the window otherwise)
+        program_path: Option<String>,
+
+        /// Run a built-in test ROM with the config first, reporting whether the interpreter passes it
+        #[arg(long)]
+        selftest: bool,
+
+        /// Reset and reload the program whenever its file changes on disk, such as after a rebuild
+        #[arg(long)]
+        watch: bool,
+
+        /// Pause when the program reaches a location: an address such as 0x206, a line of its source or a symbol (can be repeated)
+        #[arg(long = "break", value_name = "LOCATION")]
+        breakpoints: Vec<String>,
+
+        /// Let editors and other tools debug the program over TCP on an address such as 127.0.0.1:6502
+        #[arg(long, value_name = "ADDRESS")]
+        debug_server: Option<String>,
+
+        /// Log CPU batches, timer ticks and presents with timestamps to a file, to diagnose drift between threads
+        #[arg(long, value_name = "PATH")]
+        tick_log: Option<String>,
+    },
+
+    /// Print a program's instructions, in a form the asm command can assemble again
+    Disasm {
+        program_path: String,
+
+        /// File to write the listing to, instead of standard output
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Assemble a program from instructions written as the disasm command prints them
+    Asm {
+        source_path: String,
+
+        /// File to write the program to
+        #[arg(short, long)]
+        output: String,
+
+        /// Also write the labels' addresses to a .sym file beside the program, for the debugger
+        #[arg(long)]
+        symbols: bool,
+    },
+
+    /// Extract the Octo source and options from an Octo cartridge (.gif), writing the options next to the source
+    UnpackCart {
+        cart_path: String,
+
+        /// File to write the source to, with the options written beside it as JSON
+        #[arg(short, long)]
+        output: String,
+    },
+
+    /// Print a program's size, SHA-1, likely platform, first instruction and how much of it looks like code
+    Info { program_path: String },
+
+    /// Run a built-in test ROM with the config, reporting whether the interpreter passes it
+    Selftest,
+
+    /// Run a program without a window as fast as possible, reporting how fast it ran
+    Bench {
+        program_path: String,
+
+        /// Number of frames to run
+        #[arg(short, long, default_value_t = 600)]
+        frames: usize,
+    },
+
+    /// Run every ROM in a directory without a window, reporting how each one ended
+    Batch {
+        directory: String,
+
+        /// Number of frames to run each ROM for
+        #[arg(short, long, default_value_t = 600)]
+        frames: usize,
+
+        /// File to write the report to, as JSON if it ends in .json and CSV otherwise, instead of CSV on standard output
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Validate a config file, printing every problem found
+    CheckConfig {
+        #[arg(default_value = config::CONFIG_FILE_PATH)]
+        config_path: String,
+    },
+
+    /// Run a program without a window, writing the keys held and a hash of the display for each frame
+    DumpFrames {
+        program_path: String,
+
+        /// File listing the keys held on each frame, one line per frame
+        #[arg(short, long)]
+        inputs: Option<String>,
+
+        /// Number of frames to run (the number of frames of input by default)
+        #[arg(short, long)]
+        frames: Option<usize>,
+
+        /// File to write the dump to, instead of standard output
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Re-run the inputs of an earlier dump, checking every frame matches it
+        #[arg(long, conflicts_with_all = ["inputs", "output"])]
+        verify: Option<String>,
+    },
+}
+
+struct RunOptions {
+    program_path: Option<String>,
+    selftest: bool,
+    watch: bool,
+    breakpoints: Vec<String>,
+    debug_server: Option<String>,
+    tick_log: Option<String>,
+}
+
+struct DumpOptions {
     program_path: String,
+    inputs: Option<String>,
+    frames: Option<usize>,
+    output: Option<String>,
+    verify: Option<String>,
 }
 
-struct Components {
-    active: Arc<AtomicBool>,
-    cpu: Arc<CPU>,
-    gpu: Arc<GPU>,
-    ram: Arc<RAM>,
-    delay_timer: Arc<DelayTimer>,
-    sound_timer: Arc<SoundTimer>,
-    input_manager: Arc<InputManager>,
+fn read_file(path: &str) -> Result<String, String> {
+    return fs::read_to_string(path).map_err(|e| format!("Could not read {path} ({e})."));
 }
 
-fn main() {
-    println!("Starting emulator...");
+fn run_frame_dump(config_path: &str, profile: Option<&str>, options: DumpOptions) -> bool {
+    let Some(config) = config::generate_configs_from(config_path, profile) else {
+        return false;
+    };
+
+    let Some(emulator) = EmulatorBuilder::new(config)
+        .with_audio_sink(Box::new(NullAudioSink))
+        .build()
+    else {
+        return false;
+    };
+
+    if let Err(e) = emulator.load_rom(&options.program_path) {
+        eprintln!("Error: {e}");
+        return false;
+    }
+
+    let expected = match options.verify.as_deref().map(read_file) {
+        Some(dump) => match dump.and_then(|dump| tas::parse_dump(&dump)) {
+            Ok(records) => Some(records),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                return false;
+            }
+        },
+        None => None,
+    };
+
+    let inputs = match (&expected, options.inputs.as_deref()) {
+        (Some(records), _) => Ok(records.iter().map(|record| record.keys).collect()),
+        (None, Some(path)) => read_file(path).and_then(|inputs| tas::parse_inputs(&inputs)),
+        (None, None) => Ok(Vec::new()),
+    };
+
+    let inputs = match inputs {
+        Ok(inputs) => inputs,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            return false;
+        }
+    };
+
+    let frame_count = options.frames.unwrap_or(inputs.len());
+    let records = tas::run_frames(&emulator, &inputs, frame_count);
+
+    if let Some(expected) = expected {
+        let mismatch = expected
+            .iter()
+            .zip(&records)
+            .find(|(expected, record)| expected != record);
+
+        if let Some((expected, _)) = mismatch {
+            eprintln!("Error: Frame {} does not match the dump.", expected.frame);
+            return false;
+        }
+
+        if records.len() < expected.len().min(frame_count) {
+            eprintln!("Error: The program halted after frame {}.", records.len());
+            return false;
+        }
+
+        println!("All {} frames match the dump.", records.len());
+        return true;
+    }
+
+    let dump = tas::format_dump(&emulator, &records);
+    return write_output(options.output.as_deref(), &dump);
+}
+
+fn write_output(output: Option<&str>, text: &str) -> bool {
+    match output {
+        Some(path) => {
+            if let Err(e) = fs::write(path, text) {
+                eprintln!("Error: Could not write {path} ({e}).");
+                return false;
+            }
+        }
+        None => print!("{text}"),
+    }
+
+    return true;
+}
+
+fn disassemble_program(program_path: &str, output: Option<&str>) -> bool {
+    let program = match fs::read(program_path) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("Error: Could not read {program_path} ({e}).");
+            return false;
+        }
+    };
+
+    let symbols = Symbols::load(program_path).unwrap_or_default();
+    return write_output(
+        output,
+        &disassembler::disassemble_program(&program, &symbols),
+    );
+}
+
+// The options get the name loader::load_options looks for, so they apply once Octo has built the
+// source into a ROM beside it.
+fn unpack_cartridge(cart_path: &str, output: &str) -> bool {
+    let cartridge = match fs::read(cart_path)
+        .map_err(|e| e.to_string())
+        .and_then(|gif_bytes| loader::decode_cartridge(&gif_bytes))
+    {
+        Ok(cartridge) => cartridge,
+        Err(e) => {
+            eprintln!("Error: Could not unpack {cart_path} ({e}).");
+            return false;
+        }
+    };
+
+    let options_path = Path::new(output).with_extension("json");
+
+    if !write_output(Some(output), &cartridge.program)
+        || !write_output(
+            Some(&options_path.to_string_lossy()),
+            &loader::format_options(&cartridge.options),
+        )
+    {
+        return false;
+    }
+
+    println!(
+        "Unpacked the source into {output} and the options into {}.",
+        options_path.display()
+    );
+    return true;
+}
+
+fn print_program_info(program_path: &str) -> bool {
+    let program = match fs::read(program_path) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("Error: Could not read {program_path} ({e}).");
+            return false;
+        }
+    };
+
+    print!("{}", rominfo::format_info(&rominfo::inspect(&program)));
+    return true;
+}
+
+fn assemble_program(source_path: &str, output: &str, write_symbols: bool) -> bool {
+    let assembly = match read_file(source_path)
+        .and_then(|source| assembler::assemble_with_debug_info(&source))
+    {
+        Ok(assembly) => assembly,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            return false;
+        }
+    };
 
+    let program = assembly.program;
+
+    if let Err(e) = fs::write(output, &program) {
+        eprintln!("Error: Could not write {output} ({e}).");
+        return false;
+    }
+
+    println!("Assembled {} bytes into {output}.", program.len());
+
+    if write_symbols {
+        let symbols_path = Symbols::get_path(output);
+
+        if !write_output(Some(&symbols_path), &assembly.symbols.format()) {
+            return false;
+        }
+
+        println!("Wrote the labels into {symbols_path}.");
+    }
+
+    return true;
+}
+
+// Frames are run back to back, so the speed is limited only by the emulator itself.
+fn run_benchmark(
+    config_path: &str,
+    profile: Option<&str>,
+    program_path: &str,
+    frame_count: usize,
+) -> bool {
+    let Some(config) = config::generate_configs_from(config_path, profile) else {
+        return false;
+    };
+
+    let Some(emulator) = EmulatorBuilder::new(config)
+        .with_audio_sink(Box::new(NullAudioSink))
+        .build()
+    else {
+        return false;
+    };
+
+    if let Err(e) = emulator.load_rom(program_path) {
+        eprintln!("Error: {e}");
+        return false;
+    }
+
+    emulator.seed_randomness();
+    let started = Instant::now();
+    let mut frames = 0;
+
+    while frames < frame_count && emulator.is_running() && !emulator.is_paused() {
+        emulator.execute_frame();
+        frames += 1;
+    }
+
+    let seconds = started.elapsed().as_secs_f64();
+    let instructions = emulator.get_executed_instructions();
+
+    println!(
+        "Ran {frames} frames ({instructions} instructions) in {seconds:.3} s: {:.0} instructions per second, {:.1}x full speed.",
+        instructions as f64 / seconds,
+        frames as f64 / FRAMES_PER_SECOND / seconds
+    );
+
+    return emulator.is_running();
+}
+
+fn run_batch(
+    config_path: &str,
+    profile: Option<&str>,
+    directory: &str,
+    frame_count: usize,
+    output: Option<&str>,
+) -> bool {
+    let Some(config) = config::generate_configs_from(config_path, profile) else {
+        return false;
+    };
+
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Error: Could not read {directory} ({e}).");
+            return false;
+        }
+    };
+
+    let mut rom_paths: Vec<PathBuf> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| {
+            path.extension().is_some_and(|extension| {
+                chooser::ROM_EXTENSIONS
+                    .iter()
+                    .any(|e| extension.eq_ignore_ascii_case(e))
+            })
+        })
+        .collect();
+    rom_paths.sort();
+
+    let mut results = Vec::new();
+
+    for path in &rom_paths {
+        let Some(result) = batch::run_rom(&config, path, frame_count) else {
+            return false;
+        };
+
+        results.push(result);
+    }
+
+    let report = match output {
+        Some(path) if path.ends_with(".json") => batch::format_json(&results),
+        _ => batch::format_csv(&results),
+    };
+
+    if !write_output(output, &report) {
+        return false;
+    }
+
+    let halted = results
+        .iter()
+        .filter(|result| result.halt_reason.is_some())
+        .count();
+    eprintln!("Ran {} ROMs, of which {halted} halted.", results.len());
+    return true;
+}
+
+fn run_self_test(config_path: &str, profile: Option<&str>) -> bool {
+    let Some(config) = config::generate_configs_from(config_path, profile) else {
+        return false;
+    };
+
+    let Some(emulator) = EmulatorBuilder::new(config)
+        .with_audio_sink(Box::new(NullAudioSink))
+        .build()
+    else {
+        return false;
+    };
+
+    match selftest::run_self_test(&emulator) {
+        Ok(checks) => {
+            println!("Self-test passed all {checks} checks.");
+            return true;
+        }
+        Err(e) => {
+            eprintln!("Error: Self-test failed. {e}");
+            return false;
+        }
+    }
+}
+
+#[cfg(feature = "file-dialog")]
+fn pick_program_path() -> Option<String> {
+    let path = rfd::FileDialog::new()
+        .set_title("Open a CHIP-8 ROM")
+        .add_filter("CHIP-8 ROMs", &chip8rust::chooser::ROM_EXTENSIONS)
+        .pick_file()?;
+
+    return Some(path.to_string_lossy().into_owned());
+}
+
+// Without a native dialog, the ROM chooser in the window is used instead.
+#[cfg(not(feature = "file-dialog"))]
+fn pick_program_path() -> Option<String> {
+    return None;
+}
+
+fn main() -> ExitCode {
     let args = Args::parse();
+    let profile = args.profile.as_deref();
+
+    #[cfg(feature = "profiling")]
+    let _flame_capture = args
+        .flame_graph
+        .as_deref()
+        .and_then(chip8rust::profiling::start_flame_capture);
+
+    let succeeded = match args.command {
+        None => run_window(
+            &args.config,
+            profile,
+            RunOptions {
+                program_path: args.program_path,
+                selftest: args.selftest,
+                watch: args.watch,
+                breakpoints: args.breakpoints,
+                debug_server: args.debug_server,
+                tick_log: args.tick_log,
+            },
+        ),
+        Some(Command::Run {
+            program_path,
+            selftest,
+            watch,
+            breakpoints,
+            debug_server,
+            tick_log,
+        }) => run_window(
+            &args.config,
+            profile,
+            RunOptions {
+                program_path,
+                selftest,
+                watch,
+                breakpoints,
+                debug_server,
+                tick_log,
+            },
+        ),
+        Some(Command::Disasm {
+            program_path,
+            output,
+        }) => disassemble_program(&program_path, output.as_deref()),
+        Some(Command::Asm {
+            source_path,
+            output,
+            symbols,
+        }) => assemble_program(&source_path, &output, symbols),
+        Some(Command::UnpackCart { cart_path, output }) => unpack_cartridge(&cart_path, &output),
+        Some(Command::Info { program_path }) => print_program_info(&program_path),
+        Some(Command::Selftest) => run_self_test(&args.config, profile),
+        Some(Command::Bench {
+            program_path,
+            frames,
+        }) => run_benchmark(&args.config, profile, &program_path, frames),
+        Some(Command::Batch {
+            directory,
+            frames,
+            output,
+        }) => run_batch(&args.config, profile, &directory, frames, output.as_deref()),
+        Some(Command::CheckConfig { config_path }) => config::check_config(&config_path, profile),
+        Some(Command::DumpFrames {
+            program_path,
+            inputs,
+            frames,
+            output,
+            verify,
+        }) => {
+            let options = DumpOptions {
+                program_path,
+                inputs,
+                frames,
+                output,
+                verify,
+            };
+
+            run_frame_dump(&args.config, profile, options)
+        }
+    };
+
+    return match succeeded {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    };
+}
+
+fn run_window(config_path: &str, profile: Option<&str>, options: RunOptions) -> bool {
+    println!("Starting emulator...");
+
+    if !Path::new(config_path).exists() {
+        setup::run_first_time_setup(config_path);
+    }
+
+    let Some(config) = config::generate_configs_from(config_path, profile) else {
+        println!("Stopping emulator...");
+        return false;
+    };
 
-    let Some(comps) = create_components() else {
+    // The program is launched either way, as the failure has been reported.
+    if options.selftest {
+        run_self_test(config_path, profile);
+    }
+
+    let mut state = state::load_state(state::STATE_FILE_PATH);
+    let resume_last_program = config.resume_last_program;
+    let auto_save_state = config.auto_save_state;
+    let rewind_config = config.rewind.clone();
+
+    let program_path = match options.program_path {
+        Some(path) => Some(path),
+        None => match state
+            .last_program_path
+            .clone()
+            .filter(|_| resume_last_program)
+        {
+            Some(path) => {
+                println!("Resuming {path}...");
+                Some(path)
+            }
+            None => pick_program_path(),
+        },
+    };
+
+    let Some(emulator) = Emulator::try_new(config) else {
         println!("Stopping emulator...");
-        return;
+        return false;
     };
 
-    comps.ram.load_program(&args.program_path);
+    let emulator = Arc::new(emulator);
 
     let mut window_manager = WindowManager::new(
-        comps.active.clone(),
-        comps.gpu.clone(),
-        comps.input_manager.clone(),
+        emulator.clone(),
+        SettingsMenu::new(String::from(config_path), &emulator),
+        state.window,
+        state.recent_programs.clone(),
+        auto_save_state,
+        rewind_config,
     );
 
+    window_manager.set_watch(options.watch);
+    window_manager.set_breakpoint_locations(options.breakpoints);
+
+    // The window still opens if the server can't start, as it is only an extra.
+    if let Some(address) = options.debug_server {
+        match debugserver::start(emulator.clone(), &address) {
+            Ok(address) => println!("Debug server listening on {address}."),
+            Err(e) => eprintln!("Error: Could not start the debug server on {address} ({e})."),
+        }
+    }
+
+    let tick_log = options
+        .tick_log
+        .and_then(|path| match TickLog::create(&path) {
+            Ok(tick_log) => {
+                emulator.add_observer(tick_log.clone());
+                Some(tick_log)
+            }
+            Err(e) => {
+                eprintln!("Error: Could not create the tick log at {path} ({e}).");
+                None
+            }
+        });
+
+    // Without a program to run, the user picks one from the window instead.
+    match program_path {
+        Some(path) => match emulator.load_rom(&path) {
+            Ok(()) => window_manager.set_program_path(path),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                window_manager.open_rom_chooser(Some(&path), Some(e.to_string()));
+            }
+        },
+        None => window_manager.open_rom_chooser(None, None),
+    }
+
     let event_loop = match EventLoop::new() {
         Ok(l) => l,
         Err(e) => {
             eprintln!("Error: Failed to create event loop ({e}).");
-            return;
+            return false;
         }
     };
 
     event_loop.set_control_flow(ControlFlow::Poll);
 
-    let mut handles = Vec::new();
+    let handles = emulator.spawn_threads();
 
-    handles.push(thread::spawn(move || comps.delay_timer.run()));
-    handles.push(thread::spawn(move || comps.sound_timer.run()));
+    if let Err(e) = event_loop.run_app(&mut window_manager) {
+        eprintln!("Error: Window manager event loop failed ({e}).");
+        emulator.stop();
+    };
 
-    if comps.gpu.should_render_separately() {
-        handles.push(thread::spawn(move || comps.gpu.run_separate_render()));
+    state.window = window_manager.get_window_state();
+    state.recent_programs = window_manager.get_recent_programs();
+
+    if resume_last_program && let Some(path) = window_manager.get_program_path() {
+        state.last_program_path = Some(path);
     }
 
-    handles.push(thread::spawn(move || comps.cpu.run()));
+    state::save_state(state::STATE_FILE_PATH, &state);
 
-    if let Err(e) = event_loop.run_app(&mut window_manager) {
-        eprintln!("Error: Window manager event loop failed ({e}).");
-        comps.active.store(false, Ordering::Release);
-    };
+    if let Some(tick_log) = tick_log {
+        tick_log.flush();
+    }
 
-    if cfg!(debug_assertions) && comps.active.load(Ordering::Relaxed) {
-        panic!("Event loop should not have exited while active is high.");
+    if cfg!(debug_assertions) && emulator.is_running() {
+        panic!("Event loop should not have exited while the emulator is running.");
     }
 
-    for handle in handles {
-        handle.join().unwrap();
+    // A hung thread would never finish, so it is left behind if the watchdog stopped the emulator.
+    let cpu_hung = emulator.get_shutdown_reason()
+        == Some(ShutdownReason::Halted(EmulatorError::CPUNotResponding));
+
+    if !cpu_hung {
+        for handle in handles {
+            handle.join().unwrap();
+        }
     }
 
     println!("Stopping emulator...");
-}
-
-fn create_components() -> Option<Components> {
-    let config = config::generate_configs()?;
-    let active = Arc::new(AtomicBool::new(true));
-    let delay_timer = DelayTimer::try_new(active.clone(), config.delay_timer)?;
-    let sound_timer = SoundTimer::try_new(active.clone(), config.sound_timer)?;
-    let input_manager = InputManager::try_new(active.clone(), config.input)?;
-    let ram = RAM::try_new(active.clone(), config.ram)?;
-    let gpu = GPU::try_new(active.clone(), config.gpu)?;
-    let cpu = CPU::try_new(
-        active.clone(),
-        config.cpu,
-        gpu.clone(),
-        ram.clone(),
-        delay_timer.clone(),
-        sound_timer.clone(),
-        input_manager.clone(),
-    )?;
-
-    return Some(Components {
-        active,
-        cpu,
-        gpu,
-        ram,
-        delay_timer,
-        sound_timer,
-        input_manager,
-    });
+    return true;
 }
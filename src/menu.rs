@@ -0,0 +1,352 @@
+use crate::lifecycle::Lifecycle;
+use crate::overlay::{self, Canvas};
+use std::sync::Arc;
+use winit::event::MouseButton;
+use winit::keyboard::KeyCode;
+use winit_input_helper::WinitInputHelper;
+
+const CLOSE_MENU_KEYS: [KeyCode; 2] = [KeyCode::Escape, KeyCode::AltLeft];
+const PREVIOUS_MENU_KEY: KeyCode = KeyCode::ArrowLeft;
+const NEXT_MENU_KEY: KeyCode = KeyCode::ArrowRight;
+const PREVIOUS_ITEM_KEY: KeyCode = KeyCode::ArrowUp;
+const NEXT_ITEM_KEY: KeyCode = KeyCode::ArrowDown;
+const SELECT_ITEM_KEY: KeyCode = KeyCode::Enter;
+const SELECT_ITEM_BUTTON: MouseButton = MouseButton::Left;
+
+const TEXT_SCALE: usize = 2;
+const ROW_HEIGHT: usize = 7 * TEXT_SCALE;
+const MENU_MARGIN: usize = 8;
+const BAR_HEIGHT: usize = ROW_HEIGHT + MENU_MARGIN;
+const LABEL_COLUMN_CHARS: usize = 20;
+const HOTKEY_COLUMN_CHARS: usize = 8;
+const BACKGROUND_COLOR: u32 = 0x101018;
+const SELECTED_ROW_COLOR: u32 = 0x303048;
+const TEXT_COLOR: u32 = 0xFFFFFF;
+const HINT_COLOR: u32 = 0x9999AA;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MenuItem {
+    OpenRom,
+    Quit,
+    Reset,
+    QuickSave,
+    QuickLoad,
+    LoadState,
+    TogglePause,
+    OpenSettings,
+    CyclePalette,
+    InvertColors,
+    ToggleFullscreen,
+    ToggleKeypad,
+    ToggleMagnifier,
+    ToggleDebugger,
+    OpenTileViewer,
+    About,
+}
+
+struct Menu {
+    title: &'static str,
+    items: &'static [MenuItem],
+}
+
+const MENUS: [Menu; 4] = [
+    Menu {
+        title: "File",
+        items: &[MenuItem::OpenRom, MenuItem::Quit],
+    },
+    Menu {
+        title: "Emulation",
+        items: &[
+            MenuItem::Reset,
+            MenuItem::QuickSave,
+            MenuItem::QuickLoad,
+            MenuItem::LoadState,
+            MenuItem::TogglePause,
+            MenuItem::OpenSettings,
+        ],
+    },
+    Menu {
+        title: "View",
+        items: &[
+            MenuItem::CyclePalette,
+            MenuItem::InvertColors,
+            MenuItem::ToggleFullscreen,
+            MenuItem::ToggleKeypad,
+            MenuItem::ToggleMagnifier,
+            MenuItem::ToggleDebugger,
+            MenuItem::OpenTileViewer,
+        ],
+    },
+    Menu {
+        title: "Help",
+        items: &[MenuItem::About],
+    },
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum MenuTarget {
+    Title(usize),
+    Item(usize, usize),
+}
+
+pub struct MenuBar {
+    lifecycle: Arc<Lifecycle>,
+    open: bool,
+    menu: usize,
+    selected: usize,
+}
+
+impl MenuBar {
+    pub fn new(lifecycle: Arc<Lifecycle>) -> Self {
+        Self {
+            lifecycle,
+            open: false,
+            menu: 0,
+            selected: 0,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        return self.open;
+    }
+
+    pub fn open(&mut self) {
+        self.open = true;
+        self.menu = 0;
+        self.selected = 0;
+    }
+
+    // Returns the item picked by the user, if any, closing the menu bar.
+    pub fn update(&mut self, input: &WinitInputHelper) -> Option<MenuItem> {
+        let items = MENUS[self.menu].items;
+
+        if CLOSE_MENU_KEYS.iter().any(|key| input.key_pressed(*key)) {
+            self.open = false;
+        } else if input.key_pressed(PREVIOUS_MENU_KEY) {
+            self.menu = (self.menu + MENUS.len() - 1) % MENUS.len();
+            self.selected = 0;
+        } else if input.key_pressed(NEXT_MENU_KEY) {
+            self.menu = (self.menu + 1) % MENUS.len();
+            self.selected = 0;
+        } else if input.key_pressed(PREVIOUS_ITEM_KEY) {
+            self.selected = (self.selected + items.len() - 1) % items.len();
+        } else if input.key_pressed(NEXT_ITEM_KEY) {
+            self.selected = (self.selected + 1) % items.len();
+        } else if input.key_pressed(SELECT_ITEM_KEY) {
+            self.open = false;
+            return Some(items[self.selected]);
+        } else if input.mouse_pressed(SELECT_ITEM_BUTTON) {
+            let target = input
+                .cursor()
+                .and_then(|(x, y)| self.get_target_at(x as usize, y as usize));
+
+            match target {
+                Some(MenuTarget::Title(menu)) => {
+                    self.menu = menu;
+                    self.selected = 0;
+                }
+                Some(MenuTarget::Item(menu, item)) => {
+                    self.open = false;
+                    return Some(MENUS[menu].items[item]);
+                }
+                // Clicking anywhere else dismisses the menu, like a native one.
+                None => self.open = false,
+            }
+        }
+
+        return None;
+    }
+
+    fn get_target_at(&self, x: usize, y: usize) -> Option<MenuTarget> {
+        if y < BAR_HEIGHT {
+            return (0..MENUS.len())
+                .find(|menu| {
+                    let (title_x, title_width) = get_title_bounds(*menu);
+                    (title_x..title_x + title_width).contains(&x)
+                })
+                .map(MenuTarget::Title);
+        }
+
+        let (dropdown_x, _) = get_title_bounds(self.menu);
+
+        if !(dropdown_x..dropdown_x + get_dropdown_width()).contains(&x) {
+            return None;
+        }
+
+        let item = (y - BAR_HEIGHT) / ROW_HEIGHT;
+
+        return match item < MENUS[self.menu].items.len() {
+            true => Some(MenuTarget::Item(self.menu, item)),
+            false => None,
+        };
+    }
+
+    fn get_label(&self, item: MenuItem) -> &'static str {
+        return match item {
+            MenuItem::OpenRom => "Open ROM...",
+            MenuItem::Quit => "Quit",
+            MenuItem::Reset => "Reset",
+            MenuItem::QuickSave => "Save to slot 1",
+            MenuItem::QuickLoad => "Load slot 1",
+            MenuItem::LoadState => "Load state...",
+            MenuItem::TogglePause => match self.lifecycle.is_paused() {
+                true => "Resume",
+                false => "Pause",
+            },
+            MenuItem::OpenSettings => "Settings and quirks",
+            MenuItem::CyclePalette => "Next palette",
+            MenuItem::InvertColors => "Invert colors",
+            MenuItem::ToggleFullscreen => "Fullscreen",
+            MenuItem::ToggleKeypad => "Keypad",
+            MenuItem::ToggleMagnifier => "Pixel inspector",
+            MenuItem::ToggleDebugger => "Debugger",
+            MenuItem::OpenTileViewer => "Memory tiles...",
+            MenuItem::About => "About",
+        };
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<'_>) {
+        let (_, text_height) = overlay::get_text_size("", TEXT_SCALE);
+        let text_offset = (ROW_HEIGHT - text_height) / 2;
+        let (last_title_x, last_title_width) = get_title_bounds(MENUS.len() - 1);
+
+        canvas.fill_rect(
+            0,
+            0,
+            last_title_x + last_title_width + MENU_MARGIN,
+            BAR_HEIGHT,
+            BACKGROUND_COLOR,
+        );
+
+        for (i, menu) in MENUS.iter().enumerate() {
+            let (title_x, title_width) = get_title_bounds(i);
+
+            if i == self.menu {
+                canvas.fill_rect(title_x, 0, title_width, BAR_HEIGHT, SELECTED_ROW_COLOR);
+            }
+
+            overlay::draw_text(
+                canvas,
+                title_x + MENU_MARGIN,
+                MENU_MARGIN / 2 + text_offset,
+                menu.title,
+                TEXT_SCALE,
+                TEXT_COLOR,
+            );
+        }
+
+        let (dropdown_x, _) = get_title_bounds(self.menu);
+        let dropdown_width = get_dropdown_width();
+        let (label_width, _) = overlay::get_text_size(&" ".repeat(LABEL_COLUMN_CHARS), TEXT_SCALE);
+        let items = MENUS[self.menu].items;
+
+        canvas.fill_rect(
+            dropdown_x,
+            BAR_HEIGHT,
+            dropdown_width,
+            ROW_HEIGHT * items.len(),
+            BACKGROUND_COLOR,
+        );
+
+        for (i, item) in items.iter().enumerate() {
+            let y = BAR_HEIGHT + ROW_HEIGHT * i;
+
+            if i == self.selected {
+                canvas.fill_rect(
+                    dropdown_x,
+                    y,
+                    dropdown_width,
+                    ROW_HEIGHT,
+                    SELECTED_ROW_COLOR,
+                );
+            }
+
+            overlay::draw_text(
+                canvas,
+                dropdown_x + MENU_MARGIN,
+                y + text_offset,
+                self.get_label(*item),
+                TEXT_SCALE,
+                TEXT_COLOR,
+            );
+
+            overlay::draw_text(
+                canvas,
+                dropdown_x + MENU_MARGIN + label_width,
+                y + text_offset,
+                get_hotkey(*item),
+                TEXT_SCALE,
+                HINT_COLOR,
+            );
+        }
+    }
+}
+
+// Listing the hotkeys lets the menu teach them.
+fn get_hotkey(item: MenuItem) -> &'static str {
+    return match item {
+        MenuItem::OpenRom => "Ctrl+O",
+        MenuItem::QuickSave => "Shift+F1",
+        MenuItem::QuickLoad => "F1",
+        MenuItem::OpenSettings => "Esc",
+        MenuItem::CyclePalette => "F12",
+        MenuItem::InvertColors => "Shift+F12",
+        MenuItem::ToggleFullscreen => "F11",
+        MenuItem::ToggleKeypad => "Ctrl+K",
+        MenuItem::ToggleMagnifier => "Ctrl+I",
+        MenuItem::ToggleDebugger => "Ctrl+D",
+        MenuItem::OpenTileViewer => "Ctrl+M",
+        MenuItem::Quit
+        | MenuItem::Reset
+        | MenuItem::LoadState
+        | MenuItem::TogglePause
+        | MenuItem::About => "",
+    };
+}
+
+fn get_title_bounds(menu: usize) -> (usize, usize) {
+    let title_width = |menu: &Menu| {
+        let (text_width, _) = overlay::get_text_size(menu.title, TEXT_SCALE);
+        text_width + MENU_MARGIN * 2
+    };
+
+    let x = MENU_MARGIN + MENUS[..menu].iter().map(title_width).sum::<usize>();
+    return (x, title_width(&MENUS[menu]));
+}
+
+fn get_dropdown_width() -> usize {
+    let (width, _) = overlay::get_text_size(
+        &" ".repeat(LABEL_COLUMN_CHARS + HOTKEY_COLUMN_CHARS),
+        TEXT_SCALE,
+    );
+
+    return width + MENU_MARGIN * 2;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_target_at() {
+        let mut menu_bar = MenuBar::new(Lifecycle::new());
+        menu_bar.open();
+
+        let (view_x, _) = get_title_bounds(2);
+        assert_eq!(
+            Some(MenuTarget::Title(2)),
+            menu_bar.get_target_at(view_x, 0)
+        );
+
+        menu_bar.menu = 2;
+        assert_eq!(
+            Some(MenuTarget::Item(2, 1)),
+            menu_bar.get_target_at(view_x, BAR_HEIGHT + ROW_HEIGHT)
+        );
+        assert_eq!(
+            None,
+            menu_bar.get_target_at(view_x, BAR_HEIGHT + ROW_HEIGHT * 7)
+        );
+        assert_eq!(None, menu_bar.get_target_at(0, BAR_HEIGHT));
+    }
+}
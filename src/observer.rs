@@ -0,0 +1,172 @@
+use crate::lifecycle::Lifecycle;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmulatorError {
+    ProgramNotFound(String),
+    ProgramTooLarge(String),
+    InvalidCartridge { path: String, reason: String },
+    ProgramCounterOverflow,
+    IndexRegisterOverflow,
+    HeapOverflowWhileWriting { address: u16, count: usize },
+    HeapOverflowWhileReading { address: u16, count: usize },
+    StackOverflowWhilePushing,
+    StackOverflowWhilePopping,
+    CPUNotResponding,
+    UnknownOpcode { opcode: u16, address: u16 },
+}
+
+impl fmt::Display for EmulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Self::ProgramNotFound(path) => write!(f, "Could not find valid program at {path}."),
+            Self::ProgramTooLarge(path) => {
+                write!(f, "Program {path} is too large to fit in the heap.")
+            }
+            Self::InvalidCartridge { path, reason } => {
+                write!(f, "Could not build the cartridge {path} ({reason})")
+            }
+            Self::ProgramCounterOverflow => write!(f, "Program counter overflowed."),
+            Self::IndexRegisterOverflow => write!(f, "Index register overflowed."),
+            Self::HeapOverflowWhileWriting { address, count } => write!(
+                f,
+                "Heap overflowed while writing {count} byte(s) at {address:03X}, past the end at FFF."
+            ),
+            Self::HeapOverflowWhileReading { address, count } => write!(
+                f,
+                "Heap overflowed while reading {count} byte(s) at {address:03X}, past the end at FFF."
+            ),
+            Self::StackOverflowWhilePushing => write!(f, "Stack overflowed while pushing."),
+            Self::StackOverflowWhilePopping => write!(f, "Stack overflowed while popping."),
+            Self::CPUNotResponding => write!(f, "The CPU thread stopped responding."),
+            Self::UnknownOpcode { opcode, address } => {
+                write!(f, "Unknown opcode {opcode:04X} at {address:03X}.")
+            }
+        };
+    }
+}
+
+pub trait EmulatorObserver: Send + Sync {
+    // Called each time a queued frame is presented, with its pixels.
+    fn on_frame(&self, _framebuffer: &[bool]) {}
+
+    fn on_beep(&self, _beeping: bool) {}
+
+    fn on_halt(&self, _error: &EmulatorError) {}
+
+    // Called for every unknown opcode reached, whatever the unknown opcode policy does with it.
+    fn on_unknown_opcode(&self, _opcode: u16, _address: u16) {}
+    // Called after each batch of instructions the CPU thread runs, with how long it took.
+    fn on_cpu_batch(&self, _duration: Duration) {}
+    // Called each time a timer counts down, with its new value.
+    fn on_delay_timer_tick(&self, _value: u8) {}
+    fn on_sound_timer_tick(&self, _value: u8) {}
+    // Called through Emulator::notify_present, by the window each time it presents a frame.
+    fn on_present(&self) {}
+}
+
+pub struct Observers {
+    observers: RwLock<Vec<Arc<dyn EmulatorObserver>>>,
+}
+
+impl Observers {
+    pub fn new() -> Arc<Self> {
+        return Arc::new(Self {
+            observers: RwLock::new(Vec::new()),
+        });
+    }
+
+    pub fn add(&self, observer: Arc<dyn EmulatorObserver>) {
+        self.observers.write().unwrap().push(observer);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.observers.read().unwrap().is_empty();
+    }
+
+    pub fn notify_frame(&self, framebuffer: &[bool]) {
+        for observer in self.observers.read().unwrap().iter() {
+            observer.on_frame(framebuffer);
+        }
+    }
+
+    pub fn notify_beep(&self, beeping: bool) {
+        for observer in self.observers.read().unwrap().iter() {
+            observer.on_beep(beeping);
+        }
+    }
+
+    pub fn notify_unknown_opcode(&self, opcode: u16, address: u16) {
+        for observer in self.observers.read().unwrap().iter() {
+            observer.on_unknown_opcode(opcode, address);
+        }
+    }
+
+    pub fn notify_cpu_batch(&self, duration: Duration) {
+        for observer in self.observers.read().unwrap().iter() {
+            observer.on_cpu_batch(duration);
+        }
+    }
+
+    pub fn notify_delay_timer_tick(&self, value: u8) {
+        for observer in self.observers.read().unwrap().iter() {
+            observer.on_delay_timer_tick(value);
+        }
+    }
+
+    pub fn notify_sound_timer_tick(&self, value: u8) {
+        for observer in self.observers.read().unwrap().iter() {
+            observer.on_sound_timer_tick(value);
+        }
+    }
+
+    pub fn notify_present(&self) {
+        for observer in self.observers.read().unwrap().iter() {
+            observer.on_present();
+        }
+    }
+
+    pub fn halt(&self, lifecycle: &Lifecycle, error: EmulatorError) {
+        eprintln!("Error: {error}");
+        lifecycle.halt(error.clone());
+
+        for observer in self.observers.read().unwrap().iter() {
+            observer.on_halt(&error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        errors: Mutex<Vec<EmulatorError>>,
+    }
+
+    impl EmulatorObserver for RecordingObserver {
+        fn on_halt(&self, error: &EmulatorError) {
+            self.errors.lock().unwrap().push(error.clone());
+        }
+    }
+
+    #[test]
+    fn test_halt() {
+        let lifecycle = Lifecycle::new();
+        let observers = Observers::new();
+        let observer = Arc::new(RecordingObserver::default());
+        observers.add(observer.clone());
+
+        observers.halt(&lifecycle, EmulatorError::StackOverflowWhilePushing);
+
+        assert!(!lifecycle.is_running());
+        assert_eq!(
+            vec![EmulatorError::StackOverflowWhilePushing],
+            *observer.errors.lock().unwrap()
+        );
+    }
+}
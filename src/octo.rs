@@ -0,0 +1,642 @@
+//! Builds Octo source, as Octo cartridges hold it, into a program that can be run.
+//!
+//! Only the core of the language is understood: labels, `:const`, `:alias`, `:org`, `:call`, raw
+//! bytes, every CHIP-8 instruction in its Octo spelling, and `if`/`then`, `if`/`begin`/`else`/
+//! `end` and `loop`/`while`/`again` with `==`, `!=`, `key` and `-key` conditions. Macros, `:calc`,
+//! `:unpack`, the comparisons built from several instructions (`<`, `>`, `<=` and `>=`) and the
+//! SUPER-CHIP and XO-CHIP instructions are reported as unsupported rather than guessed at.
+//!
+//! As in Octo, the program starts with a jump to the `main` label.
+
+use crate::ram::PROGRAM_START_ADDRESS;
+use std::collections::HashMap;
+
+// Debugger directives, with how many tokens follow them, which change nothing in the program.
+const IGNORED_DIRECTIVES: [(&str, usize); 2] = [(":breakpoint", 1), (":monitor", 2)];
+
+#[derive(Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+enum Block {
+    // The jump past the body, patched once the body ends.
+    If { jump: usize },
+    Else { jump: usize },
+    // The jumps out of the loop, from its `while`s, patched at `again`.
+    Loop { start: u16, exits: Vec<usize> },
+}
+
+struct Compiler<'a> {
+    tokens: Vec<Token<'a>>,
+    position: usize,
+    program: Vec<u8>,
+    labels: HashMap<&'a str, u16>,
+    constants: HashMap<&'a str, u16>,
+    aliases: HashMap<&'a str, u16>,
+    // Opcodes waiting for a label defined further on, by their position in the program.
+    unresolved: Vec<(usize, &'a str, usize)>,
+    blocks: Vec<(Block, usize)>,
+}
+
+/// Builds the source into a program to be loaded at 0x200, or describes the first thing that
+/// couldn't be built.
+pub fn compile(source: &str) -> Result<Vec<u8>, String> {
+    let tokens = source
+        .lines()
+        .enumerate()
+        .flat_map(|(i, line)| {
+            let code = line.split('#').next().unwrap_or("");
+            return code
+                .split_whitespace()
+                .map(move |text| Token { text, line: i + 1 });
+        })
+        .collect();
+
+    let mut compiler = Compiler {
+        tokens,
+        position: 0,
+        program: Vec::new(),
+        labels: HashMap::new(),
+        constants: HashMap::new(),
+        aliases: HashMap::new(),
+        unresolved: Vec::new(),
+        blocks: Vec::new(),
+    };
+
+    compiler.emit(0x1000);
+
+    while compiler.position < compiler.tokens.len() {
+        compiler.statement()?;
+    }
+
+    return compiler.finish();
+}
+
+impl<'a> Compiler<'a> {
+    fn get_address(&self) -> u16 {
+        return PROGRAM_START_ADDRESS + self.program.len() as u16;
+    }
+
+    fn emit(&mut self, opcode: u16) {
+        self.program.extend(opcode.to_be_bytes());
+    }
+
+    fn patch(&mut self, position: usize, address: u16) {
+        self.program[position] |= (address >> 8) as u8;
+        self.program[position + 1] |= address as u8;
+    }
+
+    fn next(&mut self) -> Result<Token<'a>, String> {
+        let Some(token) = self.tokens.get(self.position).copied() else {
+            let line = self.tokens.last().map_or(1, |token| token.line);
+            return Err(format!("Line {line} ends partway through a statement."));
+        };
+
+        self.position += 1;
+        return Ok(token);
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), String> {
+        let token = self.next()?;
+
+        if token.text != text {
+            return Err(format!(
+                "Line {} has {} where {text} was expected.",
+                token.line, token.text
+            ));
+        }
+
+        return Ok(());
+    }
+
+    fn register(&mut self) -> Result<u16, String> {
+        let token = self.next()?;
+
+        return get_register(token.text, &self.aliases)
+            .ok_or_else(|| format!("Line {} has {} for a register.", token.line, token.text));
+    }
+
+    fn value(&mut self, max: u16) -> Result<u16, String> {
+        let token = self.next()?;
+        let value = parse_number(token.text).or_else(|| {
+            return self
+                .constants
+                .get(token.text)
+                .map(|constant| *constant as i32);
+        });
+
+        return match value {
+            Some(value) if value < 0 && value >= -(max as i32 + 1) / 2 => {
+                Ok((value + max as i32 + 1) as u16)
+            }
+            Some(value) if (0..=max as i32).contains(&value) => Ok(value as u16),
+            _ => Err(format!(
+                "Line {} has {} for a number up to {max}.",
+                token.line, token.text
+            )),
+        };
+    }
+
+    // Emits an opcode that ends in an address, which may be a label yet to be defined.
+    fn emit_with_address(&mut self, opcode: u16) -> Result<(), String> {
+        let token = self.next()?;
+        let (text, line) = (token.text, token.line);
+
+        let address = match parse_number(text) {
+            Some(address) => Some(address),
+            None => self
+                .constants
+                .get(text)
+                .or_else(|| self.labels.get(text))
+                .map(|address| *address as i32),
+        };
+
+        match address {
+            Some(address) if (0..=0xFFF).contains(&address) => {
+                self.emit(opcode | address as u16);
+            }
+            Some(_) => {
+                return Err(format!(
+                    "Line {line} has {text}, which is past the end of memory."
+                ));
+            }
+            None => {
+                self.unresolved.push((self.program.len(), text, line));
+                self.emit(opcode);
+            }
+        }
+
+        return Ok(());
+    }
+
+    // The opcode that skips the next instruction when the condition holds.
+    fn condition(&mut self) -> Result<u16, String> {
+        let x = self.register()? << 8;
+        let token = self.next()?;
+        let (text, line) = (token.text, token.line);
+
+        return match text {
+            "key" => Ok(0xE09E | x),
+            "-key" => Ok(0xE0A1 | x),
+            "==" | "!=" => {
+                let is_equal = text == "==";
+                let next = self.tokens.get(self.position).map(|token| token.text);
+
+                match next.and_then(|text| get_register(text, &self.aliases)) {
+                    Some(y) => {
+                        self.position += 1;
+                        Ok(if is_equal { 0x5000 } else { 0x9000 } | x | y << 4)
+                    }
+                    None => Ok(if is_equal { 0x3000 } else { 0x4000 } | x | self.value(0xFF)?),
+                }
+            }
+            _ => Err(format!(
+                "Line {line} compares with {text}, which isn't supported."
+            )),
+        };
+    }
+
+    fn statement(&mut self) -> Result<(), String> {
+        let token = self.next()?;
+        let (text, line) = (token.text, token.line);
+
+        if let Some(x) = get_register(text, &self.aliases) {
+            return self.register_statement(x);
+        }
+
+        if let Some((_, skipped)) = IGNORED_DIRECTIVES.iter().find(|(name, _)| *name == text) {
+            self.position += *skipped;
+            return Ok(());
+        }
+
+        match text {
+            ":" => {
+                let name = self.next()?.text;
+
+                if self.labels.insert(name, self.get_address()).is_some() {
+                    return Err(format!("Line {line} redefines a label ({name})."));
+                }
+            }
+            ":const" => {
+                let name = self.next()?.text;
+                let value = self.value(0xFFF)?;
+                self.constants.insert(name, value);
+            }
+            ":alias" => {
+                let name = self.next()?.text;
+                let register = self.register()?;
+                self.aliases.insert(name, register);
+            }
+            ":org" => {
+                let address = self.value(0xFFF)?;
+
+                if address < self.get_address() {
+                    return Err(format!(
+                        "Line {line} moves back to {address:#05X}, which is already used."
+                    ));
+                }
+
+                self.program
+                    .resize((address - PROGRAM_START_ADDRESS) as usize, 0);
+            }
+            ":call" => self.emit_with_address(0x2000)?,
+            "clear" => self.emit(0x00E0),
+            "return" | ";" => self.emit(0x00EE),
+            "jump" => self.emit_with_address(0x1000)?,
+            "jump0" => self.emit_with_address(0xB000)?,
+            "bcd" => {
+                let x = self.register()?;
+                self.emit(0xF033 | x << 8);
+            }
+            "save" => {
+                let x = self.register()?;
+                self.emit(0xF055 | x << 8);
+            }
+            "load" => {
+                let x = self.register()?;
+                self.emit(0xF065 | x << 8);
+            }
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.value(0xF)?;
+                self.emit(0xD000 | x << 8 | y << 4 | n);
+            }
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                self.emit(if text == "delay" { 0xF015 } else { 0xF018 } | x << 8);
+            }
+            "i" => self.index_statement()?,
+            "if" => {
+                let skip = self.condition()?;
+                let token = self.next()?;
+
+                match token.text {
+                    "then" => {
+                        self.emit(negate(skip));
+                        self.statement()?;
+                    }
+                    "begin" => {
+                        self.emit(skip);
+                        self.blocks.push((
+                            Block::If {
+                                jump: self.program.len(),
+                            },
+                            line,
+                        ));
+                        self.emit(0x1000);
+                    }
+                    _ => {
+                        return Err(format!(
+                            "Line {} has {} where then or begin was expected.",
+                            token.line, token.text
+                        ));
+                    }
+                }
+            }
+            "else" => {
+                let Some((Block::If { jump }, if_line)) = self.blocks.pop() else {
+                    return Err(format!("Line {line} has an else outside an if."));
+                };
+
+                self.blocks.push((
+                    Block::Else {
+                        jump: self.program.len(),
+                    },
+                    if_line,
+                ));
+                self.emit(0x1000);
+                self.patch(jump, self.get_address());
+            }
+            "end" => match self.blocks.pop() {
+                Some((Block::If { jump } | Block::Else { jump }, _)) => {
+                    self.patch(jump, self.get_address());
+                }
+                _ => return Err(format!("Line {line} has an end outside an if.")),
+            },
+            "loop" => self.blocks.push((
+                Block::Loop {
+                    start: self.get_address(),
+                    exits: Vec::new(),
+                },
+                line,
+            )),
+            "while" => {
+                let skip = self.condition()?;
+                let position = self.program.len() + 2;
+
+                let Some((Block::Loop { exits, .. }, _)) = self
+                    .blocks
+                    .iter_mut()
+                    .rev()
+                    .find(|(block, _)| matches!(block, Block::Loop { .. }))
+                else {
+                    return Err(format!("Line {line} has a while outside a loop."));
+                };
+
+                exits.push(position);
+                self.emit(skip);
+                self.emit(0x1000);
+            }
+            "again" => {
+                let Some((Block::Loop { start, exits }, _)) = self.blocks.pop() else {
+                    return Err(format!("Line {line} has an again outside a loop."));
+                };
+
+                self.emit(0x1000 | start);
+
+                for exit in exits {
+                    self.patch(exit, self.get_address());
+                }
+            }
+            _ if text.starts_with(':') => {
+                return Err(format!("Line {line} uses {text}, which isn't supported."));
+            }
+            _ => {
+                if let Some(value) = parse_number(text)
+                    .or_else(|| self.constants.get(text).map(|constant| *constant as i32))
+                {
+                    if !(-128..=0xFF).contains(&value) {
+                        return Err(format!("Line {line} has {text}, which isn't a byte."));
+                    }
+
+                    self.program.push(value as u8);
+                    return Ok(());
+                }
+
+                // Anything else names a subroutine to call, maybe defined further on.
+                self.position -= 1;
+                self.emit_with_address(0x2000)?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    fn register_statement(&mut self, x: u16) -> Result<(), String> {
+        let operator = self.next()?;
+        let (operator, line) = (operator.text, operator.line);
+        let x = x << 8;
+        let next = self.tokens.get(self.position).map(|token| token.text);
+        let y = next.and_then(|text| get_register(text, &self.aliases));
+
+        if let Some(y) = y {
+            let low = match operator {
+                ":=" => 0x0,
+                "|=" => 0x1,
+                "&=" => 0x2,
+                "^=" => 0x3,
+                "+=" => 0x4,
+                "-=" => 0x5,
+                ">>=" => 0x6,
+                "=-" => 0x7,
+                "<<=" => 0xE,
+                _ => {
+                    return Err(format!(
+                        "Line {line} has {operator}, which isn't an operator for two registers."
+                    ));
+                }
+            };
+
+            self.position += 1;
+            self.emit(0x8000 | x | y << 4 | low);
+            return Ok(());
+        }
+
+        match (operator, next) {
+            (":=", Some("random")) => {
+                self.position += 1;
+                let mask = self.value(0xFF)?;
+                self.emit(0xC000 | x | mask);
+            }
+            (":=", Some("delay")) => {
+                self.position += 1;
+                self.emit(0xF007 | x);
+            }
+            (":=", Some("key")) => {
+                self.position += 1;
+                self.emit(0xF00A | x);
+            }
+            (":=", _) => {
+                let value = self.value(0xFF)?;
+                self.emit(0x6000 | x | value);
+            }
+            ("+=", _) => {
+                let value = self.value(0xFF)?;
+                self.emit(0x7000 | x | value);
+            }
+            // Adding the negative wraps around to the same result.
+            ("-=", _) => {
+                let value = self.value(0xFF)?;
+                self.emit(0x7000 | x | (0x100 - value) & 0xFF);
+            }
+            _ => {
+                return Err(format!(
+                    "Line {line} has {operator}, which isn't an operator for a number."
+                ));
+            }
+        }
+
+        return Ok(());
+    }
+
+    fn index_statement(&mut self) -> Result<(), String> {
+        let operator = self.next()?;
+
+        match (
+            operator.text,
+            self.tokens.get(self.position).map(|t| t.text),
+        ) {
+            ("+=", _) => {
+                let x = self.register()?;
+                self.emit(0xF01E | x << 8);
+            }
+            (":=", Some("hex")) => {
+                self.position += 1;
+                let x = self.register()?;
+                self.emit(0xF029 | x << 8);
+            }
+            (":=", Some("bighex")) => {
+                self.position += 1;
+                let x = self.register()?;
+                self.emit(0xF030 | x << 8);
+            }
+            (":=", _) => self.emit_with_address(0xA000)?,
+            (operator, _) => {
+                return Err(format!(
+                    "Line {} has {operator}, which isn't an operator for i.",
+                    self.tokens[self.position - 1].line
+                ));
+            }
+        }
+
+        return Ok(());
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>, String> {
+        if let Some((_, line)) = self.blocks.last() {
+            return Err(format!("Line {line} begins a block that is never ended."));
+        }
+
+        let Some(main) = self.labels.get("main").copied() else {
+            return Err(String::from("The program has no main label."));
+        };
+
+        self.patch(0, main);
+
+        for (position, name, line) in std::mem::take(&mut self.unresolved) {
+            let Some(address) = self.labels.get(name).copied() else {
+                return Err(format!(
+                    "Line {line} refers to {name}, which is never defined."
+                ));
+            };
+
+            self.patch(position, address);
+        }
+
+        return Ok(self.program);
+    }
+}
+
+fn get_register(text: &str, aliases: &HashMap<&str, u16>) -> Option<u16> {
+    if let Some(register) = aliases.get(text) {
+        return Some(*register);
+    }
+
+    return text
+        .strip_prefix(['v', 'V'])
+        .filter(|register| register.len() == 1)
+        .and_then(|register| u16::from_str_radix(register, 16).ok());
+}
+
+fn parse_number(text: &str) -> Option<i32> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i32::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i32::from_str_radix(binary, 2).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+
+    return Some(if negative { -value } else { value });
+}
+
+// Swaps a skip for the one that skips in the opposite case.
+fn negate(skip: u16) -> u16 {
+    return match skip & 0xF000 {
+        0x3000 => skip ^ 0x7000,
+        0x4000 => skip ^ 0x7000,
+        0x5000 => skip ^ 0xC000,
+        0x9000 => skip ^ 0xC000,
+        _ => skip ^ 0x003F,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile() {
+        let source = "
+            # Counts v0 up, drawing a dot once it reaches 10.
+            :const LIMIT 10
+            :alias counter v0
+
+            : main
+                clear
+                counter := 0
+                loop
+                    counter += 1
+                    if counter == LIMIT then v1 := 1
+                    while counter != 20
+                again
+                if v1 key begin
+                    i := dot
+                    sprite v0 v1 1
+                else
+                    draw
+                end
+                jump main
+
+            : draw
+                v2 -= 1
+                i := hex v2
+                return
+
+            : dot
+                0x80 -1
+        ";
+
+        assert_eq!(
+            Ok(vec![
+                0x12, 0x02, // jump main
+                0x00, 0xE0, // clear
+                0x60, 0x00, // counter := 0
+                0x70, 0x01, // counter += 1
+                0x40, 0x0A, // if counter == LIMIT then
+                0x61, 0x01, // v1 := 1
+                0x40, 0x14, // while counter != 20
+                0x12, 0x12, // (leaves the loop)
+                0x12, 0x06, // again
+                0xE1, 0x9E, // if v1 key begin
+                0x12, 0x1C, // (goes to the else)
+                0xA2, 0x26, // i := dot
+                0xD0, 0x11, // sprite v0 v1 1
+                0x12, 0x1E, // else
+                0x22, 0x20, // draw
+                0x12, 0x02, // jump main
+                0x72, 0xFF, // v2 -= 1
+                0xF2, 0x29, // i := hex v2
+                0x00, 0xEE, // return
+                0x80, 0xFF, // 0x80 -1
+            ]),
+            compile(source)
+        );
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        assert_eq!(
+            Err(String::from("The program has no main label.")),
+            compile("clear")
+        );
+        assert_eq!(
+            Err(String::from("Line 2 uses :macro, which isn't supported.")),
+            compile(": main\n:macro twice X { X X }")
+        );
+        assert_eq!(
+            Err(String::from(
+                "Line 1 refers to nowhere, which is never defined."
+            )),
+            compile(": main jump nowhere")
+        );
+        assert_eq!(
+            Err(String::from("Line 2 begins a block that is never ended.")),
+            compile(": main\nloop\nv0 += 1")
+        );
+        assert!(
+            compile(": main if v0 < 3 then clear")
+                .unwrap_err()
+                .contains("isn't supported")
+        );
+        assert!(
+            compile(": main v0 := 256")
+                .unwrap_err()
+                .starts_with("Line 1")
+        );
+        assert!(
+            compile(": main jump 0x1000")
+                .unwrap_err()
+                .starts_with("Line 1")
+        );
+    }
+}
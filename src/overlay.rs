@@ -0,0 +1,467 @@
+use crate::framebuffer::Framebuffer;
+use std::time::Duration;
+
+pub const TIMING_HISTORY_LENGTH: usize = 180;
+
+const GRAPH_HEIGHT: usize = 50;
+const GRAPH_BAR_WIDTH: usize = 2;
+const GRAPH_PIXELS_PER_MILLI: f64 = 1.5;
+const GRAPH_BACKGROUND_COLOR: u32 = 0x202020;
+const GRAPH_TARGET_LINE_COLOR: u32 = 0xFFFFFF;
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+const GLYPH_SPACING: usize = 1;
+const KEYPAD_CELL_SIZE: usize = 16;
+const KEYPAD_CELL_SPACING: usize = 2;
+const KEYPAD_TEXT_SCALE: usize = 2;
+const KEYPAD_BACKGROUND_COLOR: u32 = 0x101018;
+const KEYPAD_KEY_COLOR: u32 = 0x303048;
+const KEYPAD_PRESSED_KEY_COLOR: u32 = 0x33CC66;
+const KEYPAD_TEXT_COLOR: u32 = 0xFFFFFF;
+// The number of cells shown on each side of the hovered one.
+const MAGNIFIER_RADIUS: usize = 4;
+const MAGNIFIER_CELL_SIZE: usize = 12;
+const MAGNIFIER_PADDING: usize = 4;
+const MAGNIFIER_TEXT_SCALE: usize = 2;
+const MAGNIFIER_BACKGROUND_COLOR: u32 = 0x101018;
+const MAGNIFIER_HIGHLIGHT_COLOR: u32 = 0xFF3333;
+const MAGNIFIER_TEXT_COLOR: u32 = 0xFFFFFF;
+
+// The keys as laid out on the original COSMAC VIP keypad.
+const KEYPAD_LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+pub struct Canvas<'a> {
+    buffer: &'a mut [u32],
+    width: usize,
+    height: usize,
+}
+
+impl<'a> Canvas<'a> {
+    pub fn new(buffer: &'a mut [u32], width: usize, height: usize) -> Self {
+        Self {
+            buffer,
+            width,
+            height,
+        }
+    }
+
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: u32) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        let end_x = (x + width).min(self.width);
+        let end_y = (y + height).min(self.height);
+
+        for row in y..end_y {
+            let start_index = row * self.width;
+            self.buffer[start_index + x..start_index + end_x].fill(color);
+        }
+    }
+}
+
+pub fn get_text_size(text: &str, scale: usize) -> (usize, usize) {
+    let glyph_count = text.chars().count();
+    let width = (glyph_count * (GLYPH_WIDTH + GLYPH_SPACING)).saturating_sub(GLYPH_SPACING);
+    return (width * scale, GLYPH_HEIGHT * scale);
+}
+
+pub fn draw_text(
+    canvas: &mut Canvas<'_>,
+    x: usize,
+    y: usize,
+    text: &str,
+    scale: usize,
+    color: u32,
+) {
+    for (i, character) in text.chars().enumerate() {
+        let glyph_x = x + i * (GLYPH_WIDTH + GLYPH_SPACING) * scale;
+
+        for (row, bits) in get_glyph(character).iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits >> (GLYPH_WIDTH - 1 - column) & 1 == 0 {
+                    continue;
+                }
+
+                canvas.fill_rect(
+                    glyph_x + column * scale,
+                    y + row * scale,
+                    scale,
+                    scale,
+                    color,
+                );
+            }
+        }
+    }
+}
+
+// Each row of a glyph is 3 bits wide, with the most significant bit on the left.
+fn get_glyph(character: char) -> [u8; GLYPH_HEIGHT] {
+    return match character.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b011, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '<' => [0b001, 0b010, 0b100, 0b010, 0b001],
+        '>' => [0b100, 0b010, 0b001, 0b010, 0b100],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        '[' => [0b011, 0b010, 0b010, 0b010, 0b011],
+        ']' => [0b110, 0b010, 0b010, 0b010, 0b110],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '?' => [0b111, 0b001, 0b010, 0b000, 0b010],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '\'' | '"' => [0b010, 0b010, 0b000, 0b000, 0b000],
+        ' ' => [0b000; GLYPH_HEIGHT],
+        _ => [0b111; GLYPH_HEIGHT],
+    };
+}
+
+pub fn get_timing_graph_size() -> (usize, usize) {
+    return (TIMING_HISTORY_LENGTH * GRAPH_BAR_WIDTH, GRAPH_HEIGHT);
+}
+
+pub fn draw_timing_graph(
+    canvas: &mut Canvas<'_>,
+    x: usize,
+    y: usize,
+    samples: &[Duration],
+    target: Duration,
+    color: u32,
+) {
+    let (width, height) = get_timing_graph_size();
+    canvas.fill_rect(x, y, width, height, GRAPH_BACKGROUND_COLOR);
+
+    for (i, sample) in samples.iter().enumerate() {
+        let bar_height = get_bar_height(*sample);
+
+        canvas.fill_rect(
+            x + i * GRAPH_BAR_WIDTH,
+            y + height - bar_height,
+            GRAPH_BAR_WIDTH,
+            bar_height,
+            color,
+        );
+    }
+
+    let target_height = get_bar_height(target);
+    canvas.fill_rect(
+        x,
+        y + height - target_height,
+        width,
+        1,
+        GRAPH_TARGET_LINE_COLOR,
+    );
+}
+
+fn get_bar_height(sample: Duration) -> usize {
+    let height = (sample.as_secs_f64() * 1000.0 * GRAPH_PIXELS_PER_MILLI) as usize;
+    return height.min(GRAPH_HEIGHT);
+}
+
+pub fn get_sprite_size(rows: usize, pixel_size: usize) -> (usize, usize) {
+    return (8 * pixel_size, rows * pixel_size);
+}
+
+// Each byte is a row of 8 pixels, with the most significant bit on the left, as drawn by DXYN.
+// Only the pixels that are set are drawn, over whatever background is already there.
+pub fn draw_sprite(
+    canvas: &mut Canvas<'_>,
+    x: usize,
+    y: usize,
+    bytes: &[u8],
+    pixel_size: usize,
+    color: u32,
+) {
+    for (row, byte) in bytes.iter().enumerate() {
+        for column in 0..8 {
+            if byte >> (7 - column) & 1 == 0 {
+                continue;
+            }
+
+            canvas.fill_rect(
+                x + column * pixel_size,
+                y + row * pixel_size,
+                pixel_size,
+                pixel_size,
+                color,
+            );
+        }
+    }
+}
+
+pub fn get_keypad_size() -> (usize, usize) {
+    let size = KEYPAD_CELL_SIZE * 4 + KEYPAD_CELL_SPACING * 5;
+    return (size, size);
+}
+
+// Takes the held keys as a bitmask, with bit n set while key n is held.
+pub fn draw_keypad(canvas: &mut Canvas<'_>, x: usize, y: usize, held_keys: u16) {
+    let (width, height) = get_keypad_size();
+    canvas.fill_rect(x, y, width, height, KEYPAD_BACKGROUND_COLOR);
+
+    let (text_width, text_height) = get_text_size("0", KEYPAD_TEXT_SCALE);
+
+    for (row, keys) in KEYPAD_LAYOUT.iter().enumerate() {
+        for (column, key) in keys.iter().enumerate() {
+            let cell_x =
+                x + KEYPAD_CELL_SPACING + column * (KEYPAD_CELL_SIZE + KEYPAD_CELL_SPACING);
+            let cell_y = y + KEYPAD_CELL_SPACING + row * (KEYPAD_CELL_SIZE + KEYPAD_CELL_SPACING);
+
+            let color = match held_keys & 1 << key != 0 {
+                true => KEYPAD_PRESSED_KEY_COLOR,
+                false => KEYPAD_KEY_COLOR,
+            };
+
+            canvas.fill_rect(cell_x, cell_y, KEYPAD_CELL_SIZE, KEYPAD_CELL_SIZE, color);
+
+            draw_text(
+                canvas,
+                cell_x + (KEYPAD_CELL_SIZE - text_width) / 2,
+                cell_y + (KEYPAD_CELL_SIZE - text_height) / 2,
+                &format!("{key:X}"),
+                KEYPAD_TEXT_SCALE,
+                KEYPAD_TEXT_COLOR,
+            );
+        }
+    }
+}
+
+pub fn get_magnifier_size() -> (usize, usize) {
+    let grid_size = (MAGNIFIER_RADIUS * 2 + 1) * MAGNIFIER_CELL_SIZE;
+    let (_, text_height) = get_text_size("", MAGNIFIER_TEXT_SCALE);
+    return (
+        grid_size + MAGNIFIER_PADDING * 2,
+        grid_size + text_height + MAGNIFIER_PADDING * 3,
+    );
+}
+
+// Shows the cells around the hovered one, which is outlined, with its coordinates and state
+// underneath. Cells past the edges of the framebuffer are left as the background, so sprites
+// clipped at an edge can be told apart from ones wrapped around it.
+pub fn draw_magnifier(
+    canvas: &mut Canvas<'_>,
+    x: usize,
+    y: usize,
+    framebuffer: &Framebuffer,
+    cell: (usize, usize),
+    colors: (u32, u32),
+) {
+    let (width, height) = get_magnifier_size();
+    let (screen_width, screen_height) = framebuffer.get_size();
+    let (cell_x, cell_y) = cell;
+    let (active_color, inactive_color) = colors;
+
+    canvas.fill_rect(x, y, width, height, MAGNIFIER_BACKGROUND_COLOR);
+
+    for row in 0..=MAGNIFIER_RADIUS * 2 {
+        for column in 0..=MAGNIFIER_RADIUS * 2 {
+            let Some(shown_x) = (cell_x + column).checked_sub(MAGNIFIER_RADIUS) else {
+                continue;
+            };
+
+            let Some(shown_y) = (cell_y + row).checked_sub(MAGNIFIER_RADIUS) else {
+                continue;
+            };
+
+            if shown_x >= screen_width || shown_y >= screen_height {
+                continue;
+            }
+
+            let color = match framebuffer.get(shown_x, shown_y) {
+                true => active_color,
+                false => inactive_color,
+            };
+
+            // A pixel's gap between cells keeps them countable.
+            canvas.fill_rect(
+                x + MAGNIFIER_PADDING + column * MAGNIFIER_CELL_SIZE,
+                y + MAGNIFIER_PADDING + row * MAGNIFIER_CELL_SIZE,
+                MAGNIFIER_CELL_SIZE - 1,
+                MAGNIFIER_CELL_SIZE - 1,
+                color,
+            );
+        }
+    }
+
+    let center_x = x + MAGNIFIER_PADDING + MAGNIFIER_RADIUS * MAGNIFIER_CELL_SIZE;
+    let center_y = y + MAGNIFIER_PADDING + MAGNIFIER_RADIUS * MAGNIFIER_CELL_SIZE;
+
+    for (outline_x, outline_y, outline_width, outline_height) in [
+        (center_x - 1, center_y - 1, MAGNIFIER_CELL_SIZE + 1, 1),
+        (center_x - 1, center_y - 1, 1, MAGNIFIER_CELL_SIZE + 1),
+        (
+            center_x - 1,
+            center_y + MAGNIFIER_CELL_SIZE - 1,
+            MAGNIFIER_CELL_SIZE + 1,
+            1,
+        ),
+        (
+            center_x + MAGNIFIER_CELL_SIZE - 1,
+            center_y - 1,
+            1,
+            MAGNIFIER_CELL_SIZE + 1,
+        ),
+    ] {
+        canvas.fill_rect(
+            outline_x,
+            outline_y,
+            outline_width,
+            outline_height,
+            MAGNIFIER_HIGHLIGHT_COLOR,
+        );
+    }
+
+    let state = match framebuffer.get(cell_x, cell_y) {
+        true => "on",
+        false => "off",
+    };
+
+    draw_text(
+        canvas,
+        x + MAGNIFIER_PADDING,
+        y + height - MAGNIFIER_PADDING - GLYPH_HEIGHT * MAGNIFIER_TEXT_SCALE,
+        &format!("x {cell_x} y {cell_y} {state}"),
+        MAGNIFIER_TEXT_SCALE,
+        MAGNIFIER_TEXT_COLOR,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_text() {
+        let mut buffer = vec![0; 16 * 8];
+        let mut canvas = Canvas::new(&mut buffer, 16, 8);
+
+        assert_eq!((7, 5), get_text_size("1-", 1));
+        assert_eq!((14, 10), get_text_size("1-", 2));
+
+        draw_text(&mut canvas, 0, 0, "1-", 1, 0xFFFFFF);
+
+        assert_eq!(0xFFFFFF, buffer[1]);
+        assert_eq!(0, buffer[0]);
+        assert_eq!(0xFFFFFF, buffer[2 * 16 + 4]);
+        assert_eq!(0xFFFFFF, buffer[2 * 16 + 6]);
+        assert_eq!(0, buffer[2 * 16 + 7]);
+    }
+
+    #[test]
+    fn test_draw_sprite() {
+        let (width, height) = get_sprite_size(2, 2);
+        let mut buffer = vec![0; width * height];
+        let mut canvas = Canvas::new(&mut buffer, width, height);
+
+        draw_sprite(&mut canvas, 0, 0, &[0b1000_0001, 0b0100_0000], 2, 0xFFFFFF);
+
+        assert_eq!((16, 4), (width, height));
+        assert_eq!(0xFFFFFF, buffer[width + 1]);
+        assert_eq!(0xFFFFFF, buffer[width + 15]);
+        assert_eq!(0, buffer[width + 2]);
+        assert_eq!(0xFFFFFF, buffer[3 * width + 2]);
+        assert_eq!(0, buffer[3 * width]);
+    }
+
+    #[test]
+    fn test_draw_keypad() {
+        let (width, height) = get_keypad_size();
+        let mut buffer = vec![0; width * height];
+        let mut canvas = Canvas::new(&mut buffer, width, height);
+
+        // Key 1 is in the top left corner, and key F in the bottom right.
+        draw_keypad(&mut canvas, 0, 0, 1 << 0x1);
+
+        let top_left = KEYPAD_CELL_SPACING * width + KEYPAD_CELL_SPACING;
+        let bottom_right =
+            (height - KEYPAD_CELL_SPACING - 1) * width + width - KEYPAD_CELL_SPACING - 1;
+
+        assert_eq!(KEYPAD_BACKGROUND_COLOR, buffer[0]);
+        assert_eq!(KEYPAD_PRESSED_KEY_COLOR, buffer[top_left]);
+        assert_eq!(KEYPAD_KEY_COLOR, buffer[bottom_right]);
+    }
+
+    #[test]
+    fn test_draw_magnifier() {
+        let (width, height) = get_magnifier_size();
+        let mut buffer = vec![0; width * height];
+        let mut canvas = Canvas::new(&mut buffer, width, height);
+
+        // A 4 by 2 display with only (1, 0) on, hovered at its top left corner.
+        let mut framebuffer = Framebuffer::new(4, 2);
+        framebuffer.load_pixels(&[false, true, false, false, false, false, false, false]);
+        draw_magnifier(
+            &mut canvas,
+            0,
+            0,
+            &framebuffer,
+            (0, 0),
+            (0xAAAAAA, 0x555555),
+        );
+
+        let cell_color = |column: usize, row: usize| {
+            let cell_x = MAGNIFIER_PADDING + column * MAGNIFIER_CELL_SIZE + 1;
+            let cell_y = MAGNIFIER_PADDING + row * MAGNIFIER_CELL_SIZE + 1;
+            return buffer[cell_y * width + cell_x];
+        };
+
+        assert_eq!(0x555555, cell_color(MAGNIFIER_RADIUS, MAGNIFIER_RADIUS));
+        assert_eq!(0xAAAAAA, cell_color(MAGNIFIER_RADIUS + 1, MAGNIFIER_RADIUS));
+        // Past the edges, nothing wraps around.
+        assert_eq!(
+            MAGNIFIER_BACKGROUND_COLOR,
+            cell_color(MAGNIFIER_RADIUS - 1, MAGNIFIER_RADIUS)
+        );
+        assert_eq!(
+            MAGNIFIER_BACKGROUND_COLOR,
+            cell_color(MAGNIFIER_RADIUS, MAGNIFIER_RADIUS + 2)
+        );
+    }
+}
@@ -0,0 +1,48 @@
+//! Spans around the emulator's own hot spots, such as the CPU's batches of instructions, waits
+//! for locks and render passes, to see where host CPU time goes at high speeds.
+//!
+//! They are only compiled in with the "profiling" feature, so cost nothing otherwise. Any tracing
+//! subscriber can record them, such as tracing-tracy, or the flame graph capture below.
+
+#[cfg(feature = "profiling")]
+pub type Span = tracing::span::EnteredSpan;
+
+#[cfg(not(feature = "profiling"))]
+pub struct Span;
+
+/// Enters a span with the given name, which lasts until the returned guard is dropped.
+macro_rules! span {
+    ($name:literal) => {{
+        #[cfg(feature = "profiling")]
+        let span = ::tracing::info_span!($name).entered();
+        #[cfg(not(feature = "profiling"))]
+        let span = $crate::profiling::Span;
+        span
+    }};
+}
+
+pub(crate) use span;
+
+/// Writes every span to the file in the folded stack format, for inferno or flamegraph.pl to turn
+/// into a flame graph. The file is only complete once the returned guard is dropped.
+#[cfg(feature = "profiling")]
+pub fn start_flame_capture(
+    path: &str,
+) -> Option<tracing_flame::FlushGuard<std::io::BufWriter<std::fs::File>>> {
+    use tracing_subscriber::prelude::*;
+
+    let (flame_layer, guard) = match tracing_flame::FlameLayer::with_file(path) {
+        Ok(flame) => flame,
+        Err(e) => {
+            eprintln!("Error: Could not create {path} ({e}).");
+            return None;
+        }
+    };
+
+    if let Err(e) = tracing_subscriber::registry().with(flame_layer).try_init() {
+        eprintln!("Error: Could not start capturing spans ({e}).");
+        return None;
+    }
+
+    return Some(guard);
+}
@@ -1,4 +1,7 @@
-use crate::config::RAMConfig;
+use crate::config::{HeapOverflowPolicy, RAMConfig, StackMode};
+use crate::emulib;
+use crate::lifecycle::Lifecycle;
+use crate::observer::{EmulatorError, Observers};
 use std::fs;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
@@ -7,91 +10,179 @@ pub const PROGRAM_START_ADDRESS: u16 = 0x200;
 pub const HEAP_SIZE: usize = 0x1000;
 
 pub struct RAM {
-    active: Arc<AtomicBool>,
+    lifecycle: Arc<Lifecycle>,
+    observers: Arc<Observers>,
     config: RAMConfig,
     heap: Mutex<[u8; HEAP_SIZE]>,
     stack: Mutex<Vec<u16>>,
     stack_ptr: AtomicUsize,
+    warned_about_stack_depth: AtomicBool,
+    program_hash: Mutex<Option<String>>,
 }
 
 impl RAM {
-    pub fn try_new(active: Arc<AtomicBool>, config: RAMConfig) -> Option<Arc<Self>> {
-        if config.stack_size == 0 {
-            eprintln!("Error: The stack size must be greater than zero.");
-            active.store(false, Ordering::Relaxed);
-            return None;
-        }
-
-        if config.font_starting_address > 0xFB0 {
-            eprintln!("Error: The starting address of the font data cannot be greater than 0xFB0.");
-            active.store(false, Ordering::Relaxed);
-            return None;
-        }
-
+    pub fn new(
+        lifecycle: Arc<Lifecycle>,
+        observers: Arc<Observers>,
+        config: RAMConfig,
+    ) -> Arc<Self> {
         let this = Self {
-            active,
+            lifecycle,
+            observers,
             heap: Mutex::new([0; HEAP_SIZE]),
             stack: Mutex::new(vec![0; config.stack_size]),
             stack_ptr: AtomicUsize::new(0),
+            warned_about_stack_depth: AtomicBool::new(false),
+            program_hash: Mutex::new(None),
             config,
         };
 
-        let font_start_addr = this.config.font_starting_address as usize;
-        this.heap.lock().unwrap()[font_start_addr..font_start_addr + 80]
-            .copy_from_slice(&this.config.font_data);
+        this.reset();
+
+        return Arc::new(this);
+    }
+
+    pub fn reset(&self) {
+        let mut heap = self.heap.lock().unwrap();
+        heap.fill(0);
+
+        let font_start_addr = self.config.font_starting_address as usize;
+        heap[font_start_addr..font_start_addr + 80].copy_from_slice(&self.config.font_data);
+
+        let big_font_start_addr = self.config.big_font_starting_address as usize;
+        heap[big_font_start_addr..big_font_start_addr + 160]
+            .copy_from_slice(&self.config.big_font_data);
 
-        return Some(Arc::new(this));
+        // A stack that has grown goes back to its starting size.
+        let mut stack = self.stack.lock().unwrap();
+        stack.clear();
+        stack.resize(self.config.stack_size, 0);
+        self.stack_ptr.store(0, Ordering::Relaxed);
+        self.warned_about_stack_depth
+            .store(false, Ordering::Relaxed);
     }
 
     #[cfg(test)]
-    pub fn new_default_conservative(active: Arc<AtomicBool>) -> Arc<Self> {
-        Self::try_new(
-            active,
+    pub fn new_default_conservative(lifecycle: Arc<Lifecycle>) -> Arc<Self> {
+        Self::new(
+            lifecycle,
+            Observers::new(),
             RAMConfig {
                 stack_size: 16,
+                stack_mode: StackMode::Fixed,
+                stack_warning_depth: None,
                 allow_stack_overflow: false,
-                allow_heap_overflow: false,
+                heap_overflow_policy: HeapOverflowPolicy::Halt,
                 font_starting_address: 0,
                 font_data: [0x67; 80],
+                big_font_starting_address: 0x50,
+                big_font_data: [0x89; 160],
             },
         )
-        .unwrap()
     }
 
     #[cfg(test)]
-    pub fn new_default_liberal(active: Arc<AtomicBool>) -> Arc<Self> {
-        Self::try_new(
-            active,
+    pub fn new_default_liberal(lifecycle: Arc<Lifecycle>) -> Arc<Self> {
+        Self::new(
+            lifecycle,
+            Observers::new(),
             RAMConfig {
                 stack_size: 16,
+                stack_mode: StackMode::Fixed,
+                stack_warning_depth: None,
                 allow_stack_overflow: true,
-                allow_heap_overflow: true,
+                heap_overflow_policy: HeapOverflowPolicy::Wrap,
                 font_starting_address: 0,
                 font_data: [0x67; 80],
+                big_font_starting_address: 0x50,
+                big_font_data: [0x89; 160],
             },
         )
-        .unwrap()
     }
 
-    pub fn load_program(&self, program_path: &String) -> bool {
-        let Ok(program) = fs::read(&program_path) else {
-            eprintln!("Error: Could not find valid program at {program_path}.");
-            self.active.store(false, Ordering::Relaxed);
-            return false;
+    // Failing to load a program doesn't stop the emulator, so the user can pick another one.
+    pub fn load_program(&self, program_path: &str) -> Result<(), EmulatorError> {
+        let Ok(program) = fs::read(program_path) else {
+            return Err(EmulatorError::ProgramNotFound(program_path.to_string()));
         };
 
+        return self.load_program_bytes(&program, program_path);
+    }
+
+    // Memory is reset first, unless the program doesn't fit, so nothing is left from the last one.
+    pub fn load_program_bytes(
+        &self,
+        program: &[u8],
+        program_name: &str,
+    ) -> Result<(), EmulatorError> {
         let start_index = PROGRAM_START_ADDRESS as usize;
 
         if start_index + program.len() > HEAP_SIZE {
-            eprintln!("Error: Program {program_path} is too large to fit in the heap.");
-            self.active.store(false, Ordering::Relaxed);
-            return false;
+            return Err(EmulatorError::ProgramTooLarge(program_name.to_string()));
         }
 
+        self.reset();
         self.heap.lock().unwrap()[start_index..start_index + program.len()]
-            .copy_from_slice(&program);
+            .copy_from_slice(program);
 
-        return true;
+        *self.program_hash.lock().unwrap() = Some(emulib::get_sha1(program));
+
+        return Ok(());
+    }
+
+    pub fn get_config(&self) -> &RAMConfig {
+        return &self.config;
+    }
+
+    pub fn get_program_hash(&self) -> Option<String> {
+        return self.program_hash.lock().unwrap().clone();
+    }
+
+    pub fn get_heap(&self) -> Vec<u8> {
+        return self.heap.lock().unwrap().to_vec();
+    }
+
+    pub fn get_stack(&self) -> (Vec<u16>, usize) {
+        let stack = self.stack.lock().unwrap();
+        return (stack.clone(), self.stack_ptr.load(Ordering::Relaxed));
+    }
+
+    // How many calls deep the program is, for the debugger to tell when a subroutine returns.
+    pub fn get_stack_depth(&self) -> usize {
+        return self.stack_ptr.load(Ordering::Relaxed);
+    }
+
+    // A growing stack has no limit.
+    pub fn get_stack_limit(&self) -> Option<usize> {
+        return match self.config.stack_mode {
+            StackMode::Fixed => Some(self.config.stack_size),
+            StackMode::Growing => None,
+        };
+    }
+
+    // The stack is padded to the configured size, and a fixed one is cut down to it, so the
+    // caller checks that the stack pointer fits first.
+    pub fn restore(
+        &self,
+        heap: &[u8],
+        stack_entries: &[u16],
+        stack_ptr: usize,
+        program_hash: Option<String>,
+    ) {
+        self.heap.lock().unwrap().copy_from_slice(heap);
+
+        let stack_entries = match self.config.stack_mode {
+            StackMode::Fixed => &stack_entries[..stack_entries.len().min(self.config.stack_size)],
+            StackMode::Growing => stack_entries,
+        };
+
+        let mut stack = self.stack.lock().unwrap();
+        stack.clear();
+        stack.resize(self.config.stack_size.max(stack_entries.len()), 0);
+        stack[..stack_entries.len()].copy_from_slice(stack_entries);
+        self.stack_ptr.store(stack_ptr, Ordering::Relaxed);
+
+        *self.program_hash.lock().unwrap() = program_hash;
     }
 
     pub fn get_hex_digit_address(&self, digit: u8) -> u16 {
@@ -102,114 +193,95 @@ impl RAM {
         return self.config.font_starting_address + ((digit as u16) * 5);
     }
 
-    #[cfg(test)]
-    pub fn write_byte(&self, val: u8, addr: u16) -> bool {
-        let mut addr = addr as usize;
+    pub fn get_big_hex_digit_address(&self, digit: u8) -> u16 {
+        if cfg!(debug_assertions) && digit > 0xF {
+            panic!("Error: Should not be possible to query for two-character hex digits.");
+        }
 
-        if addr >= HEAP_SIZE {
-            if !self.config.allow_heap_overflow {
-                eprintln!("Error: Attempting to write to non-existent memory.");
-                self.active.store(false, Ordering::Relaxed);
-                return false;
-            }
+        return self.config.big_font_starting_address + ((digit as u16) * 10);
+    }
 
-            addr %= 0x1000;
+    // Halts with where the access was, if it goes past the end of the heap and that isn't allowed.
+    fn check_heap_access(&self, addr: u16, count: usize, error: EmulatorError) -> bool {
+        if addr as usize + count > HEAP_SIZE
+            && self.config.heap_overflow_policy == HeapOverflowPolicy::Halt
+        {
+            self.observers.halt(&self.lifecycle, error);
+            return false;
         }
 
-        let mut heap = self.heap.lock().unwrap();
-        heap[addr as usize] = val;
         return true;
     }
 
-    pub fn write_bytes(&self, vals: &Vec<u8>, addr: u16) -> bool {
-        let mut addr = addr as usize;
-        let count = vals.len();
-
-        if addr >= HEAP_SIZE {
-            if !self.config.allow_heap_overflow {
-                eprintln!("Error: Heap overflowed while writing.");
-                self.active.store(false, Ordering::Relaxed);
-                return false;
-            }
-
-            addr %= 0x1000;
-        }
-
-        if addr + count > HEAP_SIZE {
-            if !self.config.allow_heap_overflow {
-                eprintln!("Error: Heap overflowed while writing.");
-                self.active.store(false, Ordering::Relaxed);
-                return false;
-            }
+    // None for addresses past the end of the heap that are ignored.
+    fn get_heap_index(&self, addr: usize) -> Option<usize> {
+        return match self.config.heap_overflow_policy {
+            HeapOverflowPolicy::Wrap => Some(addr % HEAP_SIZE),
+            _ => Some(addr).filter(|addr| *addr < HEAP_SIZE),
+        };
+    }
 
-            let count_pre_split = HEAP_SIZE - addr;
-            let count_post_split = count - count_pre_split;
+    #[cfg(test)]
+    pub fn write_byte(&self, val: u8, addr: u16) -> bool {
+        return self.write_bytes(&[val], addr);
+    }
 
-            let mut heap = self.heap.lock().unwrap();
-            heap[addr..].copy_from_slice(&vals[..count_pre_split]);
-            heap[..count_post_split].copy_from_slice(&vals[count_pre_split..]);
+    pub fn write_bytes(&self, vals: &[u8], addr: u16) -> bool {
+        let error = EmulatorError::HeapOverflowWhileWriting {
+            address: addr,
+            count: vals.len(),
+        };
 
-            return true;
+        if !self.check_heap_access(addr, vals.len(), error) {
+            return false;
         }
 
         let mut heap = self.heap.lock().unwrap();
-        heap[addr..addr + count].copy_from_slice(&vals);
+
+        for (i, val) in vals.iter().enumerate() {
+            if let Some(index) = self.get_heap_index(addr as usize + i) {
+                heap[index] = *val;
+            }
+        }
 
         return true;
     }
 
     #[cfg(test)]
     pub fn read_byte(&self, addr: u16) -> Option<u8> {
-        let mut addr = addr as usize;
-
-        if addr >= HEAP_SIZE {
-            if !self.config.allow_heap_overflow {
-                eprintln!("Error: Attempting to read from non-existent memory.");
-                self.active.store(false, Ordering::Relaxed);
-                return None;
-            }
-
-            addr %= 0x1000;
-        }
-
-        let heap = self.heap.lock().unwrap();
-        return Some(heap[addr]);
+        return self.read_bytes(addr, 1).map(|bytes| bytes[0]);
     }
 
+    // Ignored addresses read as 0.
     pub fn read_bytes(&self, addr: u16, count: u16) -> Option<Vec<u8>> {
-        let mut addr = addr as usize;
-        let count = count as usize;
-
-        if addr >= HEAP_SIZE {
-            if !self.config.allow_heap_overflow {
-                eprintln!("Error: Heap overflowed while writing.");
-                self.active.store(false, Ordering::Relaxed);
-                return None;
-            }
+        let error = EmulatorError::HeapOverflowWhileReading {
+            address: addr,
+            count: count as usize,
+        };
 
-            addr %= 0x1000;
+        if !self.check_heap_access(addr, count as usize, error) {
+            return None;
         }
 
-        if addr + count > HEAP_SIZE {
-            if !self.config.allow_heap_overflow {
-                eprintln!("Error: Heap overflowed while reading.");
-                self.active.store(false, Ordering::Relaxed);
-                return None;
-            }
-
-            let count_pre_split = HEAP_SIZE - addr;
-            let count_post_split = count - count_pre_split;
-
-            let mut bytes: Vec<u8> = Vec::with_capacity(count);
-            let heap = self.heap.lock().unwrap();
-            bytes.extend_from_slice(&heap[addr..]);
-            bytes.extend_from_slice(&heap[..count_post_split]);
+        let heap = self.heap.lock().unwrap();
 
-            return Some(bytes);
-        }
+        return Some(
+            (0..count as usize)
+                .map(|i| match self.get_heap_index(addr as usize + i) {
+                    Some(index) => heap[index],
+                    None => 0,
+                })
+                .collect(),
+        );
+    }
 
+    // For inspecting memory from outside the program, so it always wraps and never halts.
+    pub fn peek_bytes(&self, addr: u16, count: u16) -> Vec<u8> {
         let heap = self.heap.lock().unwrap();
-        return Some(heap[addr..addr + count].to_vec());
+
+        return (0..count as usize)
+            .map(|i| heap[(addr as usize + i) % HEAP_SIZE])
+            .collect();
     }
 
     pub fn push_to_stack(&self, val: u16) -> bool {
@@ -217,10 +289,14 @@ impl RAM {
 
         let stack_ptr = self.stack_ptr.load(Ordering::Relaxed);
 
-        if stack_ptr == self.config.stack_size {
+        if stack_ptr == stack.len() && self.config.stack_mode == StackMode::Growing {
+            stack.push(0);
+        }
+
+        if stack_ptr == stack.len() {
             if !self.config.allow_stack_overflow {
-                eprintln!("Error: Stack overflowed while pushing.");
-                self.active.store(false, Ordering::Relaxed);
+                self.observers
+                    .halt(&self.lifecycle, EmulatorError::StackOverflowWhilePushing);
                 return false;
             }
 
@@ -233,6 +309,16 @@ impl RAM {
         stack[stack_ptr] = val;
         self.stack_ptr.store(stack_ptr + 1, Ordering::Relaxed);
 
+        if let Some(warning_depth) = self.config.stack_warning_depth
+            && stack_ptr + 1 >= warning_depth
+            && !self.warned_about_stack_depth.swap(true, Ordering::Relaxed)
+        {
+            eprintln!(
+                "Warning: The stack has reached {} entries deep, which may be runaway recursion.",
+                stack_ptr + 1
+            );
+        }
+
         return true;
     }
 
@@ -243,14 +329,13 @@ impl RAM {
 
         if stack_ptr == 0 {
             if !self.config.allow_stack_overflow {
-                eprintln!("Error: Stack overflowed while popping.");
-                self.active.store(false, Ordering::Relaxed);
+                self.observers
+                    .halt(&self.lifecycle, EmulatorError::StackOverflowWhilePopping);
                 return None;
             }
 
-            self.stack_ptr
-                .store(self.config.stack_size - 1, Ordering::Relaxed);
-            return Some(stack[self.config.stack_size - 1]);
+            self.stack_ptr.store(stack.len() - 1, Ordering::Relaxed);
+            return Some(stack[stack.len() - 1]);
         }
 
         self.stack_ptr.store(stack_ptr - 1, Ordering::Relaxed);
@@ -268,14 +353,14 @@ mod tests {
         Liberal,
     }
 
-    fn create_objects(cfg_type: ConfigType) -> (Arc<RAM>, Arc<AtomicBool>) {
-        let active = Arc::new(AtomicBool::new(true));
+    fn create_objects(cfg_type: ConfigType) -> (Arc<RAM>, Arc<Lifecycle>) {
+        let lifecycle = Lifecycle::new();
         let ram = match cfg_type {
-            ConfigType::Conservative => RAM::new_default_conservative(active.clone()),
-            ConfigType::Liberal => RAM::new_default_liberal(active.clone()),
+            ConfigType::Conservative => RAM::new_default_conservative(lifecycle.clone()),
+            ConfigType::Liberal => RAM::new_default_liberal(lifecycle.clone()),
         };
 
-        return (ram, active);
+        return (ram, lifecycle);
     }
 
     #[test]
@@ -284,9 +369,9 @@ mod tests {
         let program_path = String::from("test_load_program_to_memory_temp_file.txt");
         fs::write(&program_path, &program).unwrap();
 
-        let (ram, active) = create_objects(ConfigType::Conservative);
+        let (ram, lifecycle) = create_objects(ConfigType::Conservative);
 
-        assert!(ram.load_program(&program_path));
+        assert!(ram.load_program(&program_path).is_ok());
 
         fs::remove_file(program_path).unwrap();
 
@@ -294,24 +379,60 @@ mod tests {
         let actual_bytes = ram.read_bytes(PROGRAM_START_ADDRESS - 1, 7).unwrap();
 
         assert_eq!(ideal_bytes, actual_bytes);
-        assert!(active.load(Ordering::Relaxed));
+        assert_eq!(
+            Some(String::from("f7ff9e8b7bb2e09b70935a5d785e0cc5d9d0abf0")),
+            ram.get_program_hash()
+        );
+        assert!(lifecycle.is_running());
+    }
+
+    #[test]
+    fn test_failed_program_load_keeps_running() {
+        let (ram, lifecycle) = create_objects(ConfigType::Conservative);
+
+        assert_eq!(
+            Err(EmulatorError::ProgramNotFound(String::from("missing.ch8"))),
+            ram.load_program("missing.ch8")
+        );
+
+        assert_eq!(
+            Err(EmulatorError::ProgramTooLarge(String::from("large.ch8"))),
+            ram.load_program_bytes(&[0; HEAP_SIZE], "large.ch8")
+        );
+
+        assert!(lifecycle.is_running());
     }
 
     #[test]
     fn test_get_hex_digit_address() {
-        let (ram, active) = create_objects(ConfigType::Conservative);
+        let (ram, lifecycle) = create_objects(ConfigType::Conservative);
 
         let ideal_byte = ram.config.font_data[50];
 
         let actual_byte = ram.read_byte(ram.get_hex_digit_address(0xA)).unwrap();
 
         assert_eq!(ideal_byte, actual_byte);
-        assert!(active.load(Ordering::Relaxed));
+        assert!(lifecycle.is_running());
+    }
+
+    #[test]
+    fn test_get_big_hex_digit_address() {
+        let (ram, lifecycle) = create_objects(ConfigType::Conservative);
+
+        let ideal_bytes = ram.config.big_font_data[100..110].to_vec();
+
+        let actual_bytes = ram
+            .read_bytes(ram.get_big_hex_digit_address(0xA), 10)
+            .unwrap();
+
+        assert_eq!(ideal_bytes, actual_bytes);
+        assert_eq!(ram.config.font_data[..], ram.read_bytes(0, 80).unwrap());
+        assert!(lifecycle.is_running());
     }
 
     #[test]
     fn test_read_write_byte_to_memory() {
-        let (ram, active) = create_objects(ConfigType::Conservative);
+        let (ram, lifecycle) = create_objects(ConfigType::Conservative);
 
         let ideal_byte = 0x56;
         let addr = 0x789;
@@ -320,12 +441,12 @@ mod tests {
         let actual_byte = ram.read_byte(addr).unwrap();
 
         assert_eq!(ideal_byte, actual_byte);
-        assert!(active.load(Ordering::Relaxed));
+        assert!(lifecycle.is_running());
     }
 
     #[test]
     fn test_read_bytes_from_memory() {
-        let (ram, active) = create_objects(ConfigType::Conservative);
+        let (ram, lifecycle) = create_objects(ConfigType::Conservative);
 
         let ideal_bytes = vec![0x48, 0x65, 0x6c, 0x6c, 0x6f];
         let start_addr: u16 = 0x789;
@@ -337,55 +458,55 @@ mod tests {
         let actual_bytes = ram.read_bytes(start_addr, 5).unwrap();
 
         assert_eq!(ideal_bytes, actual_bytes);
-        assert!(active.load(Ordering::Relaxed));
+        assert!(lifecycle.is_running());
     }
 
     #[test]
     fn test_read_memory_with_successful_overflow() {
-        let (ram, active) = create_objects(ConfigType::Liberal);
+        let (ram, lifecycle) = create_objects(ConfigType::Liberal);
 
         let ideal_bytes = vec![0x48, 0x65, 0x6c, 0x6c, 0x6f];
 
-        assert!(ram.write_bytes(&ideal_bytes[..3].to_vec(), 0xFFD));
-        assert!(ram.write_bytes(&ideal_bytes[3..].to_vec(), 0x000));
+        assert!(ram.write_bytes(&ideal_bytes[..3], 0xFFD));
+        assert!(ram.write_bytes(&ideal_bytes[3..], 0x000));
 
         let actual_bytes = ram.read_bytes(0xFFD, 5).unwrap();
 
         assert_eq!(ideal_bytes, actual_bytes);
-        assert!(active.load(Ordering::Relaxed));
+        assert!(lifecycle.is_running());
     }
 
     #[test]
     fn test_read_memory_with_failed_overflow() {
-        let (ram, active) = create_objects(ConfigType::Conservative);
+        let (ram, lifecycle) = create_objects(ConfigType::Conservative);
 
-        let ideal_bytes = vec![0x48, 0x65, 0x6c, 0x6c, 0x6f];
+        let ideal_bytes = [0x48, 0x65, 0x6c, 0x6c, 0x6f];
 
-        assert!(ram.write_bytes(&ideal_bytes[..3].to_vec(), 0xFFD));
-        assert!(ram.write_bytes(&ideal_bytes[3..].to_vec(), 0x000));
+        assert!(ram.write_bytes(&ideal_bytes[..3], 0xFFD));
+        assert!(ram.write_bytes(&ideal_bytes[3..], 0x000));
 
         assert!(ram.read_bytes(0xFFD, 5).is_none());
-        assert!(!active.load(Ordering::Relaxed));
+        assert!(!lifecycle.is_running());
     }
 
     #[test]
     fn test_read_unaddressable_memory_with_successful_overflow() {
-        let (ram, active) = create_objects(ConfigType::Liberal);
+        let (ram, lifecycle) = create_objects(ConfigType::Liberal);
 
         let ideal_bytes = vec![0x48, 0x65, 0x6c, 0x6c, 0x6f];
 
-        assert!(ram.write_bytes(&ideal_bytes[..3].to_vec(), 0xFFD));
-        assert!(ram.write_bytes(&ideal_bytes[3..].to_vec(), 0x000));
+        assert!(ram.write_bytes(&ideal_bytes[..3], 0xFFD));
+        assert!(ram.write_bytes(&ideal_bytes[3..], 0x000));
 
         let actual_bytes = ram.read_bytes(0xFFD, 5).unwrap();
 
         assert_eq!(ideal_bytes, actual_bytes);
-        assert!(active.load(Ordering::Relaxed));
+        assert!(lifecycle.is_running());
     }
 
     #[test]
     fn test_write_bytes_to_memory() {
-        let (ram, active) = create_objects(ConfigType::Conservative);
+        let (ram, lifecycle) = create_objects(ConfigType::Conservative);
 
         let ideal_bytes = vec![0x48, 0x65, 0x6c, 0x6c, 0x6f];
         let start_addr: u16 = 0x789;
@@ -400,12 +521,12 @@ mod tests {
         }
 
         assert_eq!(ideal_bytes, actual_bytes);
-        assert!(active.load(Ordering::Relaxed));
+        assert!(lifecycle.is_running());
     }
 
     #[test]
     fn test_write_memory_with_successful_overflow() {
-        let (ram, active) = create_objects(ConfigType::Liberal);
+        let (ram, lifecycle) = create_objects(ConfigType::Liberal);
 
         let ideal_bytes = vec![0x48, 0x65, 0x6c, 0x6c, 0x6f];
 
@@ -416,22 +537,44 @@ mod tests {
         actual_bytes.extend(ram.read_bytes(0x000, 2).unwrap());
 
         assert_eq!(ideal_bytes, actual_bytes);
-        assert!(active.load(Ordering::Relaxed));
+        assert!(lifecycle.is_running());
     }
 
     #[test]
     fn test_write_memory_with_failed_overflow() {
-        let (ram, active) = create_objects(ConfigType::Conservative);
+        let (ram, lifecycle) = create_objects(ConfigType::Conservative);
 
         let ideal_bytes = vec![0x48, 0x65, 0x6c, 0x6c, 0x6f];
 
         assert!(!ram.write_bytes(&ideal_bytes, 0xFFD));
-        assert!(!active.load(Ordering::Relaxed));
+        assert!(!lifecycle.is_running());
+    }
+
+    #[test]
+    fn test_ignored_heap_overflow() {
+        let (ram, lifecycle) = create_objects(ConfigType::Conservative);
+        let ram = RAM::new(
+            lifecycle.clone(),
+            Observers::new(),
+            RAMConfig {
+                heap_overflow_policy: HeapOverflowPolicy::Ignore,
+                ..ram.config.clone()
+            },
+        );
+
+        assert!(ram.write_bytes(&[0x48, 0x65, 0x6c, 0x6c, 0x6f], 0xFFD));
+
+        assert_eq!(
+            vec![0x48, 0x65, 0x6c, 0x00, 0x00],
+            ram.read_bytes(0xFFD, 5).unwrap()
+        );
+        assert_eq!(ram.config.font_data[..2], ram.read_bytes(0x000, 2).unwrap());
+        assert!(lifecycle.is_running());
     }
 
     #[test]
     fn test_stack_push_pop() {
-        let (ram, active) = create_objects(ConfigType::Conservative);
+        let (ram, lifecycle) = create_objects(ConfigType::Conservative);
 
         for i in 1..=5 {
             assert!(ram.push_to_stack(i));
@@ -441,12 +584,12 @@ mod tests {
             assert_eq!(i, ram.pop_from_stack().unwrap());
         }
 
-        assert!(active.load(Ordering::Relaxed));
+        assert!(lifecycle.is_running());
     }
 
     #[test]
     fn test_stack_push_pop_with_successful_overflow() {
-        let (ram, active) = create_objects(ConfigType::Liberal);
+        let (ram, lifecycle) = create_objects(ConfigType::Liberal);
 
         for i in 1..=20 {
             assert!(ram.push_to_stack(i));
@@ -457,26 +600,55 @@ mod tests {
         }
 
         assert_eq!(20, ram.pop_from_stack().unwrap());
-        assert!(active.load(Ordering::Relaxed));
+        assert!(lifecycle.is_running());
     }
 
     #[test]
     fn test_stack_push_with_failed_overflow() {
-        let (ram, active) = create_objects(ConfigType::Conservative);
+        let (ram, lifecycle) = create_objects(ConfigType::Conservative);
 
         for i in 1..=16 {
             assert!(ram.push_to_stack(i));
         }
 
         assert!(!ram.push_to_stack(17));
-        assert!(!active.load(Ordering::Relaxed));
+        assert!(!lifecycle.is_running());
+    }
+
+    #[test]
+    fn test_growing_stack() {
+        let (ram, lifecycle) = create_objects(ConfigType::Conservative);
+        let ram = RAM::new(
+            lifecycle.clone(),
+            Observers::new(),
+            RAMConfig {
+                stack_mode: StackMode::Growing,
+                stack_warning_depth: Some(20),
+                ..ram.config.clone()
+            },
+        );
+
+        for i in 1..=100 {
+            assert!(ram.push_to_stack(i));
+        }
+
+        assert_eq!(None, ram.get_stack_limit());
+        assert_eq!(100, ram.get_stack_depth());
+
+        for i in (1..=100).rev() {
+            assert_eq!(i, ram.pop_from_stack().unwrap());
+        }
+
+        ram.reset();
+        assert_eq!((vec![0; 16], 0), ram.get_stack());
+        assert!(lifecycle.is_running());
     }
 
     #[test]
     fn test_stack_pop_with_failed_overflow() {
-        let (ram, active) = create_objects(ConfigType::Conservative);
+        let (ram, lifecycle) = create_objects(ConfigType::Conservative);
 
         assert!(ram.pop_from_stack().is_none());
-        assert!(!active.load(Ordering::Relaxed));
+        assert!(!lifecycle.is_running());
     }
 }
@@ -0,0 +1,273 @@
+use crate::config::RewindConfig;
+use crate::savestate::SaveState;
+use std::collections::VecDeque;
+
+enum Snapshot {
+    Keyframe(Vec<u8>),
+    // The snapshot before it XORed with this one, with runs of unchanged bytes left out.
+    Delta(Vec<u8>),
+}
+
+impl Snapshot {
+    fn get_size(&self) -> usize {
+        return match self {
+            Self::Keyframe(bytes) | Self::Delta(bytes) => bytes.len(),
+        };
+    }
+}
+
+/// A memory-bounded history of save states to rewind through, newest last.
+///
+/// Only every keyframe_interval-th snapshot is stored in full. The rest only store what changed
+/// since the snapshot before, which for most programs is a few bytes of RAM and the display.
+pub struct RewindBuffer {
+    capacity: usize,
+    keyframe_interval: usize,
+    snapshots: VecDeque<Snapshot>,
+    // The newest snapshot in full, which the next delta is taken against.
+    newest: Option<Vec<u8>>,
+}
+
+impl RewindBuffer {
+    pub fn new(capacity: usize, keyframe_interval: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            keyframe_interval: keyframe_interval.max(1),
+            snapshots: VecDeque::new(),
+            newest: None,
+        }
+    }
+
+    pub fn from_config(config: &RewindConfig) -> Self {
+        let capacity = (config.history_length / config.snapshot_interval).ceil() as usize;
+        return Self::new(capacity, config.keyframe_interval);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.snapshots.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.snapshots.is_empty();
+    }
+
+    pub fn get_memory_usage(&self) -> usize {
+        let newest_size = self.newest.as_ref().map_or(0, Vec::len);
+        return self.snapshots.iter().map(Snapshot::get_size).sum::<usize>() + newest_size;
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.newest = None;
+    }
+
+    pub fn push(&mut self, save_state: &SaveState) {
+        // Thumbnails aren't needed to rewind, as the display itself is kept.
+        let mut save_state = save_state.clone();
+        save_state.thumbnail = None;
+        let bytes = save_state.to_bytes();
+
+        let deltas_since_keyframe = self
+            .snapshots
+            .iter()
+            .rev()
+            .position(|snapshot| matches!(snapshot, Snapshot::Keyframe(_)));
+
+        let snapshot = match (&self.newest, deltas_since_keyframe) {
+            (Some(newest), Some(deltas))
+                if deltas + 1 < self.keyframe_interval && newest.len() == bytes.len() =>
+            {
+                Snapshot::Delta(encode_delta(newest, &bytes))
+            }
+            _ => Snapshot::Keyframe(bytes.clone()),
+        };
+
+        self.snapshots.push_back(snapshot);
+        self.newest = Some(bytes);
+
+        if self.snapshots.len() > self.capacity {
+            self.drop_oldest();
+        }
+    }
+
+    /// Removes and returns the newest save state.
+    pub fn pop(&mut self) -> Option<SaveState> {
+        let newest = self.newest.take()?;
+        self.snapshots.pop_back();
+        self.newest = self.rebuild_newest();
+
+        return SaveState::from_bytes(&newest).ok();
+    }
+
+    // The oldest snapshot is always a keyframe, so the one after it becomes the new keyframe.
+    fn drop_oldest(&mut self) {
+        let Some(Snapshot::Keyframe(oldest)) = self.snapshots.pop_front() else {
+            return;
+        };
+
+        if let Some(Snapshot::Delta(delta)) = self.snapshots.front() {
+            let keyframe = apply_delta(&oldest, delta);
+            self.snapshots[0] = Snapshot::Keyframe(keyframe);
+        }
+    }
+
+    fn rebuild_newest(&self) -> Option<Vec<u8>> {
+        let keyframe_index = self
+            .snapshots
+            .iter()
+            .rposition(|snapshot| matches!(snapshot, Snapshot::Keyframe(_)))?;
+
+        let Snapshot::Keyframe(keyframe) = &self.snapshots[keyframe_index] else {
+            return None;
+        };
+
+        let mut bytes = keyframe.clone();
+
+        for snapshot in self.snapshots.range(keyframe_index + 1..) {
+            if let Snapshot::Delta(delta) = snapshot {
+                bytes = apply_delta(&bytes, delta);
+            }
+        }
+
+        return Some(bytes);
+    }
+}
+
+// Alternating runs of unchanged and changed bytes, each starting with its length as a u16.
+fn encode_delta(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let changes: Vec<u8> = old.iter().zip(new).map(|(a, b)| a ^ b).collect();
+    let mut delta = Vec::new();
+    let mut i = 0;
+
+    while i < changes.len() {
+        let unchanged = changes[i..]
+            .iter()
+            .take(u16::MAX as usize)
+            .take_while(|change| **change == 0)
+            .count();
+        i += unchanged;
+
+        let changed = changes[i..]
+            .iter()
+            .take(u16::MAX as usize)
+            .take_while(|change| **change != 0)
+            .count();
+
+        delta.extend_from_slice(&(unchanged as u16).to_be_bytes());
+        delta.extend_from_slice(&(changed as u16).to_be_bytes());
+        delta.extend_from_slice(&changes[i..i + changed]);
+        i += changed;
+    }
+
+    return delta;
+}
+
+fn apply_delta(old: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut bytes = old.to_vec();
+    let (mut i, mut position) = (0, 0);
+
+    while i + 4 <= delta.len() {
+        let unchanged = u16::from_be_bytes([delta[i], delta[i + 1]]) as usize;
+        let changed = u16::from_be_bytes([delta[i + 2], delta[i + 3]]) as usize;
+        i += 4;
+        position += unchanged;
+
+        for (byte, change) in bytes[position..position + changed]
+            .iter_mut()
+            .zip(&delta[i..i + changed])
+        {
+            *byte ^= change;
+        }
+
+        i += changed;
+        position += changed;
+    }
+
+    return bytes;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::EmulatorBuilder;
+
+    // Each state differs from the last by one register, one byte of memory and one pixel.
+    fn create_save_states(count: usize) -> Vec<SaveState> {
+        let mut save_state = EmulatorBuilder::default().build().unwrap().save_state();
+        let mut save_states = Vec::new();
+
+        for i in 0..count {
+            save_state.v[i % 16] = i as u8;
+            save_state.heap[0x300 + i % 0x100] ^= 0xFF;
+            save_state.framebuffer[i % 64] = !save_state.framebuffer[i % 64];
+            save_state.delay_timer = i as u8;
+            save_states.push(save_state.clone());
+        }
+
+        return save_states;
+    }
+
+    fn without_thumbnail(mut save_state: SaveState) -> SaveState {
+        save_state.thumbnail = None;
+        return save_state;
+    }
+
+    #[test]
+    fn test_encode_and_apply_delta() {
+        let old = vec![0, 1, 2, 3, 4, 5, 6, 7];
+        let new = vec![0, 1, 9, 9, 4, 5, 6, 8];
+
+        let delta = encode_delta(&old, &new);
+        assert_eq!(new, apply_delta(&old, &delta));
+        assert_eq!(encode_delta(&old, &old), vec![0, 8, 0, 0]);
+    }
+
+    #[test]
+    fn test_pop_in_reverse_order() {
+        let save_states = create_save_states(25);
+        let mut rewind_buffer = RewindBuffer::new(100, 10);
+
+        for save_state in &save_states {
+            rewind_buffer.push(save_state);
+        }
+
+        for save_state in save_states.into_iter().rev() {
+            assert_eq!(Some(without_thumbnail(save_state)), rewind_buffer.pop());
+        }
+
+        assert!(rewind_buffer.is_empty());
+        assert_eq!(None, rewind_buffer.pop());
+    }
+
+    #[test]
+    fn test_capacity_drops_oldest() {
+        let save_states = create_save_states(40);
+        let mut rewind_buffer = RewindBuffer::new(15, 4);
+
+        for save_state in &save_states {
+            rewind_buffer.push(save_state);
+        }
+
+        assert_eq!(15, rewind_buffer.len());
+
+        for save_state in save_states.into_iter().skip(25).rev() {
+            assert_eq!(Some(without_thumbnail(save_state)), rewind_buffer.pop());
+        }
+
+        assert!(rewind_buffer.is_empty());
+    }
+
+    #[test]
+    fn test_deltas_save_memory() {
+        let save_states = create_save_states(100);
+        let mut keyframes_only = RewindBuffer::new(100, 1);
+        let mut rewind_buffer = RewindBuffer::new(100, 50);
+
+        for save_state in &save_states {
+            keyframes_only.push(save_state);
+            rewind_buffer.push(save_state);
+        }
+
+        assert!(rewind_buffer.get_memory_usage() * 10 < keyframes_only.get_memory_usage());
+    }
+}
@@ -0,0 +1,164 @@
+//! A quick look at a ROM without running it, for the info command.
+//!
+//! The platform is guessed from the instructions the ROM seems to use, reading it two bytes at a
+//! time from the start. Data mixed in with the code can throw this off, so it is only a hint.
+
+use crate::disassembler;
+use crate::emulib;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(
+            f,
+            "{}",
+            match self {
+                Self::Chip8 => "CHIP-8",
+                Self::SuperChip => "SUPER-CHIP",
+                Self::XoChip => "XO-CHIP",
+            }
+        );
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomInfo {
+    pub size: usize,
+    pub sha1: String,
+    pub platform: Platform,
+    // How many instructions only the guessed platform has, which is 0 for CHIP-8.
+    pub platform_instructions: usize,
+    pub entry_opcode: Option<u16>,
+    pub code_words: usize,
+    pub data_words: usize,
+}
+
+// The lowest platform that has the opcode as an instruction but CHIP-8 doesn't, if any.
+fn get_extension_platform(opcode: u16) -> Option<Platform> {
+    let x = (opcode & 0x0F00) >> 8;
+
+    return match opcode {
+        0x00C0..=0x00CF | 0x00FB..=0x00FF => Some(Platform::SuperChip),
+        _ if opcode >> 12 == 0xD && opcode & 0xF == 0 => Some(Platform::SuperChip),
+        _ if opcode >> 12 == 0xF && matches!(opcode & 0xFF, 0x30 | 0x75 | 0x85) => {
+            Some(Platform::SuperChip)
+        }
+        0x00D0..=0x00DF | 0xF000 | 0xF002 => Some(Platform::XoChip),
+        _ if opcode >> 12 == 0x5 && matches!(opcode & 0xF, 0x2 | 0x3) => Some(Platform::XoChip),
+        _ if opcode >> 12 == 0xF && x != 0 && opcode & 0xFF == 0x01 => Some(Platform::XoChip),
+        _ if opcode >> 12 == 0xF && opcode & 0xFF == 0x3A => Some(Platform::XoChip),
+        _ => None,
+    };
+}
+
+// Machine code calls are rare enough that SYS is more likely to be data.
+fn is_chip8_instruction(opcode: u16) -> bool {
+    let mnemonic = disassembler::disassemble(opcode).mnemonic;
+    return !mnemonic.starts_with("DW") && !mnemonic.starts_with("SYS");
+}
+
+pub fn inspect(program: &[u8]) -> RomInfo {
+    let opcodes: Vec<u16> = program
+        .chunks_exact(2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        .collect();
+
+    let extensions: Vec<Platform> = opcodes
+        .iter()
+        .filter_map(|opcode| get_extension_platform(*opcode))
+        .collect();
+
+    let platform = extensions
+        .iter()
+        .copied()
+        .max_by_key(|platform| *platform as u8)
+        .unwrap_or(Platform::Chip8);
+
+    let is_code = |opcode: u16| {
+        return is_chip8_instruction(opcode)
+            || get_extension_platform(opcode).is_some_and(|p| p as u8 <= platform as u8);
+    };
+
+    let code_words = opcodes.iter().filter(|opcode| is_code(**opcode)).count();
+
+    return RomInfo {
+        size: program.len(),
+        sha1: emulib::get_sha1(program),
+        platform,
+        platform_instructions: extensions
+            .iter()
+            .filter(|p| **p as u8 <= platform as u8)
+            .count(),
+        entry_opcode: opcodes.first().copied(),
+        code_words,
+        data_words: opcodes.len() - code_words,
+    };
+}
+
+pub fn format_info(info: &RomInfo) -> String {
+    let platform = match info.platform {
+        Platform::Chip8 => format!("{}", info.platform),
+        _ => format!(
+            "{} ({} of its instructions found)",
+            info.platform, info.platform_instructions
+        ),
+    };
+
+    let entry = match info.entry_opcode {
+        Some(opcode) => format!(
+            "{opcode:04X}  {}",
+            disassembler::disassemble(opcode).mnemonic
+        ),
+        None => String::from("none"),
+    };
+
+    let words = (info.code_words + info.data_words).max(1);
+
+    return format!(
+        "Size: {} bytes\nSHA-1: {}\nPlatform: {platform}\nEntry: {entry}\nCode: {} words ({}%)\nData: {} words ({}%)\n",
+        info.size,
+        info.sha1,
+        info.code_words,
+        info.code_words * 100 / words,
+        info.data_words,
+        info.data_words * 100 / words,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inspect() {
+        // CLS; LD I, 0x208; DRW V0, V0, 5; JP 0x206; then the sprite for E
+        let program = [
+            0x00, 0xE0, 0xA2, 0x08, 0xD0, 0x05, 0x12, 0x06, 0xF0, 0x80, 0xF0, 0x80, 0xF0,
+        ];
+
+        let info = inspect(&program);
+        assert_eq!(13, info.size);
+        assert_eq!(Platform::Chip8, info.platform);
+        assert_eq!(Some(0x00E0), info.entry_opcode);
+        assert_eq!((4, 2), (info.code_words, info.data_words));
+        assert!(format_info(&info).contains("Entry: 00E0  CLS\n"));
+
+        // HIGH; DRW V1, V2, 0; LD I, long 0x0000
+        let info = inspect(&[0x00, 0xFF, 0xD1, 0x20]);
+        assert_eq!(Platform::SuperChip, info.platform);
+        assert_eq!(2, info.platform_instructions);
+        assert_eq!((2, 0), (info.code_words, info.data_words));
+
+        assert_eq!(
+            Platform::XoChip,
+            inspect(&[0x00, 0xFF, 0xF0, 0x00]).platform
+        );
+    }
+}
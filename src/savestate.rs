@@ -0,0 +1,483 @@
+//! Save states, in a versioned binary format that stays readable across releases.
+//!
+//! All numbers are big-endian. A save state starts with a header:
+//!
+//! | Bytes | Contents                                          |
+//! |-------|---------------------------------------------------|
+//! | 4     | The magic bytes `C8SS`                            |
+//! | 2     | The format version, currently 1                   |
+//! | 1     | The length of the platform name                   |
+//! | n     | The platform name, `chip8`                        |
+//!
+//! It is followed by sections, each made of a four byte tag, a four byte payload length and the
+//! payload itself. Sections can come in any order, and readers skip tags they don't know, so new
+//! sections can be added without changing the version. The version only changes when an existing
+//! section changes meaning, and states from newer versions are refused with an explanation.
+//!
+//! | Tag    | Payload                                                                   |
+//! |--------|---------------------------------------------------------------------------|
+//! | `QRKS` | The quirks in effect when saving, as `name = value` lines (optional)      |
+//! | `ROM ` | The SHA-1 of the loaded ROM as hex, empty if there was none (optional)    |
+//! | `CPU ` | The program counter, the index register and the 16 V registers            |
+//! | `RAM ` | The 4096 bytes of memory, the stack pointer and then every stack entry    |
+//! | `TIMR` | The delay timer, then the sound timer                                     |
+//! | `DISP` | The width and height, then the pixels row by row, 8 to a byte, MSB first  |
+//! | `THMB` | A downscaled copy of the display, laid out like `DISP` (optional)         |
+
+use crate::ram::HEAP_SIZE;
+use std::fmt;
+
+pub const MAGIC: &[u8; 4] = b"C8SS";
+pub const FORMAT_VERSION: u16 = 1;
+pub const PLATFORM: &str = "chip8";
+
+const QUIRKS_TAG: &[u8; 4] = b"QRKS";
+const ROM_TAG: &[u8; 4] = b"ROM ";
+const CPU_TAG: &[u8; 4] = b"CPU ";
+const RAM_TAG: &[u8; 4] = b"RAM ";
+const TIMERS_TAG: &[u8; 4] = b"TIMR";
+const DISPLAY_TAG: &[u8; 4] = b"DISP";
+const THUMBNAIL_TAG: &[u8; 4] = b"THMB";
+
+pub const THUMBNAIL_WIDTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SaveStateError {
+    NotASaveState,
+    NewerVersion(u16),
+    UnsupportedPlatform(String),
+    Truncated,
+    MissingSection(String),
+    InvalidSection(String),
+    ResolutionMismatch((usize, usize), (usize, usize)),
+    StackTooLarge(usize, usize),
+}
+
+impl fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Self::NotASaveState => write!(f, "This is not a chip8rust save state."),
+            Self::NewerVersion(version) => write!(
+                f,
+                "This save state uses format version {version}, but this build only reads up to \
+                 version {FORMAT_VERSION}. Update chip8rust to load it."
+            ),
+            Self::UnsupportedPlatform(platform) => {
+                write!(f, "This save state is for {platform}, not {PLATFORM}.")
+            }
+            Self::Truncated => write!(f, "This save state is incomplete."),
+            Self::MissingSection(tag) => {
+                write!(f, "This save state is missing its {tag} section.")
+            }
+            Self::InvalidSection(tag) => {
+                write!(f, "The {tag} section of this save state is invalid.")
+            }
+            Self::ResolutionMismatch((saved_width, saved_height), (width, height)) => write!(
+                f,
+                "This save state is for a {saved_width}x{saved_height} display, but the current \
+                 one is {width}x{height}."
+            ),
+            Self::StackTooLarge(saved_size, size) => write!(
+                f,
+                "This save state has {saved_size} entries on its stack, but the current stack \
+                 only holds {size}."
+            ),
+        };
+    }
+}
+
+/// A small copy of the display, for telling save states apart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thumbnail {
+    pub resolution: (usize, usize),
+    pub pixels: Vec<bool>,
+}
+
+impl Thumbnail {
+    // Each pixel covers a block of the display and is lit if any of it is, so thin lines survive.
+    pub fn from_framebuffer(framebuffer: &[bool], (width, height): (usize, usize)) -> Self {
+        let scale = width.div_ceil(THUMBNAIL_WIDTH).max(1);
+        let (thumbnail_width, thumbnail_height) = (width.div_ceil(scale), height.div_ceil(scale));
+        let mut pixels = vec![false; thumbnail_width * thumbnail_height];
+
+        for (i, pixel) in framebuffer.iter().enumerate() {
+            if *pixel {
+                pixels[i / width / scale * thumbnail_width + i % width / scale] = true;
+            }
+        }
+
+        return Self {
+            resolution: (thumbnail_width, thumbnail_height),
+            pixels,
+        };
+    }
+}
+
+/// Everything needed to put the machine back exactly as it was.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveState {
+    pub quirks: Vec<String>,
+    pub rom_hash: Option<String>,
+    pub pc: u16,
+    pub index: u16,
+    pub v: [u8; 16],
+    pub heap: Vec<u8>,
+    pub stack: Vec<u16>,
+    pub stack_ptr: usize,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub resolution: (usize, usize),
+    pub framebuffer: Vec<bool>,
+    pub thumbnail: Option<Thumbnail>,
+}
+
+impl SaveState {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_be_bytes());
+        bytes.push(PLATFORM.len() as u8);
+        bytes.extend_from_slice(PLATFORM.as_bytes());
+
+        write_section(&mut bytes, QUIRKS_TAG, self.quirks.join("\n").as_bytes());
+        write_section(
+            &mut bytes,
+            ROM_TAG,
+            self.rom_hash.as_deref().unwrap_or("").as_bytes(),
+        );
+
+        let mut cpu = Vec::new();
+        cpu.extend_from_slice(&self.pc.to_be_bytes());
+        cpu.extend_from_slice(&self.index.to_be_bytes());
+        cpu.extend_from_slice(&self.v);
+        write_section(&mut bytes, CPU_TAG, &cpu);
+
+        let mut ram = self.heap.clone();
+        ram.extend_from_slice(&(self.stack_ptr as u16).to_be_bytes());
+        ram.extend(self.stack.iter().flat_map(|entry| entry.to_be_bytes()));
+        write_section(&mut bytes, RAM_TAG, &ram);
+
+        write_section(
+            &mut bytes,
+            TIMERS_TAG,
+            &[self.delay_timer, self.sound_timer],
+        );
+
+        write_section(
+            &mut bytes,
+            DISPLAY_TAG,
+            &pack_pixels(self.resolution, &self.framebuffer),
+        );
+
+        if let Some(thumbnail) = &self.thumbnail {
+            write_section(
+                &mut bytes,
+                THUMBNAIL_TAG,
+                &pack_pixels(thumbnail.resolution, &thumbnail.pixels),
+            );
+        }
+
+        return bytes;
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SaveStateError> {
+        let mut reader = Reader { bytes };
+
+        if reader.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err(SaveStateError::NotASaveState);
+        }
+
+        let version = reader.take_u16()?;
+
+        if version > FORMAT_VERSION {
+            return Err(SaveStateError::NewerVersion(version));
+        }
+
+        let platform_length = reader.take(1)?[0] as usize;
+        let platform = String::from_utf8_lossy(reader.take(platform_length)?).into_owned();
+
+        if platform != PLATFORM {
+            return Err(SaveStateError::UnsupportedPlatform(platform));
+        }
+
+        let mut quirks = None;
+        let mut rom = None;
+        let mut cpu = None;
+        let mut ram = None;
+        let mut timers = None;
+        let mut display = None;
+        let mut thumbnail = None;
+
+        while !reader.bytes.is_empty() {
+            let tag = reader.take(4)?;
+            let length = u32::from_be_bytes(reader.take(4)?.try_into().unwrap()) as usize;
+            let payload = reader.take(length)?;
+
+            match tag.try_into().unwrap() {
+                QUIRKS_TAG => quirks = Some(payload),
+                ROM_TAG => rom = Some(payload),
+                CPU_TAG => cpu = Some(payload),
+                RAM_TAG => ram = Some(payload),
+                TIMERS_TAG => timers = Some(payload),
+                DISPLAY_TAG => display = Some(payload),
+                THUMBNAIL_TAG => thumbnail = Some(payload),
+                // Written by a newer version, but safe to ignore.
+                _ => (),
+            }
+        }
+
+        let quirks = match quirks {
+            Some(quirks) => parse_text(quirks, QUIRKS_TAG)?
+                .lines()
+                .map(String::from)
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let rom_hash = match rom {
+            Some(rom) => Some(parse_text(rom, ROM_TAG)?).filter(|hash| !hash.is_empty()),
+            None => None,
+        };
+
+        let mut cpu = Reader {
+            bytes: get_section(cpu, CPU_TAG)?,
+        };
+        let pc = cpu.take_u16().map_err(|_| invalid_section(CPU_TAG))?;
+        let index = cpu.take_u16().map_err(|_| invalid_section(CPU_TAG))?;
+        let v: [u8; 16] = cpu
+            .take(16)
+            .map_err(|_| invalid_section(CPU_TAG))?
+            .try_into()
+            .unwrap();
+
+        if pc > 0xFFF || !cpu.bytes.is_empty() {
+            return Err(invalid_section(CPU_TAG));
+        }
+
+        let mut ram = Reader {
+            bytes: get_section(ram, RAM_TAG)?,
+        };
+        let heap = ram
+            .take(HEAP_SIZE)
+            .map_err(|_| invalid_section(RAM_TAG))?
+            .to_vec();
+        let stack_ptr = ram.take_u16().map_err(|_| invalid_section(RAM_TAG))? as usize;
+
+        if !ram.bytes.len().is_multiple_of(2) {
+            return Err(invalid_section(RAM_TAG));
+        }
+
+        let stack: Vec<u16> = ram
+            .bytes
+            .chunks(2)
+            .map(|entry| u16::from_be_bytes([entry[0], entry[1]]))
+            .collect();
+
+        if stack_ptr > stack.len() {
+            return Err(invalid_section(RAM_TAG));
+        }
+
+        let [delay_timer, sound_timer] = get_section(timers, TIMERS_TAG)?
+            .try_into()
+            .map_err(|_| invalid_section(TIMERS_TAG))?;
+
+        let (resolution, framebuffer) =
+            unpack_pixels(get_section(display, DISPLAY_TAG)?, DISPLAY_TAG)?;
+
+        let thumbnail = match thumbnail {
+            Some(thumbnail) => {
+                let (resolution, pixels) = unpack_pixels(thumbnail, THUMBNAIL_TAG)?;
+                Some(Thumbnail { resolution, pixels })
+            }
+            None => None,
+        };
+
+        return Ok(Self {
+            quirks,
+            rom_hash,
+            pc,
+            index,
+            v,
+            heap,
+            stack,
+            stack_ptr,
+            delay_timer,
+            sound_timer,
+            resolution,
+            framebuffer,
+            thumbnail,
+        });
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], SaveStateError> {
+        if count > self.bytes.len() {
+            return Err(SaveStateError::Truncated);
+        }
+
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+
+        return Ok(taken);
+    }
+
+    fn take_u16(&mut self) -> Result<u16, SaveStateError> {
+        let bytes = self.take(2)?;
+        return Ok(u16::from_be_bytes([bytes[0], bytes[1]]));
+    }
+}
+
+fn write_section(bytes: &mut Vec<u8>, tag: &[u8; 4], payload: &[u8]) {
+    bytes.extend_from_slice(tag);
+    bytes.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(payload);
+}
+
+// The resolution, followed by the pixels packed 8 to a byte.
+fn pack_pixels((width, height): (usize, usize), pixels: &[bool]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&(width as u16).to_be_bytes());
+    bytes.extend_from_slice(&(height as u16).to_be_bytes());
+
+    bytes.extend(pixels.chunks(8).map(|pixels| {
+        pixels
+            .iter()
+            .enumerate()
+            .fold(0u8, |byte, (i, pixel)| byte | (*pixel as u8) << (7 - i))
+    }));
+
+    return bytes;
+}
+
+fn unpack_pixels(
+    payload: &[u8],
+    tag: &[u8; 4],
+) -> Result<((usize, usize), Vec<bool>), SaveStateError> {
+    let mut reader = Reader { bytes: payload };
+    let width = reader.take_u16().map_err(|_| invalid_section(tag))? as usize;
+    let height = reader.take_u16().map_err(|_| invalid_section(tag))? as usize;
+
+    if reader.bytes.len() != (width * height).div_ceil(8) {
+        return Err(invalid_section(tag));
+    }
+
+    let pixels = (0..width * height)
+        .map(|i| reader.bytes[i / 8] & (0x80 >> (i % 8)) != 0)
+        .collect();
+
+    return Ok(((width, height), pixels));
+}
+
+fn get_tag_name(tag: &[u8; 4]) -> String {
+    return String::from_utf8_lossy(tag).trim_end().to_string();
+}
+
+fn invalid_section(tag: &[u8; 4]) -> SaveStateError {
+    return SaveStateError::InvalidSection(get_tag_name(tag));
+}
+
+fn get_section<'a>(section: Option<&'a [u8]>, tag: &[u8; 4]) -> Result<&'a [u8], SaveStateError> {
+    return section.ok_or_else(|| SaveStateError::MissingSection(get_tag_name(tag)));
+}
+
+fn parse_text(payload: &[u8], tag: &[u8; 4]) -> Result<String, SaveStateError> {
+    return String::from_utf8(payload.to_vec()).map_err(|_| invalid_section(tag));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_save_state() -> SaveState {
+        let mut heap = vec![0; HEAP_SIZE];
+        heap[0x200] = 0x12;
+
+        let mut framebuffer = vec![false; 64 * 32];
+        framebuffer[0] = true;
+        framebuffer[64 * 32 - 1] = true;
+
+        return SaveState {
+            quirks: vec![String::from("use_new_shift_instruction = true")],
+            rom_hash: Some(String::from("a9993e364706816aba3e25717850c26c9cd0d89d")),
+            pc: 0x204,
+            index: 0x1234,
+            v: [7; 16],
+            heap,
+            stack: vec![0x202, 0x300, 0, 0],
+            stack_ptr: 2,
+            delay_timer: 30,
+            sound_timer: 4,
+            resolution: (64, 32),
+            thumbnail: Some(Thumbnail::from_framebuffer(&framebuffer, (64, 32))),
+            framebuffer,
+        };
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let save_state = create_save_state();
+        assert_eq!(
+            Ok(save_state.clone()),
+            SaveState::from_bytes(&save_state.to_bytes())
+        );
+    }
+
+    #[test]
+    fn test_thumbnail() {
+        let mut framebuffer = vec![false; 64 * 32];
+        framebuffer[64 + 1] = true;
+        framebuffer[64 * 32 - 1] = true;
+
+        let thumbnail = Thumbnail::from_framebuffer(&framebuffer, (64, 32));
+        assert_eq!((32, 16), thumbnail.resolution);
+        assert!(thumbnail.pixels[0]);
+        assert!(thumbnail.pixels[32 * 16 - 1]);
+        assert_eq!(2, thumbnail.pixels.iter().filter(|pixel| **pixel).count());
+
+        let thumbnail = Thumbnail::from_framebuffer(&vec![true; 128 * 64], (128, 64));
+        assert_eq!((32, 16), thumbnail.resolution);
+    }
+
+    #[test]
+    fn test_skips_unknown_sections() {
+        let save_state = create_save_state();
+        let mut bytes = save_state.to_bytes();
+        write_section(&mut bytes, b"NEW!", &[1, 2, 3]);
+
+        assert_eq!(Ok(save_state), SaveState::from_bytes(&bytes));
+    }
+
+    #[test]
+    fn test_rejects_invalid_save_states() {
+        let bytes = create_save_state().to_bytes();
+
+        assert_eq!(
+            Err(SaveStateError::NotASaveState),
+            SaveState::from_bytes(b"not a save state")
+        );
+
+        let mut newer = bytes.clone();
+        newer[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_be_bytes());
+        assert_eq!(
+            Err(SaveStateError::NewerVersion(FORMAT_VERSION + 1)),
+            SaveState::from_bytes(&newer)
+        );
+
+        assert_eq!(
+            Err(SaveStateError::Truncated),
+            SaveState::from_bytes(&bytes[..bytes.len() - 1])
+        );
+
+        let header_length = MAGIC.len() + 2 + 1 + PLATFORM.len();
+        assert_eq!(
+            Err(SaveStateError::MissingSection(String::from("CPU"))),
+            SaveState::from_bytes(&bytes[..header_length])
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! The display's rows as the window shows them, scaled up and colored, kept between frames so only
+//! the rows that changed are worked out again.
+//!
+//! A row of the display is a row of the framebuffer, or a column of it when the display is turned
+//! 90 or 270 degrees. Every row is worked out again when the colors, scale or rotation change.
+
+use crate::framebuffer::Framebuffer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Layout {
+    colors: (u32, u32),
+    cell_width: usize,
+    rotation: u16,
+}
+
+#[derive(Debug, Default)]
+pub struct ScanlineCache {
+    layout: Option<Layout>,
+    // The framebuffer the scanlines were last worked out from, or None if they are out of date.
+    framebuffer: Option<Framebuffer>,
+    scanlines: Vec<Vec<u32>>,
+}
+
+impl ScanlineCache {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// Brings the scanlines up to date with the framebuffer and returns them, one per row of the
+    /// display, each cell `cell_width` pixels wide in the active or inactive color.
+    pub fn update(
+        &mut self,
+        framebuffer: &Framebuffer,
+        colors: (u32, u32),
+        cell_width: usize,
+        rotation: u16,
+    ) -> &[Vec<u32>] {
+        let (width, height) = framebuffer.get_size();
+        let (shown_width, shown_height) = match rotation {
+            90 | 270 => (height, width),
+            _ => (width, height),
+        };
+        let layout = Layout {
+            colors,
+            cell_width,
+            rotation,
+        };
+
+        let resized = self
+            .framebuffer
+            .as_ref()
+            .is_some_and(|cached| cached.get_size() != framebuffer.get_size());
+
+        if self.layout != Some(layout) || resized {
+            self.layout = Some(layout);
+            self.framebuffer = None;
+            self.scanlines = vec![vec![0; shown_width * cell_width]; shown_height];
+        }
+
+        // Rotated rows are framebuffer columns, which aren't stored together, so they are only
+        // kept when nothing changed at all.
+        let frame_changed = self.framebuffer.as_ref() != Some(framebuffer);
+        let (active_color, inactive_color) = colors;
+
+        for shown_y in 0..shown_height {
+            let row_changed = match (&self.framebuffer, rotation) {
+                (None, _) => true,
+                (Some(cached), 0) => cached.get_row(shown_y) != framebuffer.get_row(shown_y),
+                (Some(cached), 180) => {
+                    let y = height - 1 - shown_y;
+                    cached.get_row(y) != framebuffer.get_row(y)
+                }
+                (Some(_), _) => frame_changed,
+            };
+
+            if !row_changed {
+                continue;
+            }
+
+            let scanline = &mut self.scanlines[shown_y];
+
+            for shown_x in 0..shown_width {
+                let (x, y) = unrotate_coords(shown_x, shown_y, width, height, rotation);
+
+                let color = match framebuffer.get(x, y) {
+                    true => active_color,
+                    false => inactive_color,
+                };
+
+                scanline[shown_x * cell_width..(shown_x + 1) * cell_width].fill(color);
+            }
+        }
+
+        if frame_changed {
+            match self.framebuffer.as_mut() {
+                Some(cached) => cached.clone_from(framebuffer),
+                None => self.framebuffer = Some(framebuffer.clone()),
+            }
+        }
+
+        return &self.scanlines;
+    }
+}
+
+/// Goes from where a cell is shown on the rotated display back to where it is in the framebuffer.
+pub fn unrotate_coords(
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    rotation: u16,
+) -> (usize, usize) {
+    return match rotation {
+        90 => (y, height - 1 - x),
+        180 => (width - 1 - x, height - 1 - y),
+        270 => (width - 1 - y, x),
+        _ => (x, y),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_scanlines() {
+        let mut cache = ScanlineCache::new();
+        let mut framebuffer = Framebuffer::new(3, 2);
+        framebuffer.draw_row(0, 0, 0b1000_0000, false);
+
+        let scanlines = cache.update(&framebuffer, (1, 0), 2, 0);
+        assert_eq!(vec![vec![1, 1, 0, 0, 0, 0], vec![0; 6]], scanlines);
+
+        // Only the second row changed, but both are right.
+        framebuffer.draw_row(2, 1, 0b1000_0000, false);
+        let scanlines = cache.update(&framebuffer, (1, 0), 2, 0);
+        assert_eq!(
+            vec![vec![1, 1, 0, 0, 0, 0], vec![0, 0, 0, 0, 1, 1]],
+            scanlines
+        );
+
+        // New colors or a new rotation start over.
+        let scanlines = cache.update(&framebuffer, (7, 3), 1, 180);
+        assert_eq!(vec![vec![7, 3, 3], vec![3, 3, 7]], scanlines);
+
+        let scanlines = cache.update(&framebuffer, (1, 0), 1, 90);
+        assert_eq!(vec![vec![0, 1], vec![0, 0], vec![1, 0]], scanlines);
+    }
+}
@@ -0,0 +1,243 @@
+//! A built-in test ROM, run without a window to check the interpreter before launching a program.
+//!
+//! It only checks behaviour that every quirk agrees on, so a failure points at a broken build or
+//! a config that breaks the basics, rather than at a program expecting other quirks. Each check
+//! sets VE to its number, then jumps to a loop at FAIL_ADDRESS if it fails. Passing them all ends
+//! in a loop at PASS_ADDRESS.
+
+use crate::emulator::Emulator;
+
+const PASS_ADDRESS: u16 = 0x310;
+const FAIL_ADDRESS: u16 = 0x312;
+// Far more than the ROM runs, so only a program counter gone astray runs out.
+const MAX_INSTRUCTIONS: usize = 1000;
+
+const SELF_TEST_ROM: [u16; 142] = [
+    // 1: LD Vx, byte and SE Vx, byte
+    0x6E01, // LD VE, 0x01
+    0x6012, // LD V0, 0x12
+    0x3012, // SE V0, 0x12
+    0x1312, // JP FAIL_ADDRESS
+    // 2: SNE Vx, byte
+    0x6E02, // LD VE, 0x02
+    0x4034, // SNE V0, 0x34
+    0x1312, // JP FAIL_ADDRESS
+    // 3: ADD Vx, byte
+    0x6E03, // LD VE, 0x03
+    0x7001, // ADD V0, 0x01
+    0x3013, // SE V0, 0x13
+    0x1312, // JP FAIL_ADDRESS
+    // 4: LD Vx, Vy and SE Vx, Vy
+    0x6E04, // LD VE, 0x04
+    0x8100, // LD V1, V0
+    0x5100, // SE V1, V0
+    0x1312, // JP FAIL_ADDRESS
+    // 5: OR
+    0x6E05, // LD VE, 0x05
+    0x620F, // LD V2, 0x0F
+    0x8121, // OR V1, V2
+    0x311F, // SE V1, 0x1F
+    0x1312, // JP FAIL_ADDRESS
+    // 6: AND
+    0x6E06, // LD VE, 0x06
+    0x8102, // AND V1, V0
+    0x3113, // SE V1, 0x13
+    0x1312, // JP FAIL_ADDRESS
+    // 7: XOR
+    0x6E07, // LD VE, 0x07
+    0x8123, // XOR V1, V2
+    0x311C, // SE V1, 0x1C
+    0x1312, // JP FAIL_ADDRESS
+    // 8: ADD Vx, Vy with a carry
+    0x6E08, // LD VE, 0x08
+    0x63F0, // LD V3, 0xF0
+    0x6420, // LD V4, 0x20
+    0x8344, // ADD V3, V4
+    0x3310, // SE V3, 0x10
+    0x1312, // JP FAIL_ADDRESS
+    0x3F01, // SE VF, 0x01
+    0x1312, // JP FAIL_ADDRESS
+    // 9: SUB with a borrow
+    0x6E09, // LD VE, 0x09
+    0x8345, // SUB V3, V4
+    0x33F0, // SE V3, 0xF0
+    0x1312, // JP FAIL_ADDRESS
+    0x3F00, // SE VF, 0x00
+    0x1312, // JP FAIL_ADDRESS
+    // 10: SUBN
+    0x6E0A, // LD VE, 0x0A
+    0x6310, // LD V3, 0x10
+    0x8347, // SUBN V3, V4
+    0x3310, // SE V3, 0x10
+    0x1312, // JP FAIL_ADDRESS
+    0x3F01, // SE VF, 0x01
+    0x1312, // JP FAIL_ADDRESS
+    // 11: SHR, shifting a register into itself, which every shift quirk agrees on
+    0x6E0B, // LD VE, 0x0B
+    0x6581, // LD V5, 0x81
+    0x8556, // SHR V5, V5
+    0x3540, // SE V5, 0x40
+    0x1312, // JP FAIL_ADDRESS
+    0x3F01, // SE VF, 0x01
+    0x1312, // JP FAIL_ADDRESS
+    // 12: SHL, likewise
+    0x6E0C, // LD VE, 0x0C
+    0x6581, // LD V5, 0x81
+    0x855E, // SHL V5, V5
+    0x3502, // SE V5, 0x02
+    0x1312, // JP FAIL_ADDRESS
+    0x3F01, // SE VF, 0x01
+    0x1312, // JP FAIL_ADDRESS
+    // 13: SNE Vx, Vy
+    0x6E0D, // LD VE, 0x0D
+    0x9500, // SNE V5, V0
+    0x1312, // JP FAIL_ADDRESS
+    // 14: CALL and RET
+    0x6E0E, // LD VE, 0x0E
+    0x2314, // CALL 0x314
+    0x3755, // SE V7, 0x55
+    0x1312, // JP FAIL_ADDRESS
+    // 15: LD B, Vx and LD Vx, [I]
+    0x6E0F, // LD VE, 0x0F
+    0x68EA, // LD V8, 0xEA
+    0xA318, // LD I, 0x318
+    0xF833, // LD B, V8
+    0xA318, // LD I, 0x318
+    0xF265, // LD V2, [I]
+    0x3002, // SE V0, 0x02
+    0x1312, // JP FAIL_ADDRESS
+    0x3103, // SE V1, 0x03
+    0x1312, // JP FAIL_ADDRESS
+    0x3204, // SE V2, 0x04
+    0x1312, // JP FAIL_ADDRESS
+    // 16: LD [I], Vx, reloading I in case the reads moved it
+    0x6E10, // LD VE, 0x10
+    0x6011, // LD V0, 0x11
+    0x6122, // LD V1, 0x22
+    0xA318, // LD I, 0x318
+    0xF155, // LD [I], V1
+    0x6000, // LD V0, 0x00
+    0x6100, // LD V1, 0x00
+    0xA318, // LD I, 0x318
+    0xF165, // LD V1, [I]
+    0x3011, // SE V0, 0x11
+    0x1312, // JP FAIL_ADDRESS
+    0x3122, // SE V1, 0x22
+    0x1312, // JP FAIL_ADDRESS
+    // 17: ADD I, Vx
+    0x6E11, // LD VE, 0x11
+    0xA318, // LD I, 0x318
+    0x6001, // LD V0, 0x01
+    0xF01E, // ADD I, V0
+    0xF065, // LD V0, [I]
+    0x3022, // SE V0, 0x22
+    0x1312, // JP FAIL_ADDRESS
+    // 18: LD DT, Vx and LD Vx, DT
+    0x6E12, // LD VE, 0x12
+    0x603C, // LD V0, 0x3C
+    0xF015, // LD DT, V0
+    0xF107, // LD V1, DT
+    0x4100, // SNE V1, 0x00
+    0x1312, // JP FAIL_ADDRESS
+    // 19: CLS, LD F, Vx and DRW, without and then with a collision
+    0x6E13, // LD VE, 0x13
+    0x00E0, // CLS
+    0x6000, // LD V0, 0x00
+    0xF029, // LD F, V0
+    0xD001, // DRW V0, V0, 1
+    0x3F00, // SE VF, 0x00
+    0x1312, // JP FAIL_ADDRESS
+    0xD001, // DRW V0, V0, 1
+    0x3F01, // SE VF, 0x01
+    0x1312, // JP FAIL_ADDRESS
+    // 20: RND with a mask of 0
+    0x6E14, // LD VE, 0x14
+    0xC000, // RND V0, 0x00
+    0x3000, // SE V0, 0x00
+    0x1312, // JP FAIL_ADDRESS
+    // 21: SKP and SKNP with no keys held
+    0x6E15, // LD VE, 0x15
+    0x6005, // LD V0, 0x05
+    0xE0A1, // SKNP V0
+    0x1312, // JP FAIL_ADDRESS
+    0x6100, // LD V1, 0x00
+    0xE09E, // SKP V0
+    0x6101, // LD V1, 0x01
+    0x3101, // SE V1, 0x01
+    0x1312, // JP FAIL_ADDRESS
+    // 22: JP V0, addr, with V0 and V3 equal, which every jump quirk agrees on
+    0x6E16, // LD VE, 0x16
+    0x6002, // LD V0, 0x02
+    0x6302, // LD V3, 0x02
+    0xB30E, // JP V0, 0x30E
+    // Skipped over by a correct jump
+    0x1312, // JP FAIL_ADDRESS
+    // PASS_ADDRESS
+    0x1310, // JP PASS_ADDRESS
+    // FAIL_ADDRESS
+    0x1312, // JP FAIL_ADDRESS
+    // The subroutine for check 14
+    0x6755, // LD V7, 0x55
+    0x00EE, // RET
+    // Scratch memory, for check 15 onwards
+    0x0000, 0x0000,
+];
+
+/// Runs the self-test ROM in place of whatever the emulator was running, returning how many
+/// checks passed, or why it failed.
+pub fn run_self_test(emulator: &Emulator) -> Result<u8, String> {
+    let rom: Vec<u8> = SELF_TEST_ROM
+        .iter()
+        .flat_map(|opcode| opcode.to_be_bytes())
+        .collect();
+
+    emulator.load_rom_bytes(&rom).map_err(|e| e.to_string())?;
+
+    for _ in 0..MAX_INSTRUCTIONS {
+        // Breaking on draws would otherwise stop the test at check 19.
+        if emulator.is_paused() {
+            emulator.resume();
+        }
+
+        let check = emulator.get_v_regs()[0xE];
+
+        if !emulator.step() {
+            return Err(format!("The emulator halted during check {check}."));
+        }
+
+        match emulator.get_pc() {
+            PASS_ADDRESS => return Ok(check),
+            FAIL_ADDRESS => return Err(format!("Check {check} failed.")),
+            _ => (),
+        }
+    }
+
+    return Err(String::from("The test ROM never finished."));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+    use crate::emulator::EmulatorBuilder;
+
+    #[test]
+    fn test_self_test_passes_with_any_quirks() {
+        let emulator = EmulatorBuilder::default().build().unwrap();
+        assert_eq!(Ok(22), run_self_test(&emulator));
+
+        let mut cpu_config = config::generate_default_configs().cpu;
+        cpu_config.reset_flag_for_bitwise_operations = true;
+        cpu_config.use_new_shift_instruction = true;
+        cpu_config.use_new_jump_instruction = true;
+        cpu_config.set_flag_for_index_overflow = true;
+        cpu_config.move_index_with_reads = true;
+        cpu_config.break_on_draw = config::BreakOnDraw::Always;
+
+        let emulator = EmulatorBuilder::default()
+            .with_cpu_config(cpu_config)
+            .build()
+            .unwrap();
+        assert_eq!(Ok(22), run_self_test(&emulator));
+    }
+}
@@ -0,0 +1,779 @@
+use crate::config::{
+    BreakOnDraw, CollisionReporting, KeyTriggerMode, Palette, Preset, RenderOccasion,
+    SpriteEdgePolicy, StackMode,
+};
+use crate::cpu::CPU;
+use crate::emulator::Emulator;
+use crate::gpu::GPU;
+use crate::input::InputManager;
+use crate::overlay::{self, Canvas};
+use crate::setup;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use winit::keyboard::{Key, KeyCode, NamedKey};
+use winit_input_helper::WinitInputHelper;
+
+const CLOSE_MENU_KEY: KeyCode = KeyCode::Escape;
+const PREVIOUS_ITEM_KEY: KeyCode = KeyCode::ArrowUp;
+const NEXT_ITEM_KEY: KeyCode = KeyCode::ArrowDown;
+const DECREASE_VALUE_KEY: KeyCode = KeyCode::ArrowLeft;
+const INCREASE_VALUE_KEY: KeyCode = KeyCode::ArrowRight;
+const SELECT_ITEM_KEY: KeyCode = KeyCode::Enter;
+
+const SPEED_STEP: f64 = 50.0;
+const TEXT_SCALE: usize = 2;
+const ROW_HEIGHT: usize = 7 * TEXT_SCALE;
+const MENU_MARGIN: usize = 8;
+const LABEL_COLUMN_CHARS: usize = 28;
+const VALUE_COLUMN_CHARS: usize = 36;
+const BACKGROUND_COLOR: u32 = 0x101018;
+const SELECTED_ROW_COLOR: u32 = 0x303048;
+const TEXT_COLOR: u32 = 0xFFFFFF;
+const HINT_COLOR: u32 = 0x9999AA;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum SettingsItem {
+    Palette,
+    Speed,
+    ResetFlagForBitwiseOperations,
+    UseNewShiftInstruction,
+    UseNewJumpInstruction,
+    SetFlagForIndexOverflow,
+    WriteFlagBeforeResult,
+    MoveIndexWithReads,
+    LimitToOneDrawPerFrame,
+    BreakOnDraw,
+    WrapSpritePositions,
+    HorizontalSpriteEdgePolicy,
+    VerticalSpriteEdgePolicy,
+    CollisionReporting,
+    KeyTriggerMode,
+    KeyBinding(usize),
+    Save,
+}
+
+const SETTINGS_ITEMS: [SettingsItem; 32] = [
+    SettingsItem::Palette,
+    SettingsItem::Speed,
+    SettingsItem::ResetFlagForBitwiseOperations,
+    SettingsItem::UseNewShiftInstruction,
+    SettingsItem::UseNewJumpInstruction,
+    SettingsItem::SetFlagForIndexOverflow,
+    SettingsItem::WriteFlagBeforeResult,
+    SettingsItem::MoveIndexWithReads,
+    SettingsItem::LimitToOneDrawPerFrame,
+    SettingsItem::BreakOnDraw,
+    SettingsItem::WrapSpritePositions,
+    SettingsItem::HorizontalSpriteEdgePolicy,
+    SettingsItem::VerticalSpriteEdgePolicy,
+    SettingsItem::CollisionReporting,
+    SettingsItem::KeyTriggerMode,
+    SettingsItem::KeyBinding(0x0),
+    SettingsItem::KeyBinding(0x1),
+    SettingsItem::KeyBinding(0x2),
+    SettingsItem::KeyBinding(0x3),
+    SettingsItem::KeyBinding(0x4),
+    SettingsItem::KeyBinding(0x5),
+    SettingsItem::KeyBinding(0x6),
+    SettingsItem::KeyBinding(0x7),
+    SettingsItem::KeyBinding(0x8),
+    SettingsItem::KeyBinding(0x9),
+    SettingsItem::KeyBinding(0xA),
+    SettingsItem::KeyBinding(0xB),
+    SettingsItem::KeyBinding(0xC),
+    SettingsItem::KeyBinding(0xD),
+    SettingsItem::KeyBinding(0xE),
+    SettingsItem::KeyBinding(0xF),
+    SettingsItem::Save,
+];
+
+pub struct SettingsMenu {
+    config_path: String,
+    preset: Preset,
+    cpu: Arc<CPU>,
+    gpu: Arc<GPU>,
+    input_manager: Arc<InputManager>,
+    open: bool,
+    selected: usize,
+    rebinding: bool,
+    ignore_next_update: bool,
+    status: String,
+}
+
+impl SettingsMenu {
+    pub fn new(config_path: String, emulator: &Emulator) -> Self {
+        Self {
+            config_path,
+            preset: emulator.get_preset(),
+            cpu: emulator.get_cpu(),
+            gpu: emulator.get_gpu(),
+            input_manager: emulator.get_input_manager(),
+            open: false,
+            selected: 0,
+            rebinding: false,
+            ignore_next_update: false,
+            status: String::new(),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        return self.open;
+    }
+
+    pub fn open(&mut self) {
+        self.open = true;
+        self.rebinding = false;
+        self.status.clear();
+    }
+
+    pub fn update(&mut self, input: &WinitInputHelper) {
+        if self.ignore_next_update {
+            self.ignore_next_update = false;
+            return;
+        }
+
+        if self.rebinding {
+            return;
+        }
+
+        let item = SETTINGS_ITEMS[self.selected];
+
+        if input.key_pressed(CLOSE_MENU_KEY) {
+            self.open = false;
+        } else if input.key_pressed(PREVIOUS_ITEM_KEY) {
+            self.selected = (self.selected + SETTINGS_ITEMS.len() - 1) % SETTINGS_ITEMS.len();
+        } else if input.key_pressed(NEXT_ITEM_KEY) {
+            self.selected = (self.selected + 1) % SETTINGS_ITEMS.len();
+        } else if input.key_pressed(DECREASE_VALUE_KEY) {
+            self.adjust(item, false);
+        } else if input.key_pressed(INCREASE_VALUE_KEY) {
+            self.adjust(item, true);
+        } else if input.key_pressed(SELECT_ITEM_KEY) {
+            self.select(item);
+        }
+    }
+
+    pub fn capture_key(&mut self, key: &Key) {
+        if !self.open || !self.rebinding {
+            return;
+        }
+
+        let SettingsItem::KeyBinding(index) = SETTINGS_ITEMS[self.selected] else {
+            return;
+        };
+
+        self.rebinding = false;
+        self.ignore_next_update = true;
+
+        let new_key = match key {
+            Key::Named(NamedKey::Escape) => {
+                self.status = String::from("Cancelled rebinding.");
+                return;
+            }
+            Key::Character(character) => character.to_lowercase(),
+            _ => {
+                self.status = String::from("Only character keys can be bound.");
+                return;
+            }
+        };
+
+        let mut config = self.input_manager.get_config_mut();
+
+        // Swap bindings rather than allowing two keypad keys to share a key.
+        if let Some(other) = config.key_bindings.iter().position(|k| *k == new_key) {
+            config.key_bindings.swap(index, other);
+        } else {
+            config.key_bindings[index] = new_key;
+        }
+
+        self.status.clear();
+    }
+
+    fn adjust(&mut self, item: SettingsItem, increase: bool) {
+        match item {
+            SettingsItem::Palette => {
+                let steps = match increase {
+                    true => 1,
+                    false => 2,
+                };
+
+                for _ in 0..steps {
+                    self.gpu.cycle_palette();
+                }
+            }
+            SettingsItem::Speed => {
+                let mut config = self.cpu.get_config_mut();
+
+                config.instructions_per_second = match increase {
+                    true => config.instructions_per_second + SPEED_STEP,
+                    false => (config.instructions_per_second - SPEED_STEP).max(SPEED_STEP),
+                };
+            }
+            _ => self.select(item),
+        }
+    }
+
+    fn select(&mut self, item: SettingsItem) {
+        match item {
+            SettingsItem::Palette | SettingsItem::Speed => self.adjust(item, true),
+            SettingsItem::ResetFlagForBitwiseOperations => {
+                let mut config = self.cpu.get_config_mut();
+                config.reset_flag_for_bitwise_operations ^= true;
+            }
+            SettingsItem::UseNewShiftInstruction => {
+                let mut config = self.cpu.get_config_mut();
+                config.use_new_shift_instruction ^= true;
+            }
+            SettingsItem::UseNewJumpInstruction => {
+                let mut config = self.cpu.get_config_mut();
+                config.use_new_jump_instruction ^= true;
+            }
+            SettingsItem::SetFlagForIndexOverflow => {
+                let mut config = self.cpu.get_config_mut();
+                config.set_flag_for_index_overflow ^= true;
+            }
+            SettingsItem::WriteFlagBeforeResult => {
+                let mut config = self.cpu.get_config_mut();
+                config.write_flag_before_result ^= true;
+            }
+            SettingsItem::MoveIndexWithReads => {
+                let mut config = self.cpu.get_config_mut();
+                config.move_index_with_reads ^= true;
+            }
+            // The CPU switches between free running and frame pacing on its next frame.
+            SettingsItem::LimitToOneDrawPerFrame => {
+                let mut config = self.cpu.get_config_mut();
+                config.limit_to_one_draw_per_frame ^= true;
+            }
+            SettingsItem::BreakOnDraw => {
+                let mut config = self.cpu.get_config_mut();
+                config.break_on_draw = match config.break_on_draw {
+                    BreakOnDraw::Off => BreakOnDraw::Always,
+                    BreakOnDraw::Always => BreakOnDraw::Collision,
+                    BreakOnDraw::Collision => BreakOnDraw::Off,
+                };
+            }
+            SettingsItem::WrapSpritePositions => {
+                let mut config = self.gpu.get_config_mut();
+                config.wrap_sprite_positions ^= true;
+            }
+            SettingsItem::HorizontalSpriteEdgePolicy => {
+                let mut config = self.gpu.get_config_mut();
+                config.horizontal_sprite_edge_policy =
+                    toggle_sprite_edge_policy(config.horizontal_sprite_edge_policy);
+            }
+            SettingsItem::VerticalSpriteEdgePolicy => {
+                let mut config = self.gpu.get_config_mut();
+                config.vertical_sprite_edge_policy =
+                    toggle_sprite_edge_policy(config.vertical_sprite_edge_policy);
+            }
+            SettingsItem::CollisionReporting => {
+                let mut config = self.gpu.get_config_mut();
+                config.collision_reporting = match config.collision_reporting.unwrap_or_default() {
+                    CollisionReporting::Flag => Some(CollisionReporting::RowCount),
+                    CollisionReporting::RowCount => Some(CollisionReporting::Flag),
+                };
+            }
+            SettingsItem::KeyTriggerMode => {
+                let mut config = self.input_manager.get_config_mut();
+                config.key_trigger_mode = match config.key_trigger_mode {
+                    KeyTriggerMode::Held => KeyTriggerMode::Edge,
+                    KeyTriggerMode::Edge => KeyTriggerMode::Held,
+                };
+            }
+            SettingsItem::KeyBinding(_) => {
+                self.rebinding = true;
+                self.status = String::from("Press a key to bind, or escape to cancel.");
+            }
+            SettingsItem::Save => self.save(),
+        }
+    }
+
+    fn get_label(item: SettingsItem) -> String {
+        return match item {
+            SettingsItem::Palette => String::from("Palette"),
+            SettingsItem::Speed => String::from("Speed"),
+            SettingsItem::ResetFlagForBitwiseOperations => String::from("Reset VF on AND/OR/XOR"),
+            SettingsItem::UseNewShiftInstruction => String::from("Shift VX in place"),
+            SettingsItem::UseNewJumpInstruction => String::from("Jump with VX offset"),
+            SettingsItem::SetFlagForIndexOverflow => String::from("Set VF on index overflow"),
+            SettingsItem::WriteFlagBeforeResult => String::from("Set VF before result"),
+            SettingsItem::MoveIndexWithReads => String::from("Move index on load/store"),
+            SettingsItem::LimitToOneDrawPerFrame => String::from("Display wait"),
+            SettingsItem::BreakOnDraw => String::from("Break on draw"),
+            SettingsItem::WrapSpritePositions => String::from("Wrap sprite positions"),
+            SettingsItem::HorizontalSpriteEdgePolicy => String::from("Sprite left and right edges"),
+            SettingsItem::VerticalSpriteEdgePolicy => String::from("Sprite top and bottom edges"),
+            SettingsItem::CollisionReporting => String::from("Collision reporting"),
+            SettingsItem::KeyTriggerMode => String::from("Key trigger mode"),
+            SettingsItem::KeyBinding(index) => format!("Keypad key {index:X}"),
+            SettingsItem::Save => String::from("Save settings"),
+        };
+    }
+
+    fn get_value(&self, item: SettingsItem) -> String {
+        let on_off = |value: bool| match value {
+            true => String::from("on"),
+            false => String::from("off"),
+        };
+
+        return match item {
+            SettingsItem::Palette => String::from(get_palette_name(self.gpu.get_palette())),
+            SettingsItem::Speed => format!(
+                "{} instructions/s",
+                self.cpu.get_config().instructions_per_second
+            ),
+            SettingsItem::ResetFlagForBitwiseOperations => {
+                on_off(self.cpu.get_config().reset_flag_for_bitwise_operations)
+            }
+            SettingsItem::UseNewShiftInstruction => {
+                on_off(self.cpu.get_config().use_new_shift_instruction)
+            }
+            SettingsItem::UseNewJumpInstruction => {
+                on_off(self.cpu.get_config().use_new_jump_instruction)
+            }
+            SettingsItem::SetFlagForIndexOverflow => {
+                on_off(self.cpu.get_config().set_flag_for_index_overflow)
+            }
+            SettingsItem::WriteFlagBeforeResult => {
+                on_off(self.cpu.get_config().write_flag_before_result)
+            }
+            SettingsItem::MoveIndexWithReads => on_off(self.cpu.get_config().move_index_with_reads),
+            SettingsItem::LimitToOneDrawPerFrame => {
+                on_off(self.cpu.get_config().limit_to_one_draw_per_frame)
+            }
+            SettingsItem::BreakOnDraw => {
+                String::from(get_break_on_draw_name(self.cpu.get_config().break_on_draw))
+            }
+            SettingsItem::WrapSpritePositions => {
+                on_off(self.gpu.get_config().wrap_sprite_positions)
+            }
+            SettingsItem::HorizontalSpriteEdgePolicy => String::from(get_sprite_edge_policy_name(
+                self.gpu.get_config().horizontal_sprite_edge_policy,
+            )),
+            SettingsItem::VerticalSpriteEdgePolicy => String::from(get_sprite_edge_policy_name(
+                self.gpu.get_config().vertical_sprite_edge_policy,
+            )),
+            SettingsItem::CollisionReporting => String::from(get_collision_reporting_name(
+                &self.gpu.get_config().collision_reporting,
+            )),
+            SettingsItem::KeyTriggerMode => String::from(get_key_trigger_mode_name(
+                &self.input_manager.get_config().key_trigger_mode,
+            )),
+            SettingsItem::KeyBinding(index) => {
+                if self.rebinding && SETTINGS_ITEMS[self.selected] == item {
+                    return String::from("...");
+                }
+
+                self.input_manager.get_config().key_bindings[index].clone()
+            }
+            SettingsItem::Save => format!("to {}", self.config_path),
+        };
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<'_>) {
+        let (_, text_height) = overlay::get_text_size("", TEXT_SCALE);
+        let text_offset = (ROW_HEIGHT - text_height) / 2;
+        let (label_width, _) = overlay::get_text_size(&" ".repeat(LABEL_COLUMN_CHARS), TEXT_SCALE);
+        let (value_width, _) = overlay::get_text_size(&" ".repeat(VALUE_COLUMN_CHARS), TEXT_SCALE);
+        let value_x = MENU_MARGIN * 2 + label_width;
+        let menu_width = label_width + value_width + MENU_MARGIN * 2;
+        let row_count = SETTINGS_ITEMS.len() + 3;
+
+        canvas.fill_rect(
+            MENU_MARGIN,
+            MENU_MARGIN,
+            menu_width,
+            ROW_HEIGHT * row_count + MENU_MARGIN * 2,
+            BACKGROUND_COLOR,
+        );
+
+        let mut y = MENU_MARGIN * 2;
+
+        overlay::draw_text(
+            canvas,
+            MENU_MARGIN * 2,
+            y + text_offset,
+            "Settings - arrows to change, enter to select, escape to close",
+            TEXT_SCALE,
+            HINT_COLOR,
+        );
+
+        y += ROW_HEIGHT * 2;
+
+        for (i, item) in SETTINGS_ITEMS.iter().enumerate() {
+            if i == self.selected {
+                canvas.fill_rect(MENU_MARGIN, y, menu_width, ROW_HEIGHT, SELECTED_ROW_COLOR);
+            }
+
+            overlay::draw_text(
+                canvas,
+                MENU_MARGIN * 2,
+                y + text_offset,
+                &Self::get_label(*item),
+                TEXT_SCALE,
+                TEXT_COLOR,
+            );
+
+            overlay::draw_text(
+                canvas,
+                value_x,
+                y + text_offset,
+                &self.get_value(*item),
+                TEXT_SCALE,
+                TEXT_COLOR,
+            );
+
+            y += ROW_HEIGHT;
+        }
+
+        overlay::draw_text(
+            canvas,
+            MENU_MARGIN * 2,
+            y + text_offset,
+            &self.status,
+            TEXT_SCALE,
+            HINT_COLOR,
+        );
+    }
+
+    fn save(&mut self) {
+        let is_toml = Path::new(&self.config_path)
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("toml"));
+
+        if !is_toml {
+            self.status = String::from("Only TOML configs can be saved from here.");
+            eprintln!(
+                "Error: Could not save settings to {}, as only TOML configs are supported.",
+                self.config_path
+            );
+            return;
+        }
+
+        let mut config_text = match fs::read_to_string(&self.config_path) {
+            Ok(text) => text,
+            Err(e) => {
+                self.status = String::from("Could not read the config.");
+                eprintln!(
+                    "Error: Could not read config at {} ({e}).",
+                    self.config_path
+                );
+                return;
+            }
+        };
+
+        let mut setting_values = self.get_setting_values();
+
+        // A preset would overwrite the quirks when the config is next loaded, so the config is
+        // switched to "custom", keeping the rest of what the preset set.
+        if self.preset != Preset::Custom {
+            setting_values.extend(self.get_preset_values());
+        }
+
+        for (key, value) in setting_values {
+            setup::replace_setting(&mut config_text, key, &value);
+        }
+
+        if let Err(e) = fs::write(&self.config_path, config_text) {
+            self.status = String::from("Could not write the config.");
+            eprintln!(
+                "Error: Could not write config to {} ({e}).",
+                self.config_path
+            );
+            return;
+        }
+
+        self.status = format!("Saved to {}.", self.config_path);
+        println!("Saved settings to {}.", self.config_path);
+
+        if self.preset != Preset::Custom {
+            println!("Note: Switched the preset to \"custom\", so the quirks are kept.");
+            self.preset = Preset::Custom;
+        }
+    }
+
+    fn get_setting_values(&self) -> Vec<(&'static str, String)> {
+        let cpu_config = self.cpu.get_config();
+        let gpu_config = self.gpu.get_config();
+        let input_config = self.input_manager.get_config();
+
+        let key_bindings = input_config.key_bindings.iter().map(|key| key.as_str());
+
+        let toml_bool = |value: bool| toml::Value::from(value).to_string();
+        let toml_str = |value: &str| toml::Value::from(value).to_string();
+
+        return vec![
+            (
+                "palette",
+                toml_str(get_palette_name(self.gpu.get_palette())),
+            ),
+            (
+                "instructions_per_second",
+                toml::Value::from(cpu_config.instructions_per_second).to_string(),
+            ),
+            (
+                "reset_flag_for_bitwise_operations",
+                toml_bool(cpu_config.reset_flag_for_bitwise_operations),
+            ),
+            (
+                "use_new_shift_instruction",
+                toml_bool(cpu_config.use_new_shift_instruction),
+            ),
+            (
+                "use_new_jump_instruction",
+                toml_bool(cpu_config.use_new_jump_instruction),
+            ),
+            (
+                "set_flag_for_index_overflow",
+                toml_bool(cpu_config.set_flag_for_index_overflow),
+            ),
+            (
+                "write_flag_before_result",
+                toml_bool(cpu_config.write_flag_before_result),
+            ),
+            (
+                "move_index_with_reads",
+                toml_bool(cpu_config.move_index_with_reads),
+            ),
+            (
+                "limit_to_one_draw_per_frame",
+                toml_bool(cpu_config.limit_to_one_draw_per_frame),
+            ),
+            (
+                "break_on_draw",
+                toml_str(get_break_on_draw_name(cpu_config.break_on_draw)),
+            ),
+            (
+                "wrap_sprite_positions",
+                toml_bool(gpu_config.wrap_sprite_positions),
+            ),
+            (
+                "horizontal_sprite_edge_policy",
+                toml_str(get_sprite_edge_policy_name(
+                    gpu_config.horizontal_sprite_edge_policy,
+                )),
+            ),
+            (
+                "vertical_sprite_edge_policy",
+                toml_str(get_sprite_edge_policy_name(
+                    gpu_config.vertical_sprite_edge_policy,
+                )),
+            ),
+            (
+                "collision_reporting",
+                toml_str(get_collision_reporting_name(
+                    &gpu_config.collision_reporting,
+                )),
+            ),
+            (
+                "key_trigger_mode",
+                toml_str(get_key_trigger_mode_name(&input_config.key_trigger_mode)),
+            ),
+            ("key_bindings", setup::format_key_bindings(key_bindings)),
+        ];
+    }
+
+    // The settings a preset sets that aren't in the menu, as they are now.
+    fn get_preset_values(&self) -> Vec<(&'static str, String)> {
+        let gpu_config = self.gpu.get_config();
+        let ram_config = self.cpu.ram.get_config();
+
+        let toml_str = |value: &str| toml::Value::from(value).to_string();
+
+        let render_occasion = match gpu_config.render_occasion {
+            RenderOccasion::Changes => "changes",
+            RenderOccasion::Frequency => "frequency",
+            RenderOccasion::Vsync => "vsync",
+        };
+
+        let stack_mode = match ram_config.stack_mode {
+            StackMode::Fixed => "fixed",
+            StackMode::Growing => "growing",
+        };
+
+        return vec![
+            ("preset", toml_str("custom")),
+            (
+                "horizontal_resolution",
+                gpu_config.horizontal_resolution.to_string(),
+            ),
+            (
+                "vertical_resolution",
+                gpu_config.vertical_resolution.to_string(),
+            ),
+            ("render_occasion", toml_str(render_occasion)),
+            (
+                "render_frequency",
+                toml::Value::from(gpu_config.render_frequency).to_string(),
+            ),
+            ("stack_size", ram_config.stack_size.to_string()),
+            ("stack_mode", toml_str(stack_mode)),
+            (
+                "delay_timer_decrement_rate",
+                toml::Value::from(self.cpu.delay_timer.get_decrement_rate()).to_string(),
+            ),
+            (
+                "sound_timer_decrement_rate",
+                toml::Value::from(self.cpu.sound_timer.get_decrement_rate()).to_string(),
+            ),
+        ];
+    }
+}
+
+fn get_palette_name(palette: Palette) -> &'static str {
+    return match palette {
+        Palette::Custom => "custom",
+        Palette::HighContrast => "high_contrast",
+        Palette::Deuteranopia => "deuteranopia",
+    };
+}
+
+fn get_break_on_draw_name(break_on_draw: BreakOnDraw) -> &'static str {
+    return match break_on_draw {
+        BreakOnDraw::Off => "off",
+        BreakOnDraw::Always => "always",
+        BreakOnDraw::Collision => "collision",
+    };
+}
+
+fn get_sprite_edge_policy_name(policy: SpriteEdgePolicy) -> &'static str {
+    return match policy {
+        SpriteEdgePolicy::Wrap => "wrap",
+        SpriteEdgePolicy::Clip => "clip",
+    };
+}
+
+fn toggle_sprite_edge_policy(policy: SpriteEdgePolicy) -> SpriteEdgePolicy {
+    return match policy {
+        SpriteEdgePolicy::Wrap => SpriteEdgePolicy::Clip,
+        SpriteEdgePolicy::Clip => SpriteEdgePolicy::Wrap,
+    };
+}
+
+fn get_collision_reporting_name(collision_reporting: &Option<CollisionReporting>) -> &'static str {
+    return match collision_reporting.unwrap_or_default() {
+        CollisionReporting::Flag => "flag",
+        CollisionReporting::RowCount => "row_count",
+    };
+}
+
+fn get_key_trigger_mode_name(key_trigger_mode: &KeyTriggerMode) -> &'static str {
+    return match key_trigger_mode {
+        KeyTriggerMode::Held => "held",
+        KeyTriggerMode::Edge => "edge",
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::NullAudioSink;
+    use crate::config::{self, CONFIG_FILE_PATH};
+    use crate::emulator::EmulatorBuilder;
+    use winit::keyboard::SmolStr;
+
+    fn create_settings_menu(config_path: String, preset: Preset) -> SettingsMenu {
+        let mut config = config::generate_default_configs();
+        config.preset = preset;
+
+        let emulator = EmulatorBuilder::new(config)
+            .with_audio_sink(Box::new(NullAudioSink))
+            .build()
+            .unwrap();
+
+        return SettingsMenu::new(config_path, &emulator);
+    }
+
+    #[test]
+    fn test_edit_values() {
+        let mut menu = create_settings_menu(String::from(CONFIG_FILE_PATH), Preset::Custom);
+
+        menu.select(SettingsItem::UseNewShiftInstruction);
+        assert!(menu.cpu.get_config().use_new_shift_instruction);
+        assert_eq!("on", menu.get_value(SettingsItem::UseNewShiftInstruction));
+
+        let speed = menu.cpu.get_config().instructions_per_second;
+        menu.adjust(SettingsItem::Speed, true);
+        assert_eq!(
+            speed + SPEED_STEP,
+            menu.cpu.get_config().instructions_per_second
+        );
+
+        // The speed never drops below a step.
+        for _ in 0..100 {
+            menu.adjust(SettingsItem::Speed, false);
+        }
+        assert_eq!(SPEED_STEP, menu.cpu.get_config().instructions_per_second);
+
+        menu.adjust(SettingsItem::CollisionReporting, false);
+        assert_eq!(
+            "row_count",
+            menu.get_value(SettingsItem::CollisionReporting)
+        );
+
+        menu.select(SettingsItem::BreakOnDraw);
+        menu.select(SettingsItem::BreakOnDraw);
+        assert_eq!(BreakOnDraw::Collision, menu.cpu.get_config().break_on_draw);
+    }
+
+    #[test]
+    fn test_rebind_key() {
+        let mut menu = create_settings_menu(String::from(CONFIG_FILE_PATH), Preset::Custom);
+        menu.open();
+        menu.selected = SETTINGS_ITEMS
+            .iter()
+            .position(|item| *item == SettingsItem::KeyBinding(0x0))
+            .unwrap();
+
+        menu.select(SettingsItem::KeyBinding(0x0));
+        assert_eq!("...", menu.get_value(SettingsItem::KeyBinding(0x0)));
+
+        // A key that is already bound swaps with the other binding.
+        menu.capture_key(&Key::Character(SmolStr::new("Q")));
+        let key_bindings = menu.input_manager.get_config().key_bindings.clone();
+        assert_eq!("q", key_bindings[0x0]);
+        assert_eq!("x", key_bindings[0x4]);
+
+        menu.select(SettingsItem::KeyBinding(0x0));
+        menu.capture_key(&Key::Named(NamedKey::Escape));
+        assert_eq!("q", menu.input_manager.get_config().key_bindings[0x0]);
+        assert_eq!("Cancelled rebinding.", menu.status);
+    }
+
+    #[test]
+    fn test_save_settings() {
+        let config_path = std::env::temp_dir().join(format!(
+            "chip8rust_test_save_settings_{}.toml",
+            std::process::id()
+        ));
+        fs::copy(CONFIG_FILE_PATH, &config_path).unwrap();
+
+        let config_path = config_path.to_string_lossy().into_owned();
+        let mut menu = create_settings_menu(config_path.clone(), Preset::CHIP8);
+
+        menu.select(SettingsItem::UseNewShiftInstruction);
+        menu.select(SettingsItem::CollisionReporting);
+        menu.select(SettingsItem::Save);
+
+        // Saved under the "chip8" preset, the quirks would be overwritten, so it switches.
+        let config = config::generate_configs_from(&config_path, None);
+        let _ = fs::remove_file(&config_path);
+        let config = config.unwrap();
+
+        assert_eq!(Preset::Custom, config.preset);
+        assert!(config.cpu.use_new_shift_instruction);
+        assert_eq!(
+            Some(CollisionReporting::RowCount),
+            config.gpu.collision_reporting
+        );
+        assert_eq!(64, config.gpu.horizontal_resolution);
+        assert_eq!(Preset::Custom, menu.preset);
+        assert!(menu.status.starts_with("Saved to"));
+
+        let mut menu = create_settings_menu(String::from("config.json"), Preset::Custom);
+        menu.select(SettingsItem::Save);
+        assert_eq!("Only TOML configs can be saved from here.", menu.status);
+    }
+}
@@ -0,0 +1,190 @@
+use crate::config::DEFAULT_CONFIG;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+const PRESET_CHOICES: [(&str, &str); 3] = [
+    ("chip8", "the original CHIP-8 for the COSMAC VIP"),
+    ("schip", "SUPER-CHIP 1.1 for the HP 48, in hires mode"),
+    (
+        "custom",
+        "the default custom settings, for non-standard programs",
+    ),
+];
+
+const LAYOUT_CHOICES: [(&str, &str); 3] = [
+    ("qwerty", "1234 / QWER / ASDF / ZXCV"),
+    ("azerty", "&é\"' / AZER / QSDF / WXCV"),
+    ("qwertz", "1234 / QWER / ASDF / YXCV"),
+];
+
+const PALETTE_CHOICES: [(&str, &str); 3] = [
+    (
+        "custom",
+        "white pixels on a black background, with a grey border",
+    ),
+    ("high_contrast", "high contrast white on black"),
+    (
+        "deuteranopia",
+        "yellow on dark blue, safe for red-green colorblindness",
+    ),
+];
+
+// Keys in the order of the original keypad's hexadecimal naming convention.
+const QWERTY_KEYS: [&str; 16] = [
+    "x", "1", "2", "3", "q", "w", "e", "a", "s", "d", "z", "c", "4", "r", "f", "v",
+];
+const AZERTY_KEYS: [&str; 16] = [
+    "x", "&", "é", "\"", "a", "z", "e", "q", "s", "d", "w", "c", "'", "r", "f", "v",
+];
+const QWERTZ_KEYS: [&str; 16] = [
+    "x", "1", "2", "3", "q", "w", "e", "a", "s", "d", "y", "c", "4", "r", "f", "v",
+];
+
+pub fn run_first_time_setup(config_path: &str) -> bool {
+    if !io::stdin().is_terminal() {
+        return false;
+    }
+
+    println!("No config was found at {config_path}, so one will be created.");
+
+    let mut lines = io::stdin().lock().lines();
+    let mut read_line = || lines.next()?.ok();
+
+    let Some(preset) = prompt_choice("Preset", &PRESET_CHOICES, &mut read_line) else {
+        return false;
+    };
+
+    let Some(layout) = prompt_choice("Keyboard layout", &LAYOUT_CHOICES, &mut read_line) else {
+        return false;
+    };
+
+    let Some(palette) = prompt_choice("Color palette", &PALETTE_CHOICES, &mut read_line) else {
+        return false;
+    };
+
+    let config = generate_config_text(preset, layout, palette);
+
+    if let Err(e) = fs::write(config_path, config) {
+        eprintln!("Error: Could not write config to {config_path} ({e}).");
+        return false;
+    }
+
+    println!("Created {config_path}. Edit it at any time to change further settings.");
+    return true;
+}
+
+fn prompt_choice<'a>(
+    title: &str,
+    choices: &[(&'a str, &str)],
+    read_line: &mut dyn FnMut() -> Option<String>,
+) -> Option<&'a str> {
+    println!("\n{title}:");
+
+    for (i, (name, description)) in choices.iter().enumerate() {
+        println!("  {}) {name} - {description}", i + 1);
+    }
+
+    loop {
+        print!("Choose [1]: ");
+        let _ = io::stdout().flush();
+
+        let line = read_line()?;
+        let line = line.trim();
+
+        if line.is_empty() {
+            return Some(choices[0].0);
+        }
+
+        match line.parse::<usize>() {
+            Ok(n) if (1..=choices.len()).contains(&n) => return Some(choices[n - 1].0),
+            _ => println!("Please enter a number from 1 to {}.", choices.len()),
+        }
+    }
+}
+
+fn generate_config_text(preset: &str, layout: &str, palette: &str) -> String {
+    let keys = match layout {
+        "azerty" => AZERTY_KEYS,
+        "qwertz" => QWERTZ_KEYS,
+        _ => QWERTY_KEYS,
+    };
+
+    let mut config = String::from(DEFAULT_CONFIG);
+    replace_setting(&mut config, "preset", &format!("\"{preset}\""));
+    replace_setting(&mut config, "palette", &format!("\"{palette}\""));
+    replace_setting(
+        &mut config,
+        "key_bindings",
+        &format_key_bindings(keys.into_iter()),
+    );
+    return config;
+}
+
+pub fn format_key_bindings<'a>(keys: impl Iterator<Item = &'a str>) -> String {
+    let key_list = keys
+        .map(|k| toml::Value::from(k).to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    return format!("[\n    {key_list}\n]");
+}
+
+// A setting that is commented out, as optional ones are, is uncommented to be set.
+pub fn replace_setting(config: &mut String, key: &str, value: &str) {
+    let setting = format!("\n{key} = ");
+    let commented_setting = format!("\n# {key} = ");
+
+    if !config.contains(&setting)
+        && let Some(index) = config.find(&commented_setting)
+    {
+        config.replace_range(index..index + commented_setting.len(), &setting);
+    }
+
+    let Some(key_index) = config.find(&setting) else {
+        return;
+    };
+
+    let value_start = key_index + key.len() + 4;
+
+    let value_end = match config[value_start..].starts_with('[') {
+        true => config[value_start..].find(']').map(|i| value_start + i + 1),
+        false => config[value_start..].find('\n').map(|i| value_start + i),
+    };
+
+    config.replace_range(value_start..value_end.unwrap_or(config.len()), value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, Palette, Preset};
+
+    #[test]
+    fn test_generate_config_text() {
+        let config: Config =
+            toml::from_str(&generate_config_text("custom", "azerty", "deuteranopia")).unwrap();
+
+        assert_eq!(Preset::Custom, config.preset);
+        assert_eq!(Palette::Deuteranopia, config.gpu.palette);
+        assert_eq!("\"", config.input.key_bindings[3]);
+    }
+
+    #[test]
+    fn test_prompt_choice() {
+        let mut inputs = vec!["", "7", "oops", "3"].into_iter();
+        let mut read_line = || inputs.next().map(String::from);
+
+        assert_eq!(
+            Some("chip8"),
+            prompt_choice("Preset", &PRESET_CHOICES, &mut read_line)
+        );
+        assert_eq!(
+            Some("custom"),
+            prompt_choice("Preset", &PRESET_CHOICES, &mut read_line)
+        );
+        assert_eq!(
+            None,
+            prompt_choice("Preset", &PRESET_CHOICES, &mut read_line)
+        );
+    }
+}
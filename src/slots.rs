@@ -0,0 +1,185 @@
+use crate::overlay::{self, Canvas};
+use crate::savestate::{SaveState, THUMBNAIL_WIDTH};
+use winit::keyboard::KeyCode;
+use winit_input_helper::WinitInputHelper;
+
+const CLOSE_PICKER_KEY: KeyCode = KeyCode::Escape;
+const PREVIOUS_SLOT_KEY: KeyCode = KeyCode::ArrowUp;
+const NEXT_SLOT_KEY: KeyCode = KeyCode::ArrowDown;
+const SELECT_SLOT_KEY: KeyCode = KeyCode::Enter;
+
+const TEXT_SCALE: usize = 2;
+const TEXT_ROW_HEIGHT: usize = 7 * TEXT_SCALE;
+const MENU_MARGIN: usize = 8;
+const THUMBNAIL_SCALE: usize = 2;
+const THUMBNAIL_BOX_WIDTH: usize = THUMBNAIL_WIDTH * THUMBNAIL_SCALE;
+const THUMBNAIL_BOX_HEIGHT: usize = THUMBNAIL_BOX_WIDTH / 2;
+const SLOT_ROW_HEIGHT: usize = THUMBNAIL_BOX_HEIGHT + MENU_MARGIN;
+const LABEL_COLUMN_CHARS: usize = 40;
+const BACKGROUND_COLOR: u32 = 0x101018;
+const SELECTED_ROW_COLOR: u32 = 0x303048;
+const THUMBNAIL_BACKGROUND_COLOR: u32 = 0x000000;
+const TEXT_COLOR: u32 = 0xFFFFFF;
+const HINT_COLOR: u32 = 0x9999AA;
+
+pub struct SlotPicker {
+    open: bool,
+    slots: Vec<Option<SaveState>>,
+    selected: usize,
+}
+
+impl Default for SlotPicker {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+impl SlotPicker {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            slots: Vec::new(),
+            selected: 0,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        return self.open;
+    }
+
+    // Takes the slots in order, from slot 1, with None for those that are empty.
+    pub fn open(&mut self, slots: Vec<Option<SaveState>>) {
+        self.open = true;
+        self.selected = slots.iter().position(Option::is_some).unwrap_or(0);
+        self.slots = slots;
+    }
+
+    // Returns the number of the slot picked by the user, if any, closing the picker.
+    pub fn update(&mut self, input: &WinitInputHelper) -> Option<usize> {
+        if input.key_pressed(CLOSE_PICKER_KEY) {
+            self.open = false;
+        } else if input.key_pressed(PREVIOUS_SLOT_KEY) && !self.slots.is_empty() {
+            self.selected = (self.selected + self.slots.len() - 1) % self.slots.len();
+        } else if input.key_pressed(NEXT_SLOT_KEY) && !self.slots.is_empty() {
+            self.selected = (self.selected + 1) % self.slots.len();
+        } else if input.key_pressed(SELECT_SLOT_KEY)
+            && matches!(self.slots.get(self.selected), Some(Some(_)))
+        {
+            self.open = false;
+            return Some(self.selected + 1);
+        }
+
+        return None;
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<'_>) {
+        let (_, text_height) = overlay::get_text_size("", TEXT_SCALE);
+        let text_offset = (TEXT_ROW_HEIGHT - text_height) / 2;
+        let (label_width, _) = overlay::get_text_size(&" ".repeat(LABEL_COLUMN_CHARS), TEXT_SCALE);
+        let menu_width = THUMBNAIL_BOX_WIDTH + label_width + MENU_MARGIN * 3;
+
+        canvas.fill_rect(
+            MENU_MARGIN,
+            MENU_MARGIN,
+            menu_width,
+            TEXT_ROW_HEIGHT * 2 + SLOT_ROW_HEIGHT * self.slots.len() + MENU_MARGIN * 2,
+            BACKGROUND_COLOR,
+        );
+
+        let mut y = MENU_MARGIN * 2;
+
+        overlay::draw_text(
+            canvas,
+            MENU_MARGIN * 2,
+            y + text_offset,
+            "Load state - enter to load, escape to close",
+            TEXT_SCALE,
+            HINT_COLOR,
+        );
+
+        y += TEXT_ROW_HEIGHT * 2;
+
+        for (i, slot) in self.slots.iter().enumerate() {
+            if i == self.selected {
+                canvas.fill_rect(
+                    MENU_MARGIN,
+                    y,
+                    menu_width,
+                    SLOT_ROW_HEIGHT,
+                    SELECTED_ROW_COLOR,
+                );
+            }
+
+            let thumbnail_x = MENU_MARGIN * 2;
+            let thumbnail_y = y + MENU_MARGIN / 2;
+
+            canvas.fill_rect(
+                thumbnail_x,
+                thumbnail_y,
+                THUMBNAIL_BOX_WIDTH,
+                THUMBNAIL_BOX_HEIGHT,
+                THUMBNAIL_BACKGROUND_COLOR,
+            );
+
+            // States saved before thumbnails existed just show an empty box.
+            if let Some(thumbnail) = slot.as_ref().and_then(|slot| slot.thumbnail.as_ref()) {
+                let (width, _) = thumbnail.resolution;
+
+                for (j, _) in thumbnail
+                    .pixels
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, pixel)| **pixel)
+                {
+                    let (pixel_x, pixel_y) = (j % width, j / width);
+
+                    if pixel_x < THUMBNAIL_WIDTH && pixel_y < THUMBNAIL_BOX_HEIGHT / THUMBNAIL_SCALE
+                    {
+                        canvas.fill_rect(
+                            thumbnail_x + pixel_x * THUMBNAIL_SCALE,
+                            thumbnail_y + pixel_y * THUMBNAIL_SCALE,
+                            THUMBNAIL_SCALE,
+                            THUMBNAIL_SCALE,
+                            TEXT_COLOR,
+                        );
+                    }
+                }
+            }
+
+            let (label, color) = match slot {
+                Some(_) => (format!("Slot {}", i + 1), TEXT_COLOR),
+                None => (format!("Slot {} - empty", i + 1), HINT_COLOR),
+            };
+
+            overlay::draw_text(
+                canvas,
+                thumbnail_x + THUMBNAIL_BOX_WIDTH + MENU_MARGIN,
+                y + (SLOT_ROW_HEIGHT - TEXT_ROW_HEIGHT) / 2 + text_offset,
+                &label,
+                TEXT_SCALE,
+                color,
+            );
+
+            y += SLOT_ROW_HEIGHT;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::EmulatorBuilder;
+
+    #[test]
+    fn test_open_selects_first_saved_slot() {
+        let save_state = EmulatorBuilder::default().build().unwrap().save_state();
+        let mut slot_picker = SlotPicker::new();
+
+        slot_picker.open(vec![None, None, Some(save_state), None]);
+        assert!(slot_picker.is_open());
+        assert_eq!(2, slot_picker.selected);
+
+        slot_picker.open(vec![None; 10]);
+        assert_eq!(0, slot_picker.selected);
+    }
+}
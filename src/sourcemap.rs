@@ -0,0 +1,106 @@
+//! The source a program was assembled from, so the debugger can show the line each instruction
+//! came from and break on lines rather than addresses.
+//!
+//! The source is looked for next to the program with the same name and an `.8o` or `.asm`
+//! extension, and is only used if the built-in assembler turns it into exactly the program that
+//! was loaded, so an outdated source is never shown against a newer build.
+
+use crate::assembler::{self, Assembly};
+use crate::emulib;
+use crate::symbols::Symbols;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+const SOURCE_EXTENSIONS: [&str; 2] = ["8o", "asm"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceMap {
+    lines: Vec<String>,
+    source_lines: BTreeMap<u16, usize>,
+    symbols: Symbols,
+}
+
+impl SourceMap {
+    pub fn new(source: &str, assembly: Assembly) -> Self {
+        Self {
+            lines: source.lines().map(String::from).collect(),
+            source_lines: assembly.source_lines,
+            symbols: assembly.symbols,
+        }
+    }
+
+    /// Finds the source of the loaded program, going by the SHA-1 of the ROM.
+    pub fn load(program_path: &str, rom_hash: &str) -> Option<Self> {
+        return SOURCE_EXTENSIONS.iter().find_map(|extension| {
+            let source_path = Path::new(program_path).with_extension(extension);
+
+            if source_path == Path::new(program_path) {
+                return None;
+            }
+
+            let source = fs::read_to_string(source_path).ok()?;
+            let assembly = assembler::assemble_with_debug_info(&source).ok()?;
+
+            if emulib::get_sha1(&assembly.program) != rom_hash {
+                return None;
+            }
+
+            return Some(Self::new(&source, assembly));
+        });
+    }
+
+    pub fn get_symbols(&self) -> &Symbols {
+        return &self.symbols;
+    }
+
+    // Only addresses that an instruction or run of data starts at have a line.
+    pub fn get_line(&self, address: u16) -> Option<(usize, &str)> {
+        let line_number = *self.source_lines.get(&address)?;
+        let line = self.lines.get(line_number - 1)?;
+        return Some((line_number, line.trim()));
+    }
+
+    // Lines without an instruction, such as comments, stand for the next line that has one.
+    pub fn get_address(&self, line_number: usize) -> Option<u16> {
+        return self
+            .source_lines
+            .iter()
+            .filter(|(_, source_line)| **source_line >= line_number)
+            .min_by_key(|(_, source_line)| **source_line)
+            .map(|(address, _)| *address);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_source() {
+        let directory = std::env::temp_dir().join("chip8rust_test_load_source");
+        fs::create_dir_all(&directory).unwrap();
+        let program_path = directory.join("count.ch8");
+        let program_path = program_path.to_str().unwrap();
+
+        let source = "; Counts V0 up forever.\nld v0, 0\n\nloop: add v0, 1\n  jp loop\n";
+        fs::write(directory.join("count.8o"), source).unwrap();
+        let program = assembler::assemble(source).unwrap();
+
+        let source_map = SourceMap::load(program_path, &emulib::get_sha1(&program)).unwrap();
+        assert_eq!(Some((4, "loop: add v0, 1")), source_map.get_line(0x202));
+        assert_eq!(Some((5, "jp loop")), source_map.get_line(0x204));
+        assert_eq!(Some("loop"), source_map.get_symbols().get_name(0x202));
+        assert_eq!(Some(0x200), source_map.get_address(1));
+        assert_eq!(Some(0x202), source_map.get_address(3));
+        assert_eq!(None, source_map.get_address(6));
+
+        // A source that no longer matches the program is left out.
+        assert_eq!(
+            None,
+            SourceMap::load(program_path, &emulib::get_sha1(b"other"))
+        );
+
+        let _ = fs::remove_dir_all(directory);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::savestate::SaveState;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const STATE_FILE_PATH: &str = "state.toml";
+pub const MAX_RECENT_PROGRAMS: usize = 10;
+pub const SAVE_STATE_DIRECTORY: &str = "saves";
+pub const SAVE_SLOT_COUNT: usize = 10;
+// Kept apart from the numbered slots, so it never overwrites a manual save.
+pub const AUTO_SAVE_SLOT: usize = 0;
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct State {
+    pub last_program_path: Option<String>,
+    #[serde(default)]
+    pub recent_programs: Vec<String>,
+    pub window: Option<WindowState>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WindowState {
+    pub width: u32,
+    pub height: u32,
+    pub x: Option<i32>,
+    pub y: Option<i32>,
+    pub fullscreen: bool,
+    pub monitor: Option<String>,
+    pub scale_factor: Option<f64>,
+}
+
+impl WindowState {
+    // The size is kept in physical pixels, so it is scaled to look the same on a monitor with
+    // different DPI settings.
+    pub fn get_scaled_size(&self, scale_factor: f64) -> (u32, u32) {
+        let Some(saved_scale_factor) = self.scale_factor.filter(|factor| *factor > 0.0) else {
+            return (self.width, self.height);
+        };
+
+        let scale = scale_factor / saved_scale_factor;
+
+        return (
+            (self.width as f64 * scale).round() as u32,
+            (self.height as f64 * scale).round() as u32,
+        );
+    }
+}
+
+pub fn load_state(path: &str) -> State {
+    if !Path::new(path).exists() {
+        return State::default();
+    }
+
+    let raw_state = match fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Warning: Could not read state at {path} ({e}).");
+            return State::default();
+        }
+    };
+
+    return match toml::from_str(&raw_state) {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("Warning: Could not parse state at {path} ({e}).");
+            State::default()
+        }
+    };
+}
+
+// Moves the program to the front of the list, most recent first.
+pub fn add_recent_program(recent_programs: &mut Vec<String>, program_path: &str) {
+    recent_programs.retain(|path| path != program_path);
+    recent_programs.insert(0, String::from(program_path));
+    recent_programs.truncate(MAX_RECENT_PROGRAMS);
+}
+
+pub fn save_state(path: &str, state: &State) {
+    let raw_state = match toml::to_string(state) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Warning: Could not serialise state ({e}).");
+            return;
+        }
+    };
+
+    if let Err(e) = fs::write(path, raw_state) {
+        eprintln!("Warning: Could not write state to {path} ({e}).");
+    }
+}
+
+// Slots are kept per ROM hash rather than path, so they follow the ROM if it is moved or renamed.
+pub fn get_save_slot_path(directory: &Path, rom_hash: &str, slot: usize) -> PathBuf {
+    return directory.join(rom_hash).join(format!("slot{slot}.c8s"));
+}
+
+pub fn write_save_slot(
+    directory: &Path,
+    rom_hash: &str,
+    slot: usize,
+    save_state: &SaveState,
+) -> bool {
+    let path = get_save_slot_path(directory, rom_hash, slot);
+
+    if let Some(parent) = path.parent()
+        && let Err(e) = fs::create_dir_all(parent)
+    {
+        eprintln!("Warning: Could not create {} ({e}).", parent.display());
+        return false;
+    }
+
+    if let Err(e) = fs::write(&path, save_state.to_bytes()) {
+        eprintln!(
+            "Warning: Could not write save state to {} ({e}).",
+            path.display()
+        );
+        return false;
+    }
+
+    return true;
+}
+
+pub fn read_save_slot(directory: &Path, rom_hash: &str, slot: usize) -> Option<SaveState> {
+    let path = get_save_slot_path(directory, rom_hash, slot);
+
+    if !path.exists() {
+        eprintln!("Warning: Save slot {slot} is empty.");
+        return None;
+    }
+
+    let bytes = match fs::read(&path) {
+        Ok(b) => b,
+        Err(e) => {
+            eprintln!(
+                "Warning: Could not read save state at {} ({e}).",
+                path.display()
+            );
+            return None;
+        }
+    };
+
+    return match SaveState::from_bytes(&bytes) {
+        Ok(save_state) => Some(save_state),
+        Err(e) => {
+            eprintln!(
+                "Warning: Could not load save state at {} ({e}).",
+                path.display()
+            );
+            None
+        }
+    };
+}
+
+// Empty slots are left as None, without the warning that loading one gives.
+pub fn read_save_slots(directory: &Path, rom_hash: &str) -> Vec<Option<SaveState>> {
+    return (1..=SAVE_SLOT_COUNT)
+        .map(
+            |slot| match get_save_slot_path(directory, rom_hash, slot).exists() {
+                true => read_save_slot(directory, rom_hash, slot),
+                false => None,
+            },
+        )
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load_state() {
+        let path = std::env::temp_dir().join("chip8rust_test_state.toml");
+        let path = path.to_str().unwrap();
+
+        let state = State {
+            last_program_path: Some(String::from("roms/test.ch8")),
+            recent_programs: vec![String::from("roms/test.ch8")],
+            window: Some(WindowState {
+                width: 640,
+                height: 320,
+                x: Some(-10),
+                y: None,
+                fullscreen: true,
+                monitor: Some(String::from("DP-1")),
+                scale_factor: Some(1.5),
+            }),
+        };
+
+        save_state(path, &state);
+        assert_eq!(state, load_state(path));
+
+        let _ = fs::remove_file(path);
+        assert_eq!(State::default(), load_state(path));
+    }
+
+    #[test]
+    fn test_scale_window_size() {
+        let mut window_state = WindowState {
+            width: 640,
+            height: 320,
+            x: None,
+            y: None,
+            fullscreen: false,
+            monitor: None,
+            scale_factor: Some(2.0),
+        };
+
+        assert_eq!((960, 480), window_state.get_scaled_size(3.0));
+        assert_eq!((640, 320), window_state.get_scaled_size(2.0));
+
+        // States saved before the scale factor was recorded keep their size.
+        window_state.scale_factor = None;
+        assert_eq!((640, 320), window_state.get_scaled_size(3.0));
+    }
+
+    #[test]
+    fn test_write_and_read_save_slot() {
+        let directory = std::env::temp_dir().join("chip8rust_test_saves");
+        let emulator = crate::emulator::EmulatorBuilder::default().build().unwrap();
+        let save_state = emulator.save_state();
+
+        assert!(write_save_slot(&directory, "abc", 3, &save_state));
+        assert_eq!(
+            Some(save_state.clone()),
+            read_save_slot(&directory, "abc", 3)
+        );
+        assert_eq!(None, read_save_slot(&directory, "abc", 4));
+        assert_eq!(None, read_save_slot(&directory, "def", 3));
+
+        let slots = read_save_slots(&directory, "abc");
+        assert_eq!(SAVE_SLOT_COUNT, slots.len());
+        assert_eq!(Some(save_state), slots[2]);
+        assert!(slots.iter().filter(|slot| slot.is_some()).count() == 1);
+
+        let _ = fs::remove_dir_all(directory);
+    }
+
+    #[test]
+    fn test_add_recent_program() {
+        let mut recent_programs = Vec::new();
+
+        for i in 0..MAX_RECENT_PROGRAMS + 2 {
+            add_recent_program(&mut recent_programs, &format!("{i}.ch8"));
+        }
+
+        add_recent_program(&mut recent_programs, "5.ch8");
+
+        assert_eq!(MAX_RECENT_PROGRAMS, recent_programs.len());
+        assert_eq!("5.ch8", recent_programs[0]);
+        assert_eq!("11.ch8", recent_programs[1]);
+        assert_eq!(1, recent_programs.iter().filter(|p| *p == "5.ch8").count());
+    }
+}
@@ -0,0 +1,145 @@
+//! Names for addresses in a program, such as the labels it was assembled from, so the debugger
+//! and disassembler can show `sub_20C` rather than `0x20C`.
+//!
+//! A symbol file has one symbol per line, written `name = address`, such as `loop = 0x206`.
+//! Blank lines and anything after a `;` are skipped. The symbols for a program are read from a
+//! `.sym` file next to it with the same name, which `chip8rust asm --symbols` writes.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+const SYMBOLS_EXTENSION: &str = "sym";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Symbols {
+    names: BTreeMap<u16, String>,
+}
+
+impl Symbols {
+    // Where several names share an address, the first one is kept.
+    pub fn new(symbols: impl IntoIterator<Item = (String, u16)>) -> Self {
+        let mut names = BTreeMap::new();
+
+        for (name, address) in symbols {
+            names.entry(address).or_insert(name);
+        }
+
+        Self { names }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut symbols = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or("").trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let symbol = line.split_once('=').and_then(|(name, address)| {
+                let address = address.trim();
+                let address = match address.strip_prefix("0x") {
+                    Some(hex) => u16::from_str_radix(hex, 16).ok()?,
+                    None => address.parse().ok()?,
+                };
+                return Some((String::from(name.trim()), address));
+            });
+
+            match symbol {
+                Some(symbol) if !symbol.0.is_empty() => symbols.push(symbol),
+                _ => return Err(format!("Line {} is not a valid symbol ({line}).", i + 1)),
+            }
+        }
+
+        return Ok(Self::new(symbols));
+    }
+
+    /// Reads the symbols next to the program, if it has any. A file that can't be parsed is
+    /// reported and skipped.
+    pub fn load(program_path: &str) -> Option<Self> {
+        let symbols_path = Path::new(program_path).with_extension(SYMBOLS_EXTENSION);
+
+        if symbols_path == Path::new(program_path) {
+            return None;
+        }
+
+        let text = fs::read_to_string(&symbols_path).ok()?;
+
+        return match Self::parse(&text) {
+            Ok(symbols) => Some(symbols),
+            Err(e) => {
+                eprintln!(
+                    "Warning: Ignoring symbols in {} ({e})",
+                    symbols_path.display()
+                );
+                None
+            }
+        };
+    }
+
+    pub fn get_path(program_path: &str) -> String {
+        return Path::new(program_path)
+            .with_extension(SYMBOLS_EXTENSION)
+            .to_string_lossy()
+            .into_owned();
+    }
+
+    pub fn format(&self) -> String {
+        return self
+            .names
+            .iter()
+            .map(|(address, name)| format!("{name} = {address:#05X}\n"))
+            .collect();
+    }
+
+    pub fn get_name(&self, address: u16) -> Option<&str> {
+        return self.names.get(&address).map(String::as_str);
+    }
+
+    pub fn get_address(&self, name: &str) -> Option<u16> {
+        return self
+            .names
+            .iter()
+            .find(|(_, symbol)| symbol.as_str() == name)
+            .map(|(address, _)| *address);
+    }
+
+    /// Names the address after the nearest symbol at or before it, such as `loop+4`, or gives it
+    /// in hex if there is none.
+    pub fn format_address(&self, address: u16) -> String {
+        return match self.names.range(..=address).next_back() {
+            Some((symbol_address, name)) if *symbol_address == address => name.clone(),
+            Some((symbol_address, name)) => format!("{name}+{}", address - symbol_address),
+            None => format!("{address:03X}"),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format_symbols() {
+        let symbols =
+            Symbols::parse("; From the assembler\nmain = 0x200\n\nloop = 0x206\nalso_loop = 518\n")
+                .unwrap();
+
+        assert_eq!(Some("loop"), symbols.get_name(0x206));
+        assert_eq!(Some(0x206), symbols.get_address("loop"));
+        assert_eq!("main", symbols.format_address(0x200));
+        assert_eq!("loop+4", symbols.format_address(0x20A));
+        assert_eq!("1FE", symbols.format_address(0x1FE));
+        assert_eq!("main = 0x200\nloop = 0x206\n", symbols.format());
+        assert_eq!(Ok(symbols.clone()), Symbols::parse(&symbols.format()));
+
+        assert!(
+            Symbols::parse("main = 0x200\nloop 0x206")
+                .unwrap_err()
+                .starts_with("Line 2")
+        );
+        assert!(Symbols::parse("= 0x206").is_err());
+    }
+}
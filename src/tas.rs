@@ -0,0 +1,185 @@
+//! Frame dumps, for checking that a tool-assisted run really plays out as recorded.
+//!
+//! An input file has one line per 60 Hz frame, listing the hex digits of the keys held during
+//! that frame, or `-` for none. Blank lines and lines starting with `#` are skipped.
+//!
+//! A dump has one line per frame too, with the frame number, the keys held and the SHA-1 of the
+//! display at the end of the frame. Frames are run with execute_frame and a seeded random number
+//! generator, so the same ROM, config and inputs always give the same dump, and anyone can re-run
+//! a dump's inputs to check it.
+
+use crate::emulator::Emulator;
+use crate::emulib;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameRecord {
+    pub frame: usize,
+    pub keys: u16,
+    pub framebuffer_hash: String,
+}
+
+pub fn parse_keys(text: &str) -> Option<u16> {
+    if text == "-" {
+        return Some(0);
+    }
+
+    return text.chars().try_fold(0, |keys, digit| {
+        return Some(keys | 1 << digit.to_digit(16)?);
+    });
+}
+
+pub fn format_keys(keys: u16) -> String {
+    if keys == 0 {
+        return String::from("-");
+    }
+
+    return (0..16)
+        .filter(|key| keys & 1 << key != 0)
+        .map(|key| format!("{key:X}"))
+        .collect();
+}
+
+fn get_lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    return text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+}
+
+pub fn parse_inputs(text: &str) -> Result<Vec<u16>, String> {
+    return get_lines(text)
+        .map(|(number, line)| {
+            parse_keys(line).ok_or_else(|| format!("Line {number} has invalid keys ({line})."))
+        })
+        .collect();
+}
+
+pub fn parse_dump(text: &str) -> Result<Vec<FrameRecord>, String> {
+    return get_lines(text)
+        .map(|(number, line)| {
+            let invalid_line = || format!("Line {number} is not a valid frame ({line}).");
+            let parts: Vec<&str> = line.split_whitespace().collect();
+
+            let [frame, keys, framebuffer_hash] = parts[..] else {
+                return Err(invalid_line());
+            };
+
+            return Ok(FrameRecord {
+                frame: frame.parse().map_err(|_| invalid_line())?,
+                keys: parse_keys(keys).ok_or_else(invalid_line)?,
+                framebuffer_hash: String::from(framebuffer_hash),
+            });
+        })
+        .collect();
+}
+
+pub fn get_framebuffer_hash(framebuffer: &[bool]) -> String {
+    let pixels: Vec<u8> = framebuffer.iter().map(|pixel| *pixel as u8).collect();
+    return emulib::get_sha1(&pixels);
+}
+
+pub fn format_dump(emulator: &Emulator, records: &[FrameRecord]) -> String {
+    let mut dump = format!(
+        "# chip8rust {} frame dump\n# rom_sha1 = {}\n# frame keys framebuffer_sha1\n",
+        env!("CARGO_PKG_VERSION"),
+        emulator
+            .get_rom_hash()
+            .unwrap_or_else(|| String::from("none"))
+    );
+
+    for record in records {
+        dump.push_str(&format!(
+            "{} {} {}\n",
+            record.frame,
+            format_keys(record.keys),
+            record.framebuffer_hash
+        ));
+    }
+
+    return dump;
+}
+
+/// Runs frame_count frames from the current state, holding each frame's keys from the inputs,
+/// and no keys once they run out. Stops early if the emulator halts.
+pub fn run_frames(emulator: &Emulator, inputs: &[u16], frame_count: usize) -> Vec<FrameRecord> {
+    emulator.seed_randomness();
+
+    let mut records = Vec::new();
+    let mut held_keys = 0;
+
+    for frame in 0..frame_count {
+        if !emulator.is_running() {
+            break;
+        }
+
+        let keys = inputs.get(frame).copied().unwrap_or(0);
+
+        // Only changes are passed on, so held keys don't count as new presses.
+        for key in 0..16 {
+            match (keys & 1 << key != 0, held_keys & 1 << key != 0) {
+                (true, false) => emulator.press_key(key),
+                (false, true) => emulator.release_key(key),
+                _ => (),
+            }
+        }
+
+        held_keys = keys;
+        emulator.execute_frame();
+
+        records.push(FrameRecord {
+            frame,
+            keys,
+            framebuffer_hash: get_framebuffer_hash(&emulator.framebuffer()),
+        });
+    }
+
+    return records;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::EmulatorBuilder;
+
+    #[test]
+    fn test_parse_and_format_keys() {
+        assert_eq!(Some(0), parse_keys("-"));
+        assert_eq!(Some(0b1000_0000_0010_0001), parse_keys("50F"));
+        assert_eq!(None, parse_keys("5G"));
+        assert_eq!("05F", format_keys(0b1000_0000_0010_0001));
+        assert_eq!("-", format_keys(0));
+
+        assert_eq!(
+            Ok(vec![0, 1 << 0xA, 0]),
+            parse_inputs("# menu\n-\nA\n\n-\n")
+        );
+        assert!(parse_inputs("-\nZ\n").unwrap_err().starts_with("Line 2"));
+    }
+
+    #[test]
+    fn test_dump_is_reproducible() {
+        // LD V0, K; LD F, V0; CLS; DRW V1, V1, 5; RND V2, 0xFF; JP 0x200
+        let rom = [
+            0xF0, 0x0A, 0xF0, 0x29, 0x00, 0xE0, 0xD1, 0x15, 0xC2, 0xFF, 0x12, 0x00,
+        ];
+        // Keys only count once the program is waiting for them, a frame after the last draw.
+        let inputs = parse_inputs("-\n3\n-\n-\n-\n7\n-\n").unwrap();
+
+        let run = || {
+            let emulator = EmulatorBuilder::default().build().unwrap();
+            assert!(emulator.load_rom_bytes(&rom).is_ok());
+            let records = run_frames(&emulator, &inputs, 10);
+            return (format_dump(&emulator, &records), records);
+        };
+
+        let (dump, records) = run();
+        assert_eq!(10, records.len());
+        assert_eq!(1 << 3, records[1].keys);
+        assert_ne!(records[0].framebuffer_hash, records[3].framebuffer_hash);
+        assert_ne!(records[3].framebuffer_hash, records[8].framebuffer_hash);
+
+        assert_eq!(dump, run().0);
+        assert_eq!(Ok(records), parse_dump(&dump));
+    }
+}
@@ -0,0 +1,104 @@
+//! A log of when each part of the emulator does its work, for diagnosing drift between the CPU,
+//! timer and render threads on slow machines.
+//!
+//! Each line is the time since the log was created, in seconds from a monotonic clock, followed
+//! by what happened: `cpu_batch` with the batch's length in seconds, `delay_timer` or
+//! `sound_timer` with the timer's new value, or `present`. For example,
+//! `12.016702511 delay_timer 41`.
+
+use crate::observer::EmulatorObserver;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub struct TickLog {
+    start: Instant,
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl TickLog {
+    pub fn create(path: &str) -> io::Result<Arc<Self>> {
+        let file = File::create(path)?;
+
+        return Ok(Arc::new(Self {
+            start: Instant::now(),
+            writer: Mutex::new(BufWriter::new(file)),
+        }));
+    }
+
+    // Timestamped before waiting for the lock, so lines from different threads may be slightly
+    // out of order.
+    fn log(&self, event: fmt::Arguments<'_>) {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let mut writer = self.writer.lock().unwrap();
+        let _ = writeln!(writer, "{elapsed:.9} {event}");
+    }
+
+    pub fn flush(&self) {
+        if let Err(e) = self.writer.lock().unwrap().flush() {
+            eprintln!("Warning: Could not write the tick log ({e}).");
+        }
+    }
+}
+
+impl EmulatorObserver for TickLog {
+    fn on_cpu_batch(&self, duration: Duration) {
+        self.log(format_args!("cpu_batch {:.9}", duration.as_secs_f64()));
+    }
+
+    fn on_delay_timer_tick(&self, value: u8) {
+        self.log(format_args!("delay_timer {value}"));
+    }
+
+    fn on_sound_timer_tick(&self, value: u8) {
+        self.log(format_args!("sound_timer {value}"));
+    }
+
+    fn on_present(&self) {
+        self.log(format_args!("present"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::EmulatorBuilder;
+    use std::fs;
+
+    #[test]
+    fn test_tick_log() {
+        let path = std::env::temp_dir().join("chip8rust_test_tick_log.txt");
+        let path = path.to_str().unwrap();
+        let tick_log = TickLog::create(path).unwrap();
+
+        let emulator = EmulatorBuilder::default().build().unwrap();
+        emulator.add_observer(tick_log.clone());
+
+        // LD V0, 2; LD DT, V0; JP 0x204
+        assert!(
+            emulator
+                .load_rom_bytes(&[0x60, 0x02, 0xF0, 0x15, 0x12, 0x04])
+                .is_ok()
+        );
+        assert!(emulator.run_for(Duration::from_millis(100)));
+        emulator.notify_present();
+        tick_log.flush();
+
+        let log = fs::read_to_string(path).unwrap();
+        let events: Vec<&str> = log
+            .lines()
+            .map(|line| line.split_once(' ').unwrap().1)
+            .collect();
+        assert_eq!(vec!["delay_timer 1", "delay_timer 0", "present"], events);
+
+        let times: Vec<f64> = log
+            .lines()
+            .map(|line| line.split(' ').next().unwrap().parse().unwrap())
+            .collect();
+        assert!(times.is_sorted());
+
+        let _ = fs::remove_file(path);
+    }
+}
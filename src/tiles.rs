@@ -0,0 +1,152 @@
+use crate::emulator::Emulator;
+use crate::overlay::{self, Canvas};
+use std::sync::Arc;
+use winit::keyboard::KeyCode;
+use winit_input_helper::WinitInputHelper;
+
+const CLOSE_VIEWER_KEY: KeyCode = KeyCode::Escape;
+const PREVIOUS_BYTE_KEY: KeyCode = KeyCode::ArrowLeft;
+const NEXT_BYTE_KEY: KeyCode = KeyCode::ArrowRight;
+const PREVIOUS_STRIP_KEY: KeyCode = KeyCode::ArrowUp;
+const NEXT_STRIP_KEY: KeyCode = KeyCode::ArrowDown;
+const PREVIOUS_PAGE_KEY: KeyCode = KeyCode::PageUp;
+const NEXT_PAGE_KEY: KeyCode = KeyCode::PageDown;
+const GO_TO_INDEX_KEY: KeyCode = KeyCode::KeyI;
+
+const MEMORY_SIZE: usize = 0x1000;
+const PROGRAM_START_ADDRESS: usize = 0x200;
+const STRIP_ROWS: usize = 64;
+const STRIP_COUNT: usize = 16;
+const PAGE_SIZE: usize = STRIP_ROWS * STRIP_COUNT;
+const PIXEL_SIZE: usize = 3;
+const TEXT_SCALE: usize = 2;
+const LABEL_SCALE: usize = 1;
+const ROW_HEIGHT: usize = 7 * TEXT_SCALE;
+const MENU_MARGIN: usize = 8;
+const BACKGROUND_COLOR: u32 = 0x101018;
+const STRIP_BACKGROUND_COLOR: u32 = 0x000000;
+const PIXEL_COLOR: u32 = 0xFFFFFF;
+const HINT_COLOR: u32 = 0x9999AA;
+
+/// Shows a page of memory as strips of 8-pixel-wide sprite rows, one byte per row, to find
+/// graphics in a ROM and spot when they get overwritten. The page is read afresh every frame.
+pub struct TileViewer {
+    emulator: Arc<Emulator>,
+    open: bool,
+    start: usize,
+}
+
+impl TileViewer {
+    pub fn new(emulator: Arc<Emulator>) -> Self {
+        Self {
+            emulator,
+            open: false,
+            start: PROGRAM_START_ADDRESS,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        return self.open;
+    }
+
+    // Opens where it was last left.
+    pub fn open(&mut self) {
+        self.open = true;
+    }
+
+    pub fn update(&mut self, input: &WinitInputHelper) {
+        if input.key_pressed(CLOSE_VIEWER_KEY) {
+            self.open = false;
+        } else if input.key_pressed(GO_TO_INDEX_KEY) {
+            self.start = self.emulator.get_index_reg() as usize % MEMORY_SIZE;
+        } else if let Some(offset) = [
+            (PREVIOUS_BYTE_KEY, MEMORY_SIZE - 1),
+            (NEXT_BYTE_KEY, 1),
+            (PREVIOUS_STRIP_KEY, MEMORY_SIZE - STRIP_ROWS),
+            (NEXT_STRIP_KEY, STRIP_ROWS),
+            (PREVIOUS_PAGE_KEY, MEMORY_SIZE - PAGE_SIZE),
+            (NEXT_PAGE_KEY, PAGE_SIZE),
+        ]
+        .iter()
+        .find(|(key, _)| input.key_pressed_os(*key))
+        .map(|(_, offset)| *offset)
+        {
+            self.start = (self.start + offset) % MEMORY_SIZE;
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<'_>) {
+        let (_, text_height) = overlay::get_text_size("", TEXT_SCALE);
+        let text_offset = (ROW_HEIGHT - text_height) / 2;
+        let (strip_width, strip_height) = overlay::get_sprite_size(STRIP_ROWS, PIXEL_SIZE);
+        let (_, label_height) = overlay::get_text_size("", LABEL_SCALE);
+        let strip_spacing = MENU_MARGIN;
+
+        let width = (strip_width + strip_spacing) * STRIP_COUNT + MENU_MARGIN * 3;
+        let height = ROW_HEIGHT * 2 + label_height + strip_height + MENU_MARGIN * 4;
+
+        canvas.fill_rect(MENU_MARGIN, MENU_MARGIN, width, height, BACKGROUND_COLOR);
+
+        let end = (self.start + PAGE_SIZE - 1) % MEMORY_SIZE;
+
+        overlay::draw_text(
+            canvas,
+            MENU_MARGIN * 2,
+            MENU_MARGIN * 2 + text_offset,
+            &format!(
+                "Memory {:03X}-{end:03X} - arrows and page keys to scroll, I to go to I",
+                self.start
+            ),
+            TEXT_SCALE,
+            HINT_COLOR,
+        );
+
+        let memory = self
+            .emulator
+            .peek_memory(self.start as u16, PAGE_SIZE as u16);
+        let strips_y = MENU_MARGIN * 3 + ROW_HEIGHT * 2;
+
+        for (i, strip) in memory.chunks(STRIP_ROWS).enumerate() {
+            let x = MENU_MARGIN * 2 + i * (strip_width + strip_spacing);
+
+            overlay::draw_text(
+                canvas,
+                x,
+                strips_y,
+                &format!("{:03X}", (self.start + i * STRIP_ROWS) % MEMORY_SIZE),
+                LABEL_SCALE,
+                HINT_COLOR,
+            );
+
+            let y = strips_y + label_height + MENU_MARGIN / 2;
+            canvas.fill_rect(x, y, strip_width, strip_height, STRIP_BACKGROUND_COLOR);
+            overlay::draw_sprite(canvas, x, y, strip, PIXEL_SIZE, PIXEL_COLOR);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::EmulatorBuilder;
+
+    #[test]
+    fn test_draw_shows_memory_from_start() {
+        let emulator = Arc::new(EmulatorBuilder::default().build().unwrap());
+        assert!(emulator.load_rom_bytes(&[0x80]).is_ok());
+
+        let viewer = TileViewer::new(emulator);
+        let (width, height) = (800, 400);
+        let mut buffer = vec![0; width * height];
+        viewer.draw(&mut Canvas::new(&mut buffer, width, height));
+
+        // The first byte of the program is the top row of the first strip, with its left pixel set.
+        let (_, label_height) = overlay::get_text_size("", LABEL_SCALE);
+        let x = MENU_MARGIN * 2;
+        let y = MENU_MARGIN * 3 + ROW_HEIGHT * 2 + label_height + MENU_MARGIN / 2;
+
+        assert_eq!(PIXEL_COLOR, buffer[y * width + x]);
+        assert_eq!(STRIP_BACKGROUND_COLOR, buffer[y * width + x + PIXEL_SIZE]);
+        assert_eq!(STRIP_BACKGROUND_COLOR, buffer[(y + PIXEL_SIZE) * width + x]);
+    }
+}
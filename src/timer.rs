@@ -1,56 +1,60 @@
-use crate::config::{DelayTimerConfig, SoundTimerConfig, ToneWaveform};
-use crate::emulib::Limiter;
-use rodio::source;
-use rodio::{OutputStream, Sink};
+use crate::backend::AudioSink;
+use crate::config::{DelayTimerConfig, SoundTimerConfig};
+use crate::lifecycle::Lifecycle;
+use crate::observer::Observers;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering};
 
 pub struct DelayTimer {
-    active: Arc<AtomicBool>,
+    observers: Arc<Observers>,
     config: DelayTimerConfig,
     value: AtomicU8,
+    // Every tick, counted even at 0, so the timer doubles as the frame clock.
+    ticks: AtomicU64,
 }
 
 impl DelayTimer {
-    pub fn try_new(active: Arc<AtomicBool>, config: DelayTimerConfig) -> Option<Arc<Self>> {
-        if config.delay_timer_decrement_rate <= 0.0 {
-            eprintln!("Error: The delay timer's decrement rate must be greater than zero.");
-            active.store(false, Ordering::Relaxed);
-            return None;
-        }
-
-        return Some(Arc::new(Self {
-            active,
+    pub fn new(observers: Arc<Observers>, config: DelayTimerConfig) -> Arc<Self> {
+        return Arc::new(Self {
+            observers,
             config,
             value: AtomicU8::new(0),
-        }));
+            ticks: AtomicU64::new(0),
+        });
     }
 
     #[cfg(test)]
-    pub fn new_default(active: Arc<AtomicBool>) -> Arc<Self> {
-        Self::try_new(
-            active,
+    pub fn new_default() -> Arc<Self> {
+        Self::new(
+            Observers::new(),
             DelayTimerConfig {
                 delay_timer_decrement_rate: 60.0,
             },
         )
-        .unwrap()
     }
 
-    pub fn run(&self) {
-        let mut limiter = Limiter::new(self.config.delay_timer_decrement_rate, true);
+    pub fn tick(&self) {
+        self.ticks.fetch_add(1, Ordering::Relaxed);
 
-        while self.active.load(Ordering::Relaxed) {
-            limiter.wait_if_early();
+        let ticked = self
+            .value
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| {
+                if v > 0 { Some(v - 1) } else { None }
+            });
 
-            let _ = self
-                .value
-                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| {
-                    if v > 0 { Some(v - 1) } else { None }
-                });
+        if let Ok(previous) = ticked {
+            self.observers.notify_delay_timer_tick(previous - 1);
         }
     }
 
+    pub fn get_decrement_rate(&self) -> f64 {
+        return self.config.delay_timer_decrement_rate;
+    }
+
+    pub fn get_ticks(&self) -> u64 {
+        return self.ticks.load(Ordering::Relaxed);
+    }
+
     pub fn get_value(&self) -> u8 {
         return self.value.load(Ordering::Relaxed);
     }
@@ -61,120 +65,151 @@ impl DelayTimer {
 }
 
 pub struct SoundTimer {
-    active: Arc<AtomicBool>,
+    lifecycle: Arc<Lifecycle>,
+    beeping: Arc<AtomicBool>,
+    observers: Arc<Observers>,
     config: SoundTimerConfig,
     value: AtomicU8,
-    _stream_handle: OutputStream,
-    sink: Sink,
+    audio_sink: Box<dyn AudioSink>,
 }
 
 impl SoundTimer {
-    pub fn try_new(active: Arc<AtomicBool>, config: SoundTimerConfig) -> Option<Arc<Self>> {
-        if config.sound_timer_decrement_rate <= 0.0 {
-            eprintln!("Error: The sound timer's decrement rate must be greater than zero.");
-            active.store(false, Ordering::Relaxed);
-            return None;
-        }
-
-        let stream_handle = match rodio::OutputStreamBuilder::open_default_stream() {
-            Ok(s) => s,
-            Err(e) => {
-                eprintln!("Error: Failed to open audio stream ({e}).");
-                return None;
-            }
-        };
-
-        let sink = rodio::Sink::connect_new(&stream_handle.mixer());
-        sink.pause();
-
-        match config.tone_waveform {
-            ToneWaveform::Sine => sink.append(source::SineWave::new(config.tone_frequency)),
-            ToneWaveform::Square => sink.append(source::SquareWave::new(config.tone_frequency)),
-            ToneWaveform::Triangle => sink.append(source::TriangleWave::new(config.tone_frequency)),
-            ToneWaveform::Sawtooth => sink.append(source::SawtoothWave::new(config.tone_frequency)),
-        };
-
-        return Some(Arc::new(Self {
-            active,
+    pub fn new(
+        lifecycle: Arc<Lifecycle>,
+        beeping: Arc<AtomicBool>,
+        observers: Arc<Observers>,
+        audio_sink: Box<dyn AudioSink>,
+        config: SoundTimerConfig,
+    ) -> Arc<Self> {
+        return Arc::new(Self {
+            lifecycle,
+            beeping,
+            observers,
             value: AtomicU8::new(0),
-            sink,
-            _stream_handle: stream_handle,
+            audio_sink,
             config,
-        }));
+        });
     }
 
     #[cfg(test)]
-    pub fn new_default(active: Arc<AtomicBool>) -> Arc<Self> {
-        Self::try_new(
-            active,
+    pub fn new_default(lifecycle: Arc<Lifecycle>) -> Arc<Self> {
+        use crate::backend::NullAudioSink;
+        use crate::config::{AudioBackend, ToneWaveform};
+
+        Self::new(
+            lifecycle,
+            Arc::new(AtomicBool::new(false)),
+            Observers::new(),
+            Box::new(NullAudioSink),
             SoundTimerConfig {
                 sound_timer_decrement_rate: 60.0,
                 tone_frequency: 440.0,
                 tone_waveform: ToneWaveform::Sine,
+                square_duty_cycle: 0.5,
+                mute_during_turbo: true,
+                minimum_tone_length: 0.05,
+                sample_rate: 0,
+                buffer_size: 0,
+                audio_backend: AudioBackend::Rodio,
             },
         )
-        .unwrap()
     }
 
-    pub fn run(&self) {
-        let mut limiter = Limiter::new(self.config.sound_timer_decrement_rate, true);
+    pub fn tick(&self) {
+        let ticked = self
+            .value
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| {
+                if v > 0 { Some(v - 1) } else { None }
+            });
+
+        if let Ok(previous) = ticked {
+            self.observers.notify_sound_timer_tick(previous - 1);
+        }
 
-        while self.active.load(Ordering::Relaxed) {
-            limiter.wait_if_early();
+        let beeping = self.value.load(Ordering::Relaxed) > 0;
+        let muted = self.config.mute_during_turbo && self.lifecycle.is_turbo();
 
-            let _ = self
-                .value
-                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| {
-                    if v > 0 { Some(v - 1) } else { None }
-                });
+        if beeping && !muted {
+            self.audio_sink.play();
+        } else {
+            self.audio_sink.pause();
+        }
 
-            if self.value.load(Ordering::Relaxed) > 0 {
-                self.sink.play();
-            } else {
-                self.sink.pause();
-            }
+        if self.beeping.swap(beeping, Ordering::Relaxed) != beeping {
+            self.observers.notify_beep(beeping);
         }
     }
 
+    pub fn get_decrement_rate(&self) -> f64 {
+        return self.config.sound_timer_decrement_rate;
+    }
+
+    pub fn get_value(&self) -> u8 {
+        return self.value.load(Ordering::Relaxed);
+    }
+
     pub fn set_value(&self, val: u8) {
         self.value.store(val, Ordering::Relaxed);
     }
+
+    // Used while paused when nothing else is ticking the timer, to stop any tone playing.
+    pub fn silence(&self) {
+        self.audio_sink.pause();
+    }
+
+    // Used by the program, so the tone starts straight away instead of on the next tick.
+    pub fn start(&self, val: u8) {
+        self.set_value(val);
+
+        if val > 0
+            && !self.lifecycle.is_paused()
+            && !(self.config.mute_during_turbo && self.lifecycle.is_turbo())
+        {
+            self.audio_sink.play();
+        }
+    }
+
+    pub fn is_beeping(&self) -> bool {
+        return self.beeping.load(Ordering::Relaxed);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::driver;
     use std::thread::{self, JoinHandle};
     use std::time::Duration;
 
-    fn create_delay_objects() -> (Arc<DelayTimer>, JoinHandle<()>, Arc<AtomicBool>) {
-        let active = Arc::new(AtomicBool::new(true));
-        let timer = DelayTimer::new_default(active.clone());
-        let timer_clone = timer.clone();
-        let handle = thread::spawn(move || timer_clone.run());
-        return (timer, handle, active);
+    fn create_delay_objects() -> (Arc<DelayTimer>, JoinHandle<()>, Arc<Lifecycle>) {
+        let lifecycle = Lifecycle::new();
+        let timer = DelayTimer::new_default();
+        let (timer_clone, lifecycle_clone) = (timer.clone(), lifecycle.clone());
+        let handle = thread::spawn(move || driver::run_delay_timer(&timer_clone, &lifecycle_clone));
+        return (timer, handle, lifecycle);
     }
 
-    // fn create_sound_objects() -> (Arc<SoundTimer>, JoinHandle<()>, Arc<AtomicBool>) {
-    //     let active = Arc::new(AtomicBool::new(true));
-    //     let timer = SoundTimer::new_default(active.clone());
-    //     let timer_clone = timer.clone();
-    //     let handle = thread::spawn(move || timer_clone.run());
-    //     return (timer, handle, active);
+    // fn create_sound_objects() -> (Arc<SoundTimer>, JoinHandle<()>, Arc<Lifecycle>) {
+    //     let lifecycle = Lifecycle::new();
+    //     let timer = SoundTimer::new_default(lifecycle.clone());
+    //     let (timer_clone, lifecycle_clone) = (timer.clone(), lifecycle.clone());
+    //     let handle =
+    //         thread::spawn(move || driver::run_sound_timer(&timer_clone, &lifecycle_clone));
+    //     return (timer, handle, lifecycle);
     // }
 
     #[test]
     fn test_delay_timer_decrement() {
-        let (timer, handle, active) = create_delay_objects();
+        let (timer, handle, lifecycle) = create_delay_objects();
 
         timer.set_value(5);
 
         thread::sleep(Duration::from_millis(150));
 
         assert_eq!(0, timer.get_value());
-        assert!(active.load(Ordering::Relaxed));
+        assert!(lifecycle.is_running());
 
-        active.store(false, Ordering::Relaxed);
+        lifecycle.shutdown();
         handle.join().unwrap();
     }
 }
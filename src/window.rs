@@ -1,20 +1,104 @@
+use crate::about::AboutDialog;
+use crate::backend::{Display, InputSource};
+use crate::chooser::RomChooser;
+use crate::config::RewindConfig;
+use crate::debugger::Debugger;
+use crate::emulator::Emulator;
+use crate::emulib::TimingHistory;
+use crate::framebuffer::Framebuffer;
 use crate::gpu::GPU;
 use crate::input::InputManager;
+use crate::lifecycle::{Lifecycle, ShutdownReason};
+use crate::macros::MacroPlayer;
+use crate::menu::{MenuBar, MenuItem};
+use crate::overlay::{self, Canvas};
+use crate::profiling;
+use crate::rewind::RewindBuffer;
+use crate::scanlines::{self, ScanlineCache};
+use crate::settings::SettingsMenu;
+use crate::slots::SlotPicker;
+use crate::state::{self, WindowState};
+use crate::tiles::TileViewer;
 use softbuffer::{Buffer, Context, Surface};
 use std::cmp;
+use std::fs;
 use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
 use winit::application::ApplicationHandler;
-use winit::dpi::PhysicalSize;
-use winit::event::{DeviceEvent, DeviceId, StartCause, WindowEvent};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::{DeviceEvent, DeviceId, ElementState, MouseButton, StartCause, WindowEvent};
 use winit::event_loop::ActiveEventLoop;
-use winit::window::{Window, WindowButtons, WindowId};
+use winit::keyboard::{Key, KeyCode};
+use winit::monitor::MonitorHandle;
+use winit::window::{Fullscreen, Window, WindowButtons, WindowId};
 use winit_input_helper::WinitInputHelper;
 
 const WINDOW_TITLE: &str = "CHIP-8 Emulator";
 const BASE_RESOLUTION_SCALAR: usize = 20;
+// With Shift held, inverts the colors instead.
+const CYCLE_PALETTE_HOTKEY: KeyCode = KeyCode::F12;
+const SETTINGS_MENU_HOTKEY: KeyCode = KeyCode::Escape;
+const FULLSCREEN_HOTKEY: KeyCode = KeyCode::F11;
+// Used with the control key.
+const OPEN_ROM_HOTKEY: KeyCode = KeyCode::KeyO;
+// Also needs Ctrl held.
+const KEYPAD_OVERLAY_HOTKEY: KeyCode = KeyCode::KeyK;
+// Also needs Ctrl held.
+const MAGNIFIER_HOTKEY: KeyCode = KeyCode::KeyI;
+// Also needs Ctrl held.
+const DEBUGGER_HOTKEY: KeyCode = KeyCode::KeyD;
+// Also needs Ctrl held.
+const DEBUGGER_STEP_HOTKEY: KeyCode = KeyCode::KeyN;
+const DEBUGGER_STEP_BACK_HOTKEY: KeyCode = KeyCode::KeyB;
+const DEBUGGER_STEP_OUT_HOTKEY: KeyCode = KeyCode::KeyU;
+// Also needs Ctrl held.
+const TILE_VIEWER_HOTKEY: KeyCode = KeyCode::KeyM;
+// Released on its own, like the menu key in most desktop applications.
+const MENU_BAR_HOTKEY: KeyCode = KeyCode::AltLeft;
+const MENU_BAR_BUTTON: MouseButton = MouseButton::Right;
+// Held to step back through the history, one snapshot per snapshot interval.
+const REWIND_HOTKEY: KeyCode = KeyCode::Backspace;
+// Held to run the program as fast as possible.
+const TURBO_HOTKEY: KeyCode = KeyCode::Tab;
+// Pressed on their own to load the slot, or with shift to save to it.
+const SAVE_SLOT_HOTKEYS: [KeyCode; state::SAVE_SLOT_COUNT] = [
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+];
+// Each press multiplies or divides instructions_per_second by SPEED_SCALE.
+const SPEED_UP_HOTKEYS: [KeyCode; 2] = [KeyCode::Equal, KeyCode::NumpadAdd];
+const SLOW_DOWN_HOTKEYS: [KeyCode; 2] = [KeyCode::Minus, KeyCode::NumpadSubtract];
+const SPEED_SCALE: f64 = 1.25;
+const MIN_INSTRUCTIONS_PER_SECOND: f64 = 1.0;
+const OVERLAY_REFRESH_INTERVAL: Duration = Duration::from_nanos(1_000_000_000 / 60);
+// Used until the monitor reports its refresh rate, and for monitors that never do.
+const DEFAULT_REFRESH_PERIOD: Duration = Duration::from_nanos(1_000_000_000 / 60);
+const OVERLAY_MARGIN: usize = 4;
+const STATUS_BAR_TEXT_SCALE: usize = 2;
+const STATUS_BAR_HEIGHT: usize = 5 * STATUS_BAR_TEXT_SCALE + OVERLAY_MARGIN * 2;
+const STATUS_BAR_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+const STATUS_BAR_COLOR: u32 = 0x101018;
+const STATUS_BAR_TEXT_COLOR: u32 = 0xFFFFFF;
+const FRAME_TIME_GRAPH_COLOR: u32 = 0x33CC66;
+const CPU_BATCH_TIME_GRAPH_COLOR: u32 = 0xFF9933;
+const DRAW_LATENCY_GRAPH_COLOR: u32 = 0x3399FF;
+// About a minute of frames at 60 Hz, for the summary printed on exit.
+const DRAW_LATENCY_HISTORY_LENGTH: usize = 3600;
+const FRAME_PACING_TEXT_SCALE: usize = 2;
+const NOTICE_DURATION: Duration = Duration::from_secs(2);
+const NOTICE_TEXT_SCALE: usize = 2;
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
 
 struct Size {
     pub width: usize,
@@ -38,8 +122,6 @@ impl Size {
 
 struct Position {
     pub index: usize,
-    pub x: usize,
-    pub y: usize,
     screen_width: usize,
 }
 
@@ -47,106 +129,528 @@ impl Position {
     pub fn from_coords(x: usize, y: usize, screen_width: usize) -> Self {
         Self {
             index: screen_width * y + x,
-            x,
-            y,
-            screen_width,
-        }
-    }
-
-    pub fn from_index(index: usize, screen_width: usize) -> Self {
-        Self {
-            index,
-            x: index % screen_width,
-            y: index / screen_width,
             screen_width,
         }
     }
 
-    fn update_index(&mut self) {
-        self.index = self.screen_width * self.y + self.x;
+    pub fn get_screen_width(&self) -> usize {
+        return self.screen_width;
     }
+}
 
-    pub fn scale(mut self, factor: usize) -> Self {
-        self.screen_width *= factor;
-        self.x *= factor;
-        self.y *= factor;
-        self.update_index();
-        return self;
+impl InputSource for WinitInputHelper {
+    fn is_key_pressed(&self, key: &str) -> bool {
+        return self.key_pressed_logical(Key::Character(key));
     }
 
-    pub fn add_padding(mut self, x_margin: usize, y_margin: usize) -> Self {
-        self.screen_width += x_margin * 2;
-        self.x += x_margin;
-        self.y += y_margin;
-        self.update_index();
-        return self;
+    fn is_key_released(&self, key: &str) -> bool {
+        return self.key_released_logical(Key::Character(key));
     }
+}
 
-    pub fn get_screen_width(&self) -> usize {
-        return self.screen_width;
-    }
+fn get_modified_time(path: &str) -> Option<SystemTime> {
+    return fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok();
 }
 
 pub struct WindowManager {
-    active: Arc<AtomicBool>,
+    emulator: Arc<Emulator>,
+    lifecycle: Arc<Lifecycle>,
     gpu: Arc<GPU>,
     input_manager: Arc<InputManager>,
+    cpu_batch_times: Arc<TimingHistory>,
+    frame_times: TimingHistory,
+    draw_latencies: TimingHistory,
+    last_render: Instant,
+    last_refresh: Instant,
+    refresh_period: Duration,
+    status_bar_height: usize,
+    instruction_sample: (Instant, u64),
+    instructions_per_second: f64,
     window: Option<Rc<Window>>,
     base_size: Size,
     size_factor: usize,
     window_size: Size,
+    rendered_colors: (u32, u32, u32),
+    settings_menu: SettingsMenu,
+    // Whether the emulator was running when the settings menu opened.
+    resume_after_settings: bool,
+    menu_bar: MenuBar,
+    about_dialog: AboutDialog,
+    tile_viewer: TileViewer,
+    rom_chooser: RomChooser,
+    slot_picker: SlotPicker,
+    debugger: Debugger,
+    macro_player: MacroPlayer,
+    program_path: Option<String>,
+    recent_programs: Vec<String>,
+    watch: bool,
+    watched_modified: Option<SystemTime>,
+    last_watch_check: Instant,
+    auto_save_state: bool,
+    rewind_buffer: Option<RewindBuffer>,
+    snapshot_interval: Duration,
+    last_snapshot: Instant,
+    rewinding: bool,
+    notice: Option<(String, Instant)>,
+    show_keypad: bool,
+    show_magnifier: bool,
+    // The framebuffer as of the frame being drawn.
+    frame: Framebuffer,
+    scanline_cache: ScanlineCache,
+    window_state: Option<WindowState>,
     input: WinitInputHelper,
     context: Option<Context<Rc<Window>>>,
     surface: Option<Surface<Rc<Window>, Rc<Window>>>,
 }
 
 impl WindowManager {
-    pub fn new(active: Arc<AtomicBool>, gpu: Arc<GPU>, input_manager: Arc<InputManager>) -> Self {
-        let (base_width, base_height) = gpu.get_screen_resolution();
+    pub fn new(
+        emulator: Arc<Emulator>,
+        settings_menu: SettingsMenu,
+        window_state: Option<WindowState>,
+        recent_programs: Vec<String>,
+        auto_save_state: bool,
+        rewind_config: RewindConfig,
+    ) -> Self {
+        let gpu = emulator.get_gpu();
+
+        let (base_width, base_height) = match gpu.get_display_rotation() {
+            90 | 270 => {
+                let (width, height) = gpu.get_screen_resolution();
+                (height, width)
+            }
+            _ => gpu.get_screen_resolution(),
+        };
+
+        let (aspect_width, aspect_height) = gpu.get_pixel_aspect_ratio();
+        let base_width = base_width * aspect_width;
+        let base_height = base_height * aspect_height;
 
         let base_size = Size::new(base_width, base_height);
+        let colors = (
+            gpu.get_active_color(),
+            gpu.get_inactive_color(),
+            gpu.get_border_color(),
+        );
+
+        // The status bar sits under the display, so the window grows to fit it.
+        let status_bar_height = match gpu.should_show_status_bar() {
+            true => STATUS_BAR_HEIGHT,
+            false => 0,
+        };
+
+        let (screen_width, screen_height) = gpu.get_screen_resolution();
 
         let window_size = Size::new(
             base_width.saturating_mul(BASE_RESOLUTION_SCALAR),
-            base_height.saturating_mul(BASE_RESOLUTION_SCALAR),
+            base_height.saturating_mul(BASE_RESOLUTION_SCALAR) + status_bar_height,
         );
 
+        let menu_bar = MenuBar::new(emulator.get_lifecycle());
+        let about_dialog = AboutDialog::new(emulator.clone());
+        let debugger = Debugger::new(emulator.clone());
+        let tile_viewer = TileViewer::new(emulator.clone());
+        let macro_player = MacroPlayer::new(&emulator.get_input_manager().get_config().macros);
+
         return Self {
-            active,
+            lifecycle: emulator.get_lifecycle(),
             gpu,
-            input_manager,
+            input_manager: emulator.get_input_manager(),
+            cpu_batch_times: emulator.get_cpu().batch_times.clone(),
+            emulator,
+            frame_times: TimingHistory::new(overlay::TIMING_HISTORY_LENGTH),
+            draw_latencies: TimingHistory::new(DRAW_LATENCY_HISTORY_LENGTH),
+            last_render: Instant::now(),
+            last_refresh: Instant::now(),
+            refresh_period: DEFAULT_REFRESH_PERIOD,
+            status_bar_height,
+            instruction_sample: (Instant::now(), 0),
+            instructions_per_second: 0.0,
             window: None,
             base_size,
             window_size,
             size_factor: BASE_RESOLUTION_SCALAR,
+            rendered_colors: colors,
+            settings_menu,
+            resume_after_settings: false,
+            menu_bar,
+            about_dialog,
+            tile_viewer,
+            rom_chooser: RomChooser::new(),
+            slot_picker: SlotPicker::new(),
+            debugger,
+            macro_player,
+            program_path: None,
+            watch: false,
+            watched_modified: None,
+            last_watch_check: Instant::now(),
+            recent_programs,
+            auto_save_state,
+            rewind_buffer: rewind_config
+                .enabled
+                .then(|| RewindBuffer::from_config(&rewind_config)),
+            snapshot_interval: Duration::from_secs_f64(rewind_config.snapshot_interval),
+            last_snapshot: Instant::now(),
+            rewinding: false,
+            notice: None,
+            show_keypad: false,
+            show_magnifier: false,
+            frame: Framebuffer::new(screen_width, screen_height),
+            scanline_cache: ScanlineCache::new(),
+            window_state,
             input: WinitInputHelper::new(),
             context: None,
             surface: None,
         };
     }
 
+    pub fn get_program_path(&self) -> Option<String> {
+        return self.program_path.clone();
+    }
+
+    // Called once the program has loaded, so an auto-save of it can be picked up.
+    pub fn set_program_path(&mut self, program_path: String) {
+        state::add_recent_program(&mut self.recent_programs, &program_path);
+        self.watched_modified = get_modified_time(&program_path);
+        self.debugger.load_debug_info(&program_path);
+        self.program_path = Some(program_path);
+        self.clear_rewind_history();
+        self.restore_auto_save();
+    }
+
+    // Reloads the program whenever its file changes, for when it is being rebuilt as it runs.
+    pub fn set_watch(&mut self, watch: bool) {
+        self.watch = watch;
+    }
+
+    // Each location is an address, a source line or a symbol, found again in every program loaded.
+    pub fn set_breakpoint_locations(&mut self, locations: Vec<String>) {
+        self.debugger.set_breakpoint_locations(locations);
+    }
+
+    pub fn get_recent_programs(&self) -> Vec<String> {
+        return self.recent_programs.clone();
+    }
+
+    // The emulator is paused while choosing, as there may not be a program to run yet.
+    pub fn open_rom_chooser(&mut self, failed_path: Option<&str>, error: Option<String>) {
+        let directory = failed_path
+            .or(self.program_path.as_deref())
+            .and_then(|path| Path::new(path).parent())
+            .filter(|parent| parent.is_dir())
+            .unwrap_or(Path::new("."));
+
+        self.emulator.pause();
+        self.rom_chooser
+            .open(directory, &self.recent_programs, error);
+    }
+
+    // Reloading the program also restores any memory it has modified since it started.
+    fn reset_program(&mut self) {
+        let Some(path) = self.program_path.clone() else {
+            return;
+        };
+
+        match self.emulator.load_rom(&path) {
+            Ok(()) => self.clear_rewind_history(),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                self.open_rom_chooser(Some(&path), Some(e.to_string()));
+            }
+        }
+    }
+
+    // A failed reload leaves the old program running, as the file may be caught half written.
+    fn update_watch(&mut self) {
+        if !self.watch || self.last_watch_check.elapsed() < WATCH_INTERVAL {
+            return;
+        }
+
+        self.last_watch_check = Instant::now();
+
+        let Some(path) = self.program_path.clone() else {
+            return;
+        };
+
+        let modified = get_modified_time(&path);
+
+        if modified.is_none() || modified == self.watched_modified {
+            return;
+        }
+
+        self.watched_modified = modified;
+
+        match self.emulator.load_rom(&path) {
+            Ok(()) => {
+                println!("Reloaded {path}.");
+                self.clear_rewind_history();
+                self.debugger.load_debug_info(&path);
+                self.show_notice(String::from("Reloaded program"));
+            }
+            Err(e) => eprintln!("Warning: Failed to reload the program ({e})."),
+        }
+    }
+
+    fn clear_rewind_history(&mut self) {
+        if let Some(rewind_buffer) = self.rewind_buffer.as_mut() {
+            rewind_buffer.clear();
+        }
+    }
+
+    // Snapshots are taken while running, and stepped back through while the hotkey is held.
+    fn update_rewind(&mut self) {
+        let Some(rewind_buffer) = self.rewind_buffer.as_mut() else {
+            return;
+        };
+
+        let held = self.input.key_held(REWIND_HOTKEY);
+
+        if held && !self.rewinding {
+            self.rewinding = true;
+            self.emulator.pause();
+        } else if !held && self.rewinding {
+            self.rewinding = false;
+            self.emulator.resume();
+        }
+
+        if self.last_snapshot.elapsed() < self.snapshot_interval
+            || (!self.rewinding && self.emulator.is_paused())
+            || self.emulator.get_rom_hash().is_none()
+        {
+            return;
+        }
+
+        self.last_snapshot = Instant::now();
+
+        if !self.rewinding {
+            rewind_buffer.push(&self.emulator.save_state());
+        } else if let Some(save_state) = rewind_buffer.pop()
+            && let Err(e) = self.emulator.load_state(&save_state)
+        {
+            eprintln!("Error: {e}");
+        }
+    }
+
+    // Shown briefly in the corner of the display, for changes made with hotkeys.
+    fn show_notice(&mut self, text: String) {
+        self.notice = Some((text, Instant::now()));
+    }
+
+    fn scale_speed(&mut self, factor: f64) {
+        let cpu = self.emulator.get_cpu();
+        let mut config = cpu.get_config_mut();
+
+        config.instructions_per_second = (config.instructions_per_second * factor)
+            .round()
+            .max(MIN_INSTRUCTIONS_PER_SECOND);
+
+        let instructions_per_second = config.instructions_per_second;
+        drop(config);
+
+        self.show_notice(format!("{instructions_per_second} instructions per second"));
+    }
+
+    // Checked even while a menu is open, so letting go of the hotkey there isn't missed.
+    fn update_turbo(&self) {
+        if self.input.key_pressed(TURBO_HOTKEY) {
+            self.emulator.set_turbo(true);
+        } else if self.input.key_released(TURBO_HOTKEY) {
+            self.emulator.set_turbo(false);
+        }
+    }
+
+    fn save_to_slot(&self, slot: usize) {
+        let Some(rom_hash) = self.emulator.get_rom_hash() else {
+            return;
+        };
+
+        let save_state = self.emulator.save_state();
+
+        if state::write_save_slot(
+            Path::new(state::SAVE_STATE_DIRECTORY),
+            &rom_hash,
+            slot,
+            &save_state,
+        ) {
+            println!("Saved to slot {slot}.");
+        }
+    }
+
+    fn load_from_slot(&self, slot: usize) {
+        let Some(rom_hash) = self.emulator.get_rom_hash() else {
+            return;
+        };
+
+        let Some(save_state) =
+            state::read_save_slot(Path::new(state::SAVE_STATE_DIRECTORY), &rom_hash, slot)
+        else {
+            return;
+        };
+
+        match self.emulator.load_state(&save_state) {
+            Ok(()) => println!("Loaded slot {slot}."),
+            Err(e) => eprintln!("Error: {e}"),
+        }
+    }
+
+    // The emulator is paused while the settings are open, so nothing is missed while changing them.
+    fn open_settings_menu(&mut self) {
+        self.resume_after_settings = !self.emulator.is_paused();
+        self.emulator.pause();
+        self.settings_menu.open();
+    }
+
+    // Keys released while the menu was open were never seen, so none are left held down.
+    fn close_settings_menu(&mut self) {
+        self.input_manager.reset();
+
+        if self.resume_after_settings {
+            self.emulator.resume();
+        }
+    }
+
+    // The emulator is paused while picking, so the state being replaced doesn't change under it.
+    fn open_slot_picker(&mut self) {
+        let Some(rom_hash) = self.emulator.get_rom_hash() else {
+            return;
+        };
+
+        self.emulator.pause();
+        self.slot_picker.open(state::read_save_slots(
+            Path::new(state::SAVE_STATE_DIRECTORY),
+            &rom_hash,
+        ));
+    }
+
+    fn restore_auto_save(&self) {
+        let directory = Path::new(state::SAVE_STATE_DIRECTORY);
+
+        let Some(rom_hash) = self.emulator.get_rom_hash().filter(|rom_hash| {
+            self.auto_save_state
+                && state::get_save_slot_path(directory, rom_hash, state::AUTO_SAVE_SLOT).exists()
+        }) else {
+            return;
+        };
+
+        let Some(save_state) = state::read_save_slot(directory, &rom_hash, state::AUTO_SAVE_SLOT)
+        else {
+            return;
+        };
+
+        match self.emulator.load_state(&save_state) {
+            Ok(()) => println!("Resumed from the auto-save."),
+            Err(e) => eprintln!("Error: {e}"),
+        }
+    }
+
+    // A program that halted with an error isn't worth resuming, so its last auto-save is kept.
+    fn write_auto_save(&self) {
+        if !self.auto_save_state
+            || matches!(
+                self.emulator.get_shutdown_reason(),
+                Some(ShutdownReason::Halted(_))
+            )
+        {
+            return;
+        }
+
+        if let Some(rom_hash) = self.emulator.get_rom_hash() {
+            state::write_save_slot(
+                Path::new(state::SAVE_STATE_DIRECTORY),
+                &rom_hash,
+                state::AUTO_SAVE_SLOT,
+                &self.emulator.save_state(),
+            );
+        }
+    }
+
+    fn select_menu_item(&mut self, item: MenuItem) {
+        match item {
+            MenuItem::OpenRom => self.open_rom_chooser(None, None),
+            MenuItem::Quit => self.lifecycle.shutdown(),
+            MenuItem::Reset => self.reset_program(),
+            MenuItem::QuickSave => self.save_to_slot(1),
+            MenuItem::QuickLoad => self.load_from_slot(1),
+            MenuItem::LoadState => self.open_slot_picker(),
+            MenuItem::TogglePause => match self.emulator.is_paused() {
+                true => self.emulator.resume(),
+                false => self.emulator.pause(),
+            },
+            MenuItem::OpenSettings => self.open_settings_menu(),
+            MenuItem::CyclePalette => self.gpu.cycle_palette(),
+            MenuItem::InvertColors => self.gpu.toggle_inverted_colors(),
+            MenuItem::ToggleFullscreen => self.toggle_fullscreen(),
+            MenuItem::ToggleKeypad => self.show_keypad = !self.show_keypad,
+            MenuItem::ToggleMagnifier => self.show_magnifier = !self.show_magnifier,
+            MenuItem::ToggleDebugger => self.debugger.toggle(),
+            MenuItem::OpenTileViewer => self.tile_viewer.open(),
+            MenuItem::About => self.about_dialog.open(self.program_path.as_deref()),
+        }
+    }
+
+    fn load_rom(&mut self, path: PathBuf) {
+        let path = path.to_string_lossy().into_owned();
+
+        match self.emulator.load_rom(&path) {
+            Ok(()) => {
+                println!("Loaded {path}.");
+                self.set_program_path(path);
+                self.rom_chooser.close();
+                self.emulator.resume();
+            }
+            Err(e) => {
+                eprintln!("Error: {e}");
+                self.rom_chooser.show_error(e.to_string());
+            }
+        }
+    }
+
+    // The frame goes through the Display trait like any other frontend's, which hands over a copy
+    // taken all at once, so a sprite drawn during the blit waits for the next frame rather than
+    // showing half drawn, and the CPU isn't held up while the frame is drawn.
     fn render(&mut self) {
+        let _span = profiling::span!("window.render");
+        let emulator = self.emulator.clone();
+        emulator.present_to(self);
+    }
+
+    fn draw_frame(&mut self) -> bool {
+        let status = self.get_status();
+        let hovered_cell = self
+            .show_magnifier
+            .then(|| self.get_hovered_cell())
+            .flatten();
+        let cursor = self.input.cursor();
+
         let Some(surface) = self.surface.as_mut() else {
-            return;
+            return false;
         };
 
+        let active_color = self.gpu.get_active_color();
+        let inactive_color = self.gpu.get_inactive_color();
         let border_color = self.gpu.get_border_color();
+        self.rendered_colors = (active_color, inactive_color, border_color);
 
         let (window_width, window_height) = self.window_size.get();
         let (base_width, base_height) = self.base_size.get();
         let size_factor = self.size_factor;
 
+        let display_height = window_height.saturating_sub(self.status_bar_height);
+
         let x_margin = (window_width - base_width * size_factor) / 2;
-        let y_margin = (window_height - base_height * size_factor) / 2;
+        let y_margin = (display_height - base_height * size_factor) / 2;
 
-        let gpu_buffer = self.gpu.get_framebuffer();
+        let rotation = self.gpu.get_display_rotation();
+        let (aspect_width, aspect_height) = self.gpu.get_pixel_aspect_ratio();
 
         let mut render_buffer = match surface.buffer_mut() {
             Ok(b) => b,
             Err(e) => {
                 eprintln!("Error: Failed to retrieve the render buffer ({e}).");
-                self.active.store(false, Ordering::Relaxed);
-                return;
+                self.lifecycle.shutdown();
+                return false;
             }
         };
 
@@ -175,33 +679,355 @@ impl WindowManager {
             );
 
             Self::render_square(
-                Position::from_coords(x_margin, window_height - y_margin, window_width),
+                Position::from_coords(x_margin, display_height - y_margin, window_width),
                 Size::new(window_width - (x_margin * 2), y_margin),
                 border_color,
                 &mut render_buffer,
             );
         }
 
-        for pixel in 0..gpu_buffer.len() {
-            let pos = Position::from_index(pixel, base_width)
-                .scale(size_factor)
-                .add_padding(x_margin, y_margin);
+        // Each row of the display is scaled into a scanline, which is then copied to every window
+        // row it covers. Scanlines are kept between frames, so unchanged rows are only copied.
+        let cell_width = size_factor * aspect_width;
+        let cell_height = size_factor * aspect_height;
+        let scanlines = self.scanline_cache.update(
+            &self.frame,
+            (active_color, inactive_color),
+            cell_width,
+            rotation,
+        );
 
-            let size = Size::new(self.size_factor, self.size_factor);
+        for (shown_y, scanline) in scanlines.iter().enumerate() {
+            let top = y_margin + shown_y * cell_height;
 
-            let color = match gpu_buffer[pixel] {
-                true => self.gpu.get_active_color(),
-                false => self.gpu.get_inactive_color(),
-            };
+            for row in top..top + cell_height {
+                let start_index = row * window_width + x_margin;
+                render_buffer[start_index..start_index + scanline.len()].copy_from_slice(scanline);
+            }
+        }
+
+        let now = Instant::now();
+        self.frame_times.record(now - self.last_render);
+        self.last_render = now;
+
+        if self.status_bar_height > 0 {
+            let mut canvas = Canvas::new(&mut render_buffer, window_width, window_height);
+            Self::render_status_bar(&mut canvas, display_height, window_width, &status);
+        }
+
+        if self.gpu.should_show_frame_pacing_overlay() {
+            let mut canvas = Canvas::new(&mut render_buffer, window_width, window_height);
+            Self::render_frame_pacing_overlay(
+                &mut canvas,
+                &self.frame_times,
+                &self.cpu_batch_times,
+                &self.draw_latencies,
+            );
+        }
+
+        // Shows the keys held as the emulator sees them, after key bindings and macros.
+        if self.show_keypad {
+            let mut canvas = Canvas::new(&mut render_buffer, window_width, window_height);
+            let (keypad_width, _) = overlay::get_keypad_size();
+
+            overlay::draw_keypad(
+                &mut canvas,
+                window_width.saturating_sub(keypad_width + OVERLAY_MARGIN),
+                OVERLAY_MARGIN,
+                self.input_manager.get_held_keys(),
+            );
+        }
+
+        if let (Some(cell), Some((cursor_x, cursor_y))) = (hovered_cell, cursor) {
+            let mut canvas = Canvas::new(&mut render_buffer, window_width, window_height);
+            let (magnifier_width, magnifier_height) = overlay::get_magnifier_size();
+
+            // Kept beside the cursor, rather than under it, and inside the window.
+            let x = cmp::min(
+                cursor_x as usize + OVERLAY_MARGIN * 4,
+                window_width.saturating_sub(magnifier_width),
+            );
+            let y = cmp::min(
+                cursor_y as usize + OVERLAY_MARGIN * 4,
+                window_height.saturating_sub(magnifier_height),
+            );
+
+            overlay::draw_magnifier(
+                &mut canvas,
+                x,
+                y,
+                &self.frame,
+                cell,
+                (active_color, inactive_color),
+            );
+        }
+
+        if self.debugger.is_open() {
+            let mut canvas = Canvas::new(&mut render_buffer, window_width, window_height);
+            self.debugger
+                .draw(&mut canvas, window_width, display_height);
+        }
+
+        if let Some((notice, _)) = self.notice.as_ref() {
+            let mut canvas = Canvas::new(&mut render_buffer, window_width, window_height);
+            Self::render_notice(&mut canvas, display_height, notice);
+        }
+
+        if self.settings_menu.is_open() {
+            let mut canvas = Canvas::new(&mut render_buffer, window_width, window_height);
+            self.settings_menu.draw(&mut canvas);
+        }
+
+        if self.rom_chooser.is_open() {
+            let mut canvas = Canvas::new(&mut render_buffer, window_width, window_height);
+            self.rom_chooser.draw(&mut canvas);
+        }
+
+        if self.slot_picker.is_open() {
+            let mut canvas = Canvas::new(&mut render_buffer, window_width, window_height);
+            self.slot_picker.draw(&mut canvas);
+        }
+
+        if self.tile_viewer.is_open() {
+            let mut canvas = Canvas::new(&mut render_buffer, window_width, window_height);
+            self.tile_viewer.draw(&mut canvas);
+        }
+
+        if self.about_dialog.is_open() {
+            let mut canvas = Canvas::new(&mut render_buffer, window_width, window_height);
+            self.about_dialog.draw(&mut canvas);
+        }
 
-            Self::render_square(pos, size, color, &mut render_buffer);
+        if self.menu_bar.is_open() {
+            let mut canvas = Canvas::new(&mut render_buffer, window_width, window_height);
+            self.menu_bar.draw(&mut canvas);
         }
 
+        let _present_span = profiling::span!("window.present");
+
         if let Err(e) = render_buffer.present() {
             eprintln!("Error: Failed to present the render buffer ({e}).");
-            self.active.store(false, Ordering::Relaxed);
+            self.lifecycle.shutdown();
+            return false;
+        }
+
+        self.emulator.notify_present();
+
+        if let Some(draw_time) = self.gpu.take_first_unpresented_draw() {
+            self.draw_latencies.record(draw_time.elapsed());
+        }
+
+        return true;
+    }
+
+    fn render_status_bar(canvas: &mut Canvas<'_>, y: usize, width: usize, status: &str) {
+        canvas.fill_rect(0, y, width, STATUS_BAR_HEIGHT, STATUS_BAR_COLOR);
+
+        overlay::draw_text(
+            canvas,
+            OVERLAY_MARGIN,
+            y + OVERLAY_MARGIN,
+            status,
+            STATUS_BAR_TEXT_SCALE,
+            STATUS_BAR_TEXT_COLOR,
+        );
+    }
+
+    fn render_notice(canvas: &mut Canvas<'_>, display_height: usize, notice: &str) {
+        let (text_width, text_height) = overlay::get_text_size(notice, NOTICE_TEXT_SCALE);
+        let height = text_height + OVERLAY_MARGIN * 2;
+        let y = display_height.saturating_sub(height + OVERLAY_MARGIN);
+
+        canvas.fill_rect(
+            OVERLAY_MARGIN,
+            y,
+            text_width + OVERLAY_MARGIN * 2,
+            height,
+            STATUS_BAR_COLOR,
+        );
+
+        overlay::draw_text(
+            canvas,
+            OVERLAY_MARGIN * 2,
+            y + OVERLAY_MARGIN,
+            notice,
+            NOTICE_TEXT_SCALE,
+            STATUS_BAR_TEXT_COLOR,
+        );
+    }
+
+    fn get_status(&self) -> String {
+        let state = match (self.emulator.is_paused(), self.emulator.is_turbo()) {
+            (true, _) => "paused",
+            (false, true) => "turbo",
+            (false, false) => "running",
+        };
+
+        return format!(
+            "ips {:.0}  fps {:.0}  dt {}  st {}  {state}",
+            self.instructions_per_second,
+            self.frame_times.get_rate(),
+            self.emulator.get_delay_timer(),
+            self.emulator.get_sound_timer(),
+        );
+    }
+
+    // Softbuffer can't wait for the monitor itself, so refreshes are counted off its reported
+    // refresh rate, which is looked up again each time in case the window moved to another one.
+    fn update_refresh(&mut self) {
+        let elapsed = self.last_refresh.elapsed();
+
+        if elapsed < self.refresh_period {
+            return;
+        }
+
+        // After a stall, such as the window being dragged, skip the missed refreshes.
+        if elapsed >= self.refresh_period * 2 {
+            self.last_refresh = Instant::now();
+        } else {
+            self.last_refresh += self.refresh_period;
+        }
+
+        self.emulator.on_refresh(self.refresh_period);
+
+        self.refresh_period = self
+            .window
+            .as_ref()
+            .and_then(|window| window.current_monitor())
+            .and_then(|monitor| monitor.refresh_rate_millihertz())
+            .map(|millihertz| Duration::from_secs_f64(1000.0 / millihertz as f64))
+            .unwrap_or(DEFAULT_REFRESH_PERIOD);
+    }
+
+    // Uses the CPU's instruction counter, so it reflects what actually ran rather than the config.
+    fn update_instructions_per_second(&mut self) {
+        let (sample_start, sample_instructions) = self.instruction_sample;
+        let elapsed = sample_start.elapsed();
+
+        if elapsed < STATUS_BAR_SAMPLE_INTERVAL {
             return;
         }
+
+        let executed_instructions = self.emulator.get_executed_instructions();
+        self.instructions_per_second = executed_instructions.saturating_sub(sample_instructions)
+            as f64
+            / elapsed.as_secs_f64();
+        self.instruction_sample = (Instant::now(), executed_instructions);
+    }
+
+    fn render_frame_pacing_overlay(
+        canvas: &mut Canvas<'_>,
+        frame_times: &TimingHistory,
+        cpu_batch_times: &TimingHistory,
+        draw_latencies: &TimingHistory,
+    ) {
+        let (_, graph_height) = overlay::get_timing_graph_size();
+
+        overlay::draw_timing_graph(
+            canvas,
+            OVERLAY_MARGIN,
+            OVERLAY_MARGIN,
+            &frame_times.get_samples(),
+            OVERLAY_REFRESH_INTERVAL,
+            FRAME_TIME_GRAPH_COLOR,
+        );
+
+        overlay::draw_timing_graph(
+            canvas,
+            OVERLAY_MARGIN,
+            OVERLAY_MARGIN * 2 + graph_height,
+            &cpu_batch_times.get_samples(),
+            OVERLAY_REFRESH_INTERVAL,
+            CPU_BATCH_TIME_GRAPH_COLOR,
+        );
+
+        // Only the latest latencies fit on the graph, but the summary covers them all.
+        let latencies = draw_latencies.get_samples();
+        let shown_latencies = &latencies[latencies
+            .len()
+            .saturating_sub(overlay::TIMING_HISTORY_LENGTH)..];
+
+        overlay::draw_timing_graph(
+            canvas,
+            OVERLAY_MARGIN,
+            OVERLAY_MARGIN * 3 + graph_height * 2,
+            shown_latencies,
+            OVERLAY_REFRESH_INTERVAL,
+            DRAW_LATENCY_GRAPH_COLOR,
+        );
+
+        if let Some(summary) = Self::get_draw_latency_summary(draw_latencies) {
+            overlay::draw_text(
+                canvas,
+                OVERLAY_MARGIN,
+                OVERLAY_MARGIN * 4 + graph_height * 3,
+                &summary,
+                FRAME_PACING_TEXT_SCALE,
+                DRAW_LATENCY_GRAPH_COLOR,
+            );
+        }
+    }
+
+    fn get_draw_latency_summary(draw_latencies: &TimingHistory) -> Option<String> {
+        let milliseconds = |fraction: f64| {
+            return draw_latencies
+                .get_percentile(fraction)
+                .map(|latency| latency.as_secs_f64() * 1000.0);
+        };
+
+        return Some(format!(
+            "latency p50 {:.1} p95 {:.1} max {:.1} ms",
+            milliseconds(0.5)?,
+            milliseconds(0.95)?,
+            milliseconds(1.0)?,
+        ));
+    }
+
+    // Works back from the cursor to the cell drawn under it, the same way render lays them out.
+    fn get_hovered_cell(&self) -> Option<(usize, usize)> {
+        let (cursor_x, cursor_y) = self.input.cursor()?;
+
+        if cursor_x < 0.0 || cursor_y < 0.0 {
+            return None;
+        }
+
+        let (window_width, window_height) = self.window_size.get();
+        let (base_width, base_height) = self.base_size.get();
+        let display_height = window_height.saturating_sub(self.status_bar_height);
+
+        let x_margin = window_width.saturating_sub(base_width * self.size_factor) / 2;
+        let y_margin = display_height.saturating_sub(base_height * self.size_factor) / 2;
+
+        let (screen_width, screen_height) = self.gpu.get_screen_resolution();
+        let rotation = self.gpu.get_display_rotation();
+        let (aspect_width, aspect_height) = self.gpu.get_pixel_aspect_ratio();
+
+        let cell_width = self.size_factor * aspect_width;
+        let cell_height = self.size_factor * aspect_height;
+
+        if cell_width == 0 || cell_height == 0 {
+            return None;
+        }
+
+        let x = (cursor_x as usize).checked_sub(x_margin)? / cell_width;
+        let y = (cursor_y as usize).checked_sub(y_margin)? / cell_height;
+
+        let (shown_width, shown_height) = match rotation {
+            90 | 270 => (screen_height, screen_width),
+            _ => (screen_width, screen_height),
+        };
+
+        if x >= shown_width || y >= shown_height {
+            return None;
+        }
+
+        return Some(scanlines::unrotate_coords(
+            x,
+            y,
+            screen_width,
+            screen_height,
+            rotation,
+        ));
     }
 
     fn render_square(
@@ -210,21 +1036,115 @@ impl WindowManager {
         color: u32,
         buffer: &mut Buffer<'_, Rc<Window>, Rc<Window>>,
     ) {
-        let pixel_row = vec![color; size.width];
-
         for row in 0..size.height {
             let start_index = pos.index + row * pos.get_screen_width();
-            buffer[start_index..start_index + size.width].copy_from_slice(&pixel_row);
+            buffer[start_index..start_index + size.width].fill(color);
+        }
+    }
+
+    pub fn get_window_state(&self) -> Option<WindowState> {
+        return self.window_state.clone();
+    }
+
+    fn record_window_state(&mut self) {
+        let Some(window) = self.window.as_ref() else {
+            return;
+        };
+
+        let fullscreen = window.fullscreen().is_some();
+
+        // Keep the windowed geometry while fullscreen, so it can be restored later.
+        if fullscreen && let Some(window_state) = self.window_state.as_mut() {
+            window_state.fullscreen = true;
+            return;
+        }
+
+        let size = window.inner_size();
+        let position = window.outer_position().ok();
+
+        self.window_state = Some(WindowState {
+            width: size.width,
+            height: size.height,
+            x: position.map(|p| p.x),
+            y: position.map(|p| p.y),
+            fullscreen,
+            monitor: window.current_monitor().and_then(|monitor| monitor.name()),
+            scale_factor: Some(window.scale_factor()),
+        });
+    }
+
+    // The monitor named in the config wins over the one the window was last on.
+    fn find_monitor(&self, event_loop: &ActiveEventLoop) -> Option<MonitorHandle> {
+        let configured_name = self.gpu.get_monitor();
+        let name = configured_name.clone().or_else(|| {
+            return self.window_state.as_ref()?.monitor.clone();
+        })?;
+
+        let monitor = event_loop
+            .available_monitors()
+            .find(|monitor| monitor.name().as_deref() == Some(name.as_str()));
+
+        // A remembered monitor that was unplugged since isn't worth a warning.
+        if monitor.is_none() && configured_name.is_some() {
+            let names: Vec<String> = event_loop
+                .available_monitors()
+                .filter_map(|monitor| monitor.name())
+                .collect();
+            eprintln!(
+                "Warning: There is no monitor named \"{name}\", so the window opens on the default one (the monitors are {}).",
+                names.join(", ")
+            );
+        }
+
+        return monitor;
+    }
+
+    fn toggle_fullscreen(&mut self) {
+        self.record_window_state();
+
+        let Some(window) = self.window.as_ref() else {
+            return;
+        };
+
+        match window.fullscreen() {
+            Some(_) => window.set_fullscreen(None),
+            None => window.set_fullscreen(Some(Fullscreen::Borderless(None))),
         }
     }
 
+    fn get_centered_position(
+        monitor: &MonitorHandle,
+        window_size: PhysicalSize<u32>,
+    ) -> PhysicalPosition<i32> {
+        let monitor_position = monitor.position();
+        let monitor_size = monitor.size();
+
+        return PhysicalPosition::new(
+            monitor_position.x + (monitor_size.width.saturating_sub(window_size.width) / 2) as i32,
+            monitor_position.y
+                + (monitor_size.height.saturating_sub(window_size.height) / 2) as i32,
+        );
+    }
+
+    // Moving to a monitor with a different scale factor doesn't always come with a resize event,
+    // so the window is asked for its size instead.
+    fn get_new_window_size(&self) -> Option<PhysicalSize<u32>> {
+        if let Some(new_size) = self.input.window_resized() {
+            return Some(new_size);
+        }
+
+        self.input.scale_factor_changed()?;
+        return self.window.as_ref().map(|window| window.inner_size());
+    }
+
     fn update_size(&mut self, new_size: PhysicalSize<u32>) {
         self.window_size
             .set(new_size.width as usize, new_size.height as usize);
 
         self.size_factor = cmp::min(
             new_size.width as usize / self.base_size.width,
-            new_size.height as usize / self.base_size.height,
+            (new_size.height as usize).saturating_sub(self.status_bar_height)
+                / self.base_size.height,
         );
 
         let Some(surface) = self.surface.as_mut() else {
@@ -233,31 +1153,42 @@ impl WindowManager {
 
         let Some(new_size_width_nz) = NonZeroU32::new(new_size.width) else {
             eprintln!("Error: Failed to convert window width into NonZeroU32.");
-            self.active.store(false, Ordering::Relaxed);
+            self.lifecycle.shutdown();
             return;
         };
 
         let Some(new_size_height_nz) = NonZeroU32::new(new_size.height) else {
             eprintln!("Error: Failed to convert window height into NonZeroU32.");
-            self.active.store(false, Ordering::Relaxed);
+            self.lifecycle.shutdown();
             return;
         };
 
         if let Err(e) = surface.resize(new_size_width_nz, new_size_height_nz) {
             eprintln!("Error: Failed to resize the softbuffer surface ({e}).");
-            self.active.store(false, Ordering::Relaxed);
+            self.lifecycle.shutdown();
             return;
         }
     }
 }
 
+impl Display for WindowManager {
+    fn present(&mut self, framebuffer: &[bool], resolution: (usize, usize)) -> bool {
+        if self.frame.get_size() != resolution {
+            self.frame = Framebuffer::new(resolution.0, resolution.1);
+        }
+
+        self.frame.load_pixels(framebuffer);
+        return self.draw_frame();
+    }
+}
+
 impl ApplicationHandler for WindowManager {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         if self.window.is_some() {
             return;
         }
 
-        let window_size = PhysicalSize::new(
+        let mut window_size = PhysicalSize::new(
             self.window_size.width as u32,
             self.window_size.height as u32,
         );
@@ -265,24 +1196,91 @@ impl ApplicationHandler for WindowManager {
         let increment_size =
             PhysicalSize::new(self.base_size.width as u32, self.base_size.height as u32);
 
-        let attributes = Window::default_attributes()
-            .with_inner_size(window_size)
+        let mut attributes = Window::default_attributes()
             .with_title(WINDOW_TITLE)
             .with_enabled_buttons(WindowButtons::CLOSE | WindowButtons::MINIMIZE)
             .with_resize_increments(increment_size);
 
+        let monitor = self.find_monitor(event_loop);
+        let mut position = None;
+        let mut fullscreen = false;
+
+        if let Some(window_state) = self.window_state.as_ref() {
+            let (width, height) = match monitor.as_ref() {
+                Some(monitor) => window_state.get_scaled_size(monitor.scale_factor()),
+                None => (window_state.width, window_state.height),
+            };
+
+            if width >= increment_size.width && height >= increment_size.height {
+                window_size = PhysicalSize::new(width, height);
+            }
+
+            // The saved position only makes sense on the monitor it was saved on.
+            let same_monitor = monitor
+                .as_ref()
+                .is_none_or(|monitor| monitor.name() == window_state.monitor);
+
+            if let (Some(x), Some(y), true) = (window_state.x, window_state.y, same_monitor) {
+                position = Some(PhysicalPosition::new(x, y));
+            }
+
+            fullscreen = window_state.fullscreen;
+        }
+
+        if position.is_none()
+            && let Some(monitor) = monitor.as_ref()
+        {
+            position = Some(Self::get_centered_position(monitor, window_size));
+        }
+
+        if let Some(position) = position {
+            attributes = attributes.with_position(position);
+        }
+
+        if fullscreen {
+            attributes = attributes.with_fullscreen(Some(Fullscreen::Borderless(monitor)));
+        }
+
+        attributes = attributes.with_inner_size(window_size);
+
         let window = Rc::new(event_loop.create_window(attributes).unwrap());
         let context = Context::new(window.clone()).unwrap();
         let surface = Surface::new(&context, window.clone()).unwrap();
 
-        self.update_size(window_size);
+        // The window may not get the size asked for, such as when the scale factor is applied to it.
+        let window_size = window.inner_size();
 
         self.window = Some(window);
         self.context = Some(context);
         self.surface = Some(surface);
+
+        self.update_size(window_size);
+    }
+
+    fn exiting(&mut self, _: &ActiveEventLoop) {
+        self.record_window_state();
+        self.write_auto_save();
+
+        if self.gpu.should_show_frame_pacing_overlay()
+            && let Some(summary) = Self::get_draw_latency_summary(&self.draw_latencies)
+        {
+            println!(
+                "Draw-to-present {summary} over the last {} frames drawn.",
+                self.draw_latencies.get_samples().len()
+            );
+        }
     }
 
     fn window_event(&mut self, _: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        if let WindowEvent::KeyboardInput {
+            event: key_event, ..
+        } = &event
+            && key_event.state == ElementState::Pressed
+            && !key_event.repeat
+        {
+            self.settings_menu.capture_key(&key_event.logical_key);
+        }
+
         if self.input.process_window_event(&event) {
             self.render();
         }
@@ -297,7 +1295,7 @@ impl ApplicationHandler for WindowManager {
     }
 
     fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
-        if !self.active.load(Ordering::Relaxed) {
+        if !self.lifecycle.is_running() {
             event_loop.exit();
             return;
         }
@@ -305,29 +1303,176 @@ impl ApplicationHandler for WindowManager {
         self.input.end_step();
 
         if self.input.close_requested() || self.input.destroyed() {
-            self.active.store(false, Ordering::Relaxed);
+            self.lifecycle.shutdown();
             event_loop.exit();
             return;
         }
 
-        self.input_manager.update_input(&self.input);
+        if self.rom_chooser.is_open() {
+            let can_close = self.program_path.is_some();
 
-        if let Some(new_size) = self.input.window_resized() {
+            if let Some(path) = self.rom_chooser.update(&self.input, can_close) {
+                self.load_rom(path);
+            } else if !self.rom_chooser.is_open() {
+                self.emulator.resume();
+            }
+        } else if self.menu_bar.is_open() {
+            if let Some(item) = self.menu_bar.update(&self.input) {
+                self.select_menu_item(item);
+            }
+        } else if self.slot_picker.is_open() {
+            if let Some(slot) = self.slot_picker.update(&self.input) {
+                self.load_from_slot(slot);
+            }
+
+            if !self.slot_picker.is_open() {
+                self.emulator.resume();
+            }
+        } else if self.about_dialog.is_open() {
+            self.about_dialog.update(&self.input);
+        } else if self.tile_viewer.is_open() {
+            self.tile_viewer.update(&self.input);
+        } else if self.settings_menu.is_open() {
+            self.settings_menu.update(&self.input);
+
+            if !self.settings_menu.is_open() {
+                self.close_settings_menu();
+            }
+        } else {
+            self.input_manager.update_input(&self.input);
+            self.macro_player.update(&self.input_manager);
+            self.debugger.update();
+            self.update_rewind();
+
+            if self.input.key_pressed(SETTINGS_MENU_HOTKEY) {
+                self.open_settings_menu();
+            } else if self.input.held_control() && self.input.key_pressed(OPEN_ROM_HOTKEY) {
+                self.open_rom_chooser(None, None);
+            } else if self.input.held_control() && self.input.key_pressed(KEYPAD_OVERLAY_HOTKEY) {
+                self.show_keypad = !self.show_keypad;
+            } else if self.input.held_control() && self.input.key_pressed(MAGNIFIER_HOTKEY) {
+                self.show_magnifier = !self.show_magnifier;
+            } else if self.input.held_control() && self.input.key_pressed(DEBUGGER_HOTKEY) {
+                self.debugger.toggle();
+            } else if self.input.held_control() && self.input.key_pressed(DEBUGGER_STEP_HOTKEY) {
+                self.debugger.step();
+            } else if self.input.held_control() && self.input.key_pressed(DEBUGGER_STEP_BACK_HOTKEY)
+            {
+                self.debugger.step_back();
+            } else if self.input.held_control() && self.input.key_pressed(DEBUGGER_STEP_OUT_HOTKEY)
+            {
+                self.debugger.step_out();
+            } else if self.input.held_control() && self.input.key_pressed(TILE_VIEWER_HOTKEY) {
+                self.tile_viewer.open();
+            } else if let Some(slot) = SAVE_SLOT_HOTKEYS
+                .iter()
+                .position(|key| self.input.key_pressed(*key))
+            {
+                match self.input.held_shift() {
+                    true => self.save_to_slot(slot + 1),
+                    false => self.load_from_slot(slot + 1),
+                }
+            } else if SPEED_UP_HOTKEYS
+                .iter()
+                .any(|key| self.input.key_pressed(*key))
+            {
+                self.scale_speed(SPEED_SCALE);
+            } else if SLOW_DOWN_HOTKEYS
+                .iter()
+                .any(|key| self.input.key_pressed(*key))
+            {
+                self.scale_speed(1.0 / SPEED_SCALE);
+            } else if let Some(index) = self.macro_player.get_pressed_macro(&self.input) {
+                match self.input.held_control() {
+                    true => self.macro_player.toggle_recording(index),
+                    false => self.macro_player.play(index, &self.input_manager),
+                }
+            } else if self.input.key_released(MENU_BAR_HOTKEY)
+                || self.input.mouse_pressed(MENU_BAR_BUTTON)
+            {
+                self.menu_bar.open();
+            }
+        }
+
+        self.update_turbo();
+
+        if !self.rom_chooser.is_open() {
+            self.update_watch();
+        }
+
+        if self.input.key_pressed(FULLSCREEN_HOTKEY) {
+            self.toggle_fullscreen();
+        }
+
+        if self.input.key_pressed(CYCLE_PALETTE_HOTKEY) {
+            match self.input.held_shift() {
+                true => self.gpu.toggle_inverted_colors(),
+                false => self.gpu.cycle_palette(),
+            }
+        }
+
+        if let Some(new_size) = self.get_new_window_size() {
             self.update_size(new_size);
             self.render();
         }
 
         let mut should_render = false;
 
-        if let Some(new_size) = self.input.window_resized() {
-            self.update_size(new_size);
-            should_render = true;
+        if self.gpu.is_synced_to_refresh() {
+            self.update_refresh();
         }
 
         if self.gpu.is_render_queued() {
             should_render = true;
         }
 
+        let colors = (
+            self.gpu.get_active_color(),
+            self.gpu.get_inactive_color(),
+            self.gpu.get_border_color(),
+        );
+
+        if colors != self.rendered_colors {
+            should_render = true;
+        }
+
+        if self.status_bar_height > 0 {
+            self.update_instructions_per_second();
+        }
+
+        // One more render is needed once the notice expires, to clear it.
+        if self
+            .notice
+            .as_ref()
+            .is_some_and(|(_, shown)| shown.elapsed() >= NOTICE_DURATION)
+        {
+            self.notice = None;
+            should_render = true;
+        }
+
+        if let Some(frequency) = self.gpu.get_render_heartbeat_frequency()
+            && self.last_render.elapsed().as_secs_f64() >= 1.0 / frequency
+        {
+            should_render = true;
+        }
+
+        if (self.gpu.should_show_frame_pacing_overlay()
+            || self.status_bar_height > 0
+            || self.settings_menu.is_open()
+            || self.menu_bar.is_open()
+            || self.about_dialog.is_open()
+            || self.tile_viewer.is_open()
+            || self.slot_picker.is_open()
+            || self.rom_chooser.is_open()
+            || self.notice.is_some()
+            || self.show_keypad
+            || self.show_magnifier
+            || self.debugger.is_open())
+            && self.last_render.elapsed() >= OVERLAY_REFRESH_INTERVAL
+        {
+            should_render = true;
+        }
+
         if should_render && let Some(window) = self.window.as_ref() {
             self.gpu.dequeue_render();
             window.request_redraw();
@@ -0,0 +1,114 @@
+mod reference_interpreter;
+
+use chip8rust::backend::NullAudioSink;
+use chip8rust::config;
+use chip8rust::emulator::EmulatorBuilder;
+use proptest::prelude::*;
+use reference_interpreter::ReferenceInterpreter;
+
+const PROGRAM_START_ADDRESS: usize = 0x200;
+const MAX_PROGRAM_LENGTH: usize = 64;
+
+fn with_x(base: u16) -> impl Strategy<Value = u16> {
+    return (0u16..16).prop_map(move |x| base | x << 8);
+}
+
+fn with_x_and_y(base: u16) -> impl Strategy<Value = u16> {
+    return (0u16..16, 0u16..16).prop_map(move |(x, y)| base | x << 8 | y << 4);
+}
+
+fn with_x_and_kk(base: u16) -> impl Strategy<Value = u16> {
+    return (0u16..16, 0u16..256).prop_map(move |(x, kk)| base | x << 8 | kk);
+}
+
+// Every instruction that doesn't jump, wait for input or use randomness.
+fn instruction() -> impl Strategy<Value = u16> {
+    return prop_oneof![
+        1 => Just(0x00E0),
+        1 => with_x_and_kk(0x3000),
+        1 => with_x_and_kk(0x4000),
+        1 => with_x_and_y(0x5000),
+        4 => with_x_and_kk(0x6000),
+        1 => with_x_and_kk(0x7000),
+        1 => (0u16..16, 0u16..16, prop::sample::select(vec![0, 1, 2, 3, 4, 5, 6, 7, 0xE]))
+            .prop_map(|(x, y, n)| 0x8000 | x << 8 | y << 4 | n),
+        1 => with_x_and_y(0x9000),
+        2 => (0u16..0x1000).prop_map(|nnn| 0xA000 | nnn),
+        2 => (0u16..16, 0u16..16, 0u16..16).prop_map(|(x, y, n)| 0xD000 | x << 8 | y << 4 | n),
+        1 => with_x(0xE09E),
+        1 => with_x(0xE0A1),
+        1 => with_x(0xF007),
+        1 => with_x(0xF015),
+        1 => with_x(0xF018),
+        1 => with_x(0xF01E),
+        1 => with_x(0xF029),
+        1 => with_x(0xF030),
+        1 => with_x(0xF033),
+        1 => with_x(0xF055),
+        1 => with_x(0xF065),
+    ];
+}
+
+fn format_program(program: &[u16]) -> String {
+    return program
+        .iter()
+        .map(|opcode| format!("{opcode:04X}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    // Runs random programs on both interpreters, comparing their whole state after each step.
+    #[test]
+    fn test_matches_reference_interpreter(
+        program in prop::collection::vec(instruction(), 1..MAX_PROGRAM_LENGTH)
+    ) {
+        let rom: Vec<u8> = program.iter().flat_map(|opcode| opcode.to_be_bytes()).collect();
+        let listing = format_program(&program);
+
+        let emulator = EmulatorBuilder::default()
+            .with_audio_sink(Box::new(NullAudioSink))
+            .build()
+            .unwrap();
+        prop_assert!(emulator.load_rom_bytes(&rom).is_ok());
+
+        let mut reference = ReferenceInterpreter::new(config::generate_default_configs(), &rom);
+
+        // Skips can step past the end of the program, where there is nothing left to compare.
+        while (reference.pc as usize) < PROGRAM_START_ADDRESS + rom.len() {
+            let pc = reference.pc;
+
+            emulator.step();
+
+            if !reference.step() {
+                break;
+            }
+
+            prop_assert_eq!(
+                reference.running,
+                emulator.is_running(),
+                "halting differs after {:03X} in {}", pc, listing
+            );
+
+            if !reference.running {
+                break;
+            }
+
+            prop_assert_eq!(reference.pc, emulator.get_pc(), "PC differs after {:03X} in {}", pc, listing);
+            prop_assert_eq!(reference.i, emulator.get_index_reg(), "I differs after {:03X} in {}", pc, listing);
+            prop_assert_eq!(reference.v, emulator.get_v_regs(), "V differs after {:03X} in {}", pc, listing);
+            prop_assert_eq!(reference.delay_timer, emulator.get_delay_timer());
+            prop_assert_eq!(reference.sound_timer, emulator.get_sound_timer());
+            prop_assert!(
+                reference.display == emulator.framebuffer(),
+                "display differs after {:03X} in {}", pc, listing
+            );
+            prop_assert!(
+                Some(reference.memory.to_vec()) == emulator.read_memory(0, 0x1000),
+                "memory differs after {:03X} in {}", pc, listing
+            );
+        }
+    }
+}
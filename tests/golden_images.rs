@@ -0,0 +1,71 @@
+use chip8rust::emulator::EmulatorBuilder;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+// Set this to rewrite the expected images from the current output, after checking it is right.
+const UPDATE_VARIABLE: &str = "CHIP8RUST_UPDATE_GOLDEN";
+
+fn get_golden_path(file_name: &str) -> PathBuf {
+    return PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(file_name);
+}
+
+// One line per row, with # for pixels that are on and . for those that are off.
+fn format_framebuffer(framebuffer: &[bool], width: usize) -> String {
+    let mut image = String::new();
+
+    for row in framebuffer.chunks(width) {
+        image.extend(row.iter().map(|pixel| match pixel {
+            true => '#',
+            false => '.',
+        }));
+        image.push('\n');
+    }
+
+    return image;
+}
+
+// Runs the ROM with the default config, one instruction at a time, and compares the display.
+fn check_golden_image(rom_name: &str, instruction_count: usize) {
+    let rom = fs::read(get_golden_path(&format!("{rom_name}.ch8"))).unwrap();
+    let image_path = get_golden_path(&format!("{rom_name}.txt"));
+
+    let emulator = EmulatorBuilder::default().build().unwrap();
+    assert!(emulator.load_rom_bytes(&rom).is_ok());
+
+    for _ in 0..instruction_count {
+        assert!(emulator.step(), "{rom_name} halted the emulator.");
+    }
+
+    let (width, _) = emulator.screen_resolution();
+    let image = format_framebuffer(&emulator.framebuffer(), width);
+
+    if env::var_os(UPDATE_VARIABLE).is_some() {
+        fs::write(&image_path, &image).unwrap();
+        return;
+    }
+
+    let expected_image = fs::read_to_string(&image_path).unwrap();
+    assert!(
+        expected_image == image,
+        "{rom_name} drew a different image.\nExpected:\n{expected_image}\nActual:\n{image}"
+    );
+}
+
+#[test]
+fn test_font() {
+    check_golden_image("font", 100);
+}
+
+#[test]
+fn test_sprite_collision() {
+    check_golden_image("sprite_collision", 20);
+}
+
+#[test]
+fn test_arithmetic() {
+    check_golden_image("arithmetic", 50);
+}
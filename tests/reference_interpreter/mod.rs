@@ -0,0 +1,270 @@
+use chip8rust::config::{
+    CollisionReporting, Config, HeapOverflowPolicy, IndexRegisterOverflowPolicy, SpriteEdgePolicy,
+};
+
+const MEMORY_SIZE: usize = 0x1000;
+const PROGRAM_START_ADDRESS: usize = 0x200;
+
+/// A deliberately simple CHIP-8 interpreter, written straight from the specification and the
+/// config's quirk flags, to compare the real one against.
+///
+/// It only covers what straight-line programs need, so there is no stack, input or randomness,
+/// and timers are never ticked.
+pub struct ReferenceInterpreter {
+    pub memory: [u8; MEMORY_SIZE],
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub display: Vec<bool>,
+    pub running: bool,
+    config: Config,
+}
+
+impl ReferenceInterpreter {
+    pub fn new(config: Config, program: &[u8]) -> Self {
+        let mut memory = [0; MEMORY_SIZE];
+
+        let font_start = config.ram.font_starting_address as usize;
+        memory[font_start..font_start + 80].copy_from_slice(&config.ram.font_data);
+        let big_font_start = config.ram.big_font_starting_address as usize;
+        memory[big_font_start..big_font_start + 160].copy_from_slice(&config.ram.big_font_data);
+        memory[PROGRAM_START_ADDRESS..PROGRAM_START_ADDRESS + program.len()]
+            .copy_from_slice(program);
+
+        let display_size = config.gpu.horizontal_resolution * config.gpu.vertical_resolution;
+
+        return Self {
+            memory,
+            v: [0; 16],
+            i: 0,
+            pc: PROGRAM_START_ADDRESS as u16,
+            delay_timer: 0,
+            sound_timer: 0,
+            display: vec![false; display_size],
+            running: true,
+            config,
+        };
+    }
+
+    // Out of range addresses wrap around, are left out (and so stay out of range) or halt,
+    // depending on the config.
+    fn get_address(&mut self, address: usize) -> Option<usize> {
+        if address < MEMORY_SIZE {
+            return Some(address);
+        }
+
+        return match self.config.ram.heap_overflow_policy {
+            HeapOverflowPolicy::Wrap => Some(address % MEMORY_SIZE),
+            HeapOverflowPolicy::Ignore => Some(address),
+            HeapOverflowPolicy::Halt => {
+                self.running = false;
+                None
+            }
+        };
+    }
+
+    fn mask_index(&mut self) {
+        if self.config.cpu.index_register_overflow_policy == IndexRegisterOverflowPolicy::Mask {
+            self.i &= 0xFFF;
+        }
+    }
+
+    fn read(&mut self, address: usize, count: usize) -> Option<Vec<u8>> {
+        let mut bytes = Vec::new();
+
+        for offset in 0..count {
+            let address = self.get_address(address + offset)?;
+            bytes.push(self.memory.get(address).copied().unwrap_or(0));
+        }
+
+        return Some(bytes);
+    }
+
+    fn write(&mut self, address: usize, bytes: &[u8]) {
+        // Checked up front, so that nothing is written if any of it is out of range.
+        let mut addresses = Vec::new();
+
+        for offset in 0..bytes.len() {
+            let Some(address) = self.get_address(address + offset) else {
+                return;
+            };
+
+            addresses.push(address);
+        }
+
+        for (address, byte) in addresses.into_iter().zip(bytes) {
+            if let Some(cell) = self.memory.get_mut(address) {
+                *cell = *byte;
+            }
+        }
+    }
+
+    // Returns false for instructions it doesn't cover, which self-modifying programs can produce.
+    pub fn step(&mut self) -> bool {
+        let pc = self.pc as usize;
+        let opcode = (self.memory[pc] as u16) << 8 | self.memory[pc + 1] as u16;
+        self.pc += 2;
+
+        let x = ((opcode >> 8) & 0xF) as usize;
+        let y = ((opcode >> 4) & 0xF) as usize;
+        let n = (opcode & 0xF) as usize;
+        let kk = (opcode & 0xFF) as u8;
+        let nnn = opcode & 0xFFF;
+
+        let cpu = &self.config.cpu;
+        let (vx, vy) = (self.v[x], self.v[y]);
+
+        match (opcode >> 12, n, kk) {
+            (0x0, _, 0xE0) => self.display.fill(false),
+            (0x3, _, _) if vx == kk => self.pc += 2,
+            (0x4, _, _) if vx != kk => self.pc += 2,
+            (0x5, 0x0, _) if vx == vy => self.pc += 2,
+            (0x6, _, _) => self.v[x] = kk,
+            (0x7, _, _) => self.v[x] = vx.wrapping_add(kk),
+            (0x8, 0x0, _) => self.v[x] = vy,
+            (0x8, 0x1..=0x3, _) => {
+                self.v[x] = match n {
+                    0x1 => vx | vy,
+                    0x2 => vx & vy,
+                    _ => vx ^ vy,
+                };
+
+                if cpu.reset_flag_for_bitwise_operations {
+                    self.v[0xF] = 0;
+                }
+            }
+            (0x8, 0x4 | 0x5 | 0x6 | 0x7 | 0xE, _) => {
+                let shifted = match cpu.use_new_shift_instruction {
+                    true => vx,
+                    false => vy,
+                };
+
+                let (result, flag) = match n {
+                    0x4 => (vx.wrapping_add(vy), (vx as u16 + vy as u16 > 0xFF) as u8),
+                    0x5 => (vx.wrapping_sub(vy), (vx >= vy) as u8),
+                    0x7 => (vy.wrapping_sub(vx), (vy >= vx) as u8),
+                    0x6 => (shifted / 2, shifted % 2),
+                    _ => (shifted.wrapping_mul(2), (shifted >= 0x80) as u8),
+                };
+
+                if cpu.write_flag_before_result {
+                    self.v[0xF] = flag;
+                    self.v[x] = result;
+                } else {
+                    self.v[x] = result;
+                    self.v[0xF] = flag;
+                }
+            }
+            (0x9, 0x0, _) if vx != vy => self.pc += 2,
+            (0xA, _, _) => self.i = nnn,
+            (0xD, _, _) => self.draw(vx, vy, n),
+            // No keys are ever pressed.
+            (0xE, _, 0xA1) => self.pc += 2,
+            (0xF, _, 0x07) => self.v[x] = self.delay_timer,
+            (0xF, _, 0x15) => self.delay_timer = vx,
+            (0xF, _, 0x18) => self.sound_timer = vx,
+            (0xF, _, 0x1E) => {
+                self.i += vx as u16;
+
+                if self.i > 0xFFF && cpu.set_flag_for_index_overflow {
+                    self.v[0xF] = 1;
+                }
+
+                self.mask_index();
+            }
+            (0xF, _, 0x29) => {
+                self.i = self.config.ram.font_starting_address + (vx % 16) as u16 * 5;
+            }
+            (0xF, _, 0x30) => {
+                self.i = self.config.ram.big_font_starting_address + (vx % 16) as u16 * 10;
+            }
+            (0xF, _, 0x33) => self.write(self.i as usize, &[vx / 100, vx / 10 % 10, vx % 10]),
+            (0xF, _, 0x55) => {
+                let registers = self.v;
+                self.write(self.i as usize, &registers[..=x]);
+
+                if self.config.cpu.move_index_with_reads {
+                    self.i += x as u16 + 1;
+                    self.mask_index();
+                }
+            }
+            (0xF, _, 0x65) => {
+                let Some(bytes) = self.read(self.i as usize, x + 1) else {
+                    return true;
+                };
+
+                self.v[..=x].copy_from_slice(&bytes);
+
+                if self.config.cpu.move_index_with_reads {
+                    self.i += x as u16 + 1;
+                    self.mask_index();
+                }
+            }
+            // Skips that weren't taken.
+            (0x3 | 0x4, _, _) | (0x5 | 0x9, 0x0, _) | (0xE, _, 0x9E) => (),
+            _ => return false,
+        }
+
+        return true;
+    }
+
+    fn draw(&mut self, x: u8, y: u8, height: usize) {
+        let Some(sprite) = self.read(self.i as usize, height) else {
+            return;
+        };
+
+        let gpu = &self.config.gpu;
+        let (width, screen_height) = (gpu.horizontal_resolution, gpu.vertical_resolution);
+        let (mut x, mut y) = (x as usize, y as usize);
+
+        if gpu.wrap_sprite_positions {
+            x %= width;
+            y %= screen_height;
+        } else if x >= width || y >= screen_height {
+            self.v[0xF] = 0;
+            return;
+        }
+
+        let mut collided_rows = 0;
+        let mut clipped_rows = 0;
+
+        for (row, byte) in sprite.iter().enumerate() {
+            if gpu.vertical_sprite_edge_policy == SpriteEdgePolicy::Clip && y + row >= screen_height
+            {
+                clipped_rows += 1;
+                continue;
+            }
+
+            let mut collided = false;
+
+            for column in 0..8 {
+                if byte & (0x80 >> column) == 0 {
+                    continue;
+                }
+
+                let (mut pixel_x, mut pixel_y) = (x + column, y + row);
+
+                if gpu.horizontal_sprite_edge_policy == SpriteEdgePolicy::Wrap {
+                    pixel_x %= width;
+                } else if pixel_x >= width {
+                    continue;
+                }
+
+                pixel_y %= screen_height;
+
+                let pixel = &mut self.display[pixel_y * width + pixel_x];
+                collided |= *pixel;
+                *pixel = !*pixel;
+            }
+
+            collided_rows += collided as u8;
+        }
+
+        self.v[0xF] = match gpu.collision_reporting.unwrap_or_default() {
+            CollisionReporting::Flag => (collided_rows > 0) as u8,
+            CollisionReporting::RowCount => collided_rows + clipped_rows,
+        };
+    }
+}